@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use common::IssueKind;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Default)]
+struct AgentCounts {
+    /// Detections by issue name, counted once per tick an issue is seen.
+    issues: BTreeMap<&'static str, u64>,
+    playbooks_dispatched: u64,
+    actions_submitted: u64,
+    action_submit_failures: u64,
+}
+
+/// Counters for the agent loop's own decisions, exported on `GET /metrics`.
+#[derive(Clone, Debug, Default)]
+pub struct AgentMetrics {
+    inner: Arc<Mutex<AgentCounts>>,
+}
+
+impl AgentMetrics {
+    pub async fn issues_detected(&self, issues: &[IssueKind]) {
+        let mut inner = self.inner.lock().await;
+        for issue in issues {
+            *inner.issues.entry(issue.as_str()).or_default() += 1;
+        }
+    }
+
+    /// One playbook sent with `steps` actions, `failed` of which the
+    /// dispatcher rejected.
+    pub async fn playbook_dispatched(&self, steps: usize, failed: usize) {
+        let mut inner = self.inner.lock().await;
+        inner.playbooks_dispatched += 1;
+        inner.actions_submitted += steps.saturating_sub(failed) as u64;
+        inner.action_submit_failures += failed as u64;
+    }
+
+    /// The counters, and `validators` as the known validator gauge.
+    pub async fn render_metrics(&self, validators: usize) -> String {
+        let inner = self.inner.lock().await;
+        let mut out = String::new();
+        out.push_str(
+            "# HELP agent_issues_detected_total Issues detected, once per tick each is seen.\n",
+        );
+        out.push_str("# TYPE agent_issues_detected_total counter\n");
+        for (issue, count) in &inner.issues {
+            let _ = writeln!(
+                out,
+                "agent_issues_detected_total{{issue=\"{issue}\"}} {count}"
+            );
+        }
+        for (name, help, value) in [
+            (
+                "agent_playbooks_dispatched_total",
+                "Playbooks dispatched with at least one action.",
+                inner.playbooks_dispatched,
+            ),
+            (
+                "agent_actions_submitted_total",
+                "Actions the dispatcher accepted.",
+                inner.actions_submitted,
+            ),
+            (
+                "agent_action_submit_failures_total",
+                "Actions the dispatcher failed to accept.",
+                inner.action_submit_failures,
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} counter");
+            let _ = writeln!(out, "{name} {value}");
+        }
+        out.push_str("# HELP agent_validators Validators listed in the agent's config.\n");
+        out.push_str("# TYPE agent_validators gauge\n");
+        let _ = writeln!(out, "agent_validators {validators}");
+        out
+    }
+}
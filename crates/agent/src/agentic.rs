@@ -1,24 +1,30 @@
 use std::env;
+use std::fmt;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, bail, Context, Result};
-use async_openai::{
-    config::OpenAIConfig,
-    types::{
-        ChatCompletionNamedToolChoice, ChatCompletionRequestMessage,
-        ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
-        ChatCompletionRequestUserMessageContent, ChatCompletionToolArgs,
-        ChatCompletionToolChoiceOption, ChatCompletionToolType, CreateChatCompletionRequestArgs,
-        FunctionName, FunctionObjectArgs,
-    },
-    Client,
+use async_openai::types::{
+    ChatCompletionNamedToolChoice, ChatCompletionRequestMessage,
+    ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
+    ChatCompletionRequestUserMessageContent, ChatCompletionToolArgs,
+    ChatCompletionToolChoiceOption, ChatCompletionToolType, CreateChatCompletionRequestArgs,
+    CreateChatCompletionResponse, FunctionName, FunctionObjectArgs,
 };
 use common::{
-    Action, AgenticConfig, IssueKind, OpenAiAgentConfig, Playbook, ValidatorConfig, ValidatorId,
-    ValidatorMetrics,
+    now_ts, Action, ActionCommands, AgenticConfig, AgenticMode, AgenticSettings,
+    AnthropicAgentConfig, Backoff, CompletionRetry, IssueKind, OpenAiAgentConfig,
+    OpenAiCompatibleAgentConfig, Playbook, PromptContextConfig, RestartKind, TokenBudget,
+    ValidatorConfig, ValidatorId, ValidatorMetrics,
 };
+use executor::proto::ActionRecord;
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use tracing::debug;
+use tracing::{debug, warn};
+
+use crate::call_metrics::{CallMetrics, CallOutcome};
+use crate::decision_cache::{CacheStats, DecisionCache};
+use crate::token_budget::TokenUsage;
 
 const DEFAULT_SYSTEM_PROMPT: &str = r#"System: You are Validator Copilot, an SRE operator for Solana validators.
 
@@ -31,9 +37,11 @@ Return a strictly valid JSON object with the following keys, in this order:
 - "playbook_id": string (required)
 - "rationale": short sentence as a string (required)
 - "actions": array of objects (at least one; required). Each action object includes:
-    - "kind": string; must be one of "disable_rpc", "enable_rpc", "restart_validator", "throttle_rpc_client", "run_maintenance_script", or "send_alert" (required)
+    - "kind": string; must be one of "disable_rpc", "enable_rpc", "restart_validator", "throttle_rpc_client", "run_maintenance_script", or "send_alert", or "exec_command" when "command_ids" are provided (required)
     - "message": string; required only for kind "send_alert" (omit otherwise)
     - "script_name": string; required only for kind "run_maintenance_script" (omit otherwise)
+    - "restart_kind": "soft" to reload configuration in place or "hard" (the default) to bounce the process; only for kind "restart_validator" (optional)
+    - "command_id": string; required only for kind "exec_command", must be one of "command_ids" (omit otherwise)
 
 Validation: After constructing your response, validate that all required fields are present, in the proper order, and correctly formatted. If any required fields are missing, out of order, malformed, or if kind is unrecognized, or if a kind-specific required key (such as message for send_alert or script_name for run_maintenance_script) is absent, flag the response as invalid and do not proceed."#;
 
@@ -58,7 +66,7 @@ const DEFAULT_ACTION_LIBRARY: &[PromptAction] = &[
     },
     PromptAction {
         name: "restart_validator",
-        description: "Restart the validator process to clear unhealthy state.",
+        description: "Restart the validator to clear unhealthy state. Set restart_kind to \"soft\" for a config reload instead of a full process bounce.",
         required_fields: &[],
     },
     PromptAction {
@@ -78,33 +86,117 @@ const DEFAULT_ACTION_LIBRARY: &[PromptAction] = &[
     },
 ];
 
+const EXEC_COMMAND_ACTION: PromptAction = PromptAction {
+    name: "exec_command",
+    description: "Run an operator-registered command. Provide command_id from command_ids.",
+    required_fields: &["command_id"],
+};
+
+/// Recorded as the prompt version of plans made with [`DEFAULT_SYSTEM_PROMPT`].
+/// Bump it whenever that prompt changes.
+const DEFAULT_PROMPT_VERSION: &str = "builtin-2";
+/// Recorded for a custom `system_prompt` that sets no `prompt_version`.
+const CUSTOM_PROMPT_VERSION: &str = "custom";
 const DEFAULT_TEMPERATURE: f32 = 0.2;
 const DEFAULT_API_BASE: &str = "https://api.openai.com/v1";
 const DEFAULT_API_KEY_ENV: &str = "OPENAI_API_KEY";
+const ANTHROPIC_API_BASE: &str = "https://api.anthropic.com";
+const ANTHROPIC_API_KEY_ENV: &str = "ANTHROPIC_API_KEY";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const TOOL_DESCRIPTION: &str =
+    "Produce a validator remediation plan that matches the strict JSON schema.";
 
 #[derive(Clone, Debug)]
 pub struct AgenticBrain {
     planner: Planner,
+    mode: AgenticMode,
+    budget: TokenBudget,
+    context: PromptContextConfig,
+    /// `[agentic] allowed_actions`.
+    allowed_actions: Option<Vec<String>>,
+    usage: TokenUsage,
+    cache: DecisionCache,
+    calls: CallMetrics,
 }
 
 #[derive(Clone, Debug)]
 enum Planner {
     Disabled,
-    OpenAi(OpenAiPlanner),
+    OpenAi(Box<OpenAiPlanner>),
+    Anthropic(Box<AnthropicPlanner>),
+    /// A self-hosted OpenAI-compatible server, planned for like OpenAI.
+    OpenAiCompatible(Box<OpenAiPlanner>),
 }
 
 #[derive(Clone, Debug)]
 struct OpenAiPlanner {
-    client: Client<OpenAIConfig>,
+    client: reqwest::Client,
+    /// Empty for a keyless compatible server, which then gets no
+    /// `Authorization` header rather than a bare `Bearer`.
+    api_key: String,
+    completions_url: String,
     model: String,
     system_prompt: String,
+    prompt_version: String,
     temperature: f32,
+    command_ids: Vec<String>,
+    reject_unknown_actions: bool,
+    retry: CompletionRetry,
+}
+
+/// Plans through the Anthropic Messages API, with the plan requested as a
+/// forced `propose_remediation_plan` tool call unless `tool_use` is off.
+#[derive(Clone, Debug)]
+struct AnthropicPlanner {
+    client: reqwest::Client,
+    api_key: String,
+    messages_url: String,
+    model: String,
+    system_prompt: String,
+    prompt_version: String,
+    temperature: f32,
+    max_tokens: u32,
+    tool_use: bool,
+    command_ids: Vec<String>,
+    reject_unknown_actions: bool,
+    retry: CompletionRetry,
+}
+
+#[derive(Deserialize)]
+struct AnthropicResponse {
+    #[serde(default)]
+    content: Vec<AnthropicContent>,
+    #[serde(default)]
+    usage: Option<AnthropicUsage>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicContent {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        name: String,
+        input: serde_json::Value,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct AnthropicUsage {
+    input_tokens: u64,
+    output_tokens: u64,
 }
 
 #[derive(Clone, Debug)]
 pub struct AgenticDecision {
     pub playbook: Playbook,
     pub rationale: Option<String>,
+    /// The model and prompt that produced the plan, for the decision log.
+    pub model: String,
+    pub prompt_version: String,
 }
 
 #[derive(Serialize)]
@@ -113,7 +205,47 @@ struct PromptPayload<'a> {
     metrics: &'a ValidatorMetrics,
     validator: PromptValidator<'a>,
     objectives: &'static [&'static str],
-    actions: &'static [PromptAction],
+    actions: Vec<&'static PromptAction>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    command_ids: &'a [String],
+    #[serde(flatten)]
+    context: &'a PromptContext,
+}
+
+/// Recent history sent with the prompt so the model can see, e.g., that a
+/// restart was already tried. Empty sections are left out of the payload.
+#[derive(Debug, Default, Serialize)]
+pub struct PromptContext {
+    /// Raw samples, oldest first.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub recent_metrics: Vec<ValidatorMetrics>,
+    /// Action results, oldest first.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub recent_actions: Vec<RecentAction>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecentAction {
+    pub action: serde_json::Value,
+    pub success: bool,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub message: String,
+    pub timestamp: i64,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+}
+
+impl From<ActionRecord> for RecentAction {
+    fn from(record: ActionRecord) -> Self {
+        Self {
+            action: serde_json::from_str(&record.action_json)
+                .unwrap_or(serde_json::Value::String(record.action_json)),
+            success: record.success,
+            message: record.message,
+            timestamp: record.timestamp,
+            dry_run: record.dry_run,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -146,6 +278,10 @@ struct LlmActionSpec {
     message: Option<String>,
     #[serde(default)]
     script_name: Option<String>,
+    #[serde(default)]
+    command_id: Option<String>,
+    #[serde(default)]
+    restart_kind: Option<RestartKind>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -157,19 +293,77 @@ enum LlmActionKind {
     ThrottleRpcClient,
     RunMaintenanceScript,
     SendAlert,
+    ExecCommand,
+    /// Anything else the model came up with, e.g. a hallucinated `reboot_host`.
+    #[serde(untagged)]
+    Unknown(String),
+}
+
+impl LlmActionKind {
+    fn name(&self) -> &str {
+        match self {
+            Self::DisableRpc => "disable_rpc",
+            Self::EnableRpc => "enable_rpc",
+            Self::RestartValidator => "restart_validator",
+            Self::ThrottleRpcClient => "throttle_rpc_client",
+            Self::RunMaintenanceScript => "run_maintenance_script",
+            Self::SendAlert => "send_alert",
+            Self::ExecCommand => "exec_command",
+            Self::Unknown(kind) => kind,
+        }
+    }
 }
 
 impl AgenticBrain {
-    pub fn new(cfg: Option<AgenticConfig>) -> Result<Self> {
+    pub fn new(cfg: Option<AgenticConfig>, commands: &ActionCommands) -> Result<Self> {
+        let AgenticSettings {
+            mode,
+            budget,
+            context,
+            cache,
+            allowed_actions,
+            ..
+        } = cfg
+            .as_ref()
+            .map(|cfg| cfg.settings().clone())
+            .unwrap_or_default();
         let planner = match cfg {
-            Some(agentic_cfg) => Planner::try_from(agentic_cfg)?,
+            Some(agentic_cfg) => Planner::try_from(agentic_cfg, commands.command_ids())?,
             None => Planner::Disabled,
         };
-        Ok(Self { planner })
+        Ok(Self {
+            planner,
+            mode,
+            budget,
+            context,
+            allowed_actions,
+            usage: TokenUsage::default(),
+            cache: DecisionCache::new(cache),
+            calls: CallMetrics::default(),
+        })
+    }
+
+    pub fn mode(&self) -> AgenticMode {
+        self.mode
+    }
+
+    /// How much recent history each prompt should carry.
+    pub fn context(&self) -> PromptContextConfig {
+        self.context
     }
 
     pub fn is_enabled(&self) -> bool {
-        matches!(self.planner, Planner::OpenAi(_))
+        !matches!(self.planner, Planner::Disabled)
+    }
+
+    /// The configured `provider`, or `none` when planning is disabled.
+    pub fn provider(&self) -> &'static str {
+        match self.planner {
+            Planner::Disabled => "none",
+            Planner::OpenAi(_) => "openai",
+            Planner::Anthropic(_) => "anthropic",
+            Planner::OpenAiCompatible(_) => "openai_compatible",
+        }
     }
 
     pub async fn plan(
@@ -177,46 +371,330 @@ impl AgenticBrain {
         validator: &ValidatorConfig,
         metrics: &ValidatorMetrics,
         issue: IssueKind,
+        context: &PromptContext,
     ) -> Result<Option<AgenticDecision>> {
-        match &self.planner {
+        if matches!(self.planner, Planner::Disabled) {
+            return Ok(None);
+        }
+        let now = now_ts();
+        if let Some(decision) = self
+            .cache
+            .get(&validator.id.0, issue, metrics, context, now)
+            .await
+        {
+            debug!(
+                validator = validator.id.0,
+                playbook = decision.playbook.id,
+                "reusing cached agentic decision"
+            );
+            return Ok(Some(decision));
+        }
+        if self.usage.exhausted(&self.budget, now).await {
+            warn!(
+                validator = validator.id.0,
+                "agentic token budget exhausted, using static playbook"
+            );
+            return Ok(None);
+        }
+        let start = Instant::now();
+        let decision = match &self.planner {
             Planner::Disabled => Ok(None),
-            Planner::OpenAi(planner) => planner.plan(validator, metrics, issue).await,
+            Planner::OpenAi(planner) | Planner::OpenAiCompatible(planner) => {
+                planner
+                    .plan(validator, metrics, issue, context, &self.usage)
+                    .await
+            }
+            Planner::Anthropic(planner) => {
+                planner
+                    .plan(validator, metrics, issue, context, &self.usage)
+                    .await
+            }
+        };
+        self.calls
+            .record(call_outcome(&decision), start.elapsed())
+            .await;
+        let Some(mut decision) = decision? else {
+            return Ok(None);
+        };
+        let stripped = match strip_disallowed(
+            &mut decision.playbook,
+            self.allowed_actions.as_deref(),
+            validator,
+        ) {
+            Ok(stripped) => stripped,
+            Err(undo) => {
+                warn!(
+                    validator = validator.id.0,
+                    playbook = decision.playbook.id,
+                    action = undo,
+                    "agentic plan's undoing step is not allowed, using static playbook"
+                );
+                return Ok(None);
+            }
+        };
+        for kind in stripped {
+            warn!(
+                validator = validator.id.0,
+                playbook = decision.playbook.id,
+                action = kind,
+                "stripped agentic step whose action kind is not allowed"
+            );
+        }
+        if decision.playbook.steps.is_empty() {
+            warn!(
+                validator = validator.id.0,
+                playbook = decision.playbook.id,
+                "agentic plan had no allowed steps, using static playbook"
+            );
+            return Ok(None);
+        }
+        self.cache
+            .insert(&validator.id.0, issue, metrics, context, &decision, now)
+            .await;
+        Ok(Some(decision))
+    }
+
+    /// Lookups answered from `[agentic.cache]`, and those that went to the
+    /// provider.
+    pub async fn cache_stats(&self) -> CacheStats {
+        self.cache.stats().await
+    }
+
+    /// Whether planning is paused because a token budget window is used up.
+    pub async fn budget_exhausted(&self, now: i64) -> bool {
+        self.usage.exhausted(&self.budget, now).await
+    }
+
+    pub async fn render_metrics(&self, now: i64) -> String {
+        let mut out = self.usage.render_metrics(&self.budget, now).await;
+        out.push_str(&self.cache.render_metrics().await);
+        if self.is_enabled() {
+            out.push_str(&self.calls.render_metrics().await);
         }
+        out
+    }
+}
+
+/// A provider answer that was not a valid plan, marked so it is counted as a
+/// `parse_error` rather than a failed request. Displays as the error it wraps.
+#[derive(Debug)]
+struct InvalidPlan(anyhow::Error);
+
+impl fmt::Display for InvalidPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for InvalidPlan {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+fn invalid_plan(err: anyhow::Error) -> anyhow::Error {
+    anyhow::Error::new(InvalidPlan(err))
+}
+
+fn call_outcome(result: &Result<Option<AgenticDecision>>) -> CallOutcome {
+    let timed_out = |cause: &(dyn std::error::Error + 'static)| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(reqwest::Error::is_timeout)
+    };
+    match result {
+        Ok(Some(_)) => CallOutcome::Success,
+        Ok(None) => CallOutcome::Empty,
+        Err(err) if err.chain().any(|cause| cause.is::<InvalidPlan>()) => CallOutcome::ParseError,
+        Err(err) if err.chain().any(timed_out) => CallOutcome::Timeout,
+        Err(_) => CallOutcome::Error,
     }
 }
 
 impl Planner {
-    fn try_from(cfg: AgenticConfig) -> Result<Self> {
+    fn try_from(cfg: AgenticConfig, command_ids: Vec<String>) -> Result<Self> {
         match cfg {
-            AgenticConfig::OpenAi(inner) => Ok(Self::OpenAi(OpenAiPlanner::try_new(inner)?)),
+            AgenticConfig::OpenAi(inner) => Ok(Self::OpenAi(Box::new(OpenAiPlanner::try_new(
+                inner,
+                command_ids,
+            )?))),
+            AgenticConfig::Anthropic(inner) => Ok(Self::Anthropic(Box::new(
+                AnthropicPlanner::try_new(inner, command_ids)?,
+            ))),
+            AgenticConfig::OpenAiCompatible(inner) => Ok(Self::OpenAiCompatible(Box::new(
+                OpenAiPlanner::compatible(inner, command_ids)?,
+            ))),
         }
     }
 }
 
+fn api_key(env_key: Option<String>, default: &str, provider: &str) -> Result<String> {
+    let env_key = env_key.unwrap_or_else(|| default.to_string());
+    env::var(&env_key).with_context(|| {
+        format!("environment variable {env_key} is required to use the {provider} agentic provider")
+    })
+}
+
+/// The HTTP client a planner sends its requests with, each allowed
+/// `timeout_secs`.
+fn http_client(timeout_secs: u64) -> Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(timeout_secs))
+        .build()
+        .context("failed to build the agentic HTTP client")
+}
+
+/// Whether a request answered with `status` may succeed if sent again: a
+/// rate limit or a server-side error, whatever the body says. Other
+/// rejections fail the same way every time.
+fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Send the request `build` makes, under `retry`, until it is answered with
+/// a 2xx, and return that body. No response at all (a connection failure or
+/// timeout) and [`is_transient`] statuses are retried; `what` names the
+/// request in errors and logs.
+async fn send_with_retry(
+    build: impl Fn() -> reqwest::RequestBuilder,
+    retry: CompletionRetry,
+    what: &str,
+    validator: &ValidatorId,
+) -> Result<String> {
+    let mut attempt = 1;
+    let initial = Duration::from_millis(retry.initial_backoff_ms);
+    let mut backoff = Backoff::new(initial, Duration::MAX);
+    loop {
+        let (err, transient) = match build().send().await {
+            Ok(response) => {
+                let status = response.status();
+                match response.text().await {
+                    Ok(body) if status.is_success() => return Ok(body),
+                    Ok(body) => (
+                        anyhow!("{what} returned {status}: {body}"),
+                        is_transient(status),
+                    ),
+                    Err(err) => {
+                        let transient = err.is_timeout();
+                        let err = anyhow::Error::new(err)
+                            .context(format!("failed to read {what} response"));
+                        (err, transient)
+                    }
+                }
+            }
+            Err(err) => {
+                let transient = err.is_connect() || err.is_timeout();
+                (
+                    anyhow::Error::new(err).context(format!("{what} failed")),
+                    transient,
+                )
+            }
+        };
+        if !transient || attempt >= retry.max_attempts {
+            return Err(err);
+        }
+        let delay = backoff.next_delay();
+        warn!(
+            validator = validator.0,
+            attempt,
+            retry_in_ms = delay.as_millis() as u64,
+            error = format!("{err:#}"),
+            "{what} failed, retrying"
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// The system prompt to send and the version to record for it.
+fn system_prompt(
+    system_prompt: Option<String>,
+    prompt_version: Option<String>,
+) -> (String, String) {
+    let prompt_version = prompt_version.unwrap_or_else(|| {
+        match system_prompt {
+            Some(_) => CUSTOM_PROMPT_VERSION,
+            None => DEFAULT_PROMPT_VERSION,
+        }
+        .to_string()
+    });
+    let system_prompt = system_prompt.unwrap_or_else(|| DEFAULT_SYSTEM_PROMPT.to_string());
+    (system_prompt, prompt_version)
+}
+
+/// The user message: the issue, metrics, recent history and action library
+/// as JSON.
+fn prompt_payload(
+    validator: &ValidatorConfig,
+    metrics: &ValidatorMetrics,
+    issue: IssueKind,
+    context: &PromptContext,
+    command_ids: &[String],
+) -> Result<String> {
+    let payload = PromptPayload {
+        issue,
+        metrics,
+        validator: PromptValidator {
+            id: &validator.id.0,
+            host: &validator.host,
+            prometheus_url: &validator.prometheus_url,
+        },
+        objectives: DEFAULT_OBJECTIVES,
+        actions: prompt_actions(command_ids),
+        command_ids,
+        context,
+    };
+    serde_json::to_string(&payload).context("failed to serialize prompt payload")
+}
+
 impl OpenAiPlanner {
-    fn try_new(cfg: OpenAiAgentConfig) -> Result<Self> {
-        let env_key = cfg
-            .api_key_env
-            .clone()
-            .unwrap_or_else(|| DEFAULT_API_KEY_ENV.to_string());
-        let api_key = env::var(&env_key).with_context(|| {
-            format!("environment variable {env_key} is required to use the OpenAI agentic provider")
-        })?;
-
-        let openai_cfg = OpenAIConfig::new()
-            .with_api_key(api_key)
-            .with_api_base(cfg.api_base.as_deref().unwrap_or(DEFAULT_API_BASE));
-
-        let client = Client::with_config(openai_cfg);
-        let system_prompt = cfg
-            .system_prompt
-            .unwrap_or_else(|| DEFAULT_SYSTEM_PROMPT.to_string());
+    fn try_new(cfg: OpenAiAgentConfig, command_ids: Vec<String>) -> Result<Self> {
+        let api_key = api_key(cfg.api_key_env.clone(), DEFAULT_API_KEY_ENV, "OpenAI")?;
+        Self::with_api_key(cfg, api_key, command_ids)
+    }
+
+    /// A planner for a self-hosted server. Without `api_key_env` the key is
+    /// left empty rather than read from `OPENAI_API_KEY`, so a real OpenAI
+    /// key is never sent to it.
+    fn compatible(cfg: OpenAiCompatibleAgentConfig, command_ids: Vec<String>) -> Result<Self> {
+        let api_key = match cfg.api_key_env {
+            Some(env_key) => api_key(Some(env_key), "", "OpenAI-compatible")?,
+            None => String::new(),
+        };
+        Self::with_api_key(
+            OpenAiAgentConfig {
+                model: cfg.model,
+                api_base: Some(cfg.api_base),
+                system_prompt: cfg.system_prompt,
+                prompt_version: cfg.prompt_version,
+                temperature: cfg.temperature,
+                api_key_env: None,
+                settings: cfg.settings,
+            },
+            api_key,
+            command_ids,
+        )
+    }
+
+    fn with_api_key(
+        cfg: OpenAiAgentConfig,
+        api_key: String,
+        command_ids: Vec<String>,
+    ) -> Result<Self> {
+        let api_base = cfg.api_base.as_deref().unwrap_or(DEFAULT_API_BASE);
+        let (system_prompt, prompt_version) = system_prompt(cfg.system_prompt, cfg.prompt_version);
 
         Ok(Self {
-            client,
+            client: http_client(cfg.settings.request_timeout_secs)?,
+            api_key,
+            completions_url: format!("{}/chat/completions", api_base.trim_end_matches('/')),
             model: cfg.model,
             system_prompt,
+            prompt_version,
             temperature: cfg.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+            command_ids,
+            reject_unknown_actions: cfg.settings.reject_unknown_actions,
+            retry: cfg.settings.retry,
         })
     }
 
@@ -225,20 +703,10 @@ impl OpenAiPlanner {
         validator: &ValidatorConfig,
         metrics: &ValidatorMetrics,
         issue: IssueKind,
+        context: &PromptContext,
+        usage: &TokenUsage,
     ) -> Result<Option<AgenticDecision>> {
-        let payload = PromptPayload {
-            issue,
-            metrics,
-            validator: PromptValidator {
-                id: &validator.id.0,
-                host: &validator.host,
-                prometheus_url: &validator.prometheus_url,
-            },
-            objectives: DEFAULT_OBJECTIVES,
-            actions: DEFAULT_ACTION_LIBRARY,
-        };
-        let user_payload =
-            serde_json::to_string(&payload).context("failed to serialize prompt payload")?;
+        let user_payload = prompt_payload(validator, metrics, issue, context, &self.command_ids)?;
 
         let system_msg = ChatCompletionRequestSystemMessageArgs::default()
             .content(self.system_prompt.clone())
@@ -253,50 +721,8 @@ impl OpenAiPlanner {
             .function(
                 FunctionObjectArgs::default()
                     .name(TOOL_NAME)
-                    .description("Produce a validator remediation plan that matches the strict JSON schema.")
-                    .parameters(json!({
-                        "type": "object",
-                        "properties": {
-                            "playbook_id": { "type": "string", "minLength": 1 },
-                            "rationale": { "type": "string", "minLength": 1 },
-                            "actions": {
-                                "type": "array",
-                                "minItems": 1,
-                                "items": {
-                                    "type": "object",
-                                    "properties": {
-                                        "kind": {
-                                            "type": "string",
-                                            "enum": [
-                                                "disable_rpc",
-                                                "enable_rpc",
-                                                "restart_validator",
-                                                "throttle_rpc_client",
-                                                "run_maintenance_script",
-                                                "send_alert"
-                                            ]
-                                        },
-                                        "message": { "type": "string" },
-                                        "script_name": { "type": "string" }
-                                    },
-                                    "required": ["kind"],
-                                    "additionalProperties": false,
-                                    "allOf": [
-                                        {
-                                            "if": { "properties": { "kind": { "const": "send_alert" } } },
-                                            "then": { "required": ["message"] }
-                                        },
-                                        {
-                                            "if": { "properties": { "kind": { "const": "run_maintenance_script" } } },
-                                            "then": { "required": ["script_name"] }
-                                        }
-                                    ]
-                                }
-                            }
-                        },
-                        "required": ["playbook_id", "rationale", "actions"],
-                        "additionalProperties": false
-                    }))
+                    .description(TOOL_DESCRIPTION)
+                    .parameters(plan_schema(&self.command_ids))
                     .build()
                     .context("failed to build function definition")?,
             )
@@ -322,12 +748,32 @@ impl OpenAiPlanner {
             .build()
             .context("failed to build OpenAI chat completion request")?;
 
-        let response = self
-            .client
-            .chat()
-            .create(request)
-            .await
-            .context("OpenAI chat completion failed")?;
+        let body = serde_json::to_string(&request)?;
+        let response = send_with_retry(
+            || {
+                let request = self
+                    .client
+                    .post(&self.completions_url)
+                    .header("Content-Type", "application/json")
+                    .body(body.clone());
+                if self.api_key.is_empty() {
+                    request
+                } else {
+                    request.bearer_auth(&self.api_key)
+                }
+            },
+            self.retry,
+            "OpenAI chat completion",
+            &validator.id,
+        )
+        .await?;
+        let response: CreateChatCompletionResponse =
+            serde_json::from_str(&response).context("unexpected OpenAI response")?;
+        if let Some(completion_usage) = &response.usage {
+            usage
+                .record(completion_usage.total_tokens.into(), now_ts())
+                .await;
+        }
         let Some(choice) = response.choices.first() else {
             return Ok(None);
         };
@@ -346,11 +792,7 @@ impl OpenAiPlanner {
                     );
                     let plan =
                         parse_plan_payload(&args).context("failed to parse tool call payload")?;
-                    if plan.actions.is_empty() {
-                        return Ok(None);
-                    }
-                    let decision = plan.into_decision(issue, &validator.id)?;
-                    return Ok(Some(decision));
+                    return self.decide(plan, issue, &validator.id);
                 }
             }
         }
@@ -363,15 +805,263 @@ impl OpenAiPlanner {
         );
 
         let plan = parse_plan_payload(&raw).context("failed to parse OpenAI response payload")?;
-        if plan.actions.is_empty() {
-            return Ok(None);
+        self.decide(plan, issue, &validator.id)
+    }
+
+    fn decide(
+        &self,
+        plan: LlmPlan,
+        issue: IssueKind,
+        validator: &ValidatorId,
+    ) -> Result<Option<AgenticDecision>> {
+        plan.decide(
+            issue,
+            validator,
+            &self.command_ids,
+            self.reject_unknown_actions,
+            &self.model,
+            &self.prompt_version,
+        )
+    }
+}
+
+impl AnthropicPlanner {
+    fn try_new(cfg: AnthropicAgentConfig, command_ids: Vec<String>) -> Result<Self> {
+        let api_key = api_key(cfg.api_key_env, ANTHROPIC_API_KEY_ENV, "Anthropic")?;
+        let api_base = cfg.api_base.as_deref().unwrap_or(ANTHROPIC_API_BASE);
+        let (system_prompt, prompt_version) = system_prompt(cfg.system_prompt, cfg.prompt_version);
+        Ok(Self {
+            client: http_client(cfg.settings.request_timeout_secs)?,
+            api_key,
+            messages_url: format!("{}/v1/messages", api_base.trim_end_matches('/')),
+            model: cfg.model,
+            system_prompt,
+            prompt_version,
+            temperature: cfg.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+            max_tokens: cfg.max_tokens,
+            tool_use: cfg.tool_use,
+            command_ids,
+            reject_unknown_actions: cfg.settings.reject_unknown_actions,
+            retry: cfg.settings.retry,
+        })
+    }
+
+    fn request_body(&self, user_payload: String) -> serde_json::Value {
+        let mut body = json!({
+            "model": self.model,
+            "max_tokens": self.max_tokens,
+            "temperature": self.temperature,
+            "system": self.system_prompt,
+            "messages": [{ "role": "user", "content": user_payload }],
+        });
+        if self.tool_use {
+            body["tools"] = json!([{
+                "name": TOOL_NAME,
+                "description": TOOL_DESCRIPTION,
+                "input_schema": plan_schema(&self.command_ids),
+            }]);
+            body["tool_choice"] = json!({ "type": "tool", "name": TOOL_NAME });
         }
-        let decision = plan.into_decision(issue, &validator.id)?;
-        Ok(Some(decision))
+        body
+    }
+
+    async fn plan(
+        &self,
+        validator: &ValidatorConfig,
+        metrics: &ValidatorMetrics,
+        issue: IssueKind,
+        context: &PromptContext,
+        usage: &TokenUsage,
+    ) -> Result<Option<AgenticDecision>> {
+        let user_payload = prompt_payload(validator, metrics, issue, context, &self.command_ids)?;
+        let body = serde_json::to_string(&self.request_body(user_payload))?;
+        let body = send_with_retry(
+            || {
+                self.client
+                    .post(&self.messages_url)
+                    .header("x-api-key", &self.api_key)
+                    .header("anthropic-version", ANTHROPIC_VERSION)
+                    .header("Content-Type", "application/json")
+                    .body(body.clone())
+            },
+            self.retry,
+            "Anthropic messages request",
+            &validator.id,
+        )
+        .await?;
+        let response: AnthropicResponse =
+            serde_json::from_str(&body).context("unexpected Anthropic response")?;
+        if let Some(tokens) = &response.usage {
+            usage
+                .record(tokens.input_tokens + tokens.output_tokens, now_ts())
+                .await;
+        }
+
+        let mut text = String::new();
+        for block in response.content {
+            match block {
+                AnthropicContent::ToolUse { name, input } if name == TOOL_NAME => {
+                    let args = input.to_string();
+                    debug!(
+                        validator = validator.id.0,
+                        tool = TOOL_NAME,
+                        arguments = args.as_str(),
+                        "agentic provider tool response"
+                    );
+                    let plan =
+                        parse_plan_payload(&args).context("failed to parse tool use payload")?;
+                    return self.decide(plan, issue, &validator.id);
+                }
+                AnthropicContent::Text { text: part } => text.push_str(&part),
+                _ => {}
+            }
+        }
+
+        debug!(
+            validator = validator.id.0,
+            raw_response = text.as_str(),
+            "agentic provider response"
+        );
+        let plan =
+            parse_plan_payload(&text).context("failed to parse Anthropic response payload")?;
+        self.decide(plan, issue, &validator.id)
+    }
+
+    fn decide(
+        &self,
+        plan: LlmPlan,
+        issue: IssueKind,
+        validator: &ValidatorId,
+    ) -> Result<Option<AgenticDecision>> {
+        plan.decide(
+            issue,
+            validator,
+            &self.command_ids,
+            self.reject_unknown_actions,
+            &self.model,
+            &self.prompt_version,
+        )
+    }
+}
+
+/// Drop the steps of an agentic `playbook` whose kind `[agentic]
+/// allowed_actions` or the validator's `agentic_allowed_actions` leaves out,
+/// returning the kinds dropped.
+///
+/// Fails with the kind of a dropped step that undoes a kept one, such as the
+/// `enable_rpc` after a `disable_rpc`, leaving `playbook` as it was: the plan
+/// must then be discarded rather than leave the validator off RPC.
+fn strip_disallowed(
+    playbook: &mut Playbook,
+    allowed: Option<&[String]>,
+    validator: &ValidatorConfig,
+) -> Result<Vec<&'static str>, &'static str> {
+    let permits = |list: Option<&[String]>, kind: &str| {
+        list.is_none_or(|kinds| kinds.iter().any(|allowed| allowed == kind))
+    };
+    let dropped: Vec<bool> = playbook
+        .steps
+        .iter()
+        .map(|step| {
+            !permits(allowed, step.kind())
+                || !permits(validator.agentic_allowed_actions.as_deref(), step.kind())
+        })
+        .collect();
+    let removed: Vec<&Action> = playbook
+        .steps
+        .iter()
+        .zip(&dropped)
+        .filter(|(_, dropped)| **dropped)
+        .map(|(step, _)| step)
+        .collect();
+    let unmatched = playbook
+        .steps
+        .iter()
+        .zip(&dropped)
+        .filter(|(_, dropped)| !**dropped)
+        .filter_map(|(step, _)| step.compensation())
+        .find(|undo| removed.contains(&undo));
+    if let Some(undo) = unmatched {
+        return Err(undo.kind());
+    }
+    let kinds = removed.iter().map(|step| step.kind()).collect();
+    crate::conflicts::drop_steps(playbook, &dropped);
+    Ok(kinds)
+}
+
+/// Action library offered to the model; `exec_command` only when commands are registered.
+fn prompt_actions(command_ids: &[String]) -> Vec<&'static PromptAction> {
+    let mut actions: Vec<&'static PromptAction> = DEFAULT_ACTION_LIBRARY.iter().collect();
+    if !command_ids.is_empty() {
+        actions.push(&EXEC_COMMAND_ACTION);
     }
+    actions
+}
+
+fn plan_schema(command_ids: &[String]) -> serde_json::Value {
+    let mut kinds = vec![
+        "disable_rpc",
+        "enable_rpc",
+        "restart_validator",
+        "throttle_rpc_client",
+        "run_maintenance_script",
+        "send_alert",
+    ];
+    let mut properties = json!({
+        "kind": { "type": "string" },
+        "message": { "type": "string" },
+        "script_name": { "type": "string" },
+        "restart_kind": { "type": "string", "enum": ["soft", "hard"] }
+    });
+    let mut conditionals = vec![
+        json!({
+            "if": { "properties": { "kind": { "const": "send_alert" } } },
+            "then": { "required": ["message"] }
+        }),
+        json!({
+            "if": { "properties": { "kind": { "const": "run_maintenance_script" } } },
+            "then": { "required": ["script_name"] }
+        }),
+    ];
+    if !command_ids.is_empty() {
+        kinds.push("exec_command");
+        properties["command_id"] = json!({ "type": "string", "enum": command_ids });
+        conditionals.push(json!({
+            "if": { "properties": { "kind": { "const": "exec_command" } } },
+            "then": { "required": ["command_id"] }
+        }));
+    }
+    properties["kind"]["enum"] = json!(kinds);
+
+    json!({
+        "type": "object",
+        "properties": {
+            "playbook_id": { "type": "string", "minLength": 1 },
+            "rationale": { "type": "string", "minLength": 1 },
+            "actions": {
+                "type": "array",
+                "minItems": 1,
+                "items": {
+                    "type": "object",
+                    "properties": properties,
+                    "required": ["kind"],
+                    "additionalProperties": false,
+                    "allOf": conditionals
+                }
+            }
+        },
+        "required": ["playbook_id", "rationale", "actions"],
+        "additionalProperties": false
+    })
 }
 
 fn parse_plan_payload(raw: &str) -> Result<LlmPlan> {
+    let plan = deserialize_plan(raw).map_err(invalid_plan)?;
+    plan.validate().map_err(invalid_plan)?;
+    Ok(plan)
+}
+
+fn deserialize_plan(raw: &str) -> Result<LlmPlan> {
     let trimmed = raw.trim();
     if trimmed.is_empty() {
         bail!("empty agentic response");
@@ -394,7 +1084,52 @@ fn parse_plan_payload(raw: &str) -> Result<LlmPlan> {
 }
 
 impl LlmPlan {
-    fn into_decision(self, issue: IssueKind, validator: &ValidatorId) -> Result<AgenticDecision> {
+    /// Check kind-specific required fields so malformed model output fails at
+    /// parse time with the offending action named. The tool schema enforces
+    /// the same fields; this catches providers that don't honour it.
+    fn validate(&self) -> Result<()> {
+        for (index, action) in self.actions.iter().enumerate() {
+            if let Some(field) = action.missing_field() {
+                bail!(
+                    "action {index} ({}) is missing required field {field}",
+                    action.kind.name()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The plan as a decision stamped with the model and prompt that made
+    /// it, or `None` when the model proposed no actions at all.
+    fn decide(
+        self,
+        issue: IssueKind,
+        validator: &ValidatorId,
+        command_ids: &[String],
+        reject_unknown_actions: bool,
+        model: &str,
+        prompt_version: &str,
+    ) -> Result<Option<AgenticDecision>> {
+        if self.actions.is_empty() {
+            return Ok(None);
+        }
+        let decision = self
+            .into_decision(issue, validator, command_ids, reject_unknown_actions)
+            .map_err(invalid_plan)?;
+        Ok(Some(AgenticDecision {
+            model: model.to_string(),
+            prompt_version: prompt_version.to_string(),
+            ..decision
+        }))
+    }
+
+    fn into_decision(
+        self,
+        issue: IssueKind,
+        validator: &ValidatorId,
+        command_ids: &[String],
+        reject_unknown_actions: bool,
+    ) -> Result<AgenticDecision> {
         let id = if self.playbook_id.trim().is_empty() {
             format!("agentic-{issue:?}")
                 .replace(' ', "-")
@@ -402,11 +1137,20 @@ impl LlmPlan {
         } else {
             self.playbook_id
         };
-        let steps = self
-            .actions
-            .into_iter()
-            .map(|action| action.into_action(validator))
-            .collect::<Result<Vec<_>>>()?;
+        let mut steps = Vec::with_capacity(self.actions.len());
+        for action in self.actions {
+            if let LlmActionKind::Unknown(kind) = &action.kind {
+                if reject_unknown_actions {
+                    bail!("agentic plan used unknown action kind {kind}");
+                }
+                warn!(
+                    validator = validator.0,
+                    kind, "dropping agentic step with unknown action kind"
+                );
+                continue;
+            }
+            steps.push(action.into_action(validator, command_ids)?);
+        }
         if steps.is_empty() {
             bail!("agentic plan did not include any actions");
         }
@@ -415,19 +1159,38 @@ impl LlmPlan {
                 id,
                 trigger: issue,
                 steps,
+                depends_on: Default::default(),
             },
             rationale: self.rationale.filter(|r| !r.trim().is_empty()),
+            model: String::new(),
+            prompt_version: String::new(),
         })
     }
 }
 
 impl LlmActionSpec {
-    fn into_action(self, validator: &ValidatorId) -> Result<Action> {
+    fn missing_field(&self) -> Option<&'static str> {
+        let present =
+            |value: &Option<String>| value.as_deref().is_some_and(|v| !v.trim().is_empty());
+        match self.kind {
+            LlmActionKind::RunMaintenanceScript if !present(&self.script_name) => {
+                Some("script_name")
+            }
+            LlmActionKind::SendAlert if !present(&self.message) => Some("message"),
+            LlmActionKind::ExecCommand if !present(&self.command_id) => Some("command_id"),
+            _ => None,
+        }
+    }
+
+    fn into_action(self, validator: &ValidatorId, command_ids: &[String]) -> Result<Action> {
         let v = validator.clone();
         let action = match self.kind {
             LlmActionKind::DisableRpc => Action::DisableRpc { validator: v },
             LlmActionKind::EnableRpc => Action::EnableRpc { validator: v },
-            LlmActionKind::RestartValidator => Action::RestartValidator { validator: v },
+            LlmActionKind::RestartValidator => Action::RestartValidator {
+                validator: v,
+                restart_kind: self.restart_kind.unwrap_or_default(),
+            },
             LlmActionKind::ThrottleRpcClient => Action::ThrottleRpcClient { validator: v },
             LlmActionKind::RunMaintenanceScript => Action::RunMaintenanceScript {
                 validator: v,
@@ -435,6 +1198,7 @@ impl LlmActionSpec {
                     .script_name
                     .filter(|s| !s.trim().is_empty())
                     .context("run_maintenance_script requires script_name")?,
+                params: Default::default(),
             },
             LlmActionKind::SendAlert => Action::SendAlert {
                 validator: v,
@@ -443,6 +1207,14 @@ impl LlmActionSpec {
                     .filter(|s| !s.trim().is_empty())
                     .context("send_alert requires message")?,
             },
+            LlmActionKind::ExecCommand => Action::ExecCommand {
+                validator: v,
+                command_id: self
+                    .command_id
+                    .filter(|id| command_ids.contains(id))
+                    .context("exec_command requires a registered command_id")?,
+            },
+            LlmActionKind::Unknown(kind) => bail!("unknown action kind {kind}"),
         };
         Ok(action)
     }
@@ -451,7 +1223,8 @@ impl LlmActionSpec {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use common::{IssueKind, ValidatorId};
+    use crate::test_support::{lagging, validator};
+    use common::{DecisionCacheConfig, IssueKind, ValidatorId};
 
     fn validator_id() -> ValidatorId {
         ValidatorId("validator-test".into())
@@ -464,18 +1237,25 @@ mod tests {
             "rationale": "Restart to clear slot lag.",
             "actions": [
                 {"kind": "disable_rpc"},
-                {"kind": "restart_validator"},
+                {"kind": "restart_validator", "restart_kind": "soft"},
                 {"kind": "send_alert", "message": "Restarting validator to clear slot lag"}
             ]
         }"#;
         let plan = parse_plan_payload(raw).expect("plan parsed");
         let decision = plan
-            .into_decision(IssueKind::SlotLagHigh, &validator_id())
+            .into_decision(IssueKind::SlotLagHigh, &validator_id(), &[], false)
             .expect("decision");
         assert_eq!(decision.playbook.steps.len(), 3);
+        assert_eq!(
+            decision.playbook.steps[1],
+            Action::RestartValidator {
+                validator: validator_id(),
+                restart_kind: RestartKind::Soft,
+            }
+        );
     }
 
-≥⁄⁄    #[test]
+    #[test]
     fn extracts_json_from_code_fence() {
         let raw = "Here you go:\n```json\n{\"playbook_id\":\"abc\",\"actions\":[{\"kind\":\"disable_rpc\"},{\"kind\":\"send_alert\",\"message\":\"done\"}]}\n```";
         let plan = parse_plan_payload(raw).expect("parse from fence");
@@ -484,10 +1264,676 @@ mod tests {
 
     #[test]
     fn rejects_missing_required_fields() {
-        let raw = r#"{"actions":[{"kind":"run_maintenance_script"}]}"#;
-        let plan = parse_plan_payload(raw).expect("parsed");
-        assert!(plan
-            .into_decision(IssueKind::HardwareOverload, &validator_id())
+        let raw = r#"{"actions":[{"kind":"disable_rpc"},{"kind":"run_maintenance_script"}]}"#;
+        let err = parse_plan_payload(raw).expect_err("missing script_name rejected");
+        assert_eq!(
+            err.to_string(),
+            "action 1 (run_maintenance_script) is missing required field script_name"
+        );
+    }
+
+    #[test]
+    fn exec_command_limited_to_registered_ids() {
+        let registered = vec!["collect-diagnostics".to_string()];
+        let raw = r#"{"actions":[{"kind":"exec_command","command_id":"collect-diagnostics"}]}"#;
+        let decision = parse_plan_payload(raw)
+            .expect("parsed")
+            .into_decision(IssueKind::Unknown, &validator_id(), &registered, false)
+            .expect("registered command accepted");
+        assert_eq!(
+            decision.playbook.steps,
+            vec![Action::ExecCommand {
+                validator: validator_id(),
+                command_id: "collect-diagnostics".into(),
+            }]
+        );
+
+        let raw = r#"{"actions":[{"kind":"exec_command","command_id":"rm-everything"}]}"#;
+        assert!(parse_plan_payload(raw)
+            .expect("parsed")
+            .into_decision(IssueKind::Unknown, &validator_id(), &registered, false)
+            .is_err());
+    }
+
+    #[test]
+    fn unknown_action_kind_is_dropped_from_an_otherwise_valid_plan() {
+        let raw = r#"{"actions":[{"kind":"reboot_host"},{"kind":"restart_validator"}]}"#;
+        let decision = parse_plan_payload(raw)
+            .expect("unknown kind still parses")
+            .into_decision(IssueKind::SlotLagHigh, &validator_id(), &[], false)
+            .expect("valid steps kept");
+        assert_eq!(
+            decision.playbook.steps,
+            vec![Action::RestartValidator {
+                validator: validator_id(),
+                restart_kind: RestartKind::Hard,
+            }]
+        );
+
+        let err = parse_plan_payload(raw)
+            .unwrap()
+            .into_decision(IssueKind::SlotLagHigh, &validator_id(), &[], true)
+            .expect_err("rejected when configured");
+        assert_eq!(
+            err.to_string(),
+            "agentic plan used unknown action kind reboot_host"
+        );
+
+        let raw = r#"{"actions":[{"kind":"reboot_host"}]}"#;
+        assert!(parse_plan_payload(raw)
+            .unwrap()
+            .into_decision(IssueKind::SlotLagHigh, &validator_id(), &[], false)
             .is_err());
     }
+
+    /// Serves one canned provider reply at `path`; yields the request headers
+    /// and JSON body it received.
+    async fn fake_provider(
+        path: &'static str,
+        reply: serde_json::Value,
+    ) -> (
+        String,
+        tokio::sync::oneshot::Receiver<(axum::http::HeaderMap, serde_json::Value)>,
+    ) {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let tx = std::sync::Arc::new(std::sync::Mutex::new(Some(tx)));
+        let app = axum::Router::new().route(
+            path,
+            axum::routing::post(
+                move |headers: axum::http::HeaderMap,
+                      axum::Json(body): axum::Json<serde_json::Value>| {
+                    if let Some(tx) = tx.lock().unwrap().take() {
+                        let _ = tx.send((headers, body));
+                    }
+                    let reply = reply.clone();
+                    async move { axum::Json(reply) }
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{addr}"), rx)
+    }
+
+    fn claude(api_base: &str, tool_use: bool) -> AnthropicPlanner {
+        let (system_prompt, prompt_version) = system_prompt(None, None);
+        AnthropicPlanner {
+            client: reqwest::Client::new(),
+            api_key: "test-key".into(),
+            messages_url: format!("{api_base}/v1/messages"),
+            model: "claude-test".into(),
+            system_prompt,
+            prompt_version,
+            temperature: DEFAULT_TEMPERATURE,
+            max_tokens: 512,
+            tool_use,
+            command_ids: Vec::new(),
+            reject_unknown_actions: false,
+            retry: CompletionRetry::default(),
+        }
+    }
+
+    fn test_validator() -> ValidatorConfig {
+        validator("validator-test", None)
+    }
+
+    #[tokio::test]
+    async fn local_plan_degrades_to_reply_text_when_tool_choice_is_ignored() {
+        let (api_base, request) = fake_provider("/v1/chat/completions", json!({
+            "id": "chatcmpl-local",
+            "object": "chat.completion",
+            "created": 0,
+            "model": "qwen2.5-7b-instruct",
+            "choices": [{
+                "index": 0,
+                "finish_reason": "stop",
+                "message": {
+                    "role": "assistant",
+                    "content": "Plan:\n{\"playbook_id\":\"local-restart\",\"actions\":[{\"kind\":\"restart_validator\"}]}"
+                }
+            }],
+            "usage": {"prompt_tokens": 800, "completion_tokens": 30, "total_tokens": 830}
+        }))
+        .await;
+        let planner = OpenAiPlanner::compatible(
+            OpenAiCompatibleAgentConfig {
+                api_base: format!("{api_base}/v1"),
+                model: "qwen2.5-7b-instruct".into(),
+                api_key_env: None,
+                system_prompt: None,
+                prompt_version: None,
+                temperature: None,
+                settings: AgenticSettings {
+                    mode: AgenticMode::Active,
+                    ..AgenticSettings::default()
+                },
+            },
+            Vec::new(),
+        )
+        .unwrap();
+        let usage = TokenUsage::default();
+        let decision = planner
+            .plan(
+                &test_validator(),
+                &lagging(150),
+                IssueKind::SlotLagHigh,
+                &PromptContext::default(),
+                &usage,
+            )
+            .await
+            .unwrap()
+            .expect("plan");
+        assert_eq!(decision.playbook.id, "local-restart");
+        assert_eq!(decision.model, "qwen2.5-7b-instruct");
+        assert_eq!(usage.snapshot(now_ts()).await.hour_tokens, 830);
+
+        let (headers, body) = request.await.unwrap();
+        assert!(!headers.contains_key("authorization"));
+        let mut fields: Vec<&str> = body
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        fields.sort_unstable();
+        assert_eq!(
+            fields,
+            ["messages", "model", "temperature", "tool_choice", "tools"]
+        );
+        assert_eq!(
+            body["tool_choice"],
+            json!({"type": "function", "function": {"name": TOOL_NAME}})
+        );
+    }
+
+    /// Answers each POST to `/v1/chat/completions` with the next of
+    /// `replies`, a JSON string as an HTML page; yields how many requests it
+    /// received.
+    async fn flaky_openai(
+        replies: Vec<(u16, serde_json::Value)>,
+    ) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        use std::sync::atomic::Ordering;
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = axum::Router::new().route(
+            "/v1/chat/completions",
+            axum::routing::post(move || {
+                let (status, body) = replies[counter.fetch_add(1, Ordering::SeqCst)].clone();
+                async move {
+                    use axum::response::IntoResponse;
+                    let status = axum::http::StatusCode::from_u16(status).unwrap();
+                    match body {
+                        serde_json::Value::String(page) => {
+                            (status, axum::response::Html(page)).into_response()
+                        }
+                        body => (status, axum::Json(body)).into_response(),
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{addr}/v1"), calls)
+    }
+
+    fn openai(api_base: String, max_attempts: u32) -> OpenAiPlanner {
+        OpenAiPlanner::with_api_key(
+            OpenAiAgentConfig {
+                model: "gpt-test".into(),
+                api_base: Some(api_base),
+                system_prompt: None,
+                prompt_version: None,
+                temperature: None,
+                api_key_env: None,
+                settings: AgenticSettings {
+                    mode: AgenticMode::Active,
+                    retry: CompletionRetry {
+                        max_attempts,
+                        initial_backoff_ms: 1,
+                    },
+                    ..AgenticSettings::default()
+                },
+            },
+            "test-key".into(),
+            Vec::new(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn transient_completion_failures_are_retried_but_bad_requests_are_not() {
+        let overloaded = (
+            503,
+            json!({"error": {"message": "overloaded", "type": "server_error", "param": null, "code": null}}),
+        );
+        let rate_limited = (
+            429,
+            json!({"error": {"message": "slow down", "type": "requests", "param": null, "code": "rate_limit_exceeded"}}),
+        );
+        let completion = (
+            200,
+            json!({
+                "id": "chatcmpl-retry",
+                "object": "chat.completion",
+                "created": 0,
+                "model": "gpt-test",
+                "choices": [{
+                    "index": 0,
+                    "finish_reason": "stop",
+                    "message": {
+                        "role": "assistant",
+                        "content": "{\"playbook_id\":\"retried\",\"actions\":[{\"kind\":\"restart_validator\"}]}"
+                    }
+                }]
+            }),
+        );
+        let plan = |planner: OpenAiPlanner| async move {
+            planner
+                .plan(
+                    &test_validator(),
+                    &lagging(150),
+                    IssueKind::SlotLagHigh,
+                    &PromptContext::default(),
+                    &TokenUsage::default(),
+                )
+                .await
+        };
+
+        // A proxy's error page is retried on its status alone.
+        let bad_gateway = (
+            502,
+            json!("<html><body><h1>502 Bad Gateway</h1></body></html>"),
+        );
+        let (api_base, calls) = flaky_openai(vec![
+            overloaded.clone(),
+            rate_limited,
+            bad_gateway,
+            completion,
+        ])
+        .await;
+        let decision = plan(openai(api_base, 4)).await.unwrap().expect("plan");
+        assert_eq!(decision.playbook.id, "retried");
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 4);
+
+        let (api_base, calls) = flaky_openai(vec![overloaded.clone(), overloaded.clone()]).await;
+        assert!(plan(openai(api_base, 2)).await.is_err());
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        let invalid = (
+            400,
+            json!({"error": {"message": "bad schema", "type": "invalid_request_error", "param": "tools", "code": null}}),
+        );
+        let (api_base, calls) = flaky_openai(vec![invalid, overloaded]).await;
+        assert!(plan(openai(api_base, 3)).await.is_err());
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn repeated_situations_reuse_the_cached_decision() {
+        let completion = json!({
+            "id": "chatcmpl-cached",
+            "object": "chat.completion",
+            "created": 0,
+            "model": "gpt-test",
+            "choices": [{
+                "index": 0,
+                "finish_reason": "stop",
+                "message": {
+                    "role": "assistant",
+                    "content": "{\"playbook_id\":\"cached\",\"actions\":[{\"kind\":\"restart_validator\"}]}"
+                }
+            }]
+        });
+        let (api_base, calls) = flaky_openai(vec![(200, completion)]).await;
+        let brain = AgenticBrain {
+            planner: Planner::OpenAi(Box::new(openai(api_base, 1))),
+            mode: AgenticMode::Active,
+            budget: TokenBudget::default(),
+            context: PromptContextConfig::default(),
+            allowed_actions: None,
+            usage: TokenUsage::default(),
+            cache: DecisionCache::new(DecisionCacheConfig::default()),
+            calls: CallMetrics::default(),
+        };
+        let mut metrics = lagging(150);
+        for slot_lag in [150, 160] {
+            metrics.slot_lag = slot_lag;
+            let decision = brain
+                .plan(
+                    &test_validator(),
+                    &metrics,
+                    IssueKind::SlotLagHigh,
+                    &PromptContext::default(),
+                )
+                .await
+                .unwrap()
+                .expect("plan");
+            assert_eq!(decision.playbook.id, "cached");
+        }
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(brain.cache_stats().await, CacheStats { hits: 1, misses: 1 });
+    }
+
+    #[tokio::test]
+    async fn provider_calls_are_counted_by_outcome_with_their_latency() {
+        let completion = |content: &str| {
+            (
+                200,
+                json!({
+                    "id": "chatcmpl-counted",
+                    "object": "chat.completion",
+                    "created": 0,
+                    "model": "gpt-test",
+                    "choices": [{
+                        "index": 0,
+                        "finish_reason": "stop",
+                        "message": { "role": "assistant", "content": content }
+                    }]
+                }),
+            )
+        };
+        let (api_base, _) = flaky_openai(vec![
+            completion(r#"{"playbook_id":"counted","actions":[{"kind":"restart_validator"}]}"#),
+            completion("no plan today"),
+        ])
+        .await;
+        let brain = AgenticBrain {
+            planner: Planner::OpenAi(Box::new(openai(api_base, 1))),
+            mode: AgenticMode::Active,
+            budget: TokenBudget::default(),
+            context: PromptContextConfig::default(),
+            allowed_actions: None,
+            usage: TokenUsage::default(),
+            cache: DecisionCache::new(DecisionCacheConfig::default()),
+            calls: CallMetrics::default(),
+        };
+        let plan = |slot_lag: i64| {
+            let brain = &brain;
+            async move {
+                let metrics = ValidatorMetrics {
+                    slot_lag,
+                    ..lagging(150)
+                };
+                brain
+                    .plan(
+                        &test_validator(),
+                        &metrics,
+                        IssueKind::SlotLagHigh,
+                        &PromptContext::default(),
+                    )
+                    .await
+            }
+        };
+
+        let decision = plan(150).await.unwrap().expect("plan");
+        assert_eq!(decision.playbook.id, "counted");
+        let rendered = brain.render_metrics(now_ts()).await;
+        assert!(rendered.contains("agent_agentic_calls_total{outcome=\"success\"} 1\n"));
+        assert!(rendered.contains("agent_agentic_calls_total{outcome=\"parse_error\"} 0\n"));
+        assert!(rendered.contains("agent_agentic_latency_seconds_count 1\n"));
+
+        // A cache hit sends no request, so it is not a call.
+        plan(160).await.unwrap().expect("cached plan");
+        assert!(plan(400).await.is_err());
+        assert_eq!(brain.calls.count(CallOutcome::Success).await, 1);
+        assert_eq!(brain.calls.count(CallOutcome::ParseError).await, 1);
+        let rendered = brain.render_metrics(now_ts()).await;
+        assert!(rendered.contains("agent_agentic_latency_seconds_count 2\n"));
+    }
+
+    #[test]
+    fn payload_carries_recent_actions_only_when_there_are_some() {
+        let payload = |context: &PromptContext| -> serde_json::Value {
+            let raw = prompt_payload(
+                &test_validator(),
+                &lagging(150),
+                IssueKind::SlotLagHigh,
+                context,
+                &[],
+            )
+            .unwrap();
+            serde_json::from_str(&raw).unwrap()
+        };
+        let bare = payload(&PromptContext::default());
+        assert!(bare.get("recent_actions").is_none());
+        assert!(bare.get("recent_metrics").is_none());
+
+        let context = PromptContext {
+            recent_metrics: vec![lagging(150)],
+            recent_actions: vec![RecentAction::from(ActionRecord {
+                validator_id: "validator-test".into(),
+                action_json: r#"{"kind":"restart_validator","validator":"validator-test"}"#.into(),
+                success: true,
+                message: "restarted".into(),
+                timestamp: 1_700_000_000,
+                dry_run: false,
+            })],
+        };
+        let with_context = payload(&context);
+        assert_eq!(
+            with_context["recent_actions"],
+            json!([{
+                "action": {"kind": "restart_validator", "validator": "validator-test"},
+                "success": true,
+                "message": "restarted",
+                "timestamp": 1_700_000_000,
+            }])
+        );
+        assert_eq!(with_context["recent_metrics"][0]["slot_lag"], 150);
+        assert_eq!(with_context["metrics"]["slot_lag"], 150);
+    }
+
+    #[tokio::test]
+    async fn anthropic_plan_comes_from_the_forced_tool_call() {
+        let (api_base, request) = fake_provider(
+            "/v1/messages",
+            json!({
+                "content": [
+                    {"type": "text", "text": "Restarting."},
+                    {"type": "tool_use", "id": "toolu_1", "name": TOOL_NAME, "input": {
+                        "playbook_id": "claude-restart",
+                        "rationale": "Slot lag is high.",
+                        "actions": [{"kind": "restart_validator"}]
+                    }}
+                ],
+                "usage": {"input_tokens": 300, "output_tokens": 40}
+            }),
+        )
+        .await;
+        let usage = TokenUsage::default();
+        let decision = claude(&api_base, true)
+            .plan(
+                &test_validator(),
+                &lagging(150),
+                IssueKind::SlotLagHigh,
+                &PromptContext::default(),
+                &usage,
+            )
+            .await
+            .unwrap()
+            .expect("plan");
+        assert_eq!(decision.playbook.id, "claude-restart");
+        assert_eq!(
+            decision.playbook.steps,
+            vec![Action::RestartValidator {
+                validator: validator_id(),
+                restart_kind: RestartKind::Hard,
+            }]
+        );
+        assert_eq!(decision.model, "claude-test");
+        assert_eq!(decision.prompt_version, DEFAULT_PROMPT_VERSION);
+        assert_eq!(usage.snapshot(now_ts()).await.hour_tokens, 340);
+
+        let (headers, body) = request.await.unwrap();
+        assert_eq!(headers["x-api-key"], "test-key");
+        assert_eq!(headers["anthropic-version"], ANTHROPIC_VERSION);
+        assert_eq!(body["model"], "claude-test");
+        assert_eq!(body["max_tokens"], 512);
+        assert_eq!(
+            body["tool_choice"],
+            json!({"type": "tool", "name": TOOL_NAME})
+        );
+        assert_eq!(body["tools"][0]["input_schema"], plan_schema(&[]));
+        let payload: serde_json::Value =
+            serde_json::from_str(body["messages"][0]["content"].as_str().unwrap()).unwrap();
+        assert_eq!(payload["issue"], json!(IssueKind::SlotLagHigh));
+        assert_eq!(payload["validator"]["id"], "validator-test");
+    }
+
+    #[tokio::test]
+    async fn anthropic_plan_falls_back_to_the_reply_text_without_tool_use() {
+        let (api_base, request) = fake_provider("/v1/messages", json!({
+            "content": [{"type": "text", "text": "{\"playbook_id\":\"claude-alert\",\"actions\":[{\"kind\":\"send_alert\",\"message\":\"lagging\"}]}"}],
+            "usage": {"input_tokens": 10, "output_tokens": 5}
+        }))
+        .await;
+        let decision = claude(&api_base, false)
+            .plan(
+                &test_validator(),
+                &lagging(150),
+                IssueKind::SlotLagHigh,
+                &PromptContext::default(),
+                &TokenUsage::default(),
+            )
+            .await
+            .unwrap()
+            .expect("plan");
+        assert_eq!(decision.playbook.id, "claude-alert");
+
+        let (_, body) = request.await.unwrap();
+        assert!(body.get("tools").is_none());
+        assert!(body.get("tool_choice").is_none());
+    }
+
+    #[tokio::test]
+    async fn anthropic_request_past_its_timeout_fails() {
+        let app = axum::Router::new().route(
+            "/v1/messages",
+            axum::routing::post(|| async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                "{}"
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        std::env::set_var("ANTHROPIC_TIMEOUT_TEST_KEY", "test-key");
+        let cfg: AnthropicAgentConfig = serde_json::from_value(json!({
+            "api_base": format!("http://{addr}"),
+            "api_key_env": "ANTHROPIC_TIMEOUT_TEST_KEY",
+            "request_timeout_secs": 1,
+            "retry": { "max_attempts": 1 },
+        }))
+        .unwrap();
+
+        let started = std::time::Instant::now();
+        let err = AnthropicPlanner::try_new(cfg, Vec::new())
+            .unwrap()
+            .plan(
+                &test_validator(),
+                &lagging(150),
+                IssueKind::SlotLagHigh,
+                &PromptContext::default(),
+                &TokenUsage::default(),
+            )
+            .await
+            .expect_err("timed out");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(
+            err.chain()
+                .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+                .any(reqwest::Error::is_timeout),
+            "{err:#}"
+        );
+    }
+
+    #[test]
+    fn steps_outside_the_allowlists_are_stripped() {
+        let raw = r#"{"actions":[{"kind":"disable_rpc"},{"kind":"restart_validator"},{"kind":"send_alert","message":"lagging"}]}"#;
+        let plan = || {
+            parse_plan_payload(raw)
+                .unwrap()
+                .into_decision(IssueKind::SlotLagHigh, &validator_id(), &[], false)
+                .unwrap()
+                .playbook
+        };
+        let kinds = |playbook: &Playbook| -> Vec<&str> {
+            playbook.steps.iter().map(Action::kind).collect()
+        };
+        let voting = ValidatorConfig {
+            agentic_allowed_actions: Some(vec!["send_alert".into(), "disable_rpc".into()]),
+            ..test_validator()
+        };
+
+        let mut unrestricted = plan();
+        assert!(strip_disallowed(&mut unrestricted, None, &test_validator())
+            .unwrap()
+            .is_empty());
+        assert_eq!(unrestricted.steps.len(), 3);
+
+        let mut per_validator = plan();
+        assert_eq!(
+            strip_disallowed(&mut per_validator, None, &voting).unwrap(),
+            ["restart_validator"]
+        );
+        assert_eq!(kinds(&per_validator), ["disable_rpc", "send_alert"]);
+
+        // The validator's list narrows the global one.
+        let global = ["send_alert".to_string(), "restart_validator".to_string()];
+        let mut both = plan();
+        assert_eq!(
+            strip_disallowed(&mut both, Some(&global), &voting).unwrap(),
+            ["disable_rpc", "restart_validator"]
+        );
+        assert_eq!(kinds(&both), ["send_alert"]);
+
+        let mut nothing_left = plan();
+        strip_disallowed(
+            &mut nothing_left,
+            Some(&["enable_rpc".to_string()]),
+            &test_validator(),
+        )
+        .unwrap();
+        assert!(nothing_left.steps.is_empty());
+    }
+
+    #[test]
+    fn stripping_the_step_that_undoes_a_kept_one_rejects_the_plan() {
+        let raw = r#"{"actions":[{"kind":"disable_rpc"},{"kind":"restart_validator"},{"kind":"enable_rpc"}]}"#;
+        let mut playbook = parse_plan_payload(raw)
+            .unwrap()
+            .into_decision(IssueKind::SlotLagHigh, &validator_id(), &[], false)
+            .unwrap()
+            .playbook;
+        let no_enable = ["disable_rpc".to_string(), "restart_validator".to_string()];
+        assert_eq!(
+            strip_disallowed(&mut playbook, Some(&no_enable), &test_validator()),
+            Err("enable_rpc")
+        );
+        assert_eq!(playbook.steps.len(), 3);
+
+        // Dropping the disable along with it leaves nothing to undo.
+        let restart_only = ["restart_validator".to_string()];
+        assert_eq!(
+            strip_disallowed(&mut playbook, Some(&restart_only), &test_validator()),
+            Ok(vec!["disable_rpc", "enable_rpc"])
+        );
+    }
+
+    #[test]
+    fn schema_offers_exec_command_only_when_registered() {
+        let without = plan_schema(&[]);
+        let kinds = &without["properties"]["actions"]["items"]["properties"]["kind"]["enum"];
+        assert!(!kinds.as_array().unwrap().contains(&json!("exec_command")));
+
+        let with = plan_schema(&["collect-diagnostics".to_string()]);
+        let item = &with["properties"]["actions"]["items"]["properties"];
+        assert!(item["kind"]["enum"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("exec_command")));
+        assert_eq!(item["command_id"]["enum"], json!(["collect-diagnostics"]));
+    }
 }
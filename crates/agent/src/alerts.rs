@@ -0,0 +1,111 @@
+use common::{Action, AlertsConfig, IssueKind, Playbook};
+
+/// Values available to alert message templates.
+pub struct AlertContext<'a> {
+    pub validator: &'a str,
+    pub host: &'a str,
+    pub issue: IssueKind,
+    pub risk: f64,
+}
+
+/// Replace `{{name}}` placeholders in `template`. `message` is the alert's
+/// original text; unknown placeholders are left as written.
+pub fn render(template: &str, ctx: &AlertContext, message: &str) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        let name = after[..end].trim();
+        match name {
+            "validator" => out.push_str(ctx.validator),
+            "host" => out.push_str(ctx.host),
+            "issue" => out.push_str(ctx.issue.as_str()),
+            "risk" => out.push_str(&format!("{:.2}", ctx.risk)),
+            "message" => out.push_str(message),
+            _ => out.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Render the configured template for the playbook's issue into every
+/// `SendAlert` step. Without a matching template messages stay literal.
+pub fn apply_templates(cfg: &AlertsConfig, playbook: &mut Playbook, ctx: &AlertContext) {
+    let Some(template) = cfg.templates.get(ctx.issue.as_str()) else {
+        return;
+    };
+    for step in &mut playbook.steps {
+        if let Action::SendAlert { message, .. } = step {
+            *message = render(template, ctx, message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{RestartKind, ValidatorId};
+
+    fn ctx() -> AlertContext<'static> {
+        AlertContext {
+            validator: "validator-1",
+            host: "10.0.0.5",
+            issue: IssueKind::SlotLagHigh,
+            risk: 0.734,
+        }
+    }
+
+    fn playbook() -> Playbook {
+        Playbook {
+            id: "pb-slot-lag".into(),
+            trigger: IssueKind::SlotLagHigh,
+            steps: vec![
+                Action::RestartValidator {
+                    validator: ValidatorId("validator-1".into()),
+                    restart_kind: RestartKind::Hard,
+                },
+                Action::SendAlert {
+                    validator: ValidatorId("validator-1".into()),
+                    message: "restarted".into(),
+                },
+            ],
+            depends_on: Default::default(),
+        }
+    }
+
+    #[test]
+    fn template_renders_validator_host_and_risk() {
+        let cfg = AlertsConfig {
+            templates: [(
+                "slot_lag_high".to_string(),
+                "[{{issue}}] {{validator}} on {{host}} (risk {{risk}}): {{message}} {{unknown}}"
+                    .to_string(),
+            )]
+            .into(),
+            ..AlertsConfig::default()
+        };
+        let mut playbook = playbook();
+        apply_templates(&cfg, &mut playbook, &ctx());
+        let Action::SendAlert { message, .. } = &playbook.steps[1] else {
+            panic!("expected alert step");
+        };
+        assert_eq!(
+            message,
+            "[slot_lag_high] validator-1 on 10.0.0.5 (risk 0.73): restarted {{unknown}}"
+        );
+    }
+
+    #[test]
+    fn missing_template_keeps_literal_message() {
+        let mut playbook = playbook();
+        apply_templates(&AlertsConfig::default(), &mut playbook, &ctx());
+        assert_eq!(playbook, self::playbook());
+    }
+}
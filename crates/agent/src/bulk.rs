@@ -0,0 +1,158 @@
+use common::{Action, RemediationConfig, ValidatorId};
+use executor::proto::ActionEnvelope;
+use serde::Serialize;
+use tonic::Status;
+use tracing::error;
+
+use crate::dispatch::ActionDispatcher;
+
+/// One validator of a multi-target request: the action to submit, or why it
+/// is left out.
+pub struct BulkTarget {
+    pub validator: ValidatorId,
+    pub action: Result<Action, String>,
+}
+
+/// Per-validator outcome of an endpoint that submits to many validators.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct BulkResult {
+    /// Validators whose action the daemon accepted.
+    pub submitted: Vec<String>,
+    pub failed: Vec<BulkFailure>,
+    pub skipped: Vec<BulkSkip>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BulkFailure {
+    pub validator: String,
+    pub error: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct BulkSkip {
+    pub validator: String,
+    pub reason: String,
+}
+
+/// Submit every target's action in order. A failed submission does not stop
+/// the rest.
+pub async fn submit_all<D: ActionDispatcher>(
+    dispatcher: &mut D,
+    remediation: &RemediationConfig,
+    targets: Vec<BulkTarget>,
+) -> BulkResult {
+    let mut result = BulkResult::default();
+    for target in targets {
+        let validator = target.validator.0;
+        let action = match target.action {
+            Ok(action) => action,
+            Err(reason) => {
+                result.skipped.push(BulkSkip { validator, reason });
+                continue;
+            }
+        };
+        let outcome = match serde_json::to_string(&action) {
+            Ok(action_json) => {
+                dispatcher
+                    .dispatch(ActionEnvelope {
+                        validator_id: validator.clone(),
+                        action_json,
+                        idempotency_key: String::new(),
+                        priority: remediation.priority_for(&action),
+                        ttl_secs: 0,
+                        ..ActionEnvelope::default()
+                    })
+                    .await
+            }
+            Err(err) => Err(Status::internal(err.to_string())),
+        };
+        match outcome {
+            Ok(()) => result.submitted.push(validator),
+            Err(err) => {
+                error!(
+                    validator,
+                    kind = action.kind(),
+                    ?err,
+                    "bulk submission failed"
+                );
+                result.failed.push(BulkFailure {
+                    validator,
+                    error: err.message().to_string(),
+                });
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::RestartKind;
+
+    /// Accepts everything except envelopes for `reject`.
+    struct FakeDispatcher {
+        reject: &'static str,
+        accepted: Vec<ActionEnvelope>,
+    }
+
+    impl ActionDispatcher for FakeDispatcher {
+        async fn dispatch(&mut self, envelope: ActionEnvelope) -> Result<(), Status> {
+            if envelope.validator_id == self.reject {
+                return Err(Status::unavailable("validator v2 is not connected"));
+            }
+            self.accepted.push(envelope);
+            Ok(())
+        }
+    }
+
+    fn target(id: &str, action: Result<Action, &str>) -> BulkTarget {
+        BulkTarget {
+            validator: ValidatorId(id.into()),
+            action: action.map_err(str::to_string),
+        }
+    }
+
+    fn restart(id: &str) -> Result<Action, &str> {
+        Ok(Action::RestartValidator {
+            validator: ValidatorId(id.into()),
+            restart_kind: RestartKind::Hard,
+        })
+    }
+
+    #[tokio::test]
+    async fn mixed_targets_are_categorized() {
+        let mut dispatcher = FakeDispatcher {
+            reject: "v2",
+            accepted: Vec::new(),
+        };
+        let targets = vec![
+            target("v1", restart("v1")),
+            target("v2", restart("v2")),
+            target("v3", Err("unknown validator")),
+            target("v4", restart("v4")),
+        ];
+
+        let result = submit_all(&mut dispatcher, &RemediationConfig::default(), targets).await;
+
+        assert_eq!(
+            result,
+            BulkResult {
+                submitted: vec!["v1".into(), "v4".into()],
+                failed: vec![BulkFailure {
+                    validator: "v2".into(),
+                    error: "validator v2 is not connected".into(),
+                }],
+                skipped: vec![BulkSkip {
+                    validator: "v3".into(),
+                    reason: "unknown validator".into(),
+                }],
+            }
+        );
+        assert_eq!(dispatcher.accepted.len(), 2);
+        assert_eq!(
+            dispatcher.accepted[0].priority,
+            RemediationConfig::default().priority_for(&restart("v1").unwrap())
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+
+/// Upper bounds, in seconds, of the `agent_agentic_latency_seconds` buckets.
+const LATENCY_BUCKETS: [f64; 10] = [0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0];
+
+/// How one request to the agentic provider ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallOutcome {
+    /// The provider answered with a usable plan.
+    Success,
+    /// The provider answered with no plan, or a plan without actions.
+    Empty,
+    /// The answer could not be parsed into a valid plan.
+    ParseError,
+    /// The request timed out.
+    Timeout,
+    /// Any other failure, e.g. a rejected request or a refused connection.
+    Error,
+}
+
+impl CallOutcome {
+    const ALL: [CallOutcome; 5] = [
+        CallOutcome::Success,
+        CallOutcome::Empty,
+        CallOutcome::ParseError,
+        CallOutcome::Timeout,
+        CallOutcome::Error,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CallOutcome::Success => "success",
+            CallOutcome::Empty => "empty",
+            CallOutcome::ParseError => "parse_error",
+            CallOutcome::Timeout => "timeout",
+            CallOutcome::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct CallStats {
+    /// Calls by `CallOutcome::ALL` index.
+    outcomes: [u64; CallOutcome::ALL.len()],
+    /// Per-bucket (non-cumulative) counts; the last slot is `+Inf`.
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+/// Outcome counters and a latency histogram for the requests the agentic
+/// planner sends its provider. Cache hits and budget fallbacks send none, so
+/// they are not counted here.
+#[derive(Clone, Debug, Default)]
+pub struct CallMetrics {
+    inner: Arc<Mutex<CallStats>>,
+}
+
+impl CallMetrics {
+    pub async fn record(&self, outcome: CallOutcome, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        let mut inner = self.inner.lock().await;
+        inner.outcomes[outcome as usize] += 1;
+        inner.buckets[LATENCY_BUCKETS.partition_point(|le| *le < secs)] += 1;
+        inner.sum += secs;
+        inner.count += 1;
+    }
+
+    /// Calls that ended with `outcome` so far.
+    #[cfg(test)]
+    pub async fn count(&self, outcome: CallOutcome) -> u64 {
+        self.inner.lock().await.outcomes[outcome as usize]
+    }
+
+    pub async fn render_metrics(&self) -> String {
+        let mut out = String::new();
+        let inner = self.inner.lock().await;
+        out.push_str("# HELP agent_agentic_calls_total Agentic provider requests by outcome.\n");
+        out.push_str("# TYPE agent_agentic_calls_total counter\n");
+        for outcome in CallOutcome::ALL {
+            let _ = writeln!(
+                out,
+                "agent_agentic_calls_total{{outcome=\"{}\"}} {}",
+                outcome.as_str(),
+                inner.outcomes[outcome as usize]
+            );
+        }
+        out.push_str(
+            "# HELP agent_agentic_latency_seconds Time each agentic provider request took.\n",
+        );
+        out.push_str("# TYPE agent_agentic_latency_seconds histogram\n");
+        let mut cumulative = 0;
+        for (le, count) in LATENCY_BUCKETS.iter().zip(&inner.buckets) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "agent_agentic_latency_seconds_bucket{{le=\"{le}\"}} {cumulative}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "agent_agentic_latency_seconds_bucket{{le=\"+Inf\"}} {}",
+            inner.count
+        );
+        let _ = writeln!(out, "agent_agentic_latency_seconds_sum {}", inner.sum);
+        let _ = writeln!(out, "agent_agentic_latency_seconds_count {}", inner.count);
+        out
+    }
+}
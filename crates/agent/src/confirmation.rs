@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use common::{IssueKind, ValidatorId};
+use tokio::sync::Mutex;
+
+/// When each of a validator's current issues was first seen, by sample
+/// timestamp.
+///
+/// Confirmation is time-based rather than sample-count-based, so a client
+/// publishing far faster than expected cannot confirm an issue early.
+#[derive(Clone, Default)]
+pub struct Confirmations {
+    inner: Arc<Mutex<HashMap<ValidatorId, HashMap<IssueKind, i64>>>>,
+}
+
+impl Confirmations {
+    /// Seconds `issue` must still persist on `validator` before it is
+    /// confirmed, or `None` once it has lasted `confirm_secs`.
+    pub async fn pending(
+        &self,
+        validator: &ValidatorId,
+        issue: IssueKind,
+        sample_ts: i64,
+        confirm_secs: i64,
+    ) -> Option<i64> {
+        let mut inner = self.inner.lock().await;
+        let onset = *inner
+            .entry(validator.clone())
+            .or_default()
+            .entry(issue)
+            .or_insert(sample_ts);
+        let remaining = onset + confirm_secs - sample_ts;
+        (remaining > 0).then_some(remaining)
+    }
+
+    /// Forget the onsets of issues the validator no longer shows.
+    pub async fn retain(&self, validator: &ValidatorId, current: &[IssueKind]) {
+        let mut inner = self.inner.lock().await;
+        if let Some(onsets) = inner.get_mut(validator) {
+            onsets.retain(|issue, _| current.contains(issue));
+            if onsets.is_empty() {
+                inner.remove(validator);
+            }
+        }
+    }
+
+    /// Every unconfirmed-or-confirmed issue and its onset.
+    pub async fn snapshot(&self) -> Vec<(ValidatorId, IssueKind, i64)> {
+        self.inner
+            .lock()
+            .await
+            .iter()
+            .flat_map(|(validator, onsets)| {
+                onsets
+                    .iter()
+                    .map(|(issue, onset)| (validator.clone(), *issue, *onset))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIRM_SECS: i64 = 30;
+
+    fn validator() -> ValidatorId {
+        ValidatorId("v1".into())
+    }
+
+    #[tokio::test]
+    async fn burst_of_samples_does_not_confirm_early() {
+        let confirmations = Confirmations::default();
+        // A client publishing every 100ms: 10 samples per second for 29s.
+        for tenth in 0..290 {
+            let sample_ts = 100 + tenth / 10;
+            let pending = confirmations
+                .pending(
+                    &validator(),
+                    IssueKind::SlotLagHigh,
+                    sample_ts,
+                    CONFIRM_SECS,
+                )
+                .await;
+            assert!(pending.is_some(), "confirmed after {tenth} samples");
+        }
+        let pending = confirmations
+            .pending(&validator(), IssueKind::SlotLagHigh, 130, CONFIRM_SECS)
+            .await;
+        assert_eq!(pending, None);
+    }
+
+    #[tokio::test]
+    async fn sparse_samples_confirm_after_duration() {
+        let confirmations = Confirmations::default();
+        let first = confirmations
+            .pending(&validator(), IssueKind::SlotLagHigh, 100, CONFIRM_SECS)
+            .await;
+        assert_eq!(first, Some(CONFIRM_SECS));
+        let second = confirmations
+            .pending(&validator(), IssueKind::SlotLagHigh, 130, CONFIRM_SECS)
+            .await;
+        assert_eq!(second, None);
+    }
+
+    #[tokio::test]
+    async fn simultaneous_issues_confirm_independently_and_restart_once_cleared() {
+        let confirmations = Confirmations::default();
+        confirmations
+            .pending(&validator(), IssueKind::SlotLagHigh, 100, CONFIRM_SECS)
+            .await;
+        let other = confirmations
+            .pending(&validator(), IssueKind::RpcOverload, 120, CONFIRM_SECS)
+            .await;
+        assert_eq!(other, Some(CONFIRM_SECS));
+        let lag = confirmations
+            .pending(&validator(), IssueKind::SlotLagHigh, 130, CONFIRM_SECS)
+            .await;
+        assert_eq!(lag, None);
+
+        confirmations
+            .retain(&validator(), &[IssueKind::SlotLagHigh])
+            .await;
+        let again = confirmations
+            .pending(&validator(), IssueKind::RpcOverload, 150, CONFIRM_SECS)
+            .await;
+        assert_eq!(again, Some(CONFIRM_SECS));
+        confirmations.retain(&validator(), &[]).await;
+        assert!(confirmations.snapshot().await.is_empty());
+    }
+}
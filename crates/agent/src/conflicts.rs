@@ -0,0 +1,222 @@
+use std::collections::{BTreeMap, HashMap};
+
+use common::{Action, ConflictPolicy, Playbook};
+
+/// Two steps of one dispatch that undo each other, by their index in the
+/// playbook as planned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub first: usize,
+    pub second: usize,
+    pub kinds: (&'static str, &'static str),
+}
+
+fn mutually_exclusive(a: &Action, b: &Action) -> bool {
+    matches!(
+        (a, b),
+        (Action::DisableRpc { .. }, Action::EnableRpc { .. })
+            | (Action::EnableRpc { .. }, Action::DisableRpc { .. })
+    )
+}
+
+/// Whether `later` waits, directly or transitively, on `earlier` or on a step
+/// between them. `disable_rpc`, `restart_validator`, then `enable_rpc` gated
+/// on the restart is deliberate, not a conflict.
+fn sequenced(depends_on: &BTreeMap<usize, Vec<usize>>, later: usize, earlier: usize) -> bool {
+    let mut pending = vec![later];
+    let mut seen = vec![false; later + 1];
+    while let Some(step) = pending.pop() {
+        for &dep in depends_on.get(&step).into_iter().flatten() {
+            if dep >= later || seen[dep] {
+                continue;
+            }
+            if dep >= earlier {
+                return true;
+            }
+            seen[dep] = true;
+            pending.push(dep);
+        }
+    }
+    false
+}
+
+/// Resolve unsequenced mutually exclusive steps in `playbook` per `policy`,
+/// renumbering `depends_on` around any dropped steps.
+pub fn resolve(playbook: &mut Playbook, policy: ConflictPolicy) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    let mut dropped = vec![false; playbook.steps.len()];
+    for later in 0..playbook.steps.len() {
+        for earlier in 0..later {
+            if dropped[earlier] || dropped[later] {
+                continue;
+            }
+            if !mutually_exclusive(&playbook.steps[earlier], &playbook.steps[later])
+                || sequenced(&playbook.depends_on, later, earlier)
+            {
+                continue;
+            }
+            conflicts.push(Conflict {
+                first: earlier,
+                second: later,
+                kinds: (playbook.steps[earlier].kind(), playbook.steps[later].kind()),
+            });
+            match policy {
+                ConflictPolicy::KeepFirst => dropped[later] = true,
+                ConflictPolicy::DropBoth => {
+                    dropped[earlier] = true;
+                    dropped[later] = true;
+                }
+                ConflictPolicy::Sequence => {
+                    playbook.depends_on.entry(later).or_default().push(earlier)
+                }
+            }
+        }
+    }
+    drop_steps(playbook, &dropped);
+    conflicts
+}
+
+/// Drop the steps of `playbook` that repeat an action in `dispatched`, i.e.
+/// one another issue's playbook already sent this tick. Returns how many
+/// were dropped.
+pub fn drop_repeated(playbook: &mut Playbook, dispatched: &[Action]) -> usize {
+    let dropped: Vec<bool> = playbook
+        .steps
+        .iter()
+        .map(|step| dispatched.contains(step))
+        .collect();
+    drop_steps(playbook, &dropped);
+    dropped.iter().filter(|dropped| **dropped).count()
+}
+
+/// Remove the `dropped` steps, renumbering `depends_on` around them. A step
+/// loses its dependency on a dropped step.
+pub fn drop_steps(playbook: &mut Playbook, dropped: &[bool]) {
+    if !dropped.contains(&true) {
+        return;
+    }
+    let renumbered: HashMap<usize, usize> = (0..playbook.steps.len())
+        .filter(|step| !dropped[*step])
+        .enumerate()
+        .map(|(new, old)| (old, new))
+        .collect();
+    let mut step = 0;
+    playbook.steps.retain(|_| {
+        step += 1;
+        !dropped[step - 1]
+    });
+    playbook.depends_on = std::mem::take(&mut playbook.depends_on)
+        .into_iter()
+        .filter_map(|(step, deps)| {
+            let step = *renumbered.get(&step)?;
+            let deps: Vec<usize> = deps
+                .iter()
+                .filter_map(|dep| renumbered.get(dep).copied())
+                .collect();
+            (!deps.is_empty()).then_some((step, deps))
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{IssueKind, ValidatorId};
+
+    fn v() -> ValidatorId {
+        ValidatorId("v1".into())
+    }
+
+    /// Agentic-style plan: disable, alert, enable, with the alert gated on
+    /// the disable but nothing ordering the enable.
+    fn plan() -> Playbook {
+        Playbook {
+            id: "agentic-rpc".into(),
+            trigger: IssueKind::RpcOverload,
+            steps: vec![
+                Action::DisableRpc { validator: v() },
+                Action::SendAlert {
+                    validator: v(),
+                    message: "rpc off".into(),
+                },
+                Action::EnableRpc { validator: v() },
+            ],
+            depends_on: BTreeMap::from([(1, vec![0])]),
+        }
+    }
+
+    #[test]
+    fn disable_and_enable_in_one_dispatch_are_resolved_per_policy() {
+        let expected = vec![Conflict {
+            first: 0,
+            second: 2,
+            kinds: ("disable_rpc", "enable_rpc"),
+        }];
+
+        let mut keep_first = plan();
+        assert_eq!(
+            resolve(&mut keep_first, ConflictPolicy::KeepFirst),
+            expected
+        );
+        assert_eq!(keep_first.steps, plan().steps[..2]);
+        assert_eq!(keep_first.depends_on, BTreeMap::from([(1, vec![0])]));
+
+        let mut drop_both = plan();
+        assert_eq!(resolve(&mut drop_both, ConflictPolicy::DropBoth), expected);
+        assert_eq!(drop_both.steps, plan().steps[1..2]);
+        assert!(drop_both.depends_on.is_empty());
+
+        let mut sequence = plan();
+        assert_eq!(resolve(&mut sequence, ConflictPolicy::Sequence), expected);
+        assert_eq!(sequence.steps, plan().steps);
+        assert_eq!(
+            sequence.depends_on,
+            BTreeMap::from([(1, vec![0]), (2, vec![0])])
+        );
+        // Once sequenced the pair is no longer a conflict.
+        assert!(resolve(&mut sequence, ConflictPolicy::KeepFirst).is_empty());
+    }
+
+    #[test]
+    fn steps_already_dispatched_for_another_issue_are_dropped() {
+        let slot_lag = crate::choose_playbook(IssueKind::SlotLagHigh, &v());
+        let mut hardware = crate::choose_playbook(IssueKind::HardwareOverload, &v());
+        assert_eq!(drop_repeated(&mut hardware, &slot_lag.steps), 1);
+        assert_eq!(
+            hardware.steps,
+            vec![Action::SendAlert {
+                validator: v(),
+                message: "Hardware overload detected".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn agentic_restart_plan_keeps_enable_rpc_by_default() {
+        let mut playbook = Playbook {
+            id: "agentic-restart".into(),
+            trigger: IssueKind::SlotLagHigh,
+            steps: vec![
+                Action::DisableRpc { validator: v() },
+                Action::RestartValidator {
+                    validator: v(),
+                    restart_kind: Default::default(),
+                },
+                Action::EnableRpc { validator: v() },
+            ],
+            depends_on: BTreeMap::new(),
+        };
+        let steps = playbook.steps.clone();
+        assert_eq!(resolve(&mut playbook, ConflictPolicy::default()).len(), 1);
+        assert_eq!(playbook.steps, steps);
+        assert_eq!(playbook.depends_on, BTreeMap::from([(2, vec![0])]));
+    }
+
+    #[test]
+    fn static_slot_lag_playbook_is_not_a_conflict() {
+        let mut playbook = crate::choose_playbook(IssueKind::SlotLagHigh, &v());
+        let before = playbook.clone();
+        assert!(resolve(&mut playbook, ConflictPolicy::DropBoth).is_empty());
+        assert_eq!(playbook, before);
+    }
+}
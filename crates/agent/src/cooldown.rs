@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use common::{IssueKind, ValidatorId};
+use tokio::sync::Mutex;
+
+/// One dispatch a cooldown runs from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dispatched {
+    pub at: i64,
+    /// The playbook that was sent, which for an agentic plan is the model's.
+    pub playbook: String,
+}
+
+/// Last dispatch per `(validator, issue)`, used to stop a persisting issue
+/// from re-running its playbook on every tick.
+#[derive(Clone, Default)]
+pub struct Cooldowns {
+    inner: Arc<Mutex<HashMap<(ValidatorId, IssueKind), Dispatched>>>,
+}
+
+impl Cooldowns {
+    /// Seconds left before `issue` may be remediated again on `validator`,
+    /// and the playbook dispatched for it.
+    pub async fn remaining(
+        &self,
+        validator: &ValidatorId,
+        issue: IssueKind,
+        now: i64,
+        cooldown_secs: i64,
+    ) -> Option<(i64, String)> {
+        let inner = self.inner.lock().await;
+        let dispatched = inner.get(&(validator.clone(), issue))?;
+        let remaining = dispatched.at + cooldown_secs - now;
+        (remaining > 0).then(|| (remaining, dispatched.playbook.clone()))
+    }
+
+    pub async fn start(&self, validator: &ValidatorId, issue: IssueKind, playbook: &str, now: i64) {
+        self.inner.lock().await.insert(
+            (validator.clone(), issue),
+            Dispatched {
+                at: now,
+                playbook: playbook.to_string(),
+            },
+        );
+    }
+
+    /// Every `(validator, issue)` dispatch, for `/debug/state`.
+    pub async fn snapshot(&self) -> Vec<(ValidatorId, IssueKind, Dispatched)> {
+        let inner = self.inner.lock().await;
+        inner
+            .iter()
+            .map(|((validator, issue), dispatched)| (validator.clone(), *issue, dispatched.clone()))
+            .collect()
+    }
+}
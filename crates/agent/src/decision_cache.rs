@@ -0,0 +1,361 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::Arc;
+
+use common::{DecisionCacheConfig, IssueKind, ValidatorMetrics};
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::agentic::{AgenticDecision, PromptContext};
+
+/// A sample reduced to coarse steps, so samples of one situation that differ
+/// only by noise share a cache entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct MetricsBucket {
+    slot_lag: i64,
+    vote_success_pct: i64,
+    cpu_tenths: i64,
+    ram_4gb: i64,
+    disk_5pct: i64,
+    rpc_qps_100: i64,
+    rpc_error_pct: i64,
+}
+
+impl MetricsBucket {
+    fn of(metrics: &ValidatorMetrics) -> Self {
+        let step = |value: f64, step: f64| (value / step).floor() as i64;
+        Self {
+            slot_lag: metrics.slot_lag.div_euclid(25),
+            vote_success_pct: step(metrics.vote_success_rate, 0.01),
+            cpu_tenths: step(metrics.cpu_usage, 0.1),
+            ram_4gb: step(metrics.ram_usage_gb, 4.0),
+            disk_5pct: step(metrics.disk_usage_pct, 5.0),
+            rpc_qps_100: step(metrics.rpc_qps, 100.0),
+            rpc_error_pct: step(metrics.rpc_error_rate, 0.01),
+        }
+    }
+}
+
+/// The `[agentic.context]` history sent with a prompt, without the
+/// timestamps and messages that differ between otherwise equal histories.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct ContextBucket {
+    recent_metrics: Vec<MetricsBucket>,
+    /// Each action as JSON, with whether it succeeded and was a dry run.
+    recent_actions: Vec<(String, bool, bool)>,
+}
+
+impl ContextBucket {
+    fn of(context: &PromptContext) -> Self {
+        Self {
+            recent_metrics: context
+                .recent_metrics
+                .iter()
+                .map(MetricsBucket::of)
+                .collect(),
+            recent_actions: context
+                .recent_actions
+                .iter()
+                .map(|action| (action.action.to_string(), action.success, action.dry_run))
+                .collect(),
+        }
+    }
+}
+
+type CacheKey = (String, IssueKind, MetricsBucket, ContextBucket);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    /// Decision and the time it was planned.
+    entries: HashMap<CacheKey, (AgenticDecision, i64)>,
+    stats: CacheStats,
+}
+
+/// Recent agentic decisions by validator, issue, bucketed sample and prompt
+/// context, for
+/// `[agentic.cache]`, so a validator flapping in and out of an issue is not
+/// re-planned by the model every time.
+#[derive(Clone, Debug, Default)]
+pub struct DecisionCache {
+    config: DecisionCacheConfig,
+    inner: Arc<Mutex<CacheState>>,
+}
+
+impl DecisionCache {
+    pub fn new(config: DecisionCacheConfig) -> Self {
+        Self {
+            config,
+            inner: Arc::default(),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.config.ttl_secs > 0 && self.config.max_entries > 0
+    }
+
+    fn key(
+        validator: &str,
+        issue: IssueKind,
+        metrics: &ValidatorMetrics,
+        context: &PromptContext,
+    ) -> CacheKey {
+        (
+            validator.to_string(),
+            issue,
+            MetricsBucket::of(metrics),
+            ContextBucket::of(context),
+        )
+    }
+
+    /// The decision planned for this situation within the TTL, counting the
+    /// lookup as a hit or a miss.
+    pub async fn get(
+        &self,
+        validator: &str,
+        issue: IssueKind,
+        metrics: &ValidatorMetrics,
+        context: &PromptContext,
+        now: i64,
+    ) -> Option<AgenticDecision> {
+        if !self.enabled() {
+            return None;
+        }
+        let mut inner = self.inner.lock().await;
+        let key = Self::key(validator, issue, metrics, context);
+        let cached = match inner.entries.get(&key) {
+            Some((decision, planned_at)) if now - planned_at < self.config.ttl_secs => {
+                Some(decision.clone())
+            }
+            Some(_) => {
+                inner.entries.remove(&key);
+                None
+            }
+            None => None,
+        };
+        match cached {
+            Some(_) => inner.stats.hits += 1,
+            None => inner.stats.misses += 1,
+        }
+        cached
+    }
+
+    /// Remember `decision`. At `max_entries`, expired entries go first, then
+    /// the oldest.
+    pub async fn insert(
+        &self,
+        validator: &str,
+        issue: IssueKind,
+        metrics: &ValidatorMetrics,
+        context: &PromptContext,
+        decision: &AgenticDecision,
+        now: i64,
+    ) {
+        if !self.enabled() {
+            return;
+        }
+        let mut inner = self.inner.lock().await;
+        let key = Self::key(validator, issue, metrics, context);
+        if !inner.entries.contains_key(&key) && inner.entries.len() >= self.config.max_entries {
+            let ttl_secs = self.config.ttl_secs;
+            inner
+                .entries
+                .retain(|_, (_, planned_at)| now - *planned_at < ttl_secs);
+            while inner.entries.len() >= self.config.max_entries {
+                let Some(oldest) = inner
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, (_, planned_at))| *planned_at)
+                    .map(|(key, _)| key.clone())
+                else {
+                    break;
+                };
+                inner.entries.remove(&oldest);
+            }
+        }
+        inner.entries.insert(key, (decision.clone(), now));
+    }
+
+    pub async fn stats(&self) -> CacheStats {
+        self.inner.lock().await.stats
+    }
+
+    /// Hit and miss counters in the Prometheus text format; empty when the
+    /// cache is off.
+    pub async fn render_metrics(&self) -> String {
+        let mut out = String::new();
+        if !self.enabled() {
+            return out;
+        }
+        let stats = self.stats().await;
+        out.push_str("# TYPE agent_agentic_cache_hits_total counter\n");
+        let _ = writeln!(out, "agent_agentic_cache_hits_total {}", stats.hits);
+        out.push_str("# TYPE agent_agentic_cache_misses_total counter\n");
+        let _ = writeln!(out, "agent_agentic_cache_misses_total {}", stats.misses);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agentic::RecentAction;
+    use crate::test_support::lagging;
+    use common::{Action, Playbook, RestartKind, ValidatorId};
+
+    fn decision(id: &str) -> AgenticDecision {
+        AgenticDecision {
+            playbook: Playbook {
+                id: id.into(),
+                trigger: IssueKind::SlotLagHigh,
+                steps: vec![Action::RestartValidator {
+                    validator: ValidatorId("v1".into()),
+                    restart_kind: RestartKind::Hard,
+                }],
+                depends_on: Default::default(),
+            },
+            rationale: None,
+            model: "gpt-test".into(),
+            prompt_version: "builtin-1".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn similar_samples_hit_until_the_ttl_and_the_cache_stays_bounded() {
+        let cache = DecisionCache::new(DecisionCacheConfig {
+            ttl_secs: 120,
+            max_entries: 2,
+        });
+        let slot_lag = IssueKind::SlotLagHigh;
+        let none = PromptContext::default();
+        assert!(cache
+            .get("v1", slot_lag, &lagging(110), &none, 0)
+            .await
+            .is_none());
+        cache
+            .insert("v1", slot_lag, &lagging(110), &none, &decision("first"), 0)
+            .await;
+
+        // Within the same slot lag bucket and inside the TTL.
+        let hit = cache.get("v1", slot_lag, &lagging(120), &none, 119).await;
+        assert_eq!(hit.unwrap().playbook.id, "first");
+        assert!(cache
+            .get("v1", slot_lag, &lagging(180), &none, 60)
+            .await
+            .is_none());
+        assert!(cache
+            .get("v2", slot_lag, &lagging(110), &none, 60)
+            .await
+            .is_none());
+        assert!(cache
+            .get("v1", slot_lag, &lagging(110), &none, 120)
+            .await
+            .is_none());
+        assert_eq!(cache.stats().await, CacheStats { hits: 1, misses: 4 });
+
+        for (validator, at) in [("v1", 200), ("v2", 210), ("v3", 220)] {
+            cache
+                .insert(
+                    validator,
+                    slot_lag,
+                    &lagging(110),
+                    &none,
+                    &decision(validator),
+                    at,
+                )
+                .await;
+        }
+        let inner = cache.inner.lock().await;
+        assert_eq!(inner.entries.len(), 2);
+        assert!(inner
+            .entries
+            .keys()
+            .all(|(validator, _, _, _)| validator != "v1"));
+    }
+
+    #[tokio::test]
+    async fn a_different_history_is_planned_again() {
+        let cache = DecisionCache::new(DecisionCacheConfig {
+            ttl_secs: 120,
+            max_entries: 16,
+        });
+        let slot_lag = IssueKind::SlotLagHigh;
+        let restart = |success, timestamp| RecentAction {
+            action: serde_json::json!({"kind": "restart_validator", "validator": "v1"}),
+            success,
+            message: format!("at {timestamp}"),
+            timestamp,
+            dry_run: false,
+        };
+        let failed_restart = || PromptContext {
+            recent_metrics: vec![lagging(60), lagging(110)],
+            recent_actions: vec![restart(false, 10)],
+        };
+        cache
+            .insert(
+                "v1",
+                slot_lag,
+                &lagging(110),
+                &failed_restart(),
+                &decision("after-failed-restart"),
+                0,
+            )
+            .await;
+
+        assert!(cache
+            .get("v1", slot_lag, &lagging(110), &PromptContext::default(), 1)
+            .await
+            .is_none());
+        let restarted = PromptContext {
+            recent_actions: vec![restart(true, 10)],
+            ..failed_restart()
+        };
+        assert!(cache
+            .get("v1", slot_lag, &lagging(110), &restarted, 1)
+            .await
+            .is_none());
+        // The same history, logged at another time, is the same situation.
+        let relogged = PromptContext {
+            recent_actions: vec![restart(false, 20)],
+            ..failed_restart()
+        };
+        let hit = cache.get("v1", slot_lag, &lagging(110), &relogged, 1).await;
+        assert_eq!(hit.unwrap().playbook.id, "after-failed-restart");
+    }
+
+    #[tokio::test]
+    async fn zero_ttl_disables_the_cache() {
+        let cache = DecisionCache::new(DecisionCacheConfig {
+            ttl_secs: 0,
+            max_entries: 16,
+        });
+        let metrics = lagging(110);
+        cache
+            .insert(
+                "v1",
+                IssueKind::SlotLagHigh,
+                &metrics,
+                &PromptContext::default(),
+                &decision("first"),
+                0,
+            )
+            .await;
+        assert!(cache
+            .get(
+                "v1",
+                IssueKind::SlotLagHigh,
+                &metrics,
+                &PromptContext::default(),
+                1,
+            )
+            .await
+            .is_none());
+        assert_eq!(cache.stats().await, CacheStats::default());
+        assert!(cache.render_metrics().await.is_empty());
+    }
+}
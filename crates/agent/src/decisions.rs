@@ -0,0 +1,94 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use common::{IssueKind, Playbook};
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+const DEFAULT_CAPACITY: usize = 1000;
+
+/// Bounded, in-memory timeline of what the agent observed and decided.
+#[derive(Clone)]
+pub struct DecisionLog {
+    inner: Arc<Mutex<VecDeque<DecisionRecord>>>,
+    capacity: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DecisionRecord {
+    pub timestamp: i64,
+    pub validator: String,
+    #[serde(flatten)]
+    pub event: DecisionEvent,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DecisionEvent {
+    WentStale {
+        last_updated: i64,
+        fields: Vec<&'static str>,
+    },
+    Recovered {
+        stale_secs: i64,
+    },
+    /// `[auto_undrain]` re-enabled a disabled validator.
+    Undrained {
+        healthy_secs: i64,
+        risk: f64,
+    },
+    /// Shadow mode: the static plan was dispatched, the agentic one only recorded.
+    ShadowPlan {
+        issue: IssueKind,
+        dispatched: Playbook,
+        agentic: Option<Playbook>,
+        agentic_rationale: Option<String>,
+    },
+    /// Active mode: an agentic plan was dispatched.
+    AgenticPlan {
+        issue: IssueKind,
+        playbook: Playbook,
+        rationale: Option<String>,
+        model: String,
+        prompt_version: String,
+    },
+}
+
+impl Default for DecisionLog {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl DecisionLog {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub async fn record(&self, timestamp: i64, validator: &str, event: DecisionEvent) {
+        let mut inner = self.inner.lock().await;
+        if inner.len() == self.capacity {
+            inner.pop_front();
+        }
+        inner.push_back(DecisionRecord {
+            timestamp,
+            validator: validator.to_string(),
+            event,
+        });
+    }
+
+    /// Most recent records first, optionally restricted to one validator.
+    pub async fn recent(&self, validator: Option<&str>, limit: usize) -> Vec<DecisionRecord> {
+        let inner = self.inner.lock().await;
+        inner
+            .iter()
+            .rev()
+            .filter(|record| validator.is_none_or(|id| record.validator == id))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
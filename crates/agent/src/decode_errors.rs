@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+/// Running count of undecodable metrics payloads for one validator.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DecodeErrorStats {
+    pub total: u64,
+    /// Failures since the last payload that decoded.
+    pub consecutive: u32,
+    pub last_error: Option<String>,
+}
+
+impl DecodeErrorStats {
+    /// Whether failures have persisted long enough for the configured policy to apply.
+    pub fn is_failing(&self, threshold: u32) -> bool {
+        self.consecutive >= threshold.max(1)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DecodeErrors {
+    inner: Arc<Mutex<HashMap<String, DecodeErrorStats>>>,
+}
+
+impl DecodeErrors {
+    pub async fn record_failure(&self, validator: &str, error: String) {
+        let mut inner = self.inner.lock().await;
+        let stats = inner.entry(validator.to_string()).or_default();
+        stats.total += 1;
+        stats.consecutive += 1;
+        stats.last_error = Some(error);
+    }
+
+    pub async fn record_success(&self, validator: &str) {
+        if let Some(stats) = self.inner.lock().await.get_mut(validator) {
+            stats.consecutive = 0;
+            stats.last_error = None;
+        }
+    }
+
+    pub async fn snapshot(&self) -> HashMap<String, DecodeErrorStats> {
+        self.inner.lock().await.clone()
+    }
+
+    pub async fn get(&self, validator: &str) -> DecodeErrorStats {
+        self.inner
+            .lock()
+            .await
+            .get(validator)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub async fn render_metrics(&self) -> String {
+        let inner = self.inner.lock().await;
+        let mut ids: Vec<_> = inner.keys().collect();
+        ids.sort();
+        let mut out = String::new();
+        let _ = writeln!(out, "# TYPE agent_metrics_decode_errors_total counter");
+        for id in ids {
+            let _ = writeln!(
+                out,
+                "agent_metrics_decode_errors_total{{validator=\"{id}\"}} {}",
+                inner[id].total
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn success_resets_streak_but_not_total() {
+        let errors = DecodeErrors::default();
+        errors.record_failure("v1", "eof".into()).await;
+        errors.record_failure("v1", "eof".into()).await;
+        assert!(errors.snapshot().await["v1"].is_failing(2));
+
+        errors.record_success("v1").await;
+        let stats = &errors.snapshot().await["v1"];
+        assert_eq!(stats.total, 2);
+        assert!(!stats.is_failing(2));
+        assert!(errors
+            .render_metrics()
+            .await
+            .contains("agent_metrics_decode_errors_total{validator=\"v1\"} 2"));
+    }
+}
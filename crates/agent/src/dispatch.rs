@@ -0,0 +1,274 @@
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use common::ActionQueueConfig;
+use executor::proto::executor_client::ExecutorClient;
+use executor::proto::ActionEnvelope;
+use tokio::sync::OnceCell;
+use tonic::transport::Channel;
+use tonic::Status;
+
+const NATS_PUBLISH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where action envelopes are sent: the executor daemon, or a message queue
+/// in front of it.
+pub trait ActionDispatcher {
+    async fn dispatch(&mut self, envelope: ActionEnvelope) -> Result<(), Status>;
+
+    /// Whether dispatched actions reach the daemon's queue before the next
+    /// RPC to it, so requests like a post-restart scrape queue behind them.
+    fn is_direct(&self) -> bool {
+        false
+    }
+}
+
+impl ActionDispatcher for ExecutorClient<Channel> {
+    async fn dispatch(&mut self, envelope: ActionEnvelope) -> Result<(), Status> {
+        self.submit_action(tonic::Request::new(envelope))
+            .await
+            .map(|_| ())
+    }
+
+    fn is_direct(&self) -> bool {
+        true
+    }
+}
+
+/// Publishes each envelope as JSON to a NATS subject, over one connection
+/// opened on the first publish and kept (and reconnected) by the client. A
+/// publish succeeds once it has been flushed to the server; core NATS does
+/// not acknowledge publishes.
+#[derive(Clone)]
+pub struct NatsPublisher {
+    url: String,
+    subject: String,
+    token: Option<String>,
+    ca_path: Option<String>,
+    client: Arc<OnceCell<async_nats::Client>>,
+}
+
+impl NatsPublisher {
+    pub fn from_config(cfg: &ActionQueueConfig) -> Result<Self> {
+        if !cfg.url.starts_with("nats://") && !cfg.url.starts_with("tls://") {
+            bail!("[action_queue] url must be a nats:// or tls:// URL");
+        }
+        let token = cfg
+            .token_env
+            .as_deref()
+            .map(|var| env::var(var).with_context(|| format!("{var} is not set")))
+            .transpose()?;
+        Ok(Self {
+            url: cfg.url.clone(),
+            subject: cfg.subject.clone(),
+            token,
+            ca_path: cfg.ca_path.clone(),
+            client: Arc::default(),
+        })
+    }
+
+    /// The shared connection, made now if no earlier publish made one.
+    async fn client(&self) -> Result<&async_nats::Client> {
+        self.client
+            .get_or_try_init(|| async {
+                let mut options = async_nats::ConnectOptions::new()
+                    .name("validator-copilot-agent")
+                    .connection_timeout(NATS_PUBLISH_TIMEOUT);
+                if let Some(token) = &self.token {
+                    options = options.token(token.clone());
+                }
+                // `tls://` URLs require TLS on their own.
+                if let Some(ca_path) = &self.ca_path {
+                    options = options
+                        .add_root_certificates(ca_path.into())
+                        .require_tls(true);
+                }
+                options
+                    .connect(self.url.as_str())
+                    .await
+                    .with_context(|| format!("failed to connect to NATS at {}", self.url))
+            })
+            .await
+    }
+
+    async fn publish(&self, payload: Vec<u8>) -> Result<()> {
+        let client = self.client().await?;
+        client
+            .publish(self.subject.clone(), payload.into())
+            .await
+            .context("NATS rejected the publish")?;
+        client
+            .flush()
+            .await
+            .context("failed to flush the publish to NATS")
+    }
+}
+
+impl ActionDispatcher for NatsPublisher {
+    async fn dispatch(&mut self, envelope: ActionEnvelope) -> Result<(), Status> {
+        let payload =
+            serde_json::to_vec(&envelope).map_err(|err| Status::internal(err.to_string()))?;
+        match tokio::time::timeout(NATS_PUBLISH_TIMEOUT, self.publish(payload)).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(err)) => Err(Status::unavailable(format!("{err:#}"))),
+            Err(_) => Err(Status::deadline_exceeded("NATS publish timed out")),
+        }
+    }
+}
+
+/// The dispatcher `[action_queue]` selects: NATS when configured, otherwise
+/// the daemon's `SubmitAction`.
+#[derive(Clone)]
+pub enum Dispatcher {
+    Grpc(ExecutorClient<Channel>),
+    Nats(NatsPublisher),
+}
+
+impl Dispatcher {
+    pub fn from_config(
+        queue: Option<&ActionQueueConfig>,
+        executor: ExecutorClient<Channel>,
+    ) -> Result<Self> {
+        Ok(match queue {
+            Some(queue) => Self::Nats(NatsPublisher::from_config(queue)?),
+            None => Self::Grpc(executor),
+        })
+    }
+}
+
+impl ActionDispatcher for Dispatcher {
+    async fn dispatch(&mut self, envelope: ActionEnvelope) -> Result<(), Status> {
+        match self {
+            Self::Grpc(client) => client.dispatch(envelope).await,
+            Self::Nats(publisher) => publisher.dispatch(envelope).await,
+        }
+    }
+
+    fn is_direct(&self) -> bool {
+        matches!(self, Self::Grpc(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Everything clients sent a fake NATS server, and how many connected.
+    #[derive(Default)]
+    struct Received {
+        connections: AtomicUsize,
+        publishes: Mutex<Vec<(String, String)>>,
+    }
+
+    /// Answers every `PING` and records each `PUB` subject and payload.
+    async fn fake_nats() -> (String, Arc<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let received = Arc::new(Received::default());
+        let server = received.clone();
+        tokio::spawn(async move {
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                server.connections.fetch_add(1, Ordering::SeqCst);
+                let received = server.clone();
+                tokio::spawn(async move {
+                    let mut socket = BufReader::new(socket);
+                    socket
+                        .get_mut()
+                        .write_all(b"INFO {\"server_id\":\"test\",\"max_payload\":1048576}\r\n")
+                        .await
+                        .unwrap();
+                    let mut line = String::new();
+                    while socket.read_line(&mut line).await.unwrap() > 0 {
+                        let op = line.trim_end().to_string();
+                        line.clear();
+                        if op == "PING" {
+                            socket.get_mut().write_all(b"PONG\r\n").await.unwrap();
+                        } else if let Some(args) = op.strip_prefix("PUB ") {
+                            let (subject, len) = args.rsplit_once(' ').unwrap();
+                            let mut payload = vec![0; len.parse::<usize>().unwrap() + 2];
+                            socket.read_exact(&mut payload).await.unwrap();
+                            payload.truncate(payload.len() - 2);
+                            received
+                                .publishes
+                                .lock()
+                                .unwrap()
+                                .push((subject.to_string(), String::from_utf8(payload).unwrap()));
+                        }
+                    }
+                });
+            }
+        });
+        (addr, received)
+    }
+
+    #[tokio::test]
+    async fn envelopes_are_published_as_json_over_one_connection() {
+        let (addr, received) = fake_nats().await;
+        let mut dispatcher = NatsPublisher::from_config(&ActionQueueConfig {
+            url: format!("nats://{addr}"),
+            subject: "copilot.actions".into(),
+            token_env: None,
+            ca_path: None,
+        })
+        .unwrap();
+        for step in 0..2 {
+            dispatcher
+                .dispatch(ActionEnvelope {
+                    validator_id: "v1".into(),
+                    action_json: r#"{"kind":"restart_validator","validator":"v1"}"#.into(),
+                    idempotency_key: format!("v1:slot_lag_high:slot-lag-recovery:3:{step}"),
+                    issue: "slot_lag_high".into(),
+                    ..ActionEnvelope::default()
+                })
+                .await
+                .unwrap();
+        }
+
+        let publishes = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let publishes = received.publishes.lock().unwrap().clone();
+                if publishes.len() == 2 {
+                    return publishes;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("both envelopes reach the server");
+        let (subject, payload) = &publishes[1];
+        assert_eq!(subject, "copilot.actions");
+        let envelope: serde_json::Value = serde_json::from_str(payload).unwrap();
+        assert_eq!(envelope["validator_id"], "v1");
+        assert_eq!(
+            envelope["idempotency_key"],
+            "v1:slot_lag_high:slot-lag-recovery:3:1"
+        );
+        assert_eq!(envelope["issue"], "slot_lag_high");
+        assert_eq!(received.connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn unreachable_nats_fails_the_dispatch() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        let mut dispatcher = NatsPublisher::from_config(&ActionQueueConfig {
+            url: format!("nats://{addr}"),
+            subject: "actions".into(),
+            token_env: None,
+            ca_path: None,
+        })
+        .unwrap();
+        let err = dispatcher
+            .dispatch(ActionEnvelope::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unavailable, "{err:?}");
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use common::ValidatorId;
+use tokio::sync::Mutex;
+
+/// Validators across the fleet with a disruptive playbook in progress, and
+/// when it was dispatched.
+#[derive(Clone, Default)]
+pub struct Disruptions {
+    inner: Arc<Mutex<HashMap<ValidatorId, i64>>>,
+}
+
+impl Disruptions {
+    /// Take a slot for `validator` unless `limit` validators already hold
+    /// one, returning how many do if so. A validator holding a slot keeps
+    /// it, and slots older than `hold_secs` are released first. `Ok(true)`
+    /// means the slot was newly taken, so the caller releases it again if
+    /// the playbook never goes out.
+    pub async fn acquire(
+        &self,
+        validator: &ValidatorId,
+        now: i64,
+        limit: Option<usize>,
+        hold_secs: i64,
+    ) -> Result<bool, usize> {
+        let mut inner = self.inner.lock().await;
+        inner.retain(|_, since| now - *since < hold_secs);
+        if let Some(since) = inner.get_mut(validator) {
+            *since = now;
+            Ok(false)
+        } else if limit.is_none_or(|limit| inner.len() < limit) {
+            inner.insert(validator.clone(), now);
+            Ok(true)
+        } else {
+            Err(inner.len())
+        }
+    }
+
+    /// Free `validator`'s slot once it is healthy again, or when the playbook
+    /// it was taken for was not dispatched.
+    pub async fn release(&self, validator: &ValidatorId) {
+        self.inner.lock().await.remove(validator);
+    }
+}
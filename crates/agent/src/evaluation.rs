@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use common::IssueKind;
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+/// Summary of the most recent agent-loop evaluation of one validator, used to
+/// explain why an issue did or did not lead to an action.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LastDecision {
+    pub evaluated_at: i64,
+    /// Every issue detected, most severe first.
+    pub issues: Vec<IssueKind>,
+    /// What became of the most severe issue.
+    pub playbook: Option<String>,
+    #[serde(flatten)]
+    pub outcome: DecisionOutcome,
+    /// What became of the remaining issues, in `issues` order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub others: Vec<IssueDecision>,
+}
+
+impl LastDecision {
+    /// `decisions` in severity order, one per detected issue.
+    pub fn from_issues(evaluated_at: i64, decisions: Vec<IssueDecision>) -> Self {
+        let issues = decisions.iter().map(|decision| decision.issue).collect();
+        let mut decisions = decisions.into_iter();
+        let (playbook, outcome) = match decisions.next() {
+            Some(primary) => (primary.playbook, primary.outcome),
+            None => (None, DecisionOutcome::NoIssue),
+        };
+        Self {
+            evaluated_at,
+            issues,
+            playbook,
+            outcome,
+            others: decisions.collect(),
+        }
+    }
+}
+
+/// The outcome for one of several issues detected together.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct IssueDecision {
+    pub issue: IssueKind,
+    pub playbook: Option<String>,
+    #[serde(flatten)]
+    pub outcome: DecisionOutcome,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum DecisionOutcome {
+    NoIssue,
+    Dispatched { plan_source: &'static str },
+    Suppressed { reason: SuppressionReason },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SuppressionReason {
+    Cooldown {
+        remaining_secs: i64,
+    },
+    /// The issue has not yet persisted for `remediation.confirm_secs`.
+    Confirming {
+        remaining_secs: i64,
+    },
+    /// A `[[suppressions]]` rule makes the issue informational here.
+    Rule {
+        rule: String,
+    },
+    /// `in_progress` validators already hold every
+    /// `[remediation.disruption]` slot; retried on the next evaluation.
+    DisruptionLimit {
+        in_progress: usize,
+    },
+}
+
+#[derive(Clone, Default)]
+pub struct Evaluations {
+    inner: Arc<Mutex<HashMap<String, LastDecision>>>,
+}
+
+impl Evaluations {
+    pub async fn record(&self, validator: &str, decision: LastDecision) {
+        self.inner
+            .lock()
+            .await
+            .insert(validator.to_string(), decision);
+    }
+
+    /// Whether `validator`'s last evaluation found no issue.
+    pub async fn healthy(&self, validator: &str) -> bool {
+        self.inner
+            .lock()
+            .await
+            .get(validator)
+            .is_some_and(|decision| decision.issues.is_empty())
+    }
+
+    pub async fn snapshot(&self) -> HashMap<String, LastDecision> {
+        self.inner.lock().await.clone()
+    }
+}
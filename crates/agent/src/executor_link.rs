@@ -0,0 +1,27 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Whether the agent currently holds a live metrics subscription to the
+/// executor daemon. Reported by `/readyz`.
+#[derive(Clone, Default)]
+pub struct ExecutorStatus {
+    connected: Arc<AtomicBool>,
+}
+
+impl ExecutorStatus {
+    pub fn set_connected(&self, connected: bool) {
+        self.connected.store(connected, Ordering::Relaxed);
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    pub fn label(&self) -> &'static str {
+        if self.is_connected() {
+            "connected"
+        } else {
+            "disconnected"
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+
+use common::{RiskLevel, ValidatorConfig};
+use serde::Serialize;
+
+use crate::ValidatorSummary;
+
+/// The group of validators that lack the `[groups] by` label or any tag.
+const UNGROUPED: &str = "ungrouped";
+
+/// One group's roll-up for `GET /api/groups`.
+#[derive(Debug, Serialize)]
+pub struct GroupSummary {
+    pub name: String,
+    pub validators: usize,
+    /// Validators by their most severe status, e.g. `ok` or `SlotLagHigh`.
+    pub status: BTreeMap<String, usize>,
+    /// Validators with a risk score, by its band.
+    pub risk: BTreeMap<RiskLevel, usize>,
+    /// The validator with the highest risk score.
+    pub worst: Option<WorstValidator>,
+    /// Mean of one minus `degradation` over the validators with a score;
+    /// `None` when none has one.
+    pub health: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorstValidator {
+    pub id: String,
+    pub risk_score: f64,
+    pub risk: RiskLevel,
+}
+
+/// The groups `validator` belongs to under `by`.
+fn groups_of<'a>(validator: &'a ValidatorConfig, by: &str) -> Vec<&'a str> {
+    let groups: Vec<&str> = if by == "tag" {
+        validator.tags.iter().map(String::as_str).collect()
+    } else {
+        validator
+            .labels
+            .get(by)
+            .map(String::as_str)
+            .into_iter()
+            .collect()
+    };
+    if groups.is_empty() {
+        vec![UNGROUPED]
+    } else {
+        groups
+    }
+}
+
+/// Roll `summaries` up by group, in group name order. `validators` is the
+/// config the summaries were built from, in the same order.
+pub fn group_summaries(
+    validators: &[ValidatorConfig],
+    summaries: &[ValidatorSummary],
+    by: &str,
+) -> Vec<GroupSummary> {
+    let mut members: BTreeMap<&str, Vec<&ValidatorSummary>> = BTreeMap::new();
+    for (validator, summary) in validators.iter().zip(summaries) {
+        for group in groups_of(validator, by) {
+            members.entry(group).or_default().push(summary);
+        }
+    }
+    members
+        .into_iter()
+        .map(|(name, summaries)| summarize(name, &summaries))
+        .collect()
+}
+
+fn summarize(name: &str, summaries: &[&ValidatorSummary]) -> GroupSummary {
+    let mut status = BTreeMap::new();
+    let mut risk = BTreeMap::new();
+    let mut worst: Option<WorstValidator> = None;
+    let mut health = Vec::new();
+    for summary in summaries {
+        if let Some(first) = summary.status.first() {
+            *status.entry(first.clone()).or_default() += 1;
+        }
+        if let Some(score) = summary.risk_score {
+            let level = RiskLevel::of(score);
+            *risk.entry(level).or_default() += 1;
+            if worst.as_ref().is_none_or(|worst| score > worst.risk_score) {
+                worst = Some(WorstValidator {
+                    id: summary.id.clone(),
+                    risk_score: score,
+                    risk: level,
+                });
+            }
+        }
+        health.extend(summary.degradation.map(|degradation| 1.0 - degradation));
+    }
+    GroupSummary {
+        name: name.to_string(),
+        validators: summaries.len(),
+        status,
+        risk,
+        worst,
+        health: (!health.is_empty()).then(|| health.iter().sum::<f64>() / health.len() as f64),
+    }
+}
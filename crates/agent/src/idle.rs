@@ -0,0 +1,48 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use common::ValidatorMetrics;
+
+/// What detection on one validator depends on besides the clock: the
+/// sample's values (not its timestamps) and whether its vote and RPC windows
+/// are failing.
+pub fn fingerprint(metrics: &ValidatorMetrics, vote_failing: bool, rpc_overloaded: bool) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    metrics.slot_lag.hash(&mut hasher);
+    for value in [
+        metrics.vote_success_rate,
+        metrics.cpu_usage,
+        metrics.ram_usage_gb,
+        metrics.disk_usage_pct,
+        metrics.rpc_qps,
+        metrics.rpc_error_rate,
+    ] {
+        value.to_bits().hash(&mut hasher);
+    }
+    (vote_failing, rpc_overloaded).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// For `[detection] skip_unchanged`: the fingerprint each validator was last
+/// fully evaluated on, kept only while that evaluation found no issue.
+#[derive(Debug, Default)]
+pub struct IdleTracker {
+    healthy: HashMap<String, u64>,
+}
+
+impl IdleTracker {
+    /// Whether `validator` was found healthy on an identical fingerprint, so
+    /// evaluating it again would find nothing new.
+    pub fn unchanged(&self, validator: &str, fingerprint: u64) -> bool {
+        self.healthy.get(validator) == Some(&fingerprint)
+    }
+
+    pub fn evaluated(&mut self, validator: &str, fingerprint: u64, healthy: bool) {
+        if healthy {
+            self.healthy.insert(validator.to_string(), fingerprint);
+        } else {
+            self.healthy.remove(validator);
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+
+/// Upper bounds, in seconds, of the `agent_loop_phase_seconds` buckets.
+const PHASE_BUCKETS: [f64; 11] = [
+    0.001, 0.005, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// A part of one agent loop tick.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LoopPhase {
+    /// Copying the metrics cache and decode error counts, once per tick.
+    Snapshot,
+    /// Detecting issues on one validator's sample.
+    Detection,
+    /// Building the prompt context and asking the agentic provider for a plan.
+    Planning,
+    /// Submitting one playbook's actions, and any post-restart scrape.
+    Dispatch,
+}
+
+impl LoopPhase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LoopPhase::Snapshot => "snapshot",
+            LoopPhase::Detection => "detection",
+            LoopPhase::Planning => "planning",
+            LoopPhase::Dispatch => "dispatch",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Histogram {
+    /// Per-bucket (non-cumulative) counts; the last slot is `+Inf`.
+    counts: [u64; PHASE_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+/// Time spent in each agent loop phase, for `[profiling] loop_timings`.
+/// Disabled, it records nothing and renders nothing.
+#[derive(Clone, Debug, Default)]
+pub struct LoopTimings {
+    inner: Option<Arc<Mutex<BTreeMap<LoopPhase, Histogram>>>>,
+}
+
+impl LoopTimings {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            inner: enabled.then(Arc::default),
+        }
+    }
+
+    pub async fn record(&self, phase: LoopPhase, elapsed: Duration) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        let secs = elapsed.as_secs_f64();
+        let mut inner = inner.lock().await;
+        let histogram = inner.entry(phase).or_default();
+        histogram.counts[PHASE_BUCKETS.partition_point(|le| *le < secs)] += 1;
+        histogram.sum += secs;
+        histogram.count += 1;
+    }
+
+    /// Run `fut`, recording how long it took under `phase`.
+    pub async fn time<F: Future>(&self, phase: LoopPhase, fut: F) -> F::Output {
+        if self.inner.is_none() {
+            return fut.await;
+        }
+        let start = Instant::now();
+        let output = fut.await;
+        self.record(phase, start.elapsed()).await;
+        output
+    }
+
+    /// Samples recorded for `phase` so far.
+    #[cfg(test)]
+    pub async fn count(&self, phase: LoopPhase) -> u64 {
+        match &self.inner {
+            Some(inner) => inner.lock().await.get(&phase).map_or(0, |h| h.count),
+            None => 0,
+        }
+    }
+
+    pub async fn render_metrics(&self) -> String {
+        let mut out = String::new();
+        let Some(inner) = &self.inner else {
+            return out;
+        };
+        let inner = inner.lock().await;
+        out.push_str("# HELP agent_loop_phase_seconds Time spent in each agent loop phase.\n");
+        out.push_str("# TYPE agent_loop_phase_seconds histogram\n");
+        for (phase, histogram) in inner.iter() {
+            let phase = phase.as_str();
+            let mut cumulative = 0;
+            for (le, count) in PHASE_BUCKETS.iter().zip(&histogram.counts) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "agent_loop_phase_seconds_bucket{{phase=\"{phase}\",le=\"{le}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "agent_loop_phase_seconds_bucket{{phase=\"{phase}\",le=\"+Inf\"}} {}",
+                histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "agent_loop_phase_seconds_sum{{phase=\"{phase}\"}} {}",
+                histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "agent_loop_phase_seconds_count{{phase=\"{phase}\"}} {}",
+                histogram.count
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn samples_land_in_their_phase_and_bucket() {
+        let timings = LoopTimings::new(true);
+        timings
+            .record(LoopPhase::Planning, Duration::from_millis(300))
+            .await;
+        timings
+            .record(LoopPhase::Planning, Duration::from_secs(20))
+            .await;
+        let rendered = timings.render_metrics().await;
+        assert!(rendered
+            .contains("agent_loop_phase_seconds_bucket{phase=\"planning\",le=\"0.25\"} 0\n"));
+        assert!(
+            rendered.contains("agent_loop_phase_seconds_bucket{phase=\"planning\",le=\"0.5\"} 1\n")
+        );
+        assert!(rendered
+            .contains("agent_loop_phase_seconds_bucket{phase=\"planning\",le=\"+Inf\"} 2\n"));
+        assert!(!rendered.contains("phase=\"detection\""));
+
+        let disabled = LoopTimings::new(false);
+        disabled
+            .record(LoopPhase::Planning, Duration::from_millis(300))
+            .await;
+        assert!(disabled.render_metrics().await.is_empty());
+    }
+}
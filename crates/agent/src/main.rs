@@ -1,24 +1,100 @@
+mod agent_metrics;
 mod agentic;
+mod alerts;
+mod bulk;
+mod call_metrics;
+mod confirmation;
+mod conflicts;
+mod cooldown;
+mod decision_cache;
+mod decisions;
+mod decode_errors;
+mod dispatch;
+mod disruption;
+mod evaluation;
+mod executor_link;
+mod groups;
+mod idle;
+mod loop_timings;
+mod metric_alerts;
+mod notify;
+mod rpc_window;
+mod smoothing;
+mod staleness;
+#[cfg(test)]
+mod test_support;
+mod token_budget;
+mod undrain;
+mod vote_window;
+mod warmup;
 
-use agentic::{AgenticBrain, AgenticDecision};
-use anyhow::{Context, Result};
-use axum::{extract::State, routing::get, Json, Router};
-use common::{risk_score, Action, Config, IssueKind, Playbook, ValidatorId, ValidatorMetrics};
+use agent_metrics::AgentMetrics;
+use agentic::{AgenticBrain, AgenticDecision, PromptContext, RecentAction};
+use alerts::AlertContext;
+use anyhow::Result;
+use axum::{
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    extract::{Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use bulk::{submit_all, BulkResult, BulkTarget};
+use common::{
+    composite_health, explain_risk, idempotency_key, now_ts, risk_score_with, Action, AgenticMode,
+    Backoff, Config, DecodeErrorPolicy, DetectionRule, DetectionThresholds, IssueKind,
+    MetricsTransport, Playbook, RemediationConfig, RestartKind, Severity, StalenessConfig,
+    ValidatorConfig, ValidatorId, ValidatorMetrics,
+};
+use confirmation::Confirmations;
+use cooldown::Cooldowns;
+use decision_cache::CacheStats;
+use decisions::{DecisionEvent, DecisionLog, DecisionRecord};
+use decode_errors::DecodeErrors;
+use dispatch::{ActionDispatcher, Dispatcher};
+use disruption::Disruptions;
+use evaluation::{DecisionOutcome, Evaluations, IssueDecision, LastDecision, SuppressionReason};
 use executor::proto::executor_client::ExecutorClient;
-use executor::proto::{ActionEnvelope, MetricsWatchRequest};
-use serde::Serialize;
-use std::collections::HashMap;
+use executor::proto::{
+    ActionEnvelope, ActionHistoryRequest, MetricsUpdate, MetricsWatchRequest,
+    PendingActionsRequest, PlaybookExecution, PlaybookExecutionsRequest, RecoveryCheck,
+    ScrapeRequest, StepState, ValidatorState, ValidatorStatesRequest, ValidatorToggleRequest,
+};
+use executor::tls::{self, DaemonConnector};
+use executor_link::ExecutorStatus;
+use idle::IdleTracker;
+use loop_timings::{LoopPhase, LoopTimings};
+use metric_alerts::MetricAlertTracker;
+use notify::{Notification, NotificationRouter};
+use rpc_window::RpcHistory;
+use serde::{Deserialize, Serialize};
+use smoothing::Smoother;
+use staleness::StaleTracker;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::env;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::Mutex;
-use tokio::time::interval;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, Mutex};
+use tokio::time::{interval, interval_at, sleep};
 use tower_http::cors::{Any, CorsLayer};
-use tracing::{error, info};
+use tracing::{error, info, warn};
+use undrain::UndrainTracker;
+use vote_window::VoteHistory;
+use warmup::MetricsWarmup;
 
 const ACTION_POLL_INTERVAL_SECS: u64 = 10;
-const MAX_RAM_GB: f64 = 128.0;
 const DEFAULT_SERVER_ADDR: &str = "http://127.0.0.1:50051";
+const DEFAULT_DECISIONS_LIMIT: usize = 100;
+/// Samples buffered for `/api/stream` clients; one that falls further behind
+/// gets a fresh snapshot.
+const METRICS_UPDATES_CAPACITY: usize = 256;
+/// The `/api/stream` subprotocol a token may follow, per [`stream_token`].
+const STREAM_PROTOCOL: &str = "bearer";
+/// How long `/api/stream` reuses the daemon's list of disabled validators.
+const STREAM_DISABLED_REFRESH: Duration = Duration::from_secs(10);
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -31,83 +107,280 @@ async fn main() -> Result<()> {
     let cfg = Arc::new(common::load_config()?);
     let server_addr =
         env::var("EXECUTOR_SERVER_ADDR").unwrap_or_else(|_| DEFAULT_SERVER_ADDR.to_string());
-    let channel = tonic::transport::Endpoint::from_shared(server_addr.clone())?
-        .connect()
-        .await
-        .context("failed to connect to executor daemon")?;
-    let metrics_client = ExecutorClient::new(channel.clone());
-    let action_client = ExecutorClient::new(channel);
+    let endpoint = tonic::transport::Endpoint::from_shared(server_addr.clone())?;
+    let daemon_tls = cfg
+        .daemon
+        .tls
+        .as_ref()
+        .map(DaemonConnector::from_daemon_config)
+        .transpose()?;
+    // Actions go over a lazy channel so the agent can start before the daemon.
+    let action_client = ExecutorClient::new(tls::connect_lazy(&endpoint, daemon_tls.as_ref()));
+    let dispatcher = Dispatcher::from_config(cfg.action_queue.as_ref(), action_client.clone())?;
+    if let Some(queue) = &cfg.action_queue {
+        info!(
+            url = queue.url,
+            subject = queue.subject,
+            "dispatching actions to NATS"
+        );
+    }
+    let executor_status = ExecutorStatus::default();
+    let api_token = api_token(&cfg.api.token_env);
+    if api_token.is_none() {
+        warn!(
+            token_env = cfg.api.token_env,
+            "no API token set, the agent's HTTP API is unauthenticated"
+        );
+    }
 
-    let metrics_cache = MetricsCache::default();
-    let agentic_brain = Arc::new(AgenticBrain::new(cfg.agentic.clone())?);
+    let prompt_context = cfg
+        .agentic
+        .as_ref()
+        .map(|agentic| agentic.settings().context)
+        .unwrap_or_default();
+    let metrics_cache = MetricsCache::new(
+        Smoother::new(cfg.detection.smoothing.clone()),
+        cfg.staleness.clone(),
+    )
+    .with_history(prompt_context.metric_samples);
+    let votes = VoteHistory::new(cfg.detection.clone());
+    let rpc = RpcHistory::new(cfg.detection.clone());
+    let agentic_brain = Arc::new(AgenticBrain::new(cfg.agentic.clone(), &cfg.commands)?);
     if agentic_brain.is_enabled() {
-        info!("agentic planning enabled via OpenAI provider");
+        info!(
+            mode = ?agentic_brain.mode(),
+            provider = agentic_brain.provider(),
+            "agentic planning enabled"
+        );
     } else {
         info!("agentic planning disabled (no provider configured)");
     }
 
-    let metrics_task_cache = metrics_cache.clone();
-    tokio::spawn(async move {
-        subscribe_metrics_loop(metrics_client, metrics_task_cache).await;
-    });
-    let agent_cfg = cfg.clone();
-    let agent_metrics_cache = metrics_cache.clone();
-    let planner = agentic_brain.clone();
-    tokio::spawn(async move {
-        if let Err(err) =
-            run_agent_loop(action_client, agent_cfg, agent_metrics_cache, planner).await
-        {
-            error!(?err, "agent loop terminated");
-        }
-    });
+    let poll_interval = (cfg.daemon.metrics_transport == MetricsTransport::Poll)
+        .then(|| Duration::from_secs(cfg.daemon.metrics_poll_interval_secs));
+    tokio::spawn(executor_connection_loop(
+        endpoint,
+        daemon_tls,
+        poll_interval,
+        executor_status.clone(),
+        metrics_cache.clone(),
+        votes.clone(),
+        rpc.clone(),
+    ));
 
     let app_state = AppState {
         config: cfg.clone(),
         metrics: metrics_cache,
+        executor: action_client,
+        dispatcher,
+        brain: agentic_brain,
+        decisions: DecisionLog::default(),
+        cooldowns: Cooldowns::default(),
+        confirmations: Confirmations::default(),
+        disruptions: Disruptions::default(),
+        votes,
+        rpc,
+        evaluations: Evaluations::default(),
+        executor_status,
+        notifier: NotificationRouter::from_config(&cfg.alerts),
+        loop_timings: LoopTimings::new(cfg.profiling.loop_timings),
+        agent_metrics: AgentMetrics::default(),
+        warmup: MetricsWarmup::new(now_ts(), cfg.ingest.warmup_grace_secs),
+        registered: Arc::default(),
+        api_token,
     };
+    let agent_state = app_state.clone();
+    tokio::spawn(async move {
+        if let Err(err) = run_agent_loop(agent_state).await {
+            error!(?err, "agent loop terminated");
+        }
+    });
+
+    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
+    info!("agent http listening on 0.0.0.0:3000");
+    axum::serve(listener, router(app_state)).await?;
+    Ok(())
+}
+
+fn router(app_state: AppState) -> Router {
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
         .allow_headers(Any);
 
-    let app = Router::new()
-        .route("/health", get(health))
+    let protected = Router::new()
         .route("/debug/actions/pending", get(pending_actions))
+        .route("/debug/state", get(debug_state))
+        .route("/debug/cooldowns", get(debug_cooldowns))
         .route("/api/validators", get(list_validators))
+        .route("/api/groups", get(list_groups))
+        .route("/api/stream", get(stream_validators))
+        .route("/api/validators/:id/scrape", post(scrape_validator))
+        .route(
+            "/api/validators/:id/playbooks/active",
+            get(active_playbooks),
+        )
         .route("/api/actions", get(actions_summary))
+        .route("/api/actions/bulk", post(submit_bulk_action))
+        .route("/api/actions/history", get(action_history))
+        .route("/api/maintenance/run", post(run_maintenance))
+        .route("/api/decisions", get(list_decisions))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            require_api_token,
+        ));
+    Router::new()
+        .route("/health", get(health))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(export_metrics))
+        .merge(protected)
         .with_state(app_state)
-        .layer(cors);
+        .layer(cors)
+}
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await?;
-    info!("agent http listening on 0.0.0.0:3000");
-    axum::serve(listener, app).await?;
-    Ok(())
+/// With an API token configured, reject requests that do not carry it as
+/// `Authorization: Bearer <token>`, or for `/api/stream` per [`stream_token`].
+async fn require_api_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(token) = state.api_token.as_deref() else {
+        return next.run(request).await;
+    };
+    let presented = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string)
+        .or_else(|| stream_token(&request));
+    if presented.is_some_and(|presented| common::tokens_match(&presented, token)) {
+        next.run(request).await
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+        )
+            .into_response()
+    }
+}
+
+/// Browsers cannot set headers on a WebSocket, so `/api/stream` also takes
+/// the token as the subprotocol offered after `bearer`
+/// (`Sec-WebSocket-Protocol: bearer, <token>`) or as `?token=<token>`.
+fn stream_token(request: &Request) -> Option<String> {
+    if request.uri().path() != "/api/stream" {
+        return None;
+    }
+    let from_protocol = request
+        .headers()
+        .get(header::SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|protocols| {
+            let mut protocols = protocols.split(',').map(str::trim);
+            protocols.find(|&protocol| protocol == STREAM_PROTOCOL)?;
+            protocols.next().map(str::to_string)
+        });
+    from_protocol.or_else(|| {
+        Query::<HashMap<String, String>>::try_from_uri(request.uri())
+            .ok()?
+            .0
+            .remove("token")
+    })
+}
+
+/// The token named by `[api] token_env`, if it is set and non-empty.
+fn api_token(token_env: &str) -> Option<String> {
+    env::var(token_env).ok().filter(|token| !token.is_empty())
+}
+
+/// Send the playbook's alerts to the sinks routed for its issue, off the loop.
+fn notify_routed_sinks(
+    notifier: &NotificationRouter,
+    validator: &ValidatorId,
+    playbook: &Playbook,
+    severity: Severity,
+) {
+    if !notifier.is_routed(playbook.trigger) {
+        return;
+    }
+    let messages = playbook.steps.iter().filter_map(|step| match step {
+        Action::SendAlert { message, .. } => Some(message),
+        _ => None,
+    });
+    for (step, message) in messages.enumerate() {
+        let notifier = notifier.clone();
+        let notification = Notification {
+            validator: validator.0.clone(),
+            issue: Some(playbook.trigger),
+            alert: None,
+            severity: Some(severity),
+            message: message.clone(),
+            step,
+        };
+        tokio::spawn(async move {
+            notifier.notify(&notification).await;
+        });
+    }
+}
+
+/// Keep a metrics subscription to the daemon alive, reconnecting with backoff
+/// whenever the daemon is unreachable or the stream ends. With a
+/// `poll_interval` the daemon is polled instead of streamed from.
+async fn executor_connection_loop(
+    endpoint: tonic::transport::Endpoint,
+    daemon_tls: Option<DaemonConnector>,
+    poll_interval: Option<Duration>,
+    status: ExecutorStatus,
+    cache: MetricsCache,
+    votes: VoteHistory,
+    rpc: RpcHistory,
+) {
+    let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(30));
+    loop {
+        match tls::connect(&endpoint, daemon_tls.as_ref()).await {
+            Ok(channel) => {
+                info!("connected to executor daemon");
+                status.set_connected(true);
+                backoff.reset();
+                let client = ExecutorClient::new(channel);
+                match poll_interval {
+                    Some(every) => {
+                        poll_metrics_loop(client, every, &cache, &votes, &rpc).await;
+                    }
+                    None => {
+                        subscribe_metrics_loop(client, cache.clone(), votes.clone(), rpc.clone())
+                            .await;
+                    }
+                }
+                status.set_connected(false);
+                warn!("executor metrics subscription ended, reconnecting");
+            }
+            Err(err) => {
+                warn!(?err, "executor daemon unreachable, retrying");
+            }
+        }
+        sleep(backoff.next_delay()).await;
+    }
 }
 
 async fn subscribe_metrics_loop(
     mut client: ExecutorClient<tonic::transport::Channel>,
     cache: MetricsCache,
+    votes: VoteHistory,
+    rpc: RpcHistory,
 ) {
     let request = tonic::Request::new(MetricsWatchRequest {
         validator_ids: vec![],
         include_snapshot: true,
+        ..MetricsWatchRequest::default()
     });
     match client.subscribe_metrics(request).await {
         Ok(stream) => {
             let mut inner = stream.into_inner();
             while let Ok(Some(update)) = inner.message().await {
-                match serde_json::from_str::<ValidatorMetrics>(&update.metrics_json) {
-                    Ok(metrics) => {
-                        cache.insert(update.validator_id.clone(), metrics).await;
-                    }
-                    Err(err) => {
-                        error!(
-                            validator = update.validator_id,
-                            ?err,
-                            "invalid metrics payload"
-                        );
-                    }
-                }
+                ingest_update(&cache, &votes, &rpc, update).await;
             }
         }
         Err(err) => {
@@ -116,206 +389,1981 @@ async fn subscribe_metrics_loop(
     }
 }
 
-async fn run_agent_loop(
+/// Fetch samples with `GetAllMetrics` every `every`, asking only for those
+/// the daemon accepted after the last one seen. Returns once a poll fails.
+async fn poll_metrics_loop(
     mut client: ExecutorClient<tonic::transport::Channel>,
-    config: Arc<Config>,
-    metrics: MetricsCache,
-    brain: Arc<AgenticBrain>,
-) -> Result<()> {
-    let mut ticker = interval(Duration::from_secs(ACTION_POLL_INTERVAL_SECS));
+    every: Duration,
+    cache: &MetricsCache,
+    votes: &VoteHistory,
+    rpc: &RpcHistory,
+) {
+    let mut ticker = interval(every);
+    let mut after_seq = 0;
+    loop {
+        ticker.tick().await;
+        let request = tonic::Request::new(MetricsWatchRequest {
+            validator_ids: vec![],
+            include_snapshot: true,
+            backfill_since: 0,
+            after_seq,
+        });
+        match client.get_all_metrics(request).await {
+            Ok(snapshot) => {
+                for update in snapshot.into_inner().updates {
+                    after_seq = after_seq.max(update.seq);
+                    ingest_update(cache, votes, rpc, update).await;
+                }
+            }
+            Err(err) => {
+                error!(?err, "metrics poll failed");
+                return;
+            }
+        }
+    }
+}
+
+/// Decode one streamed update into the cache. Failures are counted per
+/// validator; `[ingest] on_decode_error` decides what the count means.
+async fn ingest_update(
+    cache: &MetricsCache,
+    votes: &VoteHistory,
+    rpc: &RpcHistory,
+    update: MetricsUpdate,
+) {
+    match serde_json::from_str::<ValidatorMetrics>(&update.metrics_json) {
+        Ok(mut metrics) => {
+            if update.received_at > 0 {
+                cache
+                    .check_clock(&update.validator_id, &mut metrics, update.received_at)
+                    .await;
+            }
+            let smoothed = cache.insert(update.validator_id.clone(), metrics).await;
+            votes.record(&update.validator_id, &smoothed).await;
+            rpc.record(&update.validator_id, &smoothed).await;
+        }
+        Err(err) => {
+            error!(
+                validator = update.validator_id,
+                ?err,
+                "invalid metrics payload"
+            );
+            cache
+                .decode_errors
+                .record_failure(&update.validator_id, err.to_string())
+                .await;
+        }
+    }
+}
+
+async fn run_agent_loop(state: AppState) -> Result<()> {
+    let mut client = state.executor.clone();
+    let mut dispatcher = state.dispatcher.clone();
+    let tick = Duration::from_secs(ACTION_POLL_INTERVAL_SECS);
+    let warmup_tick = match state.config.ingest.warmup_tick_secs {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    };
+    let mut ticker = interval(warmup_tick.unwrap_or(tick));
+    let mut warming = warmup_tick.is_some();
+    let mut stale_tracker = StaleTracker::default();
+    let mut metric_alerts = MetricAlertTracker::default();
+    let mut idle = IdleTracker::default();
+    let mut undrain = UndrainTracker::default();
     info!(
         "agent loop started for {} validators",
-        config.validators.len()
+        state.validators().await.len()
     );
     loop {
         ticker.tick().await;
-        let snapshot = metrics.snapshot().await;
-        for validator in &config.validators {
-            let Some(metrics) = snapshot.get(&validator.id.0) else {
-                continue;
+        run_tick(
+            &state,
+            &mut client,
+            &mut dispatcher,
+            &mut stale_tracker,
+            &mut metric_alerts,
+            &mut idle,
+            &mut undrain,
+        )
+        .await?;
+        if warming && state.warmup.is_warm().await {
+            warming = false;
+            ticker = interval_at(tokio::time::Instant::now() + tick, tick);
+        }
+    }
+}
+
+/// One pass of the agent loop over every validator with a sample: alert,
+/// track freshness, then detect, plan and dispatch. With `[auto_undrain]`,
+/// disabled validators are only watched for a healthy run.
+async fn run_tick<D: ActionDispatcher>(
+    state: &AppState,
+    client: &mut ExecutorClient<tonic::transport::Channel>,
+    dispatcher: &mut D,
+    stale_tracker: &mut StaleTracker,
+    metric_alerts: &mut MetricAlertTracker,
+    idle: &mut IdleTracker,
+    undrain: &mut UndrainTracker,
+) -> Result<()> {
+    if state.config.detection.restart_vote_grace_secs > 0 {
+        refresh_restarts(state).await;
+    }
+    if state.config.daemon.registration.is_some() {
+        refresh_registered(state).await;
+    }
+    let validators = state.validators().await;
+    let (snapshot, decode_errors) = state
+        .loop_timings
+        .time(LoopPhase::Snapshot, async {
+            (
+                state.metrics.detection_snapshot().await,
+                state.metrics.decode_errors.snapshot().await,
+            )
+        })
+        .await;
+    state.warmup.observe(snapshot.is_empty(), now_ts()).await;
+    let drained = auto_undrain(state, client, undrain, &snapshot).await;
+    let ingest = &state.config.ingest;
+    for validator in &validators {
+        let Some(metrics) = snapshot.get(&validator.id.0) else {
+            continue;
+        };
+        // The daemon holds their actions, which would all land at once on
+        // re-enable.
+        if drained.contains(&validator.id.0) {
+            continue;
+        }
+        let decode_failing = decode_errors
+            .get(&validator.id.0)
+            .is_some_and(|stats| stats.is_failing(ingest.decode_failure_threshold));
+        if decode_failing && ingest.on_decode_error == DecodeErrorPolicy::BadData {
+            continue;
+        }
+        for notification in
+            metric_alerts.observe(&state.config.metric_alerts, &validator.id.0, metrics)
+        {
+            info!(
+                validator = validator.id.0,
+                alert = notification.alert,
+                message = notification.message,
+                "metric alert fired"
+            );
+            let notifier = state.notifier.clone();
+            tokio::spawn(async move {
+                notifier.notify(&notification).await;
+            });
+        }
+        let now = now_ts();
+        if let Some(event) = stale_tracker.observe(
+            &validator.id.0,
+            metrics,
+            now,
+            &state.config.staleness,
+            decode_failing && ingest.on_decode_error == DecodeErrorPolicy::CountStale,
+        ) {
+            info!(
+                validator = validator.id.0,
+                ?event,
+                "metrics freshness changed"
+            );
+            state.decisions.record(now, &validator.id.0, event).await;
+        }
+        let fingerprint = if state.config.detection.skip_unchanged {
+            Some(idle::fingerprint(
+                metrics,
+                state.votes.failing(&validator.id.0).await,
+                state.rpc.overloaded(&validator.id.0).await,
+            ))
+        } else {
+            None
+        };
+        // Healthy last time on the same values: only going stale could
+        // change the outcome.
+        if fingerprint.is_some_and(|fingerprint| idle.unchanged(&validator.id.0, fingerprint))
+            && state.config.staleness.overdue_secs(metrics, now) <= 0
+        {
+            continue;
+        }
+        let plans = evaluate_validator(state, validator, metrics, now).await;
+        if let Some(fingerprint) = fingerprint {
+            let healthy = state.evaluations.healthy(&validator.id.0).await;
+            idle.evaluated(&validator.id.0, fingerprint, healthy);
+        }
+        // Actions sent for a more severe issue this tick; later playbooks skip them.
+        let mut dispatched: Vec<Action> = Vec::new();
+        for DispatchPlan {
+            mut playbook,
+            rationale,
+            plan_source,
+            mut decision,
+            reserved_slot,
+        } in plans
+        {
+            if let Some(severity) =
+                gate_by_severity(&state.config.remediation, &validator.id, &mut playbook)
+            {
+                // Only the alerts run, not the plan as proposed.
+                decision = None;
+                info!(
+                    validator = validator.id.0,
+                    issue = ?playbook.trigger,
+                    ?severity,
+                    "issue is below min_action_severity, only alerting"
+                );
+            }
+            let alert_ctx = AlertContext {
+                validator: &validator.id.0,
+                host: &validator.host,
+                issue: playbook.trigger,
+                risk: risk_score_with(metrics, &state.config.risk_weights),
             };
-            if let Some(issue) = detect_issue(metrics) {
-                let agentic_plan = match brain.plan(validator, metrics, issue).await {
-                    Ok(plan) => plan,
-                    Err(err) => {
-                        error!(
-                            validator = validator.id.0,
-                            ?err,
-                            "agentic planning failed, falling back to rule-based playbook"
-                        );
-                        None
-                    }
-                };
-                let (playbook, rationale, plan_source) = match agentic_plan {
-                    Some(AgenticDecision {
-                        playbook,
-                        rationale,
-                        ..
-                    }) => (playbook, rationale, "agentic"),
-                    None => (choose_playbook(issue, &validator.id), None, "rule_based"),
-                };
+            alerts::apply_templates(&state.config.alerts, &mut playbook, &alert_ctx);
+            let policy = state.config.remediation.conflict_policy;
+            for conflict in conflicts::resolve(&mut playbook, policy) {
+                warn!(
+                    validator = validator.id.0,
+                    playbook = %playbook.id,
+                    first = conflict.kinds.0,
+                    first_step = conflict.first,
+                    second = conflict.kinds.1,
+                    second_step = conflict.second,
+                    ?policy,
+                    "conflicting actions in one dispatch"
+                );
+            }
+            let repeated = conflicts::drop_repeated(&mut playbook, &dispatched);
+            if repeated > 0 {
                 info!(
                     validator = validator.id.0,
-                    issue = ?issue,
                     playbook = %playbook.id,
-                    plan_source,
-                    rationale = rationale.as_deref(),
-                    "issue detected, dispatching actions via executor"
+                    repeated,
+                    "skipping actions already dispatched for a more severe issue"
                 );
-                for action in playbook.steps {
-                    let action_json = serde_json::to_string(&action)?;
-                    let request = tonic::Request::new(ActionEnvelope {
-                        validator_id: validator.id.0.clone(),
-                        action_json,
-                    });
-                    if let Err(err) = client.submit_action(request).await {
-                        error!(validator = validator.id.0, ?err, "failed to submit action");
-                    }
+            }
+            // Conflict resolution or an earlier plan may have taken the
+            // steps the slot was reserved for.
+            let disruption = &state.config.remediation.disruption;
+            if reserved_slot
+                && !playbook
+                    .steps
+                    .iter()
+                    .any(|step| disruption.is_disruptive(step))
+            {
+                state.disruptions.release(&validator.id).await;
+            }
+            if playbook.steps.is_empty() {
+                continue;
+            }
+            dispatched.extend(playbook.steps.iter().cloned());
+            notify_routed_sinks(
+                &state.notifier,
+                &validator.id,
+                &playbook,
+                state.config.remediation.severity_for(playbook.trigger),
+            );
+            info!(
+                validator = validator.id.0,
+                issue = ?playbook.trigger,
+                playbook = %playbook.id,
+                plan_source,
+                rationale = rationale.as_deref(),
+                "issue detected, dispatching actions via executor"
+            );
+            let dispatch_start = Instant::now();
+            let dispatch =
+                dispatch_playbook(dispatcher, &state.config, &validator.id, &playbook, now).await;
+            // Nothing reached the daemon, so nothing is disrupting the
+            // validator.
+            if reserved_slot
+                && dispatch
+                    .as_ref()
+                    .map_or(true, |&failed| failed == playbook.steps.len())
+            {
+                state.disruptions.release(&validator.id).await;
+            }
+            let failed = dispatch?;
+            if let Some(event) = decision {
+                state.decisions.record(now, &validator.id.0, event).await;
+            }
+            state
+                .agent_metrics
+                .playbook_dispatched(playbook.steps.len(), failed)
+                .await;
+            if dispatcher.is_direct()
+                && playbook
+                    .steps
+                    .iter()
+                    .any(|action| matches!(action, Action::RestartValidator { .. }))
+            {
+                // Queued behind the restart, so the next sample shows whether it recovered.
+                let request = tonic::Request::new(ScrapeRequest {
+                    validator_id: validator.id.0.clone(),
+                });
+                if let Err(err) = client.scrape_now(request).await {
+                    warn!(
+                        validator = validator.id.0,
+                        ?err,
+                        "failed to request post-restart scrape"
+                    );
                 }
             }
+            state
+                .loop_timings
+                .record(LoopPhase::Dispatch, dispatch_start.elapsed())
+                .await;
         }
     }
+    Ok(())
 }
 
-async fn health() -> &'static str {
-    "ok"
+/// Recent samples and action results for the agentic prompt, as far as
+/// `[agentic.context]` asks for them. A failed history lookup only leaves
+/// the actions out.
+async fn prompt_context(state: &AppState, validator: &ValidatorId) -> PromptContext {
+    let limits = state.brain.context();
+    let recent_metrics = state.metrics.history(&validator.0).await;
+    let mut recent_actions = Vec::new();
+    if limits.recent_actions > 0 {
+        let mut client = state.executor.clone();
+        match client
+            .list_action_history(tonic::Request::new(ActionHistoryRequest {
+                validator_id: validator.0.clone(),
+                limit: limits.recent_actions as u32,
+                ..ActionHistoryRequest::default()
+            }))
+            .await
+        {
+            Ok(response) => {
+                recent_actions = response
+                    .into_inner()
+                    .records
+                    .into_iter()
+                    .rev()
+                    .map(RecentAction::from)
+                    .collect()
+            }
+            Err(err) => warn!(
+                validator = validator.0,
+                error = err.message(),
+                "action history unavailable, planning without recent actions"
+            ),
+        }
+    }
+    PromptContext {
+        recent_metrics,
+        recent_actions,
+    }
 }
 
-async fn pending_actions() -> Json<serde_json::Value> {
-    Json(serde_json::json!({ "pending": 0 }))
-}
+/// Decide what, if anything, to dispatch for one validator: a plan per
+/// detected issue, most severe first. The outcomes are recorded so
+/// `/api/validators?explain=true` can say why nothing happened.
+async fn evaluate_validator(
+    state: &AppState,
+    validator: &ValidatorConfig,
+    metrics: &ValidatorMetrics,
+    now: i64,
+) -> Vec<DispatchPlan> {
+    // The last-known sample of a validator whose client stopped publishing
+    // says nothing about its health now, so nothing else is detected on it.
+    let detect = async {
+        if state.config.staleness.overdue_secs(metrics, now) > 0 {
+            vec![IssueKind::MetricsStale]
+        } else {
+            detect_issues_with(
+                metrics,
+                &state.config.detection.thresholds,
+                state.votes.failing(&validator.id.0).await,
+                state.rpc.overloaded(&validator.id.0).await,
+                &state.config.detection.rules,
+            )
+        }
+    };
+    let mut issues = state.loop_timings.time(LoopPhase::Detection, detect).await;
+    state.agent_metrics.issues_detected(&issues).await;
+    let remediation = &state.config.remediation;
+    issues.sort_by_key(|issue| Reverse(remediation.severity_for(*issue)));
+    state.confirmations.retain(&validator.id, &issues).await;
+    if issues.is_empty() {
+        state.disruptions.release(&validator.id).await;
+    }
 
-async fn actions_summary() -> Json<ActionsResponse> {
-    Json(ActionsResponse { pending: 0 })
+    let mut plans = Vec::new();
+    let mut decisions = Vec::with_capacity(issues.len());
+    for issue in issues {
+        let (decision, plan) = evaluate_issue(state, validator, metrics, issue, now).await;
+        decisions.push(decision);
+        plans.extend(plan);
+    }
+    state
+        .evaluations
+        .record(&validator.id.0, LastDecision::from_issues(now, decisions))
+        .await;
+    plans
 }
 
-async fn list_validators(State(state): State<AppState>) -> Json<ValidatorsResponse> {
-    let snapshot = state.metrics.snapshot().await;
-    let mut validators = Vec::with_capacity(state.config.validators.len());
+/// Whether one detected issue is dispatched, and the plan if so.
+async fn evaluate_issue(
+    state: &AppState,
+    validator: &ValidatorConfig,
+    metrics: &ValidatorMetrics,
+    issue: IssueKind,
+    now: i64,
+) -> (IssueDecision, Option<DispatchPlan>) {
+    let suppressed = |playbook: Option<String>, reason| IssueDecision {
+        issue,
+        playbook,
+        outcome: DecisionOutcome::Suppressed { reason },
+    };
+    if let Some(rule) = state.config.suppression_for(validator, issue, metrics) {
+        info!(
+            validator = validator.id.0,
+            issue = ?issue,
+            rule,
+            "issue suppressed by rule, not remediating"
+        );
+        return (suppressed(None, SuppressionReason::Rule { rule }), None);
+    }
+
+    let confirm_secs = state.config.remediation.confirm_secs;
+    // A stale sample's timestamp no longer advances, so staleness is
+    // confirmed on the agent's clock.
+    let sample_ts = if issue == IssueKind::MetricsStale {
+        now
+    } else {
+        metrics.last_updated
+    };
+    if let Some(remaining_secs) = state
+        .confirmations
+        .pending(&validator.id, issue, sample_ts, confirm_secs)
+        .await
+    {
+        return (
+            suppressed(None, SuppressionReason::Confirming { remaining_secs }),
+            None,
+        );
+    }
 
-    for cfg in &state.config.validators {
-        let metrics_opt = snapshot.get(&cfg.id.0).cloned();
-        let (status, risk) = match metrics_opt.as_ref() {
-            Some(metrics) => (
-                detect_issue(metrics)
-                    .map(|i| format!("{:?}", i))
-                    .unwrap_or_else(|| "ok".into()),
-                Some(risk_score(metrics)),
+    let cooldown_secs = state.config.remediation.cooldown_secs;
+    if let Some((remaining_secs, playbook)) = state
+        .cooldowns
+        .remaining(&validator.id, issue, now, cooldown_secs)
+        .await
+    {
+        info!(
+            validator = validator.id.0,
+            issue = ?issue,
+            playbook,
+            remaining_secs,
+            "issue still in cooldown, suppressing playbook"
+        );
+        return (
+            suppressed(
+                Some(playbook),
+                SuppressionReason::Cooldown { remaining_secs },
             ),
-            None => ("no_data".into(), None),
-        };
-        validators.push(ValidatorSummary {
-            id: cfg.id.0.clone(),
-            host: cfg.host.clone(),
-            prometheus_url: cfg.prometheus_url.clone(),
-            metrics: metrics_opt,
-            status,
-            risk_score: risk,
-        });
+            None,
+        );
     }
 
-    Json(ValidatorsResponse { validators })
+    // Planning on metrics known to be out of date could pick a restart;
+    // staleness only ever alerts.
+    let agentic_plan = if issue == IssueKind::MetricsStale {
+        None
+    } else {
+        let plan = async {
+            let context = prompt_context(state, &validator.id).await;
+            state.brain.plan(validator, metrics, issue, &context).await
+        };
+        match state.loop_timings.time(LoopPhase::Planning, plan).await {
+            Ok(plan) => plan,
+            Err(err) => {
+                error!(
+                    validator = validator.id.0,
+                    ?err,
+                    "agentic planning failed, falling back to rule-based playbook"
+                );
+                None
+            }
+        }
+    };
+    let plan = select_plan(
+        state.brain.mode(),
+        &validator.id,
+        issue,
+        agentic_plan,
+        &state.decisions,
+        now,
+    )
+    .await;
+    let remediation = &state.config.remediation;
+    let disruptive = remediation.acts_on(issue)
+        && plan
+            .playbook
+            .steps
+            .iter()
+            .any(|step| remediation.disruption.is_disruptive(step));
+    let mut reserved_slot = false;
+    if disruptive {
+        let limit = remediation.disruption.limit(state.validators().await.len());
+        let hold_secs = remediation.disruption.hold_secs;
+        match state
+            .disruptions
+            .acquire(&validator.id, now, limit, hold_secs)
+            .await
+        {
+            Ok(newly) => reserved_slot = newly,
+            Err(in_progress) => {
+                info!(
+                validator = validator.id.0,
+                issue = ?issue,
+                    in_progress,
+                    "fleet disruption limit reached, deferring playbook"
+                );
+                return (
+                    suppressed(
+                        Some(plan.playbook.id),
+                        SuppressionReason::DisruptionLimit { in_progress },
+                    ),
+                    None,
+                );
+            }
+        }
+    }
+    state
+        .cooldowns
+        .start(&validator.id, issue, &plan.playbook.id, now)
+        .await;
+    let decision = IssueDecision {
+        issue,
+        playbook: Some(plan.playbook.id.clone()),
+        outcome: DecisionOutcome::Dispatched {
+            plan_source: plan.plan_source,
+        },
+    };
+    (
+        decision,
+        Some(DispatchPlan {
+            reserved_slot,
+            ..plan
+        }),
+    )
 }
 
-#[derive(Clone)]
-struct AppState {
-    config: Arc<Config>,
-    metrics: MetricsCache,
+struct DispatchPlan {
+    playbook: Playbook,
+    rationale: Option<String>,
+    plan_source: &'static str,
+    /// Recorded in the decision log once the plan is dispatched.
+    decision: Option<DecisionEvent>,
+    /// Whether evaluating the plan took a fleet disruption slot, to be given
+    /// back if the plan's disruptive steps never go out.
+    reserved_slot: bool,
 }
 
-#[derive(Clone, Default)]
-struct MetricsCache {
-    inner: Arc<Mutex<HashMap<String, ValidatorMetrics>>>,
+/// Pick the playbook to dispatch. In shadow mode the static playbook always runs
+/// and the agentic proposal is only recorded in the decision log; in active mode
+/// an agentic plan carries its decision, rationale included, to be recorded
+/// if it gets past the cooldown, disruption and severity gates.
+async fn select_plan(
+    mode: AgenticMode,
+    validator: &ValidatorId,
+    issue: IssueKind,
+    agentic_plan: Option<AgenticDecision>,
+    decisions: &DecisionLog,
+    now: i64,
+) -> DispatchPlan {
+    match (mode, agentic_plan) {
+        (AgenticMode::Shadow, agentic) => {
+            let playbook = choose_playbook(issue, validator);
+            let (agentic, agentic_rationale) = match agentic {
+                Some(AgenticDecision {
+                    playbook,
+                    rationale,
+                    ..
+                }) => (Some(playbook), rationale),
+                None => (None, None),
+            };
+            decisions
+                .record(
+                    now,
+                    &validator.0,
+                    DecisionEvent::ShadowPlan {
+                        issue,
+                        dispatched: playbook.clone(),
+                        agentic,
+                        agentic_rationale,
+                    },
+                )
+                .await;
+            DispatchPlan {
+                playbook,
+                rationale: None,
+                plan_source: "rule_based_shadow",
+                decision: None,
+                reserved_slot: false,
+            }
+        }
+        (AgenticMode::Active, Some(agentic)) => DispatchPlan {
+            decision: Some(DecisionEvent::AgenticPlan {
+                issue,
+                playbook: agentic.playbook.clone(),
+                rationale: agentic.rationale.clone(),
+                model: agentic.model,
+                prompt_version: agentic.prompt_version,
+            }),
+            playbook: agentic.playbook,
+            rationale: agentic.rationale,
+            plan_source: "agentic",
+            reserved_slot: false,
+        },
+        (AgenticMode::Active, None) => DispatchPlan {
+            playbook: choose_playbook(issue, validator),
+            rationale: None,
+            plan_source: "rule_based",
+            decision: None,
+            reserved_slot: false,
+        },
+    }
 }
 
-impl MetricsCache {
-    async fn insert(&self, id: String, metrics: ValidatorMetrics) {
-        self.inner.lock().await.insert(id, metrics);
-    }
+async fn health() -> &'static str {
+    "ok"
+}
 
-    async fn snapshot(&self) -> HashMap<String, ValidatorMetrics> {
-        self.inner.lock().await.clone()
-    }
+async fn readyz(State(state): State<AppState>) -> (StatusCode, Json<serde_json::Value>) {
+    let code = if state.executor_status.is_connected() && !state.warmup.overdue(now_ts()).await {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        code,
+        Json(serde_json::json!({
+            "executor": state.executor_status.label(),
+            "metrics": state.warmup.label().await,
+        })),
+    )
 }
 
-#[derive(Serialize)]
-struct ActionsResponse {
-    pending: i64,
+async fn export_metrics(State(state): State<AppState>) -> String {
+    let mut out = state
+        .agent_metrics
+        .render_metrics(state.validators().await.len())
+        .await;
+    out.push_str(&state.brain.render_metrics(now_ts()).await);
+    out.push_str(&state.metrics.decode_errors.render_metrics().await);
+    out.push_str(&state.loop_timings.render_metrics().await);
+    out
 }
 
-#[derive(Serialize)]
-struct ValidatorsResponse {
-    validators: Vec<ValidatorSummary>,
+/// Every `(validator, issue)` dispatch time with the cooldown left on it, by
+/// validator and then dispatch time.
+async fn cooldown_timers(state: &AppState, now: i64) -> Vec<DebugCooldown> {
+    let cooldown_secs = state.config.remediation.cooldown_secs;
+    let mut cooldowns: Vec<DebugCooldown> = state
+        .cooldowns
+        .snapshot()
+        .await
+        .into_iter()
+        .map(|(validator, issue, dispatched)| DebugCooldown {
+            validator: validator.0,
+            issue,
+            playbook: dispatched.playbook,
+            dispatched_at: dispatched.at,
+            remaining_secs: (dispatched.at + cooldown_secs - now).max(0),
+        })
+        .collect();
+    cooldowns.sort_by(|a, b| (&a.validator, a.dispatched_at).cmp(&(&b.validator, b.dispatched_at)));
+    cooldowns
 }
 
-#[derive(Serialize)]
-struct ValidatorSummary {
-    id: String,
-    host: String,
-    prometheus_url: String,
-    metrics: Option<ValidatorMetrics>,
-    status: String,
-    risk_score: Option<f64>,
+/// The issues whose playbooks are currently held back by their cooldown.
+async fn debug_cooldowns(State(state): State<AppState>) -> Json<Vec<DebugCooldown>> {
+    let mut cooldowns = cooldown_timers(&state, now_ts()).await;
+    cooldowns.retain(|cooldown| cooldown.remaining_secs > 0);
+    Json(cooldowns)
+}
+
+/// Everything the agent holds in memory, for attaching to bug reports.
+async fn debug_state(State(state): State<AppState>) -> Json<DebugState> {
+    let now = now_ts();
+    let metrics: BTreeMap<String, ValidatorMetrics> =
+        state.metrics.snapshot().await.into_iter().collect();
+    let cooldowns = cooldown_timers(&state, now).await;
+    let mut confirmations: Vec<DebugConfirmation> = state
+        .confirmations
+        .snapshot()
+        .await
+        .into_iter()
+        .map(|(validator, issue, onset)| DebugConfirmation {
+            validator: validator.0,
+            issue,
+            onset,
+        })
+        .collect();
+    confirmations
+        .sort_by(|a, b| (&a.validator, a.issue.as_str()).cmp(&(&b.validator, b.issue.as_str())));
+    let active_issues = state
+        .evaluations
+        .snapshot()
+        .await
+        .into_iter()
+        .filter(|(_, decision)| !decision.issues.is_empty())
+        .map(|(validator, decision)| (validator, decision.issues))
+        .collect();
+    let disabled = disabled_validators(&state).await;
+    let automation_paused = state
+        .validators()
+        .await
+        .iter()
+        .map(|v| (v.id.0.clone(), disabled.contains(&v.id.0)))
+        .collect();
+    Json(DebugState {
+        generated_at: now,
+        executor: state.executor_status.label(),
+        metrics_cache_size: metrics.len(),
+        metrics,
+        cooldowns,
+        confirmations,
+        active_issues,
+        automation_paused,
+        agentic_budget_exhausted: state.brain.budget_exhausted(now).await,
+        agentic_cache: state.brain.cache_stats().await,
+        config: redacted_config(&state.config),
+    })
 }
 
-/// Detect issues using simple rule-based logic.
-pub fn detect_issue(metrics: &ValidatorMetrics) -> Option<IssueKind> {
-    if metrics.slot_lag > 50 {
-        return Some(IssueKind::SlotLagHigh);
+/// The config as JSON with validator auth tokens, notification and result
+/// webhook URLs (which often carry a secret in the path) and the userinfo
+/// of every other URL, e.g. a `redis_url` password, blanked out.
+fn redacted_config(config: &Config) -> serde_json::Value {
+    let mut value = serde_json::to_value(config).unwrap_or_default();
+    redact_userinfo(&mut value);
+    if let Some(validators) = value["validators"].as_array_mut() {
+        for validator in validators {
+            validator["auth_token"] = serde_json::json!("<redacted>");
+        }
     }
-    if metrics.vote_success_rate < 0.8 {
-        return Some(IssueKind::VoteFailureSpike);
+    if let Some(sinks) = value["alerts"]["sinks"].as_object_mut() {
+        for sink in sinks.values_mut() {
+            if sink.get("url").is_some() {
+                sink["url"] = serde_json::json!("<redacted>");
+            }
+        }
     }
-    if metrics.cpu_usage > 0.9 || metrics.ram_usage_gb > 0.9 * MAX_RAM_GB {
-        return Some(IssueKind::HardwareOverload);
+    if value["daemon"]["result_webhook"].is_object() {
+        value["daemon"]["result_webhook"]["url"] = serde_json::json!("<redacted>");
     }
-    if metrics.disk_usage_pct > 90.0 {
-        return Some(IssueKind::DiskAlmostFull);
+    value
+}
+
+/// Replace the `user:password` part of every URL string in `value`.
+fn redact_userinfo(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::String(url) => {
+            let Some((scheme, rest)) = url.split_once("://") else {
+                return;
+            };
+            let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+            if let Some((_, host)) = authority.rsplit_once('@') {
+                let path = &rest[authority.len()..];
+                *url = format!("{scheme}://<redacted>@{host}{path}");
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact_userinfo),
+        serde_json::Value::Object(fields) => fields.values_mut().for_each(redact_userinfo),
+        _ => {}
     }
-    if metrics.rpc_qps > 1000.0 && metrics.rpc_error_rate > 0.05 {
-        return Some(IssueKind::RpcOverload);
+}
+
+/// The daemon's undelivered actions, grouped by validator in delivery order.
+async fn pending_actions(
+    State(state): State<AppState>,
+    Query(query): Query<PendingActionsQuery>,
+) -> Result<Json<PendingActionsResponse>, (StatusCode, String)> {
+    let mut client = state.executor.clone();
+    let actions = client
+        .list_pending_actions(tonic::Request::new(PendingActionsRequest {
+            validator_id: query.validator.unwrap_or_default(),
+        }))
+        .await
+        .map_err(|err| (StatusCode::BAD_GATEWAY, err.message().to_string()))?
+        .into_inner()
+        .actions;
+    let mut response = PendingActionsResponse {
+        pending: actions.len(),
+        validators: BTreeMap::new(),
+    };
+    for action in actions {
+        response
+            .validators
+            .entry(action.validator_id)
+            .or_default()
+            .push(PendingActionEntry {
+                kind: action.kind,
+                age_secs: action.age_secs,
+                priority: action.priority,
+                action_id: Some(action.action_id).filter(|id| !id.is_empty()),
+            });
     }
-    None
+    Ok(Json(response))
 }
 
-/// Hard-coded playbooks that can be swapped for learned policies later.
-pub fn choose_playbook(issue: IssueKind, validator: &ValidatorId) -> Playbook {
-    match issue {
-        IssueKind::SlotLagHigh => Playbook {
-            id: "slot-lag-recovery".into(),
-            trigger: issue,
-            steps: vec![
-                Action::DisableRpc {
-                    validator: validator.clone(),
-                },
-                Action::RestartValidator {
-                    validator: validator.clone(),
-                },
-                Action::EnableRpc {
-                    validator: validator.clone(),
-                },
-            ],
-        },
-        IssueKind::RpcOverload => Playbook {
-            id: "rpc-overload".into(),
-            trigger: issue,
-            steps: vec![
-                Action::ThrottleRpcClient {
-                    validator: validator.clone(),
-                },
-                Action::SendAlert {
-                    validator: validator.clone(),
-                    message: "RPC overload detected".into(),
-                },
-            ],
-        },
-        IssueKind::DiskAlmostFull => Playbook {
-            id: "disk-cleanup".into(),
-            trigger: issue,
-            steps: vec![Action::RunMaintenanceScript {
-                validator: validator.clone(),
-                script_name: "cleanup-logs.sh".into(),
-            }],
+async fn actions_summary() -> Json<ActionsResponse> {
+    Json(ActionsResponse { pending: 0 })
+}
+
+async fn list_validators(
+    State(state): State<AppState>,
+    Query(query): Query<ValidatorsQuery>,
+) -> Json<ValidatorsResponse> {
+    let disabled = disabled_validators(&state).await;
+    let mut validators = validator_summaries(&state, &disabled, query.explain).await;
+    if query.sort == Some(ValidatorSort::Degradation) {
+        sort_by_degradation(&mut validators);
+    }
+    Json(ValidatorsResponse { validators })
+}
+
+async fn list_groups(State(state): State<AppState>) -> Json<GroupsResponse> {
+    let disabled = disabled_validators(&state).await;
+    let validators = validator_summaries(&state, &disabled, false).await;
+    let by = state.config.groups.by.clone();
+    let groups = groups::group_summaries(&state.validators().await, &validators, &by);
+    Json(GroupsResponse { by, groups })
+}
+
+async fn stream_validators(ws: WebSocketUpgrade, State(state): State<AppState>) -> Response {
+    // Selected so a browser that sent its token as a subprotocol accepts the
+    // handshake; the token itself is never echoed.
+    ws.protocols([STREAM_PROTOCOL])
+        .on_upgrade(move |socket| stream_summaries(socket, state))
+}
+
+/// Send every validator's summary, then the summary of each validator as its
+/// samples arrive, until the client goes away.
+async fn stream_summaries(mut socket: WebSocket, state: AppState) {
+    // Subscribed before the snapshot so no sample falls between them.
+    let mut updates = state.metrics.subscribe();
+    let mut disabled = disabled_validators(&state).await;
+    let mut disabled_at = Instant::now();
+    let snapshot = StreamMessage::Snapshot {
+        validators: validator_summaries(&state, &disabled, false).await,
+    };
+    if send_stream_message(&mut socket, &snapshot).await.is_err() {
+        return;
+    }
+    loop {
+        let message = tokio::select! {
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_)) | Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+            update = updates.recv() => update,
+        };
+        if disabled_at.elapsed() >= STREAM_DISABLED_REFRESH {
+            disabled = disabled_validators(&state).await;
+            disabled_at = Instant::now();
+        }
+        let message = match message {
+            Ok(id) => {
+                let validators = state.validators().await;
+                let Some(cfg) = validators.iter().find(|cfg| cfg.id.0 == id) else {
+                    continue;
+                };
+                StreamMessage::Update {
+                    validator: Box::new(validator_summary(&state, cfg, &disabled, now_ts()).await),
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => StreamMessage::Snapshot {
+                validators: validator_summaries(&state, &disabled, false).await,
+            },
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        if send_stream_message(&mut socket, &message).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn send_stream_message(socket: &mut WebSocket, message: &StreamMessage) -> Result<()> {
+    let text = serde_json::to_string(message)?;
+    socket.send(Message::Text(text)).await?;
+    Ok(())
+}
+
+/// Most degraded first; validators without a score last, in config order.
+fn sort_by_degradation(validators: &mut [ValidatorSummary]) {
+    validators.sort_by(|a, b| {
+        let score = |summary: &ValidatorSummary| summary.degradation.unwrap_or(-1.0);
+        score(b).total_cmp(&score(a))
+    });
+}
+
+async fn validator_summaries(
+    state: &AppState,
+    disabled: &HashSet<String>,
+    explain: bool,
+) -> Vec<ValidatorSummary> {
+    let mut last_decisions = if explain {
+        state.evaluations.snapshot().await
+    } else {
+        HashMap::new()
+    };
+    let now = now_ts();
+    let configs = state.validators().await;
+    let mut validators = Vec::with_capacity(configs.len());
+    for cfg in &configs {
+        let mut summary = validator_summary(state, cfg, disabled, now).await;
+        summary.last_decision = last_decisions.remove(&cfg.id.0);
+        validators.push(summary);
+    }
+    validators
+}
+
+/// One validator's entry of [`validator_summaries`], without its last
+/// decision.
+async fn validator_summary(
+    state: &AppState,
+    cfg: &ValidatorConfig,
+    disabled: &HashSet<String>,
+    now: i64,
+) -> ValidatorSummary {
+    let ingest = &state.config.ingest;
+    let weights = &state.config.risk_weights;
+    let metrics_opt = state.metrics.get(&cfg.id.0).await;
+    let detection = state.metrics.detection_get(&cfg.id.0).await;
+    let detection_metrics = detection.as_ref();
+    let decode = state.metrics.decode_errors.get(&cfg.id.0).await;
+    let clock_drift_secs = state
+        .metrics
+        .clock_drift
+        .lock()
+        .await
+        .get(&cfg.id.0)
+        .copied();
+    let bad_data = ingest.on_decode_error == DecodeErrorPolicy::BadData
+        && decode.is_failing(ingest.decode_failure_threshold);
+    let (status, risk, issues) = match detection_metrics {
+        _ if disabled.contains(&cfg.id.0) => {
+            let risk = detection_metrics.map(|metrics| risk_score_with(metrics, weights));
+            (vec!["disabled".into()], risk, Vec::new())
+        }
+        _ if bad_data => (vec!["bad_data".into()], None, Vec::new()),
+        // A risk score from the last-known sample would look healthy.
+        Some(metrics) if state.config.staleness.overdue_secs(metrics, now) > 0 => {
+            (vec!["stale".into()], None, Vec::new())
+        }
+        Some(metrics) => {
+            let issues = detect_issues_with(
+                metrics,
+                &state.config.detection.thresholds,
+                state.votes.failing(&cfg.id.0).await,
+                state.rpc.overloaded(&cfg.id.0).await,
+                &state.config.detection.rules,
+            );
+            let status = if issues.is_empty() {
+                vec!["ok".into()]
+            } else {
+                issues.iter().map(|i| format!("{:?}", i)).collect()
+            };
+            (status, Some(risk_score_with(metrics, weights)), issues)
+        }
+        None => (vec!["no_data".into()], None, Vec::new()),
+    };
+    let degradation = risk.map(|risk| {
+        let severities = issues
+            .iter()
+            .map(|issue| state.config.remediation.severity_for(*issue));
+        composite_health(risk, severities, &state.config.degradation)
+    });
+    let risk_explanation = risk
+        .and(detection_metrics)
+        .map(|metrics| explain_risk(metrics, weights));
+    ValidatorSummary {
+        id: cfg.id.0.clone(),
+        host: cfg.host.clone(),
+        prometheus_url: cfg.prometheus_url.clone(),
+        metrics: metrics_opt,
+        status,
+        risk_score: risk,
+        risk_explanation,
+        degradation,
+        decode_errors: decode.total,
+        decode_error: decode.last_error.filter(|_| bad_data),
+        clock_drift_secs,
+        last_decision: None,
+    }
+}
+
+/// Validators an operator has paused on the daemon. Best effort: an unreachable
+/// daemon just means no validator is reported as disabled.
+async fn disabled_validators(state: &AppState) -> HashSet<String> {
+    disabled_validator_states(state)
+        .await
+        .into_iter()
+        .map(|v| v.validator_id)
+        .collect()
+}
+
+/// The daemon's states of the validators in [`disabled_validators`].
+async fn disabled_validator_states(state: &AppState) -> Vec<ValidatorState> {
+    let mut client = state.executor.clone();
+    match client
+        .list_validator_states(tonic::Request::new(ValidatorStatesRequest {}))
+        .await
+    {
+        Ok(response) => response
+            .into_inner()
+            .validators
+            .into_iter()
+            .filter(|v| !v.enabled)
+            .collect(),
+        Err(err) => {
+            error!(?err, "failed to fetch validator states from executor");
+            Vec::new()
+        }
+    }
+}
+
+/// Re-enable validators disabled on the daemon with `auto_undrain` whose
+/// fresh samples have stayed below `[auto_undrain] max_risk` for
+/// `healthy_secs`, and return the ones still disabled. A drain without the
+/// flag is an operator's and holds. Empty without `[auto_undrain]`; best
+/// effort, like [`disabled_validators`].
+async fn auto_undrain(
+    state: &AppState,
+    client: &mut ExecutorClient<tonic::transport::Channel>,
+    undrain: &mut UndrainTracker,
+    snapshot: &HashMap<String, ValidatorMetrics>,
+) -> HashSet<String> {
+    let Some(cfg) = &state.config.auto_undrain else {
+        return HashSet::new();
+    };
+    let disabled = disabled_validator_states(state).await;
+    let liftable: HashSet<&str> = disabled
+        .iter()
+        .filter(|v| v.auto_undrain)
+        .map(|v| v.validator_id.as_str())
+        .collect();
+    let mut drained: HashSet<String> = disabled.iter().map(|v| v.validator_id.clone()).collect();
+    let now = now_ts();
+    for validator in &state.validators().await {
+        let id = &validator.id.0;
+        let risk = snapshot
+            .get(id)
+            .filter(|metrics| state.config.staleness.overdue_secs(metrics, now) <= 0)
+            .map(|metrics| risk_score_with(metrics, &state.config.risk_weights));
+        let Some(healthy_secs) =
+            undrain.observe(id, liftable.contains(id.as_str()), risk, now, cfg)
+        else {
+            continue;
+        };
+        match client
+            .enable_validator(tonic::Request::new(ValidatorToggleRequest {
+                validator_id: id.clone(),
+                ..ValidatorToggleRequest::default()
+            }))
+            .await
+        {
+            Ok(_) => {
+                info!(
+                    validator = id,
+                    healthy_secs, "auto-undrained: re-enabled after a sustained healthy run"
+                );
+                let event = DecisionEvent::Undrained {
+                    healthy_secs,
+                    risk: risk.unwrap_or_default(),
+                };
+                state.decisions.record(now, id, event).await;
+                drained.remove(id);
+            }
+            Err(err) => warn!(validator = id, ?err, "failed to auto-undrain validator"),
+        }
+    }
+    drained
+}
+
+/// Replace the self-registered validators with those the daemon lists now.
+/// Best effort, like [`disabled_validators`]: the last list is kept while
+/// the daemon is unreachable.
+async fn refresh_registered(state: &AppState) {
+    let mut client = state.executor.clone();
+    match client
+        .list_validator_states(tonic::Request::new(ValidatorStatesRequest {}))
+        .await
+    {
+        Ok(response) => {
+            let registered = registered_validators(&state.config, response.into_inner().validators);
+            *state.registered.lock().await = registered;
+        }
+        Err(err) => {
+            warn!(?err, "failed to fetch registered validators from executor");
+        }
+    }
+}
+
+/// The daemon's self-registered validators missing from the config.
+fn registered_validators(config: &Config, states: Vec<ValidatorState>) -> Vec<ValidatorConfig> {
+    states
+        .into_iter()
+        .filter(|state| {
+            state.registered
+                && !config
+                    .validators
+                    .iter()
+                    .any(|v| v.id.0 == state.validator_id)
+        })
+        .map(|state| ValidatorConfig {
+            id: ValidatorId(state.validator_id),
+            host: state.host,
+            prometheus_url: state.prometheus_url,
+            auth_token: String::new(),
+            allowed_scripts: None,
+            tags: state.tags,
+            labels: BTreeMap::new(),
+            agentic_allowed_actions: None,
+            metrics_fallbacks: Vec::new(),
+        })
+        .collect()
+}
+
+/// Pass the restart completion times the daemon has seen on to vote
+/// detection. Best effort, like [`disabled_validators`].
+async fn refresh_restarts(state: &AppState) {
+    let mut client = state.executor.clone();
+    match client
+        .list_validator_states(tonic::Request::new(ValidatorStatesRequest {}))
+        .await
+    {
+        Ok(response) => {
+            for validator in response.into_inner().validators {
+                if validator.last_restart_at > 0 {
+                    state
+                        .votes
+                        .restarted(&validator.validator_id, validator.last_restart_at)
+                        .await;
+                }
+            }
+        }
+        Err(err) => {
+            warn!(?err, "failed to fetch restart times from executor");
+        }
+    }
+}
+
+async fn list_decisions(
+    State(state): State<AppState>,
+    Query(query): Query<DecisionsQuery>,
+) -> Json<DecisionsResponse> {
+    let limit = query.limit.unwrap_or(DEFAULT_DECISIONS_LIMIT);
+    let decisions = state
+        .decisions
+        .recent(query.validator.as_deref(), limit)
+        .await;
+    Json(DecisionsResponse { decisions })
+}
+
+/// Action results reported to the daemon, newest first.
+async fn action_history(
+    State(state): State<AppState>,
+    Query(query): Query<ActionHistoryQuery>,
+) -> Result<Json<ActionHistoryResponse>, (StatusCode, String)> {
+    let mut client = state.executor.clone();
+    let records = client
+        .list_action_history(tonic::Request::new(ActionHistoryRequest {
+            validator_id: query.validator.unwrap_or_default(),
+            since: query.since.unwrap_or_default(),
+            until: query.until.unwrap_or_default(),
+            limit: query.limit.unwrap_or(DEFAULT_DECISIONS_LIMIT) as u32,
+        }))
+        .await
+        .map_err(|err| (StatusCode::BAD_GATEWAY, err.message().to_string()))?
+        .into_inner()
+        .records;
+    Ok(Json(ActionHistoryResponse {
+        records: records
+            .into_iter()
+            .map(|record| ActionHistoryEntry {
+                validator: record.validator_id,
+                action: serde_json::from_str(&record.action_json)
+                    .unwrap_or(serde_json::Value::String(record.action_json)),
+                success: record.success,
+                message: record.message,
+                timestamp: record.timestamp,
+                dry_run: record.dry_run,
+            })
+            .collect(),
+    }))
+}
+
+/// Asks a validator's client to scrape and publish its metrics immediately.
+async fn scrape_validator(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if !state.validators().await.iter().any(|v| v.id.0 == id) {
+        return Err((StatusCode::NOT_FOUND, format!("unknown validator {id}")));
+    }
+    let mut client = state.executor.clone();
+    client
+        .scrape_now(tonic::Request::new(ScrapeRequest {
+            validator_id: id.clone(),
+        }))
+        .await
+        .map_err(|err| (StatusCode::BAD_GATEWAY, err.message().to_string()))?;
+    info!(validator = id, "manual scrape requested");
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// The validator's playbook runs on the daemon that still have a step to
+/// run, newest first, with each step's state.
+async fn active_playbooks(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<PlaybookExecutionEntry>>, (StatusCode, String)> {
+    if !state.validators().await.iter().any(|v| v.id.0 == id) {
+        return Err((StatusCode::NOT_FOUND, format!("unknown validator {id}")));
+    }
+    let mut client = state.executor.clone();
+    let executions = client
+        .list_playbook_executions(tonic::Request::new(PlaybookExecutionsRequest {
+            validator_id: id,
+        }))
+        .await
+        .map_err(|err| (StatusCode::BAD_GATEWAY, err.message().to_string()))?
+        .into_inner()
+        .executions;
+    Ok(Json(active_entries(executions)))
+}
+
+/// The runs among `executions` with a step still pending, delivered or
+/// running.
+fn active_entries(executions: Vec<PlaybookExecution>) -> Vec<PlaybookExecutionEntry> {
+    executions
+        .into_iter()
+        .filter(|execution| {
+            execution.steps.iter().any(|step| {
+                matches!(
+                    step.state(),
+                    StepState::Pending | StepState::Delivered | StepState::Running
+                )
+            })
+        })
+        .map(|execution| PlaybookExecutionEntry {
+            plan_id: execution.plan_id,
+            issue: Some(execution.issue).filter(|issue| !issue.is_empty()),
+            started_at: execution.started_at,
+            updated_at: execution.updated_at,
+            steps: execution
+                .steps
+                .into_iter()
+                .map(|step| PlaybookStepEntry {
+                    step: step.step,
+                    state: match step.state() {
+                        StepState::Pending => "pending",
+                        StepState::Delivered => "delivered",
+                        StepState::Running => "running",
+                        StepState::Succeeded => "succeeded",
+                        StepState::Failed => "failed",
+                        StepState::Skipped => "skipped",
+                    },
+                    kind: step.kind,
+                    message: Some(step.message).filter(|message| !message.is_empty()),
+                    rolled_back: step.rolled_back,
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Runs an allowlisted maintenance script on every registered validator.
+async fn run_maintenance(
+    State(state): State<AppState>,
+    Json(request): Json<MaintenanceRunRequest>,
+) -> Result<Json<MaintenanceRunResponse>, (StatusCode, String)> {
+    let script_name = request.script_name.trim().to_string();
+    if !state
+        .config
+        .maintenance
+        .allowed_scripts
+        .contains(&script_name)
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("script {script_name} is not in the maintenance allowlist"),
+        ));
+    }
+    if let Err(err) = state
+        .config
+        .commands
+        .script_command(&script_name, &request.params)
+    {
+        return Err((StatusCode::BAD_REQUEST, err.to_string()));
+    }
+
+    let validators = state.validators().await;
+    let targets = maintenance_targets(&state.config, &validators, &script_name, &request.params);
+    let mut dispatcher = state.dispatcher.clone();
+    let result = submit_all(&mut dispatcher, &state.config.remediation, targets).await;
+    info!(
+        script = %script_name,
+        submitted = result.submitted.len(),
+        failed = result.failed.len(),
+        skipped = result.skipped.len(),
+        "maintenance run requested"
+    );
+    Ok(Json(MaintenanceRunResponse {
+        script_name,
+        result,
+    }))
+}
+
+/// Submits one action to several validators (all registered ones by default).
+async fn submit_bulk_action(
+    State(state): State<AppState>,
+    Json(request): Json<BulkActionRequest>,
+) -> Result<Json<BulkResult>, (StatusCode, String)> {
+    let targets = bulk_targets(&state.config, &state.validators().await, request)
+        .map_err(|err| (StatusCode::BAD_REQUEST, format!("invalid action: {err}")))?;
+    let mut dispatcher = state.dispatcher.clone();
+    let result = submit_all(&mut dispatcher, &state.config.remediation, targets).await;
+    info!(
+        submitted = result.submitted.len(),
+        failed = result.failed.len(),
+        skipped = result.skipped.len(),
+        "bulk action requested"
+    );
+    Ok(Json(result))
+}
+
+/// Build each requested validator's copy of the action, skipping validators
+/// that are not registered or whose script allowlist excludes it.
+fn bulk_targets(
+    config: &Config,
+    validators: &[ValidatorConfig],
+    request: BulkActionRequest,
+) -> Result<Vec<BulkTarget>, serde_json::Error> {
+    let ids = if request.validators.is_empty() {
+        validators.iter().map(|v| v.id.0.clone()).collect()
+    } else {
+        request.validators
+    };
+    let mut targets = Vec::with_capacity(ids.len());
+    for id in ids {
+        let validator = ValidatorId(id);
+        let Some(validator_cfg) = validators.iter().find(|v| v.id == validator) else {
+            targets.push(BulkTarget {
+                validator,
+                action: Err("unknown validator".into()),
+            });
+            continue;
+        };
+        let mut fields = request.action.clone();
+        fields.insert("validator".into(), serde_json::json!(validator.0));
+        let action: Action = serde_json::from_value(fields.into())?;
+        let action = match &action {
+            Action::RunMaintenanceScript { script_name, .. }
+                if !config.script_allowed(validator_cfg, script_name) =>
+            {
+                Err("script not allowlisted for validator".to_string())
+            }
+            _ => Ok(action),
+        };
+        targets.push(BulkTarget { validator, action });
+    }
+    Ok(targets)
+}
+
+/// Resolve which validators should receive a maintenance script run.
+fn maintenance_targets(
+    config: &Config,
+    validators: &[ValidatorConfig],
+    script_name: &str,
+    params: &HashMap<String, String>,
+) -> Vec<BulkTarget> {
+    validators
+        .iter()
+        .map(|validator| BulkTarget {
+            validator: validator.id.clone(),
+            action: maintenance_action(config, validator, script_name, params)
+                .ok_or_else(|| "script not allowlisted for validator".to_string()),
+        })
+        .collect()
+}
+
+fn maintenance_action(
+    config: &Config,
+    validator: &ValidatorConfig,
+    script_name: &str,
+    params: &HashMap<String, String>,
+) -> Option<Action> {
+    config
+        .script_allowed(validator, script_name)
+        .then(|| Action::RunMaintenanceScript {
+            validator: validator.id.clone(),
+            script_name: script_name.to_string(),
+            params: params.clone(),
+        })
+}
+
+/// State shared by the agent loop and the HTTP API.
+#[derive(Clone)]
+struct AppState {
+    config: Arc<Config>,
+    metrics: MetricsCache,
+    executor: ExecutorClient<tonic::transport::Channel>,
+    /// Where playbook and bulk actions go; `executor` unless `[action_queue]` is set.
+    dispatcher: Dispatcher,
+    brain: Arc<AgenticBrain>,
+    decisions: DecisionLog,
+    cooldowns: Cooldowns,
+    confirmations: Confirmations,
+    disruptions: Disruptions,
+    votes: VoteHistory,
+    rpc: RpcHistory,
+    evaluations: Evaluations,
+    executor_status: ExecutorStatus,
+    notifier: NotificationRouter,
+    loop_timings: LoopTimings,
+    agent_metrics: AgentMetrics,
+    warmup: MetricsWarmup,
+    /// Validators that registered themselves with the daemon, as last listed
+    /// by `ListValidatorStates`, with `[daemon.registration]` set.
+    registered: Arc<Mutex<Vec<ValidatorConfig>>>,
+    /// Required on `/api/*` and `/debug/*` when set.
+    api_token: Option<String>,
+}
+
+impl AppState {
+    /// The configured validators followed by the self-registered ones.
+    async fn validators(&self) -> Vec<ValidatorConfig> {
+        let mut validators = self.config.validators.clone();
+        validators.extend(self.registered.lock().await.iter().cloned());
+        validators
+    }
+}
+
+/// Latest raw sample per validator, plus the smoothed view detection uses.
+#[derive(Clone)]
+struct MetricsCache {
+    inner: Arc<Mutex<HashMap<String, ValidatorMetrics>>>,
+    smoother: Smoother,
+    decode_errors: DecodeErrors,
+    staleness: StalenessConfig,
+    /// Latest flagged client clock drift per validator, in seconds.
+    clock_drift: Arc<Mutex<HashMap<String, i64>>>,
+    /// The last `history_len` raw samples per validator, oldest first, for
+    /// `[agentic.context] metric_samples`.
+    history: Arc<Mutex<HashMap<String, VecDeque<ValidatorMetrics>>>>,
+    history_len: usize,
+    /// The id of each validator as a sample for it is stored.
+    updates: broadcast::Sender<String>,
+}
+
+impl Default for MetricsCache {
+    fn default() -> Self {
+        Self::new(Smoother::default(), StalenessConfig::default())
+    }
+}
+
+impl MetricsCache {
+    fn new(smoother: Smoother, staleness: StalenessConfig) -> Self {
+        Self {
+            inner: Arc::default(),
+            smoother,
+            decode_errors: DecodeErrors::default(),
+            staleness,
+            clock_drift: Arc::default(),
+            history: Arc::default(),
+            history_len: 0,
+            updates: broadcast::channel(METRICS_UPDATES_CAPACITY).0,
+        }
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.updates.subscribe()
+    }
+
+    fn with_history(self, history_len: usize) -> Self {
+        Self {
+            history_len,
+            ..self
+        }
+    }
+
+    /// Compare the client's `last_updated` with the daemon's receive time,
+    /// flagging drift and, with `use_receive_time`, re-basing the sample's
+    /// timestamps onto the daemon clock.
+    async fn check_clock(&self, id: &str, metrics: &mut ValidatorMetrics, received_at: i64) {
+        let mut drifting = self.clock_drift.lock().await;
+        match self
+            .staleness
+            .clock_drift(metrics.last_updated, received_at)
+        {
+            Some(drift) => {
+                drifting.insert(id.to_string(), drift);
+            }
+            None => {
+                drifting.remove(id);
+            }
+        }
+        if self.staleness.use_receive_time {
+            let offset = received_at - metrics.last_updated;
+            metrics.last_updated = received_at;
+            for ts in metrics.field_timestamps.values_mut() {
+                *ts += offset;
+            }
+        }
+    }
+
+    /// Store a raw sample and return its smoothed counterpart.
+    async fn insert(&self, id: String, metrics: ValidatorMetrics) -> ValidatorMetrics {
+        self.decode_errors.record_success(&id).await;
+        let smoothed = self.smoother.update(&id, &metrics).await;
+        if self.history_len > 0 {
+            let mut history = self.history.lock().await;
+            let samples = history.entry(id.clone()).or_default();
+            if samples.len() == self.history_len {
+                samples.pop_front();
+            }
+            samples.push_back(metrics.clone());
+        }
+        self.inner.lock().await.insert(id.clone(), metrics);
+        // Nobody listening is fine.
+        let _ = self.updates.send(id);
+        smoothed
+    }
+
+    /// Recent raw samples for `id`, oldest first.
+    async fn history(&self, id: &str) -> Vec<ValidatorMetrics> {
+        self.history
+            .lock()
+            .await
+            .get(id)
+            .map(|samples| samples.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    async fn snapshot(&self) -> HashMap<String, ValidatorMetrics> {
+        self.inner.lock().await.clone()
+    }
+
+    /// The samples detection and scoring should read.
+    async fn detection_snapshot(&self) -> HashMap<String, ValidatorMetrics> {
+        if self.smoother.is_enabled() {
+            self.smoother.snapshot().await
+        } else {
+            self.snapshot().await
+        }
+    }
+
+    async fn get(&self, id: &str) -> Option<ValidatorMetrics> {
+        self.inner.lock().await.get(id).cloned()
+    }
+
+    /// `id`'s entry of [`Self::detection_snapshot`].
+    async fn detection_get(&self, id: &str) -> Option<ValidatorMetrics> {
+        if self.smoother.is_enabled() {
+            self.smoother.get(id).await
+        } else {
+            self.get(id).await
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ValidatorsQuery {
+    #[serde(default)]
+    explain: bool,
+    sort: Option<ValidatorSort>,
+}
+
+#[derive(Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum ValidatorSort {
+    Degradation,
+}
+
+#[derive(Deserialize)]
+struct DecisionsQuery {
+    validator: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct DecisionsResponse {
+    decisions: Vec<DecisionRecord>,
+}
+
+#[derive(Deserialize)]
+struct PendingActionsQuery {
+    validator: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PendingActionsResponse {
+    pending: usize,
+    validators: BTreeMap<String, Vec<PendingActionEntry>>,
+}
+
+#[derive(Serialize)]
+struct PlaybookExecutionEntry {
+    plan_id: String,
+    issue: Option<String>,
+    started_at: i64,
+    updated_at: i64,
+    steps: Vec<PlaybookStepEntry>,
+}
+
+#[derive(Serialize)]
+struct PlaybookStepEntry {
+    step: i32,
+    kind: String,
+    state: &'static str,
+    message: Option<String>,
+    /// Whether the compensation undoing the step succeeded, once it ran.
+    rolled_back: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct PendingActionEntry {
+    kind: String,
+    age_secs: i64,
+    priority: i32,
+    action_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ActionHistoryQuery {
+    validator: Option<String>,
+    /// Unix seconds, inclusive.
+    since: Option<i64>,
+    until: Option<i64>,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct ActionHistoryResponse {
+    records: Vec<ActionHistoryEntry>,
+}
+
+#[derive(Serialize)]
+struct ActionHistoryEntry {
+    validator: String,
+    action: serde_json::Value,
+    success: bool,
+    message: String,
+    timestamp: i64,
+    dry_run: bool,
+}
+
+#[derive(Deserialize)]
+struct MaintenanceRunRequest {
+    script_name: String,
+    /// Arguments for a `[commands.scripts]` library script.
+    #[serde(default)]
+    params: HashMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct MaintenanceRunResponse {
+    script_name: String,
+    #[serde(flatten)]
+    result: BulkResult,
+}
+
+#[derive(Deserialize)]
+struct BulkActionRequest {
+    /// Registered validator ids; empty targets all of them.
+    #[serde(default)]
+    validators: Vec<String>,
+    /// An action body without `validator`, e.g. `{"kind": "disable_rpc"}`.
+    action: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct DebugState {
+    generated_at: i64,
+    executor: &'static str,
+    metrics_cache_size: usize,
+    metrics: BTreeMap<String, ValidatorMetrics>,
+    cooldowns: Vec<DebugCooldown>,
+    /// Onset of each validator's current issue, by sample timestamp.
+    confirmations: Vec<DebugConfirmation>,
+    /// Issues found in each validator's last evaluation.
+    active_issues: BTreeMap<String, Vec<IssueKind>>,
+    /// Per configured validator: automation disabled on the daemon. All
+    /// `false` when the daemon is unreachable.
+    automation_paused: BTreeMap<String, bool>,
+    agentic_budget_exhausted: bool,
+    /// `[agentic.cache]` hits and misses since startup.
+    agentic_cache: CacheStats,
+    config: serde_json::Value,
+}
+
+#[derive(Serialize)]
+struct DebugCooldown {
+    validator: String,
+    issue: IssueKind,
+    playbook: String,
+    dispatched_at: i64,
+    remaining_secs: i64,
+}
+
+#[derive(Serialize)]
+struct DebugConfirmation {
+    validator: String,
+    issue: IssueKind,
+    onset: i64,
+}
+
+#[derive(Serialize)]
+struct ActionsResponse {
+    pending: i64,
+}
+
+#[derive(Serialize)]
+struct ValidatorsResponse {
+    validators: Vec<ValidatorSummary>,
+}
+
+/// A frame of `/api/stream`.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamMessage {
+    Snapshot { validators: Vec<ValidatorSummary> },
+    Update { validator: Box<ValidatorSummary> },
+}
+
+#[derive(Serialize)]
+struct GroupsResponse {
+    by: String,
+    groups: Vec<groups::GroupSummary>,
+}
+
+#[derive(Serialize)]
+struct ValidatorSummary {
+    id: String,
+    host: String,
+    prometheus_url: String,
+    metrics: Option<ValidatorMetrics>,
+    /// `ok`, `disabled`, `bad_data`, `no_data`, or every detected issue, most
+    /// severe first.
+    status: Vec<String>,
+    risk_score: Option<f64>,
+    /// e.g. `At risk: slot lag 120, disk 92%`; present whenever `risk_score` is.
+    risk_explanation: Option<String>,
+    /// `risk_score` compounded with the detected issues' severities, per
+    /// `[degradation]`; present whenever `risk_score` is.
+    degradation: Option<f64>,
+    /// Undecodable metrics payloads received since the agent started.
+    decode_errors: u64,
+    /// The latest parse error, while the validator is reported as `bad_data`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    decode_error: Option<String>,
+    /// Set when the client's clock is off by more than `[staleness] max_clock_drift_secs`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    clock_drift_secs: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_decision: Option<LastDecision>,
+}
+
+/// Detect issues using simple rule-based logic, returning every issue that
+/// fires, most severe first by [`IssueKind::default_severity`].
+/// Single-sample rules per `[detection.thresholds]`; `vote_failing` and
+/// `rpc_overloaded` are the windowed verdicts from [`VoteHistory`] and
+/// [`RpcHistory`]. The scripted `[detection] rules` are evaluated too, and an
+/// issue they repeat is reported once.
+pub fn detect_issues_with(
+    metrics: &ValidatorMetrics,
+    thresholds: &DetectionThresholds,
+    vote_failing: bool,
+    rpc_overloaded: bool,
+    rules: &[DetectionRule],
+) -> Vec<IssueKind> {
+    let built_in = [
+        (
+            metrics.slot_lag > thresholds.slot_lag,
+            IssueKind::SlotLagHigh,
+        ),
+        (vote_failing, IssueKind::VoteFailureSpike),
+        (
+            metrics.cpu_usage > thresholds.cpu_usage
+                || metrics.ram_usage_gb > thresholds.ram_usage_gb,
+            IssueKind::HardwareOverload,
+        ),
+        (
+            metrics.disk_usage_pct > thresholds.disk_usage_pct,
+            IssueKind::DiskAlmostFull,
+        ),
+        (rpc_overloaded, IssueKind::RpcOverload),
+    ];
+    let mut issues: Vec<IssueKind> = built_in
+        .into_iter()
+        .filter_map(|(fired, issue)| fired.then_some(issue))
+        .collect();
+    for issue in rules.iter().filter_map(|rule| rule.evaluate(metrics)) {
+        if !issues.contains(&issue) {
+            issues.push(issue);
+        }
+    }
+    issues.sort_by_key(|issue| Reverse(issue.default_severity()));
+    issues
+}
+
+/// The most severe of [`detect_issues_with`].
+#[cfg(test)]
+pub fn detect_issue_with(
+    metrics: &ValidatorMetrics,
+    thresholds: &DetectionThresholds,
+    vote_failing: bool,
+    rpc_overloaded: bool,
+    rules: &[DetectionRule],
+) -> Option<IssueKind> {
+    detect_issues_with(metrics, thresholds, vote_failing, rpc_overloaded, rules)
+        .into_iter()
+        .next()
+}
+
+/// Send one envelope per step of `playbook`, tied together by a plan id. A
+/// failed step is logged and the rest are still sent. Returns how many steps
+/// failed.
+async fn dispatch_playbook<D: ActionDispatcher>(
+    dispatcher: &mut D,
+    config: &Config,
+    validator: &ValidatorId,
+    playbook: &Playbook,
+    now: i64,
+) -> Result<usize> {
+    let remediation = &config.remediation;
+    let plan_id = format!("{}:{}:{now}", validator.0, playbook.id);
+    // RPC comes back only once the validator has caught up again.
+    let recovery_check = remediation
+        .recovery_check
+        .as_ref()
+        .filter(|_| playbook.trigger == IssueKind::SlotLagHigh)
+        .map(|check| RecoveryCheck {
+            max_slot_lag: config.detection.thresholds.slot_lag,
+            attempts: check.attempts,
+            interval_secs: check.interval_secs,
+        });
+    let mut failed = 0;
+    for (step, action) in playbook.steps.iter().enumerate() {
+        let envelope = ActionEnvelope {
+            validator_id: validator.0.clone(),
+            action_json: serde_json::to_string(action)?,
+            idempotency_key: idempotency_key(
+                validator,
+                playbook.trigger,
+                &playbook.id,
+                step,
+                now,
+                remediation.idempotency_bucket_secs,
+            ),
+            priority: remediation.priority_for(action),
+            ttl_secs: 0,
+            issue: playbook.trigger.as_str().to_string(),
+            plan_id: plan_id.clone(),
+            plan_deadline_secs: remediation.max_plan_secs,
+            step: step as i32,
+            depends_on: playbook
+                .depends_on
+                .get(&step)
+                .map(|deps| deps.iter().map(|&dep| dep as i32).collect())
+                .unwrap_or_default(),
+            recovery_check: recovery_check
+                .clone()
+                .filter(|_| matches!(action, Action::EnableRpc { .. })),
+            compensation_json: action
+                .compensation()
+                .map(|compensation| serde_json::to_string(&compensation))
+                .transpose()?
+                .unwrap_or_default(),
+            ..ActionEnvelope::default()
+        };
+        if let Err(err) = dispatcher.dispatch(envelope).await {
+            error!(validator = validator.0, ?err, "failed to submit action");
+            failed += 1;
+        }
+    }
+    Ok(failed)
+}
+
+/// Below `[remediation] min_action_severity`, reduce `playbook` to its alerts,
+/// or to a single alert when it has none. Returns the issue's severity when
+/// the playbook was reduced.
+fn gate_by_severity(
+    remediation: &RemediationConfig,
+    validator: &ValidatorId,
+    playbook: &mut Playbook,
+) -> Option<Severity> {
+    let issue = playbook.trigger;
+    if remediation.acts_on(issue) {
+        return None;
+    }
+    let severity = remediation.severity_for(issue);
+    playbook
+        .steps
+        .retain(|action| matches!(action, Action::SendAlert { .. }));
+    if playbook.steps.is_empty() {
+        playbook.steps.push(Action::SendAlert {
+            validator: validator.clone(),
+            message: format!(
+                "{} detected; below min_action_severity, not remediated",
+                issue.as_str()
+            ),
+        });
+    }
+    playbook.depends_on.clear();
+    Some(severity)
+}
+
+/// Hard-coded playbooks that can be swapped for learned policies later.
+pub fn choose_playbook(issue: IssueKind, validator: &ValidatorId) -> Playbook {
+    match issue {
+        IssueKind::SlotLagHigh => Playbook {
+            id: "slot-lag-recovery".into(),
+            trigger: issue,
+            steps: vec![
+                Action::DisableRpc {
+                    validator: validator.clone(),
+                },
+                Action::RestartValidator {
+                    validator: validator.clone(),
+                    restart_kind: RestartKind::Hard,
+                },
+                Action::EnableRpc {
+                    validator: validator.clone(),
+                },
+            ],
+            // Only bring RPC back once the restart has succeeded.
+            depends_on: BTreeMap::from([(2, vec![1])]),
+        },
+        IssueKind::RpcOverload => Playbook {
+            id: "rpc-overload".into(),
+            trigger: issue,
+            steps: vec![
+                Action::ThrottleRpcClient {
+                    validator: validator.clone(),
+                },
+                Action::SendAlert {
+                    validator: validator.clone(),
+                    message: "RPC overload detected".into(),
+                },
+            ],
+            depends_on: Default::default(),
+        },
+        IssueKind::DiskAlmostFull => Playbook {
+            id: "disk-cleanup".into(),
+            trigger: issue,
+            steps: vec![Action::RunMaintenanceScript {
+                validator: validator.clone(),
+                script_name: "cleanup-logs.sh".into(),
+                params: HashMap::new(),
+            }],
+            depends_on: Default::default(),
         },
         IssueKind::HardwareOverload => Playbook {
             id: "hardware-throttle".into(),
@@ -323,67 +2371,1607 @@ pub fn choose_playbook(issue: IssueKind, validator: &ValidatorId) -> Playbook {
             steps: vec![
                 Action::DisableRpc {
                     validator: validator.clone(),
-                },
-                Action::SendAlert {
+                },
+                Action::SendAlert {
+                    validator: validator.clone(),
+                    message: "Hardware overload detected".into(),
+                },
+            ],
+            depends_on: Default::default(),
+        },
+        IssueKind::VoteFailureSpike => Playbook {
+            id: "vote-health".into(),
+            trigger: issue,
+            steps: vec![Action::SendAlert {
+                validator: validator.clone(),
+                message: "Vote success degraded".into(),
+            }],
+            depends_on: Default::default(),
+        },
+        IssueKind::MetricsStale => Playbook {
+            id: "metrics-stale".into(),
+            trigger: issue,
+            steps: vec![Action::SendAlert {
+                validator: validator.clone(),
+                message: "No fresh metrics; the validator client may be down".into(),
+            }],
+            depends_on: Default::default(),
+        },
+        IssueKind::Custom(custom) => Playbook {
+            id: "custom-issue".into(),
+            trigger: issue,
+            steps: vec![Action::SendAlert {
+                validator: validator.clone(),
+                message: format!("{} detected", custom.name()),
+            }],
+            depends_on: Default::default(),
+        },
+        _ => Playbook {
+            id: "unknown-issue".into(),
+            trigger: issue,
+            steps: vec![Action::SendAlert {
+                validator: validator.clone(),
+                message: "Unknown issue detected".into(),
+            }],
+            depends_on: Default::default(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{base_metrics, metrics_at, validator};
+
+    fn config(validators: Vec<ValidatorConfig>) -> Config {
+        Config {
+            validators,
+            redis_url: "redis://127.0.0.1:6379".into(),
+            maintenance: common::MaintenanceConfig {
+                allowed_scripts: vec!["diag.sh".into(), "cleanup-logs.sh".into()],
+            },
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn disk_metric_alert_fires_before_disk_issue_is_detected() {
+        let alerts = [common::MetricAlert {
+            name: "disk-85".into(),
+            field: "disk_usage_pct".into(),
+            comparison: common::Comparison::Above,
+            threshold: 85.0,
+            severity: common::Severity::Warning,
+            message: Some("disk filling up".into()),
+            issue: None,
+        }];
+        let mut metrics = base_metrics();
+        metrics.disk_usage_pct = 87.0;
+
+        assert_eq!(
+            detect_issue_with(&metrics, &DetectionThresholds::default(), false, false, &[]),
+            None
+        );
+        let fired = MetricAlertTracker::default().observe(&alerts, "v1", &metrics);
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].alert.as_deref(), Some("disk-85"));
+        assert_eq!(fired[0].issue, None);
+        assert_eq!(fired[0].message, "disk filling up");
+    }
+
+    #[test]
+    fn maintenance_run_targets_every_validator() {
+        let cfg = config(vec![validator("v1", None), validator("v2", None)]);
+        let targets = maintenance_targets(&cfg, &cfg.validators, "diag.sh", &HashMap::new());
+        assert_eq!(targets.len(), 2);
+        for target in targets {
+            assert_eq!(
+                target.action,
+                Ok(Action::RunMaintenanceScript {
+                    validator: target.validator.clone(),
+                    script_name: "diag.sh".into(),
+                    params: HashMap::new(),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn maintenance_run_skips_validator_whose_allowlist_excludes_script() {
+        let cfg = config(vec![
+            validator("v1", None),
+            validator("v2", Some(vec!["cleanup-logs.sh".into()])),
+        ]);
+        let targets = maintenance_targets(&cfg, &cfg.validators, "diag.sh", &HashMap::new());
+        assert!(targets[0].action.is_ok());
+        assert_eq!(targets[1].validator, ValidatorId("v2".into()));
+        assert!(targets[1].action.is_err());
+    }
+
+    #[tokio::test]
+    async fn self_registered_validators_join_summaries_and_bulk_targets() {
+        let cfg = config(vec![validator("v1", None)]);
+        let state_for = |id: &str, registered| ValidatorState {
+            validator_id: id.into(),
+            enabled: true,
+            registered,
+            host: format!("{id}.local"),
+            tags: vec!["archival".into()],
+            ..ValidatorState::default()
+        };
+        let registered =
+            registered_validators(&cfg, vec![state_for("v1", false), state_for("v9", true)]);
+        assert_eq!(registered.len(), 1);
+        assert_eq!(registered[0].id.0, "v9");
+        assert_eq!(registered[0].host, "v9.local");
+        assert_eq!(registered[0].tags, vec!["archival".to_string()]);
+
+        let state = app_state(cfg);
+        *state.registered.lock().await = registered;
+        let ids: Vec<String> = validator_summaries(&state, &HashSet::new(), false)
+            .await
+            .into_iter()
+            .map(|summary| summary.id)
+            .collect();
+        assert_eq!(ids, ["v1", "v9"]);
+        let request = BulkActionRequest {
+            validators: Vec::new(),
+            action: serde_json::json!({"kind": "restart_validator"})
+                .as_object()
+                .unwrap()
+                .clone(),
+        };
+        let targets = bulk_targets(&state.config, &state.validators().await, request).unwrap();
+        assert_eq!(targets.len(), 2);
+    }
+
+    #[test]
+    fn bulk_targets_cover_requested_validators_and_skip_the_rest() {
+        let cfg = config(vec![
+            validator("v1", None),
+            validator("v2", Some(vec!["cleanup-logs.sh".into()])),
+        ]);
+        let request = |validators: &[&str]| BulkActionRequest {
+            validators: validators.iter().map(|v| v.to_string()).collect(),
+            action: serde_json::json!({"kind": "run_maintenance_script", "script_name": "diag.sh"})
+                .as_object()
+                .unwrap()
+                .clone(),
+        };
+
+        let targets = bulk_targets(&cfg, &cfg.validators, request(&["v1", "v2", "ghost"])).unwrap();
+        let outcomes: Vec<_> = targets
+            .iter()
+            .map(|t| (t.validator.0.as_str(), t.action.clone()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                (
+                    "v1",
+                    Ok(Action::RunMaintenanceScript {
+                        validator: ValidatorId("v1".into()),
+                        script_name: "diag.sh".into(),
+                        params: HashMap::new(),
+                    })
+                ),
+                ("v2", Err("script not allowlisted for validator".into())),
+                ("ghost", Err("unknown validator".into())),
+            ]
+        );
+        assert_eq!(
+            bulk_targets(&cfg, &cfg.validators, request(&[]))
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let bad = BulkActionRequest {
+            validators: Vec::new(),
+            action: serde_json::json!({"kind": "reboot_planet"})
+                .as_object()
+                .unwrap()
+                .clone(),
+        };
+        assert!(bulk_targets(&cfg, &cfg.validators, bad).is_err());
+    }
+
+    #[derive(Default)]
+    struct RecordingDispatcher {
+        envelopes: Vec<ActionEnvelope>,
+    }
+
+    impl ActionDispatcher for RecordingDispatcher {
+        async fn dispatch(&mut self, envelope: ActionEnvelope) -> Result<(), tonic::Status> {
+            self.envelopes.push(envelope);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn playbook_steps_are_dispatched_as_linked_envelopes() {
+        let validator = ValidatorId("v1".into());
+        let mut cfg = config(vec![]);
+        cfg.remediation.max_plan_secs = 120;
+        cfg.remediation.recovery_check = Some(common::RecoveryCheckConfig::default());
+        let remediation = &cfg.remediation;
+        let playbook = choose_playbook(IssueKind::SlotLagHigh, &validator);
+        let now = 3 * remediation.idempotency_bucket_secs;
+        let mut dispatcher = RecordingDispatcher::default();
+        dispatch_playbook(&mut dispatcher, &cfg, &validator, &playbook, now)
+            .await
+            .unwrap();
+
+        assert_eq!(dispatcher.envelopes.len(), 3);
+        let enable = &dispatcher.envelopes[2];
+        assert_eq!(
+            *enable,
+            ActionEnvelope {
+                validator_id: "v1".into(),
+                action_json: serde_json::to_string(&playbook.steps[2]).unwrap(),
+                idempotency_key: "v1:slot_lag_high:slot-lag-recovery:3:2".into(),
+                priority: remediation.priority_for(&playbook.steps[2]),
+                issue: "slot_lag_high".into(),
+                plan_id: format!("v1:slot-lag-recovery:{now}"),
+                plan_deadline_secs: 120,
+                step: 2,
+                depends_on: vec![1],
+                recovery_check: Some(RecoveryCheck {
+                    max_slot_lag: cfg.detection.thresholds.slot_lag,
+                    attempts: 6,
+                    interval_secs: 10,
+                }),
+                ..ActionEnvelope::default()
+            }
+        );
+        assert!(dispatcher
+            .envelopes
+            .iter()
+            .all(|envelope| envelope.plan_id == enable.plan_id));
+        // Only the step re-enabling RPC waits for the validator to catch up.
+        assert!(dispatcher.envelopes[..2]
+            .iter()
+            .all(|envelope| envelope.recovery_check.is_none()));
+        // Disabling RPC is undone if the restart fails; the restart cannot be.
+        assert_eq!(
+            dispatcher.envelopes[0].compensation_json,
+            serde_json::to_string(&playbook.steps[2]).unwrap()
+        );
+        assert!(dispatcher.envelopes[1].compensation_json.is_empty());
+    }
+
+    #[tokio::test]
+    async fn restarted_agent_dispatches_under_the_same_keys() {
+        let validator = ValidatorId("v1".into());
+        // The key's bucket does not depend on the cooldown, even one turned off.
+        let mut cfg = config(vec![]);
+        cfg.remediation.cooldown_secs = 0;
+        let playbook = choose_playbook(IssueKind::SlotLagHigh, &validator);
+        let mut keys = Vec::new();
+        // The second instance starts 40s after the first, within one bucket.
+        for now in [600, 640] {
+            let mut dispatcher = RecordingDispatcher::default();
+            dispatch_playbook(&mut dispatcher, &cfg, &validator, &playbook, now)
+                .await
+                .unwrap();
+            keys.push(
+                dispatcher
+                    .envelopes
+                    .into_iter()
+                    .map(|envelope| envelope.idempotency_key)
+                    .collect::<Vec<_>>(),
+            );
+        }
+        assert_eq!(keys[0], keys[1]);
+        assert_eq!(keys[0][0], "v1:slot_lag_high:slot-lag-recovery:2:0");
+    }
+
+    #[test]
+    fn only_issues_at_min_action_severity_run_their_playbook() {
+        let validator = ValidatorId("v1".into());
+        let remediation = RemediationConfig {
+            min_action_severity: Some(Severity::Critical),
+            ..RemediationConfig::default()
+        };
+
+        // Warning: the alert step is kept, disable_rpc is not.
+        let mut hardware = choose_playbook(IssueKind::HardwareOverload, &validator);
+        assert_eq!(
+            gate_by_severity(&remediation, &validator, &mut hardware),
+            Some(Severity::Warning)
+        );
+        assert_eq!(
+            hardware.steps,
+            [Action::SendAlert {
+                validator: validator.clone(),
+                message: "Hardware overload detected".into(),
+            }]
+        );
+
+        // Warning without an alert step: one is added in place of the script.
+        let mut disk = choose_playbook(IssueKind::DiskAlmostFull, &validator);
+        gate_by_severity(&remediation, &validator, &mut disk);
+        assert!(matches!(
+            &disk.steps[..],
+            [Action::SendAlert { message, .. }] if message.starts_with("disk_almost_full detected")
+        ));
+
+        // Critical: the full playbook, dependencies included.
+        let full = choose_playbook(IssueKind::SlotLagHigh, &validator);
+        let mut slot_lag = full.clone();
+        assert_eq!(
+            gate_by_severity(&remediation, &validator, &mut slot_lag),
+            None
+        );
+        assert_eq!(slot_lag, full);
+
+        // An override can promote an issue past the gate.
+        let remediation = RemediationConfig {
+            severities: HashMap::from([("disk_almost_full".into(), Severity::Critical)]),
+            ..remediation
+        };
+        let mut disk = choose_playbook(IssueKind::DiskAlmostFull, &validator);
+        assert_eq!(gate_by_severity(&remediation, &validator, &mut disk), None);
+    }
+
+    #[tokio::test]
+    async fn shadow_mode_dispatches_static_and_records_agentic() {
+        let validator = ValidatorId("v1".into());
+        let agentic = AgenticDecision {
+            playbook: Playbook {
+                id: "agentic-restart".into(),
+                trigger: IssueKind::SlotLagHigh,
+                steps: vec![Action::RestartValidator {
+                    validator: validator.clone(),
+                    restart_kind: RestartKind::Hard,
+                }],
+                depends_on: Default::default(),
+            },
+            rationale: Some("restart clears lag".into()),
+            model: "gpt-4o-mini".into(),
+            prompt_version: "builtin-1".into(),
+        };
+        let decisions = DecisionLog::default();
+        let plan = select_plan(
+            AgenticMode::Shadow,
+            &validator,
+            IssueKind::SlotLagHigh,
+            Some(agentic.clone()),
+            &decisions,
+            42,
+        )
+        .await;
+
+        let static_playbook = choose_playbook(IssueKind::SlotLagHigh, &validator);
+        assert_eq!(plan.playbook, static_playbook);
+        assert_eq!(plan.plan_source, "rule_based_shadow");
+
+        let records = decisions.recent(Some("v1"), 10).await;
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            records[0].event,
+            DecisionEvent::ShadowPlan {
+                issue: IssueKind::SlotLagHigh,
+                dispatched: static_playbook,
+                agentic: Some(agentic.playbook),
+                agentic_rationale: agentic.rationale,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn agentic_dispatch_records_rationale_in_decisions() {
+        let state = app_state(config(vec![validator("v1", None)]));
+        let validator = ValidatorId("v1".into());
+        let agentic = AgenticDecision {
+            playbook: Playbook {
+                id: "agentic-restart".into(),
+                trigger: IssueKind::SlotLagHigh,
+                steps: vec![Action::RestartValidator {
                     validator: validator.clone(),
-                    message: "Hardware overload detected".into(),
+                    restart_kind: RestartKind::Hard,
+                }],
+                depends_on: Default::default(),
+            },
+            rationale: Some("lag is growing and catchup stalled".into()),
+            model: "gpt-4o-mini".into(),
+            prompt_version: "ops-2".into(),
+        };
+        let plan = select_plan(
+            AgenticMode::Active,
+            &validator,
+            IssueKind::SlotLagHigh,
+            Some(agentic),
+            &state.decisions,
+            42,
+        )
+        .await;
+        assert_eq!(plan.plan_source, "agentic");
+        // Nothing is logged until the plan is dispatched.
+        assert!(state.decisions.recent(Some("v1"), 10).await.is_empty());
+        state
+            .decisions
+            .record(42, "v1", plan.decision.unwrap())
+            .await;
+
+        let Json(response) = list_decisions(
+            State(state),
+            Query(DecisionsQuery {
+                validator: Some("v1".into()),
+                limit: None,
+            }),
+        )
+        .await;
+        let body = serde_json::to_value(&response).unwrap();
+        let record = &body["decisions"][0];
+        assert_eq!(record["event"], "agentic_plan");
+        assert_eq!(record["timestamp"], 42);
+        assert_eq!(record["playbook"]["id"], "agentic-restart");
+        assert_eq!(record["rationale"], "lag is growing and catchup stalled");
+        assert_eq!(record["model"], "gpt-4o-mini");
+        assert_eq!(record["prompt_version"], "ops-2");
+    }
+
+    fn app_state(config: Config) -> AppState {
+        let channel = tonic::transport::Endpoint::from_static("http://127.0.0.1:1").connect_lazy();
+        AppState {
+            warmup: MetricsWarmup::new(now_ts(), config.ingest.warmup_grace_secs),
+            votes: VoteHistory::new(config.detection.clone()),
+            rpc: RpcHistory::new(config.detection.clone()),
+            config: Arc::new(config),
+            metrics: MetricsCache::default(),
+            executor: ExecutorClient::new(channel.clone()),
+            dispatcher: Dispatcher::Grpc(ExecutorClient::new(channel)),
+            brain: Arc::new(AgenticBrain::new(None, &Default::default()).unwrap()),
+            decisions: DecisionLog::default(),
+            cooldowns: Cooldowns::default(),
+            confirmations: Confirmations::default(),
+            disruptions: Disruptions::default(),
+            evaluations: Evaluations::default(),
+            executor_status: ExecutorStatus::default(),
+            notifier: NotificationRouter::default(),
+            loop_timings: LoopTimings::new(true),
+            agent_metrics: AgentMetrics::default(),
+            registered: Arc::default(),
+            api_token: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn http_server_starts_and_readyz_reports_disconnected_executor() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let state = app_state(config(vec![validator("v1", None)]));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /readyz HTTP/1.1\r\nHost: agent\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 503"), "{response}");
+        assert!(
+            response.ends_with(r#"{"executor":"disconnected","metrics":"awaiting"}"#),
+            "{response}"
+        );
+
+        state.executor_status.set_connected(true);
+        assert_eq!(state.executor_status.label(), "connected");
+    }
+
+    #[tokio::test]
+    async fn api_and_debug_routes_require_the_token_when_one_is_set() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        async fn status(addr: std::net::SocketAddr, path: &str, auth: Option<&str>) -> String {
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let auth = auth.map_or(String::new(), |auth| format!("Authorization: {auth}\r\n"));
+            let request =
+                format!("GET {path} HTTP/1.1\r\nHost: agent\r\n{auth}Connection: close\r\n\r\n");
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response.lines().next().unwrap().to_string()
+        }
+        async fn serve(state: AppState) -> std::net::SocketAddr {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let app = router(state);
+            tokio::spawn(async move { axum::serve(listener, app).await });
+            addr
+        }
+
+        let state = AppState {
+            api_token: Some("s3cret".into()),
+            ..app_state(config(vec![validator("v1", None)]))
+        };
+        let addr = serve(state).await;
+        let ok = "HTTP/1.1 200 OK";
+        let unauthorized = "HTTP/1.1 401 Unauthorized";
+        assert_eq!(status(addr, "/api/validators", None).await, unauthorized);
+        assert_eq!(
+            status(addr, "/api/validators", Some("Bearer wrong")).await,
+            unauthorized
+        );
+        assert_eq!(
+            status(addr, "/api/validators", Some("s3cret")).await,
+            unauthorized
+        );
+        assert_eq!(status(addr, "/debug/cooldowns", None).await, unauthorized);
+        assert_eq!(
+            status(addr, "/api/validators", Some("Bearer s3cret")).await,
+            ok
+        );
+        assert_eq!(
+            status(addr, "/debug/cooldowns", Some("Bearer s3cret")).await,
+            ok
+        );
+        assert_eq!(status(addr, "/health", None).await, ok);
+
+        let open = serve(app_state(config(vec![validator("v1", None)]))).await;
+        assert_eq!(status(open, "/api/validators", None).await, ok);
+    }
+
+    #[tokio::test]
+    async fn stream_takes_the_token_as_a_subprotocol_or_query_parameter() {
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+        use tokio_tungstenite::tungstenite::Error as WsError;
+
+        let state = AppState {
+            api_token: Some("s3cret".into()),
+            ..app_state(config(vec![validator("v1", None)]))
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(state);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        let connect = |query: &str, protocols: Option<&str>| {
+            let mut request = format!("ws://{addr}/api/stream{query}")
+                .into_client_request()
+                .unwrap();
+            if let Some(protocols) = protocols {
+                request
+                    .headers_mut()
+                    .insert("sec-websocket-protocol", protocols.parse().unwrap());
+            }
+            tokio_tungstenite::connect_async(request)
+        };
+        let rejected = |result: Result<_, WsError>| matches!(result, Err(WsError::Http(response)) if response.status() == 401);
+
+        let (_, response) = connect("", Some("bearer, s3cret")).await.unwrap();
+        assert_eq!(response.headers()["sec-websocket-protocol"], "bearer");
+        connect("?token=s3cret", None).await.unwrap();
+        assert!(rejected(connect("", None).await));
+        assert!(rejected(connect("", Some("bearer, wrong")).await));
+        assert!(rejected(connect("", Some("s3cret")).await));
+        assert!(rejected(connect("?token=wrong", None).await));
+        // Only the stream takes the token outside the Authorization header.
+        let response = reqwest::get(format!("http://{addr}/api/validators?token=s3cret"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn stream_sends_a_snapshot_then_each_arriving_sample() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let state = app_state(config(vec![validator("v1", None), validator("v2", None)]));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/api/stream"))
+            .await
+            .unwrap();
+        async fn next_frame<S>(ws: &mut S) -> serde_json::Value
+        where
+            S: futures_util::Stream<Item = tokio_tungstenite::tungstenite::Result<WsMessage>>
+                + Unpin,
+        {
+            let frame = tokio::time::timeout(Duration::from_secs(5), ws.next())
+                .await
+                .expect("frame")
+                .unwrap()
+                .unwrap();
+            serde_json::from_str(frame.to_text().unwrap()).unwrap()
+        }
+        let snapshot = next_frame(&mut ws).await;
+        assert_eq!(snapshot["type"], "snapshot");
+        assert_eq!(snapshot["validators"][0]["status"][0], "no_data");
+        assert_eq!(snapshot["validators"].as_array().unwrap().len(), 2);
+
+        let mut lagging = base_metrics();
+        lagging.slot_lag = 75;
+        state.metrics.insert("v2".into(), lagging).await;
+        let update = next_frame(&mut ws).await;
+        assert_eq!(update["type"], "update");
+        assert_eq!(update["validator"]["id"], "v2");
+        assert_eq!(update["validator"]["status"][0], "SlotLagHigh");
+
+        ws.send(WsMessage::Close(None)).await.unwrap();
+        drop(ws);
+        // The handler drops its subscription once the client has gone.
+        for _ in 0..100 {
+            if state.metrics.updates.receiver_count() == 0 {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(state.metrics.updates.receiver_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn debug_state_dumps_caches_and_redacts_tokens() {
+        let state = app_state(config(vec![validator("v1", None), validator("v2", None)]));
+        let mut lagging = base_metrics();
+        lagging.slot_lag = 75;
+        state.metrics.insert("v1".into(), lagging.clone()).await;
+        state.metrics.insert("v2".into(), base_metrics()).await;
+        let v1 = &state.config.validators[0];
+        assert!(!evaluate_validator(&state, v1, &lagging, now_ts())
+            .await
+            .is_empty());
+
+        let Json(dump) = debug_state(State(state)).await;
+        let dump = serde_json::to_value(&dump).unwrap();
+        assert_eq!(dump["metrics_cache_size"], 2);
+        assert_eq!(
+            dump["automation_paused"],
+            serde_json::json!({"v1": false, "v2": false})
+        );
+        assert_eq!(dump["executor"], "disconnected");
+        assert_eq!(
+            dump["agentic_cache"],
+            serde_json::json!({"hits": 0, "misses": 0})
+        );
+        assert_eq!(dump["cooldowns"][0]["validator"], "v1");
+        assert_eq!(dump["cooldowns"][0]["issue"], "slot_lag_high");
+        assert_eq!(
+            dump["active_issues"]["v1"],
+            serde_json::json!(["slot_lag_high"])
+        );
+        assert_eq!(dump["config"]["validators"][0]["auth_token"], "<redacted>");
+        assert_eq!(dump["config"]["redis_url"], "redis://127.0.0.1:6379");
+
+        let mut cfg = config(vec![validator("v1", None)]);
+        cfg.redis_url = "redis://:hunter2@redis.corp:6379/0".into();
+        cfg.alerts.sinks.insert(
+            "slack".into(),
+            common::SinkConfig::Webhook {
+                url: "https://hooks.slack.com/services/T0/B0/secret".into(),
+            },
+        );
+        let redacted = redacted_config(&cfg);
+        assert_eq!(
+            redacted["redis_url"],
+            "redis://<redacted>@redis.corp:6379/0"
+        );
+        assert_eq!(redacted["alerts"]["sinks"]["slack"]["url"], "<redacted>");
+        assert!(!redacted.to_string().contains("hunter2"));
+        assert!(!dump.to_string().contains("secret"), "{dump}");
+    }
+
+    #[tokio::test]
+    async fn debug_cooldowns_lists_only_issues_still_cooling_down() {
+        let mut cfg = config(vec![validator("v1", None), validator("v2", None)]);
+        cfg.remediation.cooldown_secs = 300;
+        let state = app_state(cfg);
+        let now = now_ts();
+        let cooldown_secs = state.config.remediation.cooldown_secs;
+        let (v1, v2) = (ValidatorId("v1".into()), ValidatorId("v2".into()));
+        state
+            .cooldowns
+            .start(&v1, IssueKind::SlotLagHigh, "slot-lag-recovery", now - 10)
+            .await;
+        state
+            .cooldowns
+            .start(
+                &v2,
+                IssueKind::RpcOverload,
+                "rpc-overload",
+                now - cooldown_secs - 1,
+            )
+            .await;
+
+        let Json(cooldowns) = debug_cooldowns(State(state)).await;
+        let cooldowns = serde_json::to_value(&cooldowns).unwrap();
+        assert_eq!(cooldowns.as_array().unwrap().len(), 1, "{cooldowns}");
+        assert_eq!(cooldowns[0]["validator"], "v1");
+        assert_eq!(cooldowns[0]["issue"], "slot_lag_high");
+        assert_eq!(cooldowns[0]["playbook"], "slot-lag-recovery");
+        let remaining = cooldowns[0]["remaining_secs"].as_i64().unwrap();
+        assert!((cooldown_secs - 11..=cooldown_secs - 10).contains(&remaining));
+    }
+
+    #[tokio::test]
+    async fn a_tick_records_detection_and_dispatch_timings() {
+        let state = app_state(config(vec![validator("v1", None), validator("v2", None)]));
+        let mut lagging = base_metrics();
+        lagging.slot_lag = 75;
+        state.metrics.insert("v1".into(), lagging).await;
+        state.metrics.insert("v2".into(), base_metrics()).await;
+
+        run_tick(
+            &state,
+            &mut state.executor.clone(),
+            &mut state.dispatcher.clone(),
+            &mut StaleTracker::default(),
+            &mut MetricAlertTracker::default(),
+            &mut IdleTracker::default(),
+            &mut UndrainTracker::default(),
+        )
+        .await
+        .unwrap();
+
+        let timings = &state.loop_timings;
+        assert_eq!(timings.count(LoopPhase::Snapshot).await, 1);
+        assert_eq!(timings.count(LoopPhase::Detection).await, 2);
+        // Only v1 has an issue, and without a provider planning is instant
+        // but still timed.
+        assert_eq!(timings.count(LoopPhase::Planning).await, 1);
+        assert_eq!(timings.count(LoopPhase::Dispatch).await, 1);
+        let exported = export_metrics(State(state)).await;
+        assert!(exported.contains("agent_loop_phase_seconds_count{phase=\"dispatch\"} 1\n"));
+    }
+
+    #[tokio::test]
+    async fn unchanged_healthy_validators_skip_evaluation() {
+        let mut cfg = config(vec![validator("v1", None), validator("v2", None)]);
+        cfg.detection.skip_unchanged = true;
+        let state = app_state(cfg);
+        state.metrics.insert("v1".into(), base_metrics()).await;
+        state.metrics.insert("v2".into(), base_metrics()).await;
+        async fn tick(state: &AppState, idle: &mut IdleTracker) {
+            run_tick(
+                state,
+                &mut state.executor.clone(),
+                &mut state.dispatcher.clone(),
+                &mut StaleTracker::default(),
+                &mut MetricAlertTracker::default(),
+                idle,
+                &mut UndrainTracker::default(),
+            )
+            .await
+            .unwrap();
+        }
+        let mut idle = IdleTracker::default();
+
+        tick(&state, &mut idle).await;
+        assert_eq!(state.loop_timings.count(LoopPhase::Detection).await, 2);
+        // A republished sample with only a newer timestamp is unchanged.
+        let mut republished = base_metrics();
+        republished.last_updated += 1;
+        state.metrics.insert("v1".into(), republished).await;
+        let mut lagging = base_metrics();
+        lagging.slot_lag = 75;
+        state.metrics.insert("v2".into(), lagging).await;
+        tick(&state, &mut idle).await;
+        assert_eq!(state.loop_timings.count(LoopPhase::Detection).await, 3);
+        let evaluations = state.evaluations.snapshot().await;
+        assert_eq!(
+            evaluations["v2"].issues,
+            vec![IssueKind::SlotLagHigh],
+            "the changed validator was evaluated"
+        );
+
+        // Once it has an issue it is evaluated every tick, changed or not.
+        tick(&state, &mut idle).await;
+        assert_eq!(state.loop_timings.count(LoopPhase::Detection).await, 4);
+    }
+
+    #[tokio::test]
+    async fn explain_reports_cooldown_suppression() {
+        let mut cfg = config(vec![validator("v1", None)]);
+        cfg.remediation.cooldown_secs = 300;
+        let state = app_state(cfg);
+        let mut lagging = base_metrics();
+        lagging.slot_lag = 75;
+        state.metrics.insert("v1".into(), lagging.clone()).await;
+        let v1 = &state.config.validators[0];
+
+        let first = evaluate_validator(&state, v1, &lagging, 1_000).await;
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].playbook.id, "slot-lag-recovery");
+        assert!(evaluate_validator(&state, v1, &lagging, 1_010)
+            .await
+            .is_empty());
+
+        let summaries = validator_summaries(&state, &HashSet::new(), true).await;
+        assert!(summaries[0]
+            .risk_explanation
+            .as_deref()
+            .unwrap()
+            .contains(": slot lag 75"));
+        assert_eq!(
+            summaries[0].last_decision,
+            Some(LastDecision {
+                evaluated_at: 1_010,
+                issues: vec![IssueKind::SlotLagHigh],
+                playbook: Some("slot-lag-recovery".into()),
+                outcome: DecisionOutcome::Suppressed {
+                    reason: SuppressionReason::Cooldown {
+                        remaining_secs: 290
+                    },
                 },
-            ],
-        },
-        IssueKind::VoteFailureSpike => Playbook {
-            id: "vote-health".into(),
-            trigger: issue,
-            steps: vec![Action::SendAlert {
-                validator: validator.clone(),
-                message: "Vote success degraded".into(),
-            }],
-        },
-        _ => Playbook {
-            id: "unknown-issue".into(),
-            trigger: issue,
-            steps: vec![Action::SendAlert {
-                validator: validator.clone(),
-                message: "Unknown issue detected".into(),
-            }],
-        },
+                others: vec![],
+            })
+        );
+        let plain = validator_summaries(&state, &HashSet::new(), false).await;
+        assert!(plain[0].last_decision.is_none());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[tokio::test]
+    async fn empty_ticks_log_awaiting_metrics_until_the_first_sample() {
+        #[derive(Clone, Default)]
+        struct CapturedLogs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+        impl std::io::Write for CapturedLogs {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for CapturedLogs {
+            type Writer = Self;
+
+            fn make_writer(&'a self) -> Self {
+                self.clone()
+            }
+        }
+
+        impl CapturedLogs {
+            fn take(&self) -> String {
+                String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+            }
+        }
+
+        let logs = CapturedLogs::default();
+        let _guard = tracing::subscriber::set_default(
+            tracing_subscriber::fmt()
+                .with_writer(logs.clone())
+                .with_ansi(false)
+                .finish(),
+        );
+        let mut cfg = config(vec![validator("v1", None)]);
+        cfg.detection.restart_vote_grace_secs = 0;
+        let state = app_state(cfg);
+        let tick = || {
+            let state = &state;
+            let mut client = state.executor.clone();
+            let mut dispatcher = state.dispatcher.clone();
+            async move {
+                run_tick(
+                    state,
+                    &mut client,
+                    &mut dispatcher,
+                    &mut StaleTracker::default(),
+                    &mut MetricAlertTracker::default(),
+                    &mut IdleTracker::default(),
+                    &mut UndrainTracker::default(),
+                )
+                .await
+                .unwrap()
+            }
+        };
+
+        tick().await;
+        assert!(logs.take().contains("awaiting metrics"));
+        assert!(!state.warmup.is_warm().await);
+        assert!(state.evaluations.snapshot().await.is_empty());
+
+        state.metrics.insert("v1".into(), base_metrics()).await;
+        tick().await;
+        let logged = logs.take();
+        assert!(!logged.contains("awaiting metrics"), "{logged}");
+        assert!(logged.contains("metrics cache warm"), "{logged}");
+        assert!(state.warmup.is_warm().await);
+        assert!(state.evaluations.snapshot().await.contains_key("v1"));
+    }
+
+    #[tokio::test]
+    async fn archival_disk_usage_is_suppressed_but_other_validators_still_remediate() {
+        let mut archival = validator("archive-1", None);
+        archival.tags = vec!["archival".into()];
+        let mut cfg = config(vec![archival, validator("v2", None)]);
+        cfg.suppressions = vec![common::SuppressionRule {
+            name: Some("archival-disk".into()),
+            issue: IssueKind::DiskAlmostFull,
+            tag: Some("archival".into()),
+            validator: None,
+            when: None,
+        }];
+        let state = app_state(cfg);
+        let mut full = base_metrics();
+        full.disk_usage_pct = 95.0;
+
+        let archival = &state.config.validators[0];
+        assert!(evaluate_validator(&state, archival, &full, 1_000)
+            .await
+            .is_empty());
+        assert_eq!(
+            state.evaluations.snapshot().await["archive-1"].outcome,
+            DecisionOutcome::Suppressed {
+                reason: SuppressionReason::Rule {
+                    rule: "archival-disk".into()
+                },
+            }
+        );
 
-    fn base_metrics() -> ValidatorMetrics {
-        ValidatorMetrics {
-            slot_lag: 0,
-            vote_success_rate: 0.99,
-            cpu_usage: 0.2,
-            ram_usage_gb: 16.0,
-            disk_usage_pct: 30.0,
-            rpc_qps: 100.0,
-            rpc_error_rate: 0.001,
-            last_updated: 0,
+        let regular = &state.config.validators[1];
+        let plans = evaluate_validator(&state, regular, &full, 1_000).await;
+        assert_eq!(plans.len(), 1, "untagged validator still remediated");
+        assert_eq!(plans[0].playbook.trigger, IssueKind::DiskAlmostFull);
+    }
+
+    #[tokio::test]
+    async fn validators_roll_up_into_their_region_groups() {
+        let in_region = |id: &str, region: &str| ValidatorConfig {
+            labels: BTreeMap::from([("region".to_string(), region.to_string())]),
+            ..validator(id, None)
+        };
+        let state = app_state(config(vec![
+            in_region("v1", "eu-west"),
+            in_region("v2", "eu-west"),
+            in_region("v3", "us-east"),
+        ]));
+        let mut lagging = base_metrics();
+        lagging.slot_lag = 500;
+        state.metrics.insert("v1".into(), base_metrics()).await;
+        state.metrics.insert("v2".into(), lagging).await;
+        state.metrics.insert("v3".into(), base_metrics()).await;
+
+        let summaries = validator_summaries(&state, &HashSet::new(), false).await;
+        let groups = groups::group_summaries(&state.config.validators, &summaries, "region");
+        let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(names, ["eu-west", "us-east"]);
+
+        let eu = &groups[0];
+        assert_eq!(eu.validators, 2);
+        assert_eq!(
+            eu.status,
+            BTreeMap::from([("SlotLagHigh".to_string(), 1), ("ok".to_string(), 1)])
+        );
+        assert_eq!(eu.risk.values().sum::<usize>(), 2);
+        let worst = eu.worst.as_ref().unwrap();
+        assert_eq!(worst.id, "v2");
+        assert_eq!(
+            worst.risk,
+            common::RiskLevel::of(summaries[1].risk_score.unwrap())
+        );
+        let expected =
+            1.0 - (summaries[0].degradation.unwrap() + summaries[1].degradation.unwrap()) / 2.0;
+        assert!((eu.health.unwrap() - expected).abs() < 1e-9);
+
+        let us = &groups[1];
+        assert_eq!(us.validators, 1);
+        assert_eq!(us.status, BTreeMap::from([("ok".to_string(), 1)]));
+        assert_eq!(us.worst.as_ref().unwrap().id, "v3");
+        assert!(us.health.unwrap() > eu.health.unwrap());
+
+        let by_tag = groups::group_summaries(&state.config.validators, &summaries, "tag");
+        assert_eq!(by_tag.len(), 1);
+        assert_eq!(by_tag[0].name, "ungrouped");
+        assert_eq!(by_tag[0].validators, 3);
+    }
+
+    #[tokio::test]
+    async fn simultaneous_issues_each_get_a_plan_most_severe_first() {
+        let state = app_state(config(vec![validator("v1", None)]));
+        let mut metrics = base_metrics();
+        metrics.disk_usage_pct = 95.0;
+        metrics.slot_lag = 75;
+        state.metrics.insert("v1".into(), metrics.clone()).await;
+        let v1 = &state.config.validators[0];
+
+        let plans = evaluate_validator(&state, v1, &metrics, 1_000).await;
+        let ids: Vec<&str> = plans.iter().map(|plan| plan.playbook.id.as_str()).collect();
+        assert_eq!(ids, ["slot-lag-recovery", "disk-cleanup"]);
+
+        let summaries = validator_summaries(&state, &HashSet::new(), true).await;
+        assert_eq!(summaries[0].status, ["SlotLagHigh", "DiskAlmostFull"]);
+        let decision = summaries[0].last_decision.clone().unwrap();
+        assert_eq!(
+            decision.issues,
+            [IssueKind::SlotLagHigh, IssueKind::DiskAlmostFull]
+        );
+        assert_eq!(decision.playbook.as_deref(), Some("slot-lag-recovery"));
+        assert_eq!(
+            decision.others,
+            [IssueDecision {
+                issue: IssueKind::DiskAlmostFull,
+                playbook: Some("disk-cleanup".into()),
+                outcome: DecisionOutcome::Dispatched {
+                    plan_source: "rule_based",
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn only_runs_with_a_step_left_are_active() {
+        use executor::proto::PlaybookStep;
+
+        let run = |plan_id: &str, states: &[StepState]| {
+            let mut execution = PlaybookExecution {
+                plan_id: plan_id.into(),
+                ..PlaybookExecution::default()
+            };
+            for (step, &state) in states.iter().enumerate() {
+                let mut step = PlaybookStep {
+                    step: step as i32,
+                    kind: "restart_validator".into(),
+                    ..PlaybookStep::default()
+                };
+                step.set_state(state);
+                execution.steps.push(step);
+            }
+            execution
+        };
+        let entries = active_entries(vec![
+            run("waiting", &[StepState::Succeeded, StepState::Delivered]),
+            run("done", &[StepState::Succeeded, StepState::Skipped]),
+            run("failed", &[StepState::Failed]),
+            run("running", &[StepState::Running, StepState::Pending]),
+        ]);
+        let active: Vec<(&str, Vec<&str>)> = entries
+            .iter()
+            .map(|entry| {
+                let states = entry.steps.iter().map(|step| step.state).collect();
+                (entry.plan_id.as_str(), states)
+            })
+            .collect();
+        assert_eq!(
+            active,
+            [
+                ("waiting", vec!["succeeded", "delivered"]),
+                ("running", vec!["running", "pending"]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn loop_decisions_are_counted_on_the_metrics_endpoint() {
+        /// Rejects the second step of every plan.
+        struct FlakyDispatcher;
+
+        impl ActionDispatcher for FlakyDispatcher {
+            async fn dispatch(&mut self, envelope: ActionEnvelope) -> Result<(), tonic::Status> {
+                match envelope.step {
+                    1 => Err(tonic::Status::unavailable("executor down")),
+                    _ => Ok(()),
+                }
+            }
+        }
+
+        let state = app_state(config(vec![validator("v1", None), validator("v2", None)]));
+        let mut lagging = base_metrics();
+        lagging.slot_lag = 75;
+        state.metrics.insert("v1".into(), lagging).await;
+        state.metrics.insert("v2".into(), base_metrics()).await;
+        // Registered with the daemon rather than configured.
+        state.registered.lock().await.push(validator("v3", None));
+
+        run_tick(
+            &state,
+            &mut state.executor.clone(),
+            &mut FlakyDispatcher,
+            &mut StaleTracker::default(),
+            &mut MetricAlertTracker::default(),
+            &mut IdleTracker::default(),
+            &mut UndrainTracker::default(),
+        )
+        .await
+        .unwrap();
+
+        let rendered = export_metrics(State(state.clone())).await;
+        for line in [
+            "agent_issues_detected_total{issue=\"slot_lag_high\"} 1",
+            "agent_playbooks_dispatched_total 1",
+            "agent_actions_submitted_total 2",
+            "agent_action_submit_failures_total 1",
+            "agent_validators 3",
+        ] {
+            assert!(rendered.contains(&format!("{line}\n")), "missing {line}");
+        }
+    }
+
+    #[tokio::test]
+    async fn second_concurrent_restart_is_deferred_past_the_fleet_limit() {
+        let mut cfg = config(vec![validator("v1", None), validator("v2", None)]);
+        cfg.remediation.disruption.max_validators = Some(1);
+        let state = app_state(cfg);
+        let mut lagging = base_metrics();
+        lagging.slot_lag = 75;
+        let (v1, v2) = (&state.config.validators[0], &state.config.validators[1]);
+
+        let plans = evaluate_validator(&state, v1, &lagging, 1_000).await;
+        assert_eq!(plans[0].playbook.id, "slot-lag-recovery");
+        assert!(evaluate_validator(&state, v2, &lagging, 1_000)
+            .await
+            .is_empty());
+        assert_eq!(
+            state.evaluations.snapshot().await["v2"].outcome,
+            DecisionOutcome::Suppressed {
+                reason: SuppressionReason::DisruptionLimit { in_progress: 1 },
+            }
+        );
+
+        // v1 recovering frees its slot, and v2's deferred restart goes out
+        // on its next evaluation.
+        evaluate_validator(&state, v1, &base_metrics(), 1_010).await;
+        let plans = evaluate_validator(&state, v2, &lagging, 1_010).await;
+        assert_eq!(plans[0].playbook.id, "slot-lag-recovery");
+
+        assert!(plans[0].reserved_slot);
+
+        let limit = common::DisruptionLimit {
+            max_percent: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(limit.limit(5), Some(1));
+        assert_eq!(limit.limit(40), Some(4));
+    }
+
+    #[tokio::test]
+    async fn restart_that_never_reached_the_daemon_frees_its_disruption_slot() {
+        let mut cfg = config(vec![validator("v1", None), validator("v2", None)]);
+        cfg.remediation.disruption.max_validators = Some(1);
+        let state = app_state(cfg);
+        let mut lagging = base_metrics();
+        lagging.slot_lag = 75;
+        state.metrics.insert("v1".into(), lagging.clone()).await;
+        state.metrics.insert("v2".into(), lagging).await;
+
+        // The test executor is unreachable, so v1's restart fails to submit
+        // and v2 may still take the one slot on the same tick.
+        run_tick(
+            &state,
+            &mut state.executor.clone(),
+            &mut state.dispatcher.clone(),
+            &mut StaleTracker::default(),
+            &mut MetricAlertTracker::default(),
+            &mut IdleTracker::default(),
+            &mut UndrainTracker::default(),
+        )
+        .await
+        .unwrap();
+
+        let evaluations = state.evaluations.snapshot().await;
+        for id in ["v1", "v2"] {
+            assert!(
+                matches!(evaluations[id].outcome, DecisionOutcome::Dispatched { .. }),
+                "{id}: {:?}",
+                evaluations[id].outcome
+            );
+        }
+        assert_eq!(
+            state
+                .disruptions
+                .acquire(&ValidatorId("v3".into()), now_ts(), Some(1), 600)
+                .await,
+            Ok(true)
+        );
+    }
+
+    #[tokio::test]
+    async fn validator_with_more_issues_ranks_as_more_degraded() {
+        let state = app_state(config(vec![validator("v1", None), validator("v2", None)]));
+        let disk_full = ValidatorMetrics {
+            disk_usage_pct: 95.0,
+            ..base_metrics()
+        };
+        state.metrics.insert("v1".into(), disk_full.clone()).await;
+        state
+            .metrics
+            .insert(
+                "v2".into(),
+                ValidatorMetrics {
+                    cpu_usage: 0.95,
+                    ..disk_full
+                },
+            )
+            .await;
+
+        let mut summaries = validator_summaries(&state, &HashSet::new(), false).await;
+        assert_eq!(summaries[0].status, ["DiskAlmostFull"]);
+        assert_eq!(summaries[1].status.len(), 2);
+        let (one, two) = (
+            summaries[0].degradation.unwrap(),
+            summaries[1].degradation.unwrap(),
+        );
+        assert!(one > summaries[0].risk_score.unwrap());
+        assert!(two > one, "{two} <= {one}");
+
+        sort_by_degradation(&mut summaries);
+        assert_eq!(summaries[0].id, "v2");
+    }
+
+    #[tokio::test]
+    async fn stale_metrics_only_alert_and_report_no_risk() {
+        let state = app_state(config(vec![validator("v1", None)]));
+        let mut stale = base_metrics();
+        stale.slot_lag = 75;
+        stale.last_updated -= state.config.staleness.max_metric_age_secs + 1;
+        state.metrics.insert("v1".into(), stale.clone()).await;
+        let v1 = &state.config.validators[0];
+
+        let plans = evaluate_validator(&state, v1, &stale, now_ts()).await;
+        assert_eq!(plans.len(), 1, "the lagging slot is not acted on");
+        assert_eq!(plans[0].playbook.trigger, IssueKind::MetricsStale);
+        assert!(plans[0]
+            .playbook
+            .steps
+            .iter()
+            .all(|step| matches!(step, Action::SendAlert { .. })));
+
+        let summaries = validator_summaries(&state, &HashSet::new(), false).await;
+        assert_eq!(summaries[0].status, ["stale"]);
+        assert_eq!(summaries[0].risk_score, None);
+        assert_eq!(summaries[0].risk_explanation, None);
+        assert_eq!(summaries[0].degradation, None);
+    }
+
+    #[tokio::test]
+    async fn repeated_decode_failures_flip_validator_to_bad_data() {
+        let mut cfg = config(vec![validator("v1", None)]);
+        cfg.ingest.on_decode_error = DecodeErrorPolicy::BadData;
+        let state = app_state(cfg);
+        let update = |metrics_json: String| MetricsUpdate {
+            validator_id: "v1".into(),
+            metrics_json,
+            ..MetricsUpdate::default()
+        };
+        let good = serde_json::to_string(&base_metrics()).unwrap();
+        ingest_update(
+            &state.metrics,
+            &state.votes,
+            &state.rpc,
+            update(good.clone()),
+        )
+        .await;
+
+        for _ in 0..2 {
+            ingest_update(&state.metrics, &state.votes, &state.rpc, update("{".into())).await;
         }
+        let summaries = validator_summaries(&state, &HashSet::new(), false).await;
+        assert_eq!(summaries[0].decode_errors, 2);
+        assert_eq!(summaries[0].status, ["ok"]);
+
+        ingest_update(&state.metrics, &state.votes, &state.rpc, update("{".into())).await;
+        let summaries = validator_summaries(&state, &HashSet::new(), false).await;
+        assert_eq!(summaries[0].status, ["bad_data"]);
+        assert!(summaries[0]
+            .decode_error
+            .as_deref()
+            .unwrap()
+            .contains("EOF"));
+
+        ingest_update(&state.metrics, &state.votes, &state.rpc, update(good)).await;
+        let summaries = validator_summaries(&state, &HashSet::new(), false).await;
+        assert_eq!(summaries[0].decode_errors, 3);
+        assert_eq!(summaries[0].status, ["ok"]);
+    }
+
+    #[tokio::test]
+    async fn large_client_clock_skew_is_flagged() {
+        let state = app_state(config(vec![validator("v1", None)]));
+        let skewed = |last_updated: i64| MetricsUpdate {
+            validator_id: "v1".into(),
+            metrics_json: serde_json::to_string(&ValidatorMetrics {
+                last_updated,
+                ..base_metrics()
+            })
+            .unwrap(),
+            received_at: 10_000,
+            ..MetricsUpdate::default()
+        };
+
+        ingest_update(&state.metrics, &state.votes, &state.rpc, skewed(9_990)).await;
+        let summaries = validator_summaries(&state, &HashSet::new(), false).await;
+        assert_eq!(summaries[0].clock_drift_secs, None);
+
+        // Host clock ten minutes behind the daemon.
+        ingest_update(&state.metrics, &state.votes, &state.rpc, skewed(9_400)).await;
+        let summaries = validator_summaries(&state, &HashSet::new(), false).await;
+        assert_eq!(summaries[0].clock_drift_secs, Some(600));
+        assert_eq!(summaries[0].metrics.as_ref().unwrap().last_updated, 9_400);
+    }
+
+    #[tokio::test]
+    async fn receive_time_rebases_skewed_samples() {
+        let cache = MetricsCache::new(
+            Smoother::default(),
+            StalenessConfig {
+                use_receive_time: true,
+                ..StalenessConfig::default()
+            },
+        );
+        let mut metrics = ValidatorMetrics {
+            last_updated: 9_400,
+            field_timestamps: [("disk_usage_pct".to_string(), 9_300)].into(),
+            ..base_metrics()
+        };
+        cache.check_clock("v1", &mut metrics, 10_000).await;
+        assert_eq!(metrics.last_updated, 10_000);
+        assert_eq!(metrics.field_timestamps["disk_usage_pct"], 9_900);
     }
 
     #[test]
     fn detects_slot_lag_issue() {
         let mut m = base_metrics();
         m.slot_lag = 75;
-        assert_eq!(detect_issue(&m), Some(IssueKind::SlotLagHigh));
+        assert_eq!(
+            detect_issue_with(&m, &DetectionThresholds::default(), false, false, &[]),
+            Some(IssueKind::SlotLagHigh)
+        );
     }
 
     #[test]
-    fn detects_vote_failure_issue() {
+    fn configured_thresholds_replace_the_defaults() {
+        let mut m = base_metrics();
+        m.slot_lag = 120;
+        let high_latency = DetectionThresholds {
+            slot_lag: 150,
+            ..DetectionThresholds::default()
+        };
+        assert_eq!(
+            detect_issue_with(&m, &high_latency, false, false, &[]),
+            None
+        );
+        m.slot_lag = 151;
+        assert_eq!(
+            detect_issue_with(&m, &high_latency, false, false, &[]),
+            Some(IssueKind::SlotLagHigh)
+        );
+    }
+
+    #[test]
+    fn scripted_rules_fire_alongside_built_in_rules() {
+        let rules = [DetectionRule::parse("slot_lag > 30 => slot_lag_high").unwrap()];
+        let mut m = base_metrics();
+        m.slot_lag = 40;
+        assert_eq!(
+            detect_issue_with(&m, &DetectionThresholds::default(), false, false, &[]),
+            None
+        );
+        assert_eq!(
+            detect_issue_with(&m, &DetectionThresholds::default(), false, false, &rules),
+            Some(IssueKind::SlotLagHigh)
+        );
+        // Both fire; the more severe is reported first.
+        m.cpu_usage = 0.95;
+        assert_eq!(
+            detect_issues_with(&m, &DetectionThresholds::default(), false, false, &rules),
+            [IssueKind::SlotLagHigh, IssueKind::HardwareOverload]
+        );
+        m.slot_lag = 10;
+        assert_eq!(
+            detect_issue_with(&m, &DetectionThresholds::default(), false, false, &rules),
+            Some(IssueKind::HardwareOverload)
+        );
+    }
+
+    #[tokio::test]
+    async fn detects_vote_failure_issue() {
         let mut m = base_metrics();
-        m.vote_success_rate = 0.5;
-        assert_eq!(detect_issue(&m), Some(IssueKind::VoteFailureSpike));
+        m.vote_success_rate = 0.3;
+        let votes = VoteHistory::new(Default::default());
+        votes.record("v1", &m).await;
+        assert_eq!(
+            detect_issue_with(
+                &m,
+                &DetectionThresholds::default(),
+                votes.failing("v1").await,
+                false,
+                &[]
+            ),
+            Some(IssueKind::VoteFailureSpike)
+        );
     }
 
     #[test]
     fn detects_hardware_overload_issue() {
         let mut m = base_metrics();
         m.cpu_usage = 0.95;
-        assert_eq!(detect_issue(&m), Some(IssueKind::HardwareOverload));
+        assert_eq!(
+            detect_issue_with(&m, &DetectionThresholds::default(), false, false, &[]),
+            Some(IssueKind::HardwareOverload)
+        );
+    }
+
+    /// Daemon stand-in listing `states` and recording `EnableValidator`.
+    #[derive(Clone, Default)]
+    struct DrainDaemon {
+        states: Vec<ValidatorState>,
+        enabled: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    type Streamed<T> = std::pin::Pin<
+        Box<dyn tokio_stream::Stream<Item = Result<T, tonic::Status>> + Send + 'static>,
+    >;
+
+    #[tonic::async_trait]
+    impl executor::proto::executor_server::Executor for DrainDaemon {
+        type StreamActionsStream = Streamed<ActionEnvelope>;
+        type SubscribeMetricsStream = Streamed<executor::proto::MetricsUpdate>;
+
+        async fn stream_actions(
+            &self,
+            _request: tonic::Request<executor::proto::ConnectRequest>,
+        ) -> Result<tonic::Response<Self::StreamActionsStream>, tonic::Status> {
+            Err(tonic::Status::unimplemented("not used"))
+        }
+
+        async fn report_result(
+            &self,
+            _request: tonic::Request<executor::proto::ActionResult>,
+        ) -> Result<tonic::Response<executor::proto::ReportAck>, tonic::Status> {
+            Err(tonic::Status::unimplemented("not used"))
+        }
+
+        async fn publish_metrics(
+            &self,
+            _request: tonic::Request<executor::proto::MetricsUpdate>,
+        ) -> Result<tonic::Response<executor::proto::ReportAck>, tonic::Status> {
+            Err(tonic::Status::unimplemented("not used"))
+        }
+
+        async fn subscribe_metrics(
+            &self,
+            _request: tonic::Request<executor::proto::MetricsWatchRequest>,
+        ) -> Result<tonic::Response<Self::SubscribeMetricsStream>, tonic::Status> {
+            Err(tonic::Status::unimplemented("not used"))
+        }
+
+        async fn get_all_metrics(
+            &self,
+            _request: tonic::Request<executor::proto::MetricsWatchRequest>,
+        ) -> Result<tonic::Response<executor::proto::MetricsSnapshot>, tonic::Status> {
+            Err(tonic::Status::unimplemented("not used"))
+        }
+
+        async fn submit_action(
+            &self,
+            _request: tonic::Request<ActionEnvelope>,
+        ) -> Result<tonic::Response<executor::proto::ReportAck>, tonic::Status> {
+            Err(tonic::Status::unimplemented("not used"))
+        }
+
+        async fn disable_validator(
+            &self,
+            _request: tonic::Request<ValidatorToggleRequest>,
+        ) -> Result<tonic::Response<executor::proto::ReportAck>, tonic::Status> {
+            Err(tonic::Status::unimplemented("not used"))
+        }
+
+        async fn enable_validator(
+            &self,
+            request: tonic::Request<ValidatorToggleRequest>,
+        ) -> Result<tonic::Response<executor::proto::ReportAck>, tonic::Status> {
+            let validator_id = request.into_inner().validator_id;
+            self.enabled.lock().unwrap().push(validator_id);
+            Ok(tonic::Response::new(executor::proto::ReportAck {}))
+        }
+
+        async fn list_validator_states(
+            &self,
+            _request: tonic::Request<ValidatorStatesRequest>,
+        ) -> Result<tonic::Response<executor::proto::ValidatorStatesResponse>, tonic::Status>
+        {
+            Ok(tonic::Response::new(
+                executor::proto::ValidatorStatesResponse {
+                    validators: self.states.clone(),
+                },
+            ))
+        }
+
+        async fn scrape_now(
+            &self,
+            _request: tonic::Request<ScrapeRequest>,
+        ) -> Result<tonic::Response<executor::proto::ReportAck>, tonic::Status> {
+            Err(tonic::Status::unimplemented("not used"))
+        }
+
+        async fn list_action_history(
+            &self,
+            _request: tonic::Request<ActionHistoryRequest>,
+        ) -> Result<tonic::Response<executor::proto::ActionHistoryResponse>, tonic::Status>
+        {
+            Err(tonic::Status::unimplemented("not used"))
+        }
+
+        async fn list_pending_actions(
+            &self,
+            _request: tonic::Request<PendingActionsRequest>,
+        ) -> Result<tonic::Response<executor::proto::PendingActionsResponse>, tonic::Status>
+        {
+            Err(tonic::Status::unimplemented("not used"))
+        }
+
+        async fn list_playbook_executions(
+            &self,
+            _request: tonic::Request<PlaybookExecutionsRequest>,
+        ) -> Result<tonic::Response<executor::proto::PlaybookExecutionsResponse>, tonic::Status>
+        {
+            Err(tonic::Status::unimplemented("not used"))
+        }
+
+        async fn register_validator(
+            &self,
+            _request: tonic::Request<executor::proto::RegisterValidatorRequest>,
+        ) -> Result<tonic::Response<executor::proto::ReportAck>, tonic::Status> {
+            Err(tonic::Status::unimplemented("not used"))
+        }
+    }
+
+    #[tokio::test]
+    async fn only_drains_flagged_for_auto_undrain_are_lifted() {
+        let drained = |id: &str, auto_undrain: bool| ValidatorState {
+            validator_id: id.into(),
+            enabled: false,
+            auto_undrain,
+            ..ValidatorState::default()
+        };
+        let daemon = DrainDaemon {
+            // v2 was drained by an operator.
+            states: vec![drained("v1", true), drained("v2", false)],
+            ..DrainDaemon::default()
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tonic::transport::Server::builder()
+            .add_service(executor::proto::executor_server::ExecutorServer::new(
+                daemon.clone(),
+            ))
+            .serve_with_incoming(futures_util::stream::unfold(listener, |listener| async {
+                let conn = listener.accept().await.map(|(tcp, _)| tcp);
+                Some((conn, listener))
+            }));
+        tokio::spawn(server);
+        let channel = tonic::transport::Endpoint::from_shared(format!("http://{addr}"))
+            .unwrap()
+            .connect_lazy();
+        let mut cfg = config(vec![validator("v1", None), validator("v2", None)]);
+        cfg.auto_undrain = Some(common::AutoUndrainConfig {
+            max_risk: 0.25,
+            healthy_secs: 0,
+        });
+        let state = AppState {
+            executor: ExecutorClient::new(channel),
+            ..app_state(cfg)
+        };
+        let snapshot = HashMap::from([
+            ("v1".to_string(), metrics_at(now_ts())),
+            ("v2".to_string(), metrics_at(now_ts())),
+        ]);
+
+        let still_drained = auto_undrain(
+            &state,
+            &mut state.executor.clone(),
+            &mut UndrainTracker::default(),
+            &snapshot,
+        )
+        .await;
+        assert_eq!(*daemon.enabled.lock().unwrap(), ["v1"]);
+        assert_eq!(still_drained, HashSet::from(["v2".to_string()]));
+        let decisions = state.decisions.recent(Some("v1"), 10).await;
+        assert!(matches!(
+            decisions[0].event,
+            DecisionEvent::Undrained { .. }
+        ));
+        assert!(state.decisions.recent(Some("v2"), 10).await.is_empty());
     }
 }
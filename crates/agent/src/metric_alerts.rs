@@ -0,0 +1,81 @@
+use std::collections::HashSet;
+
+use common::{MetricAlert, ValidatorMetrics};
+
+use crate::notify::Notification;
+
+/// Edge-triggers `[[metric_alerts]]`: an alert notifies once when a
+/// validator's sample first breaches it, and re-arms once a sample no longer
+/// does.
+#[derive(Debug, Default)]
+pub struct MetricAlertTracker {
+    /// `(validator, alert name)` pairs currently breached.
+    firing: HashSet<(String, String)>,
+}
+
+impl MetricAlertTracker {
+    pub fn observe(
+        &mut self,
+        alerts: &[MetricAlert],
+        validator: &str,
+        metrics: &ValidatorMetrics,
+    ) -> Vec<Notification> {
+        let mut fired = Vec::new();
+        for alert in alerts {
+            let key = (validator.to_string(), alert.name.clone());
+            let Some(value) = alert.breached(metrics) else {
+                self.firing.remove(&key);
+                continue;
+            };
+            if self.firing.insert(key) {
+                fired.push(Notification {
+                    validator: validator.to_string(),
+                    issue: alert.issue,
+                    alert: Some(alert.name.clone()),
+                    severity: Some(alert.severity),
+                    message: alert.message_for(value),
+                    step: 0,
+                });
+            }
+        }
+        fired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::metrics_at;
+    use common::{Comparison, Severity};
+
+    fn disk(disk_usage_pct: f64) -> ValidatorMetrics {
+        ValidatorMetrics {
+            disk_usage_pct,
+            ..metrics_at(0)
+        }
+    }
+
+    #[test]
+    fn alert_fires_once_per_breach_and_rearms() {
+        let alerts = [MetricAlert {
+            name: "disk-85".into(),
+            field: "disk_usage_pct".into(),
+            comparison: Comparison::Above,
+            threshold: 85.0,
+            severity: Severity::Critical,
+            message: None,
+            issue: None,
+        }];
+        let mut tracker = MetricAlertTracker::default();
+
+        let fired = tracker.observe(&alerts, "v1", &disk(87.0));
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].message, "disk_usage_pct 87 > 85");
+        assert_eq!(fired[0].severity, Some(Severity::Critical));
+        assert!(tracker.observe(&alerts, "v1", &disk(88.0)).is_empty());
+        assert_eq!(tracker.observe(&alerts, "v2", &disk(86.0)).len(), 1);
+
+        assert!(tracker.observe(&alerts, "v1", &disk(80.0)).is_empty());
+        assert_eq!(tracker.observe(&alerts, "v1", &disk(90.0)).len(), 1);
+    }
+}
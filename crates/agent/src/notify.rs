@@ -0,0 +1,374 @@
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use common::{AlertsConfig, IssueKind, Severity, SinkConfig};
+use futures_util::future::{join_all, BoxFuture};
+use serde::Serialize;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Notification {
+    pub validator: String,
+    /// The issue a playbook alert is for, or a metric alert names as its
+    /// `issue`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issue: Option<IssueKind>,
+    /// The `[[metric_alerts]]` rule that fired, with its severity.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alert: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    pub message: String,
+    /// Which of its playbook's alert steps this is, counting from 0. A metric
+    /// alert is 0, so it still matches the first alert of its issue's
+    /// playbook, while a playbook's later alerts are not taken for
+    /// duplicates of its first.
+    #[serde(skip)]
+    pub step: usize,
+}
+
+impl Notification {
+    /// The `[alerts.routes]` key this notification is delivered by.
+    fn route(&self) -> &str {
+        match (&self.alert, &self.issue) {
+            (Some(alert), _) => alert,
+            (None, Some(issue)) => issue.as_str(),
+            (None, None) => "",
+        }
+    }
+
+    /// Identifies the logical alert, whichever path raised it: the validator,
+    /// the issue (else the metric alert's name), the alert step and the
+    /// severity.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.validator.hash(&mut hasher);
+        match self.issue {
+            Some(issue) => issue.as_str().hash(&mut hasher),
+            None => self.alert.as_deref().unwrap_or_default().hash(&mut hasher),
+        }
+        self.step.hash(&mut hasher);
+        self.severity.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// A destination alerts can be delivered to (pager, chat channel, ticketing).
+pub trait NotificationSink: Send + Sync {
+    fn send<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<()>>;
+}
+
+struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl NotificationSink for WebhookSink {
+    fn send<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            self.client
+                .post(&self.url)
+                .header("Content-Type", "application/json")
+                .body(serde_json::to_string(notification)?)
+                .send()
+                .await?
+                .error_for_status()?;
+            Ok(())
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SinkOutcome {
+    pub sink: String,
+    pub error: Option<String>,
+}
+
+/// Fans an issue's alerts out to every sink routed for it, in parallel.
+#[derive(Clone, Default)]
+pub struct NotificationRouter {
+    sinks: HashMap<String, Arc<dyn NotificationSink>>,
+    routes: HashMap<String, Vec<String>>,
+    /// Zero disables deduplication.
+    dedup_window: Duration,
+    /// When each fingerprint was last delivered, within `dedup_window`, or
+    /// began delivering.
+    delivered: Arc<Mutex<HashMap<u64, Instant>>>,
+}
+
+impl NotificationRouter {
+    pub fn from_config(cfg: &AlertsConfig) -> Self {
+        let client = reqwest::Client::new();
+        let sinks = cfg
+            .sinks
+            .iter()
+            .map(|(name, sink)| {
+                let sink: Arc<dyn NotificationSink> = match sink {
+                    SinkConfig::Webhook { url } => Arc::new(WebhookSink {
+                        client: client.clone(),
+                        url: url.clone(),
+                    }),
+                };
+                (name.clone(), sink)
+            })
+            .collect();
+        Self {
+            sinks,
+            routes: cfg.routes.clone(),
+            dedup_window: Duration::from_secs(cfg.dedup_window_secs),
+            delivered: Arc::default(),
+        }
+    }
+
+    pub fn is_routed(&self, issue: IssueKind) -> bool {
+        self.routes.contains_key(issue.as_str())
+    }
+
+    /// Whether a notification with the same fingerprint was delivered, or is
+    /// being delivered, within the dedup window. If not, this one is recorded
+    /// until [`NotificationRouter::forget`] drops it.
+    async fn is_duplicate(&self, notification: &Notification, now: Instant) -> bool {
+        if self.dedup_window.is_zero() {
+            return false;
+        }
+        let mut delivered = self.delivered.lock().await;
+        delivered.retain(|_, at| now.saturating_duration_since(*at) < self.dedup_window);
+        match delivered.entry(notification.fingerprint()) {
+            Entry::Occupied(_) => true,
+            Entry::Vacant(slot) => {
+                slot.insert(now);
+                false
+            }
+        }
+    }
+
+    /// Drop the record [`NotificationRouter::is_duplicate`] made at `at`, for
+    /// a notification no sink accepted, so the next attempt is delivered.
+    async fn forget(&self, notification: &Notification, at: Instant) {
+        let mut delivered = self.delivered.lock().await;
+        if let Entry::Occupied(entry) = delivered.entry(notification.fingerprint()) {
+            if *entry.get() == at {
+                entry.remove();
+            }
+        }
+    }
+
+    /// Deliver to all routed sinks at once; one sink failing never stops the
+    /// others. Returns one outcome per routed sink, in route order, or none
+    /// when the notification duplicates one delivered within the window.
+    pub async fn notify(&self, notification: &Notification) -> Vec<SinkOutcome> {
+        let Some(route) = self.routes.get(notification.route()) else {
+            return Vec::new();
+        };
+        let started = Instant::now();
+        if self.is_duplicate(notification, started).await {
+            info!(
+                validator = notification.validator,
+                route = notification.route(),
+                "duplicate notification suppressed"
+            );
+            return Vec::new();
+        }
+        let deliveries = route.iter().map(|name| async move {
+            let result = match self.sinks.get(name) {
+                Some(sink) => sink.send(notification).await,
+                None => Err(anyhow::anyhow!("sink {name} is not configured")),
+            };
+            SinkOutcome {
+                sink: name.clone(),
+                error: result.err().map(|err| format!("{err:#}")),
+            }
+        });
+        let outcomes = join_all(deliveries).await;
+
+        let delivered: Vec<&str> = outcomes
+            .iter()
+            .filter(|outcome| outcome.error.is_none())
+            .map(|outcome| outcome.sink.as_str())
+            .collect();
+        if delivered.is_empty() && !self.dedup_window.is_zero() {
+            self.forget(notification, started).await;
+        }
+        for outcome in &outcomes {
+            if let Some(err) = &outcome.error {
+                error!(
+                    validator = notification.validator,
+                    sink = outcome.sink,
+                    err,
+                    "notification sink failed"
+                );
+            }
+        }
+        info!(
+            validator = notification.validator,
+            route = notification.route(),
+            ?delivered,
+            "notification fan-out complete"
+        );
+        outcomes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct MockSink {
+        calls: Arc<AtomicUsize>,
+        fail: bool,
+    }
+
+    impl NotificationSink for MockSink {
+        fn send<'a>(&'a self, _notification: &'a Notification) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async move {
+                self.calls.fetch_add(1, Ordering::SeqCst);
+                if self.fail {
+                    anyhow::bail!("ticketing api returned 500");
+                }
+                Ok(())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn fans_out_to_every_sink_and_reports_failures() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut router = NotificationRouter::default();
+        for (name, fail) in [("pager", false), ("team-chat", false), ("tickets", true)] {
+            let sink = MockSink {
+                calls: calls.clone(),
+                fail,
+            };
+            router.sinks.insert(name.into(), Arc::new(sink));
+        }
+        router.routes.insert(
+            "vote_failure_spike".into(),
+            vec!["pager".into(), "tickets".into(), "team-chat".into()],
+        );
+
+        let outcomes = router
+            .notify(&Notification {
+                validator: "v1".into(),
+                issue: Some(IssueKind::VoteFailureSpike),
+                alert: None,
+                severity: None,
+                message: "vote rate collapsed".into(),
+                step: 0,
+            })
+            .await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        let failed: Vec<&str> = outcomes
+            .iter()
+            .filter(|outcome| outcome.error.is_some())
+            .map(|outcome| outcome.sink.as_str())
+            .collect();
+        assert_eq!(failed, ["tickets"]);
+        assert_eq!(
+            outcomes[1].error.as_deref(),
+            Some("ticketing api returned 500")
+        );
+        assert_eq!(outcomes.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn same_fingerprint_from_metric_alert_and_playbook_notifies_once() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut router = NotificationRouter::from_config(&AlertsConfig::default());
+        let sink = MockSink {
+            calls: calls.clone(),
+            fail: false,
+        };
+        router.sinks.insert("pager".into(), Arc::new(sink));
+        router
+            .routes
+            .insert("disk_almost_full".into(), vec!["pager".into()]);
+        router.routes.insert("disk-85".into(), vec!["pager".into()]);
+
+        let metric_alert = Notification {
+            validator: "v1".into(),
+            issue: Some(IssueKind::DiskAlmostFull),
+            alert: Some("disk-85".into()),
+            severity: Some(Severity::Warning),
+            message: "disk_usage_pct 91 > 85".into(),
+            step: 0,
+        };
+        let playbook_alert = Notification {
+            alert: None,
+            message: "Disk almost full".into(),
+            ..metric_alert.clone()
+        };
+        assert_eq!(metric_alert.fingerprint(), playbook_alert.fingerprint());
+        assert_eq!(router.notify(&metric_alert).await.len(), 1);
+        assert!(router.notify(&playbook_alert).await.is_empty());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // An escalation, or another validator, is a different alert.
+        let escalated = Notification {
+            severity: Some(Severity::Critical),
+            ..playbook_alert.clone()
+        };
+        let other = Notification {
+            validator: "v2".into(),
+            ..playbook_alert
+        };
+        let next_step = Notification {
+            step: 1,
+            message: "Still filling".into(),
+            ..metric_alert.clone()
+        };
+        assert_eq!(router.notify(&escalated).await.len(), 1);
+        assert_eq!(router.notify(&other).await.len(), 1);
+        assert_eq!(router.notify(&next_step).await.len(), 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn alert_no_sink_accepted_is_not_taken_for_a_duplicate() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut router = NotificationRouter::from_config(&AlertsConfig::default());
+        let sink = MockSink {
+            calls: calls.clone(),
+            fail: true,
+        };
+        router.sinks.insert("pager".into(), Arc::new(sink));
+        router
+            .routes
+            .insert("disk_almost_full".into(), vec!["pager".into()]);
+        let alert = Notification {
+            validator: "v1".into(),
+            issue: Some(IssueKind::DiskAlmostFull),
+            alert: None,
+            severity: Some(Severity::Warning),
+            message: "Disk almost full".into(),
+            step: 0,
+        };
+
+        assert!(router.notify(&alert).await[0].error.is_some());
+        assert!(router.notify(&alert).await[0].error.is_some());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn unrouted_issue_notifies_nobody() {
+        let router = NotificationRouter::default();
+        let outcomes = router
+            .notify(&Notification {
+                validator: "v1".into(),
+                issue: Some(IssueKind::DiskAlmostFull),
+                alert: None,
+                severity: None,
+                message: "disk".into(),
+                step: 0,
+            })
+            .await;
+        assert!(outcomes.is_empty());
+        assert!(!router.is_routed(IssueKind::DiskAlmostFull));
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use common::{DetectionConfig, ValidatorMetrics};
+use tokio::sync::Mutex;
+
+/// `(last_updated, rpc_qps, rpc_error_rate)`, oldest first.
+type RpcSamples = VecDeque<(i64, f64, f64)>;
+
+/// Recent RPC load per validator, keyed by sample timestamp.
+///
+/// Error bursts under heavy load rarely line up with a single sample, so an
+/// `RpcOverload` needs qps above the threshold across the whole window and the
+/// window's mean error rate above the error threshold.
+#[derive(Clone)]
+pub struct RpcHistory {
+    inner: Arc<Mutex<HashMap<String, RpcSamples>>>,
+    cfg: Arc<DetectionConfig>,
+}
+
+impl RpcHistory {
+    pub fn new(cfg: DetectionConfig) -> Self {
+        Self {
+            inner: Arc::default(),
+            cfg: Arc::new(cfg),
+        }
+    }
+
+    pub async fn record(&self, validator: &str, metrics: &ValidatorMetrics) {
+        let mut inner = self.inner.lock().await;
+        let samples = inner.entry(validator.to_string()).or_default();
+        if samples
+            .back()
+            .is_some_and(|(ts, _, _)| *ts >= metrics.last_updated)
+        {
+            return;
+        }
+        samples.push_back((
+            metrics.last_updated,
+            metrics.rpc_qps,
+            metrics.rpc_error_rate,
+        ));
+        // As with votes, keep one sample at or before the window start so
+        // coverage of the full window can be checked.
+        let window_start = metrics.last_updated - self.cfg.rpc_window_secs;
+        while samples.len() > 1 && samples[1].0 <= window_start {
+            samples.pop_front();
+        }
+    }
+
+    pub async fn overloaded(&self, validator: &str) -> bool {
+        let inner = self.inner.lock().await;
+        inner
+            .get(validator)
+            .is_some_and(|samples| is_overloaded(samples, &self.cfg))
+    }
+}
+
+fn is_overloaded(samples: &RpcSamples, cfg: &DetectionConfig) -> bool {
+    let (Some(&(oldest_ts, ..)), Some(&(newest_ts, ..))) = (samples.front(), samples.back()) else {
+        return false;
+    };
+    if newest_ts - oldest_ts < cfg.rpc_window_secs {
+        return false;
+    }
+    let sustained = samples
+        .iter()
+        .all(|(_, qps, _)| *qps > cfg.rpc_qps_threshold);
+    let mean_error_rate =
+        samples.iter().map(|(_, _, errors)| errors).sum::<f64>() / samples.len() as f64;
+    sustained && mean_error_rate > cfg.rpc_error_rate_threshold
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::metrics_at;
+
+    fn history() -> RpcHistory {
+        RpcHistory::new(DetectionConfig {
+            rpc_qps_threshold: 1000.0,
+            rpc_error_rate_threshold: 0.05,
+            rpc_window_secs: 30,
+            ..DetectionConfig::default()
+        })
+    }
+
+    fn sample(last_updated: i64, rpc_qps: f64, rpc_error_rate: f64) -> ValidatorMetrics {
+        ValidatorMetrics {
+            rpc_qps,
+            rpc_error_rate,
+            ..metrics_at(last_updated)
+        }
+    }
+
+    async fn verdicts(history: &RpcHistory, samples: &[(i64, f64, f64)]) -> Vec<bool> {
+        let mut verdicts = Vec::new();
+        for &(ts, qps, errors) in samples {
+            history.record("v1", &sample(ts, qps, errors)).await;
+            verdicts.push(history.overloaded("v1").await);
+        }
+        verdicts
+    }
+
+    #[tokio::test]
+    async fn intermittent_errors_under_sustained_load_fire() {
+        let history = history();
+        let samples = [
+            (0, 1500.0, 0.12),
+            (10, 1600.0, 0.01),
+            (20, 1400.0, 0.09),
+            (30, 1500.0, 0.02),
+        ];
+        assert_eq!(
+            verdicts(&history, &samples).await,
+            [false, false, false, true]
+        );
+    }
+
+    #[tokio::test]
+    async fn clean_high_qps_does_not_fire() {
+        let history = history();
+        let samples: Vec<(i64, f64, f64)> = (0..=4).map(|i| (i * 10, 1800.0, 0.01)).collect();
+        assert!(!verdicts(&history, &samples).await.contains(&true));
+    }
+
+    #[tokio::test]
+    async fn load_dip_inside_window_does_not_fire() {
+        let history = history();
+        let samples = [
+            (0, 1500.0, 0.2),
+            (10, 400.0, 0.2),
+            (20, 1500.0, 0.2),
+            (30, 1500.0, 0.2),
+        ];
+        assert!(!verdicts(&history, &samples).await.contains(&true));
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use common::ValidatorMetrics;
+use tokio::sync::Mutex;
+
+/// Per-validator exponential moving average of the configured metric fields.
+///
+/// Detection and risk scoring read the smoothed view so one noisy sample
+/// doesn't trip a rule; the API keeps showing raw samples.
+#[derive(Clone, Default)]
+pub struct Smoother {
+    state: Arc<Mutex<HashMap<String, ValidatorMetrics>>>,
+    alphas: Arc<HashMap<String, f64>>,
+}
+
+impl Smoother {
+    pub fn new(alphas: HashMap<String, f64>) -> Self {
+        let alphas = alphas
+            .into_iter()
+            .map(|(field, alpha)| (field, alpha.clamp(f64::EPSILON, 1.0)))
+            .collect();
+        Self {
+            state: Arc::default(),
+            alphas: Arc::new(alphas),
+        }
+    }
+
+    /// Fold `raw` into the validator's average and return the smoothed sample.
+    pub async fn update(&self, validator: &str, raw: &ValidatorMetrics) -> ValidatorMetrics {
+        if self.alphas.is_empty() {
+            return raw.clone();
+        }
+        let mut state = self.state.lock().await;
+        let smoothed = match state.get(validator) {
+            Some(prev) if prev.last_updated < raw.last_updated => ema(prev, raw, &self.alphas),
+            Some(prev) => return prev.clone(),
+            None => raw.clone(),
+        };
+        state.insert(validator.to_string(), smoothed.clone());
+        smoothed
+    }
+
+    pub async fn snapshot(&self) -> HashMap<String, ValidatorMetrics> {
+        self.state.lock().await.clone()
+    }
+
+    pub async fn get(&self, validator: &str) -> Option<ValidatorMetrics> {
+        self.state.lock().await.get(validator).cloned()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.alphas.is_empty()
+    }
+}
+
+fn ema(
+    prev: &ValidatorMetrics,
+    raw: &ValidatorMetrics,
+    alphas: &HashMap<String, f64>,
+) -> ValidatorMetrics {
+    let blend = |field: &str, prev: f64, raw: f64| match alphas.get(field) {
+        Some(alpha) => alpha * raw + (1.0 - alpha) * prev,
+        None => raw,
+    };
+    ValidatorMetrics {
+        slot_lag: blend("slot_lag", prev.slot_lag as f64, raw.slot_lag as f64).round() as i64,
+        vote_success_rate: blend(
+            "vote_success_rate",
+            prev.vote_success_rate,
+            raw.vote_success_rate,
+        ),
+        cpu_usage: blend("cpu_usage", prev.cpu_usage, raw.cpu_usage),
+        ram_usage_gb: blend("ram_usage_gb", prev.ram_usage_gb, raw.ram_usage_gb),
+        disk_usage_pct: blend("disk_usage_pct", prev.disk_usage_pct, raw.disk_usage_pct),
+        rpc_qps: blend("rpc_qps", prev.rpc_qps, raw.rpc_qps),
+        rpc_error_rate: blend("rpc_error_rate", prev.rpc_error_rate, raw.rpc_error_rate),
+        last_updated: raw.last_updated,
+        field_timestamps: raw.field_timestamps.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect_issue_with;
+    use crate::test_support::metrics_at;
+    use common::DetectionThresholds;
+
+    fn sample(last_updated: i64, slot_lag: i64) -> ValidatorMetrics {
+        ValidatorMetrics {
+            slot_lag,
+            ..metrics_at(last_updated)
+        }
+    }
+
+    #[tokio::test]
+    async fn single_spike_in_flat_series_does_not_trip_detection() {
+        let smoother = Smoother::new([("slot_lag".to_string(), 0.3)].into());
+        let series = [10, 10, 10, 100, 10, 10];
+        for (i, slot_lag) in series.into_iter().enumerate() {
+            let raw = sample(i as i64 * 5, slot_lag);
+            let smoothed = smoother.update("v1", &raw).await;
+            assert_eq!(
+                detect_issue_with(
+                    &smoothed,
+                    &DetectionThresholds::default(),
+                    false,
+                    false,
+                    &[]
+                ),
+                None,
+                "sample {i}: {smoothed:?}"
+            );
+        }
+        assert!(detect_issue_with(
+            &sample(15, 100),
+            &DetectionThresholds::default(),
+            false,
+            false,
+            &[]
+        )
+        .is_some());
+    }
+
+    #[tokio::test]
+    async fn sustained_shift_still_trips_detection() {
+        let smoother = Smoother::new([("slot_lag".to_string(), 0.3)].into());
+        let mut smoothed = smoother.update("v1", &sample(0, 10)).await;
+        for i in 1..=6 {
+            smoothed = smoother.update("v1", &sample(i * 5, 100)).await;
+        }
+        assert!(smoothed.slot_lag > 50, "{smoothed:?}");
+        assert_eq!(smoothed.cpu_usage, 0.2);
+    }
+
+    #[tokio::test]
+    async fn disabled_smoothing_returns_raw_samples() {
+        let smoother = Smoother::default();
+        let raw = sample(0, 100);
+        assert_eq!(smoother.update("v1", &raw).await, raw);
+        assert!(smoother.snapshot().await.is_empty());
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use common::{StalenessConfig, ValidatorMetrics, METRIC_FIELDS};
+
+use crate::decisions::DecisionEvent;
+
+/// Per-validator fresh/stale state machine.
+///
+/// A validator only transitions to stale once one of its metric fields has
+/// been older than its allowed age for a further `debounce_secs`, so short
+/// publish gaps don't flap the state. It recovers once every field is fresh.
+/// `decode_failing` (the `count_stale` decode policy) marks it stale at once,
+/// since the client is publishing but none of it is usable.
+#[derive(Debug, Default)]
+pub struct StaleTracker {
+    stale_since: HashMap<String, i64>,
+}
+
+impl StaleTracker {
+    pub fn observe(
+        &mut self,
+        validator: &str,
+        metrics: &ValidatorMetrics,
+        now: i64,
+        cfg: &StalenessConfig,
+        decode_failing: bool,
+    ) -> Option<DecisionEvent> {
+        let overdue = cfg.overdue_secs(metrics, now);
+        match self.stale_since.get(validator).copied() {
+            None if decode_failing => {
+                self.stale_since.insert(validator.to_string(), now);
+                Some(DecisionEvent::WentStale {
+                    last_updated: metrics.last_updated,
+                    fields: METRIC_FIELDS.to_vec(),
+                })
+            }
+            None if overdue > cfg.debounce_secs => {
+                self.stale_since
+                    .insert(validator.to_string(), now - overdue);
+                Some(DecisionEvent::WentStale {
+                    last_updated: metrics.last_updated,
+                    fields: cfg.stale_fields(metrics, now),
+                })
+            }
+            Some(since) if overdue <= 0 && !decode_failing => {
+                self.stale_since.remove(validator);
+                Some(DecisionEvent::Recovered {
+                    stale_secs: (now - since).max(0),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::metrics_at;
+
+    fn cfg() -> StalenessConfig {
+        StalenessConfig {
+            max_metric_age_secs: 60,
+            debounce_secs: 30,
+            ..StalenessConfig::default()
+        }
+    }
+
+    #[test]
+    fn fresh_stale_fresh_emits_one_event_each() {
+        let cfg = cfg();
+        let mut tracker = StaleTracker::default();
+        let mut events = Vec::new();
+        // Fresh samples, then a brief gap inside the debounce window.
+        for (last_updated, now) in [(0, 5), (10, 20), (10, 80)] {
+            events.extend(tracker.observe("v1", &metrics_at(last_updated), now, &cfg, false));
+        }
+        assert!(events.is_empty(), "brief gap must not flap: {events:?}");
+
+        // Publishing stops for good: several ticks past the debounce.
+        for now in [101, 110, 150] {
+            events.extend(tracker.observe("v1", &metrics_at(10), now, &cfg, false));
+        }
+
+        // Metrics resume.
+        for (last_updated, now) in [(160, 161), (170, 171)] {
+            events.extend(tracker.observe("v1", &metrics_at(last_updated), now, &cfg, false));
+        }
+        assert_eq!(
+            events,
+            vec![
+                DecisionEvent::WentStale {
+                    last_updated: 10,
+                    fields: METRIC_FIELDS.to_vec(),
+                },
+                DecisionEvent::Recovered { stale_secs: 91 },
+            ]
+        );
+    }
+
+    #[test]
+    fn persistent_decode_failures_go_stale_without_debounce() {
+        let cfg = cfg();
+        let mut tracker = StaleTracker::default();
+        let fresh = metrics_at(100);
+        assert!(tracker.observe("v1", &fresh, 101, &cfg, false).is_none());
+        assert_eq!(
+            tracker.observe("v1", &fresh, 102, &cfg, true),
+            Some(DecisionEvent::WentStale {
+                last_updated: 100,
+                fields: METRIC_FIELDS.to_vec(),
+            })
+        );
+        assert!(tracker.observe("v1", &fresh, 103, &cfg, true).is_none());
+        assert_eq!(
+            tracker.observe("v1", &metrics_at(110), 110, &cfg, false),
+            Some(DecisionEvent::Recovered { stale_secs: 8 })
+        );
+    }
+}
@@ -0,0 +1,45 @@
+//! Fixtures shared by the agent's unit tests.
+
+use common::{now_ts, ValidatorConfig, ValidatorId, ValidatorMetrics};
+
+/// A healthy sample taken at `last_updated`.
+pub fn metrics_at(last_updated: i64) -> ValidatorMetrics {
+    ValidatorMetrics {
+        slot_lag: 0,
+        vote_success_rate: 0.99,
+        cpu_usage: 0.2,
+        ram_usage_gb: 16.0,
+        disk_usage_pct: 30.0,
+        rpc_qps: 100.0,
+        rpc_error_rate: 0.001,
+        last_updated,
+        field_timestamps: Default::default(),
+    }
+}
+
+/// A healthy sample, fresh per `[staleness]` whenever it is evaluated.
+pub fn base_metrics() -> ValidatorMetrics {
+    metrics_at(now_ts())
+}
+
+/// A sample `slot_lag` slots behind, otherwise healthy.
+pub fn lagging(slot_lag: i64) -> ValidatorMetrics {
+    ValidatorMetrics {
+        slot_lag,
+        ..metrics_at(0)
+    }
+}
+
+pub fn validator(id: &str, allowed_scripts: Option<Vec<String>>) -> ValidatorConfig {
+    ValidatorConfig {
+        id: ValidatorId(id.into()),
+        host: format!("{id}.local"),
+        prometheus_url: format!("http://{id}.local:9100/metrics"),
+        auth_token: format!("{id}-secret"),
+        allowed_scripts,
+        tags: Vec::new(),
+        labels: Default::default(),
+        agentic_allowed_actions: None,
+        metrics_fallbacks: Vec::new(),
+    }
+}
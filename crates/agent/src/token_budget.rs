@@ -0,0 +1,132 @@
+use std::fmt::Write as _;
+use std::sync::Arc;
+
+use common::TokenBudget;
+use tokio::sync::Mutex;
+
+const HOUR_SECS: i64 = 3600;
+const DAY_SECS: i64 = 86_400;
+
+/// Tokens spent by the agentic planner in the current clock hour and UTC day.
+#[derive(Clone, Debug, Default)]
+pub struct TokenUsage {
+    inner: Arc<Mutex<UsageWindows>>,
+}
+
+#[derive(Debug, Default)]
+struct UsageWindows {
+    hour: i64,
+    hour_tokens: u64,
+    day: i64,
+    day_tokens: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UsageSnapshot {
+    pub hour_tokens: u64,
+    pub day_tokens: u64,
+}
+
+impl UsageWindows {
+    fn roll(&mut self, now: i64) {
+        let hour = now.div_euclid(HOUR_SECS);
+        if hour != self.hour {
+            self.hour = hour;
+            self.hour_tokens = 0;
+        }
+        let day = now.div_euclid(DAY_SECS);
+        if day != self.day {
+            self.day = day;
+            self.day_tokens = 0;
+        }
+    }
+}
+
+impl TokenUsage {
+    pub async fn record(&self, tokens: u64, now: i64) {
+        let mut inner = self.inner.lock().await;
+        inner.roll(now);
+        inner.hour_tokens += tokens;
+        inner.day_tokens += tokens;
+    }
+
+    pub async fn snapshot(&self, now: i64) -> UsageSnapshot {
+        let mut inner = self.inner.lock().await;
+        inner.roll(now);
+        UsageSnapshot {
+            hour_tokens: inner.hour_tokens,
+            day_tokens: inner.day_tokens,
+        }
+    }
+
+    /// Whether either window has reached its cap.
+    pub async fn exhausted(&self, budget: &TokenBudget, now: i64) -> bool {
+        let usage = self.snapshot(now).await;
+        budget
+            .hourly_tokens
+            .is_some_and(|cap| usage.hour_tokens >= cap)
+            || budget
+                .daily_tokens
+                .is_some_and(|cap| usage.day_tokens >= cap)
+    }
+
+    /// Usage gauges in the Prometheus text format.
+    pub async fn render_metrics(&self, budget: &TokenBudget, now: i64) -> String {
+        let usage = self.snapshot(now).await;
+        let exhausted = self.exhausted(budget, now).await;
+        let mut out = String::new();
+        out.push_str("# HELP agent_agentic_tokens_used Tokens spent by the agentic planner in the current window.\n");
+        out.push_str("# TYPE agent_agentic_tokens_used gauge\n");
+        for (window, tokens) in [("hour", usage.hour_tokens), ("day", usage.day_tokens)] {
+            let _ = writeln!(
+                out,
+                "agent_agentic_tokens_used{{window=\"{window}\"}} {tokens}"
+            );
+        }
+        out.push_str("# HELP agent_agentic_budget_exhausted 1 while agentic planning is paused by the token budget.\n");
+        out.push_str("# TYPE agent_agentic_budget_exhausted gauge\n");
+        let _ = writeln!(
+            out,
+            "agent_agentic_budget_exhausted {}",
+            u8::from(exhausted)
+        );
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn usage_past_hourly_cap_disables_until_next_hour() {
+        let budget = TokenBudget {
+            hourly_tokens: Some(1_000),
+            daily_tokens: None,
+        };
+        let usage = TokenUsage::default();
+        let start = 10 * HOUR_SECS;
+        usage.record(600, start + 5).await;
+        assert!(!usage.exhausted(&budget, start + 10).await);
+        usage.record(600, start + 20).await;
+        assert!(usage.exhausted(&budget, start + 30).await);
+        let rendered = usage.render_metrics(&budget, start + 30).await;
+        assert!(rendered.contains("agent_agentic_tokens_used{window=\"hour\"} 1200\n"));
+        assert!(rendered.contains("agent_agentic_budget_exhausted 1\n"));
+
+        assert!(!usage.exhausted(&budget, start + HOUR_SECS).await);
+        assert_eq!(usage.snapshot(start + HOUR_SECS).await.day_tokens, 1_200);
+    }
+
+    #[tokio::test]
+    async fn daily_cap_outlasts_hourly_resets() {
+        let budget = TokenBudget {
+            hourly_tokens: None,
+            daily_tokens: Some(1_000),
+        };
+        let usage = TokenUsage::default();
+        usage.record(1_000, 3 * HOUR_SECS).await;
+        assert!(usage.exhausted(&budget, 5 * HOUR_SECS).await);
+        assert!(!usage.exhausted(&budget, DAY_SECS).await);
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use common::AutoUndrainConfig;
+
+/// When each validator drained with `auto_undrain` began its current
+/// healthy run, for `[auto_undrain]`.
+///
+/// Only ticks seen while a validator is so drained count, so a run starts no
+/// earlier than the drain, and an unhealthy or stale tick starts it over.
+#[derive(Debug, Default)]
+pub struct UndrainTracker {
+    healthy_since: HashMap<String, i64>,
+}
+
+impl UndrainTracker {
+    /// The seconds `validator` has been healthy once that reaches
+    /// `healthy_secs`, meaning it should be re-enabled. `risk` is its risk
+    /// score, `None` without a fresh sample; `drained` is whether it is
+    /// disabled with `auto_undrain`. Keeps answering until the validator is
+    /// seen otherwise, so a failed re-enable is retried.
+    pub fn observe(
+        &mut self,
+        validator: &str,
+        drained: bool,
+        risk: Option<f64>,
+        now: i64,
+        cfg: &AutoUndrainConfig,
+    ) -> Option<i64> {
+        if !drained || !risk.is_some_and(|risk| risk < cfg.max_risk) {
+            self.healthy_since.remove(validator);
+            return None;
+        }
+        let since = *self
+            .healthy_since
+            .entry(validator.to_string())
+            .or_insert(now);
+        let healthy_secs = now - since;
+        (healthy_secs >= cfg.healthy_secs as i64).then_some(healthy_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg() -> AutoUndrainConfig {
+        AutoUndrainConfig {
+            max_risk: 0.25,
+            healthy_secs: 300,
+        }
+    }
+
+    #[test]
+    fn drained_then_healthy_validator_undrains_after_the_window() {
+        let mut tracker = UndrainTracker::default();
+        assert_eq!(tracker.observe("v1", true, Some(0.1), 1_000, &cfg()), None);
+        assert_eq!(tracker.observe("v1", true, Some(0.1), 1_299, &cfg()), None);
+        assert_eq!(
+            tracker.observe("v1", true, Some(0.1), 1_300, &cfg()),
+            Some(300)
+        );
+        // Still disabled next tick, e.g. the enable call failed: retried.
+        assert_eq!(
+            tracker.observe("v1", true, Some(0.1), 1_310, &cfg()),
+            Some(310)
+        );
+        // Enabled again; a later drain starts a new window.
+        assert_eq!(tracker.observe("v1", false, Some(0.1), 1_320, &cfg()), None);
+        assert_eq!(tracker.observe("v1", true, Some(0.1), 1_330, &cfg()), None);
+    }
+
+    #[test]
+    fn unhealthy_or_stale_validator_stays_drained() {
+        let mut tracker = UndrainTracker::default();
+        for now in (1_000..2_000).step_by(10) {
+            assert_eq!(tracker.observe("v1", true, Some(0.6), now, &cfg()), None);
+        }
+        assert_eq!(tracker.observe("v1", true, Some(0.1), 2_000, &cfg()), None);
+        // One tick at the threshold or without a fresh sample restarts the run.
+        assert_eq!(tracker.observe("v1", true, Some(0.25), 2_200, &cfg()), None);
+        assert_eq!(tracker.observe("v1", true, Some(0.1), 2_210, &cfg()), None);
+        assert_eq!(tracker.observe("v1", true, None, 2_400, &cfg()), None);
+        assert_eq!(tracker.observe("v1", true, Some(0.1), 2_410, &cfg()), None);
+        assert_eq!(tracker.observe("v1", true, Some(0.1), 2_709, &cfg()), None);
+        assert_eq!(
+            tracker.observe("v1", true, Some(0.1), 2_710, &cfg()),
+            Some(300)
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use common::{DetectionConfig, ValidatorMetrics};
+use tokio::sync::Mutex;
+
+/// `(last_updated, vote_success_rate)`, oldest first.
+type VoteSamples = VecDeque<(i64, f64)>;
+
+/// Recent vote success rates per validator, keyed by sample timestamp.
+///
+/// Vote rate dips briefly at epoch boundaries, so a `VoteFailureSpike` needs
+/// the rate to stay under the threshold for the whole window; only a drop
+/// below the hard floor fires on a single sample. Nothing fires while the
+/// newest sample is within `restart_vote_grace_secs` of a completed restart.
+#[derive(Clone)]
+pub struct VoteHistory {
+    inner: Arc<Mutex<HashMap<String, VoteSamples>>>,
+    /// When each validator's latest `restart_validator` completed.
+    restarts: Arc<Mutex<HashMap<String, i64>>>,
+    cfg: Arc<DetectionConfig>,
+}
+
+impl VoteHistory {
+    pub fn new(cfg: DetectionConfig) -> Self {
+        Self {
+            inner: Arc::default(),
+            restarts: Arc::default(),
+            cfg: Arc::new(cfg),
+        }
+    }
+
+    pub async fn restarted(&self, validator: &str, completed_at: i64) {
+        self.restarts
+            .lock()
+            .await
+            .insert(validator.to_string(), completed_at);
+    }
+
+    pub async fn record(&self, validator: &str, metrics: &ValidatorMetrics) {
+        let mut inner = self.inner.lock().await;
+        let samples = inner.entry(validator.to_string()).or_default();
+        if samples
+            .back()
+            .is_some_and(|(ts, _)| *ts >= metrics.last_updated)
+        {
+            return;
+        }
+        samples.push_back((metrics.last_updated, metrics.vote_success_rate));
+        // Keep the newest sample at or before the window start so coverage of
+        // the full window can still be checked.
+        let window_start = metrics.last_updated - self.cfg.vote_window_secs;
+        while samples.len() > 1 && samples[1].0 <= window_start {
+            samples.pop_front();
+        }
+    }
+
+    pub async fn failing(&self, validator: &str) -> bool {
+        let restarted_at = self.restarts.lock().await.get(validator).copied();
+        let inner = self.inner.lock().await;
+        inner.get(validator).is_some_and(|samples| {
+            !in_restart_grace(samples, restarted_at, &self.cfg) && is_failing(samples, &self.cfg)
+        })
+    }
+}
+
+fn in_restart_grace(
+    samples: &VoteSamples,
+    restarted_at: Option<i64>,
+    cfg: &DetectionConfig,
+) -> bool {
+    match (restarted_at, samples.back()) {
+        (Some(restarted_at), Some(&(newest_ts, _))) => {
+            newest_ts < restarted_at + cfg.restart_vote_grace_secs
+        }
+        _ => false,
+    }
+}
+
+fn is_failing(samples: &VoteSamples, cfg: &DetectionConfig) -> bool {
+    let (Some(&(oldest_ts, _)), Some(&(newest_ts, newest_rate))) =
+        (samples.front(), samples.back())
+    else {
+        return false;
+    };
+    if newest_rate < cfg.vote_rate_floor {
+        return true;
+    }
+    newest_ts - oldest_ts >= cfg.vote_window_secs
+        && samples
+            .iter()
+            .all(|(_, rate)| *rate < cfg.vote_rate_threshold)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::metrics_at;
+
+    fn history() -> VoteHistory {
+        VoteHistory::new(DetectionConfig {
+            vote_rate_threshold: 0.8,
+            vote_rate_floor: 0.5,
+            vote_window_secs: 60,
+            ..DetectionConfig::default()
+        })
+    }
+
+    fn sample(last_updated: i64, vote_success_rate: f64) -> ValidatorMetrics {
+        ValidatorMetrics {
+            vote_success_rate,
+            ..metrics_at(last_updated)
+        }
+    }
+
+    async fn verdicts(history: &VoteHistory, samples: &[(i64, f64)]) -> Vec<bool> {
+        let mut verdicts = Vec::new();
+        for &(ts, rate) in samples {
+            history.record("v1", &sample(ts, rate)).await;
+            verdicts.push(history.failing("v1").await);
+        }
+        verdicts
+    }
+
+    #[tokio::test]
+    async fn brief_epoch_boundary_dip_does_not_fire() {
+        let history = history();
+        let samples = [
+            (0, 0.95),
+            (10, 0.7),
+            (20, 0.72),
+            (30, 0.95),
+            (40, 0.7),
+            (50, 0.7),
+        ];
+        assert!(!verdicts(&history, &samples).await.contains(&true));
+    }
+
+    #[tokio::test]
+    async fn sustained_dip_fires_once_window_is_covered() {
+        let history = history();
+        let samples: Vec<(i64, f64)> = (0..=6).map(|i| (i * 10, 0.7)).collect();
+        let verdicts = verdicts(&history, &samples).await;
+        assert_eq!(verdicts, [false, false, false, false, false, false, true]);
+    }
+
+    #[tokio::test]
+    async fn dip_within_restart_grace_does_not_fire() {
+        let history = VoteHistory::new(DetectionConfig {
+            restart_vote_grace_secs: 120,
+            ..DetectionConfig::default()
+        });
+        history.restarted("v1", 1_000).await;
+        let samples: Vec<(i64, f64)> = (0..=12).map(|i| (1_000 + i * 10, 0.3)).collect();
+        let verdicts = verdicts(&history, &samples).await;
+        assert!(!verdicts[..12].contains(&true));
+        // Still dipping once the grace has run out.
+        assert!(verdicts[12]);
+    }
+
+    #[tokio::test]
+    async fn catastrophic_single_sample_fires_immediately() {
+        let history = history();
+        assert_eq!(
+            verdicts(&history, &[(0, 0.95), (10, 0.2)]).await,
+            [false, true]
+        );
+    }
+}
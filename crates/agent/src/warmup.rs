@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// Whether any validator has published a sample since the agent started, so
+/// an empty first few ticks read as waiting for data, not as a broken loop.
+#[derive(Clone, Debug)]
+pub struct MetricsWarmup {
+    started_at: i64,
+    /// `[ingest] warmup_grace_secs`.
+    grace_secs: i64,
+    /// When the first sample was seen.
+    warm_at: Arc<Mutex<Option<i64>>>,
+}
+
+impl MetricsWarmup {
+    pub fn new(started_at: i64, grace_secs: u64) -> Self {
+        Self {
+            started_at,
+            grace_secs: grace_secs as i64,
+            warm_at: Arc::default(),
+        }
+    }
+
+    /// Log a tick's snapshot until the first one with samples in it: `awaiting
+    /// metrics` while it is empty, as a warning once past the grace period.
+    pub async fn observe(&self, empty: bool, now: i64) {
+        let mut warm_at = self.warm_at.lock().await;
+        if warm_at.is_some() {
+            return;
+        }
+        let waited_secs = now - self.started_at;
+        if !empty {
+            *warm_at = Some(now);
+            info!(waited_secs, "metrics cache warm, evaluating validators");
+        } else if waited_secs > self.grace_secs {
+            warn!(
+                waited_secs,
+                grace_secs = self.grace_secs,
+                "awaiting metrics: no validator has published a sample yet"
+            );
+        } else {
+            info!(waited_secs, "awaiting metrics");
+        }
+    }
+
+    pub async fn is_warm(&self) -> bool {
+        self.warm_at.lock().await.is_some()
+    }
+
+    /// Still empty past the grace period.
+    pub async fn overdue(&self, now: i64) -> bool {
+        !self.is_warm().await && now - self.started_at > self.grace_secs
+    }
+
+    pub async fn label(&self) -> &'static str {
+        if self.is_warm().await {
+            "warm"
+        } else {
+            "awaiting"
+        }
+    }
+}
@@ -1,14 +1,23 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use config::Config as RawConfig;
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+mod rules;
+
+pub use rules::DetectionRule;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ValidatorId(pub String);
 
+/// Serialized with the current field names. Legacy spellings are accepted on
+/// input so older publishers and consumers keep working.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ValidatorMetrics {
     pub slot_lag: i64,
+    #[serde(alias = "vote_rate")]
     pub vote_success_rate: f64,
     pub cpu_usage: f64,
     pub ram_usage_gb: f64,
@@ -16,10 +25,49 @@ pub struct ValidatorMetrics {
     pub rpc_qps: f64,
     pub rpc_error_rate: f64,
     pub last_updated: i64,
+    /// Per-field sample timestamps (unix seconds) when the exporter reports them.
+    /// Fields without an entry were refreshed at `last_updated`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub field_timestamps: BTreeMap<String, i64>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "snake_case")]
+/// Names of the `ValidatorMetrics` sample fields, as used in per-field settings.
+pub const METRIC_FIELDS: &[&str] = &[
+    "slot_lag",
+    "vote_success_rate",
+    "cpu_usage",
+    "ram_usage_gb",
+    "disk_usage_pct",
+    "rpc_qps",
+    "rpc_error_rate",
+];
+
+impl ValidatorMetrics {
+    /// The value of the sample field named `field` (see [`METRIC_FIELDS`]).
+    pub fn field(&self, field: &str) -> Option<f64> {
+        Some(match field {
+            "slot_lag" => self.slot_lag as f64,
+            "vote_success_rate" => self.vote_success_rate,
+            "cpu_usage" => self.cpu_usage,
+            "ram_usage_gb" => self.ram_usage_gb,
+            "disk_usage_pct" => self.disk_usage_pct,
+            "rpc_qps" => self.rpc_qps,
+            "rpc_error_rate" => self.rpc_error_rate,
+            _ => return None,
+        })
+    }
+
+    /// When `field` was last refreshed.
+    pub fn field_updated_at(&self, field: &str) -> i64 {
+        self.field_timestamps
+            .get(field)
+            .copied()
+            .unwrap_or(self.last_updated)
+    }
+}
+
+/// Serialized as its [`IssueKind::as_str`] name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum IssueKind {
     SlotLagHigh,
     VoteFailureSpike,
@@ -27,7 +75,129 @@ pub enum IssueKind {
     DiskAlmostFull,
     RpcOverload,
     NetworkUnstable,
+    /// No fresh metrics within `[staleness] max_metric_age_secs`.
+    MetricsStale,
     Unknown,
+    /// Org-specific, reported by a `[detection] rules` entry ending in
+    /// `=> custom:<name>`.
+    Custom(CustomIssue),
+}
+
+/// The name of a custom issue, interned so [`IssueKind`] stays `Copy`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CustomIssue(&'static str);
+
+impl CustomIssue {
+    const PREFIX: &'static str = "custom:";
+    /// Distinct names interned before [`CustomIssue::new`] refuses more.
+    const MAX_NAMES: usize = 256;
+
+    /// `name` must be snake_case, e.g. `fork_suspected`.
+    pub fn new(name: &str) -> Result<Self, String> {
+        use std::sync::{Mutex, OnceLock};
+        static NAMES: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            return Err(format!("custom issue name `{name}` is not snake_case"));
+        }
+        let full = format!("{}{name}", Self::PREFIX);
+        let mut names = NAMES
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(interned) = names.get(full.as_str()) {
+            return Ok(Self(interned));
+        }
+        if names.len() >= Self::MAX_NAMES {
+            return Err(format!("more than {} custom issue names", Self::MAX_NAMES));
+        }
+        let interned: &'static str = Box::leak(full.into_boxed_str());
+        names.insert(interned);
+        Ok(Self(interned))
+    }
+
+    /// The name without its `custom:` prefix.
+    pub fn name(self) -> &'static str {
+        &self.0[Self::PREFIX.len()..]
+    }
+}
+
+impl std::fmt::Debug for CustomIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for IssueKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        const BUILT_IN: [IssueKind; 8] = [
+            IssueKind::SlotLagHigh,
+            IssueKind::VoteFailureSpike,
+            IssueKind::HardwareOverload,
+            IssueKind::DiskAlmostFull,
+            IssueKind::RpcOverload,
+            IssueKind::NetworkUnstable,
+            IssueKind::MetricsStale,
+            IssueKind::Unknown,
+        ];
+        if let Some(custom) = name.strip_prefix(CustomIssue::PREFIX) {
+            return CustomIssue::new(custom).map(Self::Custom);
+        }
+        BUILT_IN
+            .into_iter()
+            .find(|issue| issue.as_str() == name)
+            .ok_or_else(|| format!("unknown issue kind `{name}`"))
+    }
+}
+
+impl Serialize for IssueKind {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for IssueKind {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl IssueKind {
+    /// Built-in severity, overridable under `[remediation.severities]`.
+    pub fn default_severity(self) -> Severity {
+        match self {
+            Self::SlotLagHigh | Self::VoteFailureSpike => Severity::Critical,
+            Self::HardwareOverload
+            | Self::DiskAlmostFull
+            | Self::RpcOverload
+            | Self::NetworkUnstable
+            | Self::MetricsStale
+            | Self::Custom(_) => Severity::Warning,
+            Self::Unknown => Severity::Info,
+        }
+    }
+
+    /// The snake_case name used in serialized output and config keys.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SlotLagHigh => "slot_lag_high",
+            Self::VoteFailureSpike => "vote_failure_spike",
+            Self::HardwareOverload => "hardware_overload",
+            Self::DiskAlmostFull => "disk_almost_full",
+            Self::RpcOverload => "rpc_overload",
+            Self::NetworkUnstable => "network_unstable",
+            Self::MetricsStale => "metrics_stale",
+            Self::Unknown => "unknown",
+            Self::Custom(custom) => custom.0,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -41,6 +211,9 @@ pub enum Action {
     },
     RestartValidator {
         validator: ValidatorId,
+        /// Omitted on the wire for a hard restart, so older peers still parse it.
+        #[serde(default, skip_serializing_if = "RestartKind::is_hard")]
+        restart_kind: RestartKind,
     },
     ThrottleRpcClient {
         validator: ValidatorId,
@@ -48,11 +221,109 @@ pub enum Action {
     RunMaintenanceScript {
         validator: ValidatorId,
         script_name: String,
+        /// Arguments for a script registered under `[commands.scripts]`.
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        params: HashMap<String, String>,
     },
     SendAlert {
         validator: ValidatorId,
         message: String,
     },
+    /// Runs an operator-defined command registered under `command_id` in `[commands.custom]`.
+    ExecCommand {
+        validator: ValidatorId,
+        command_id: String,
+    },
+}
+
+/// How disruptive a `restart_validator` is.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RestartKind {
+    /// Reload configuration in place, e.g. with `SIGHUP`.
+    Soft,
+    /// Bounce the whole validator process.
+    #[default]
+    Hard,
+}
+
+impl RestartKind {
+    fn is_hard(&self) -> bool {
+        *self == Self::Hard
+    }
+}
+
+/// Every [`Action::kind`].
+pub const ACTION_KINDS: &[&str] = &[
+    "disable_rpc",
+    "enable_rpc",
+    "restart_validator",
+    "throttle_rpc_client",
+    "run_maintenance_script",
+    "send_alert",
+    "exec_command",
+];
+
+impl Action {
+    /// The serialized `kind` tag, also used as the key in `[remediation.priorities]`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::DisableRpc { .. } => "disable_rpc",
+            Self::EnableRpc { .. } => "enable_rpc",
+            Self::RestartValidator { .. } => "restart_validator",
+            Self::ThrottleRpcClient { .. } => "throttle_rpc_client",
+            Self::RunMaintenanceScript { .. } => "run_maintenance_script",
+            Self::SendAlert { .. } => "send_alert",
+            Self::ExecCommand { .. } => "exec_command",
+        }
+    }
+
+    /// The validator this action is for.
+    pub fn validator(&self) -> &ValidatorId {
+        match self {
+            Self::DisableRpc { validator }
+            | Self::EnableRpc { validator }
+            | Self::RestartValidator { validator, .. }
+            | Self::ThrottleRpcClient { validator }
+            | Self::RunMaintenanceScript { validator, .. }
+            | Self::SendAlert { validator, .. }
+            | Self::ExecCommand { validator, .. } => validator,
+        }
+    }
+
+    /// The action that undoes this one when a later step of its playbook
+    /// fails. Restarts, scripts, custom commands and alerts cannot be undone,
+    /// and nothing lifts a client throttle, so only `disable_rpc` has one.
+    pub fn compensation(&self) -> Option<Action> {
+        match self {
+            Self::DisableRpc { validator } => Some(Self::EnableRpc {
+                validator: validator.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The `[commands.actions]` key of this action's command template: its
+    /// kind, except `restart_validator_soft` for a soft restart.
+    pub fn template_key(&self) -> &'static str {
+        match self {
+            Self::RestartValidator {
+                restart_kind: RestartKind::Soft,
+                ..
+            } => "restart_validator_soft",
+            _ => self.kind(),
+        }
+    }
+
+    /// Default delivery priority: alerts first, disruptive steps last.
+    fn default_priority(&self) -> i32 {
+        match self {
+            Self::SendAlert { .. } => 100,
+            Self::ThrottleRpcClient { .. } | Self::DisableRpc { .. } | Self::EnableRpc { .. } => 50,
+            Self::RunMaintenanceScript { .. } | Self::ExecCommand { .. } => 20,
+            Self::RestartValidator { .. } => 0,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -60,6 +331,10 @@ pub struct Playbook {
     pub id: String,
     pub trigger: IssueKind,
     pub steps: Vec<Action>,
+    /// Prerequisites by step index: a step only runs once every step listed
+    /// for it has succeeded, and is skipped if one fails.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub depends_on: BTreeMap<usize, Vec<usize>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -68,107 +343,3187 @@ pub struct ValidatorConfig {
     pub host: String,
     pub prometheus_url: String,
     pub auth_token: String,
+    /// Maintenance scripts this validator may run. `None` inherits the global allowlist.
+    #[serde(default)]
+    pub allowed_scripts: Option<Vec<String>>,
+    /// Free-form labels, e.g. `archival`, that `[[suppressions]]` rules match on.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Key/value metadata, e.g. `region = "eu-west"`, that `[groups]` can
+    /// group validators by.
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// Action kinds agentic plans may use on this validator, narrowing
+    /// `[agentic] allowed_actions`. `None` inherits it.
+    #[serde(default)]
+    pub agentic_allowed_actions: Option<Vec<String>>,
+    /// Where the validator client reads metrics from when `prometheus_url`
+    /// fails, tried in order: exporter URLs, or `file://` paths to a
+    /// Prometheus text file.
+    #[serde(default)]
+    pub metrics_fallbacks: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct Config {
     pub validators: Vec<ValidatorConfig>,
     pub redis_url: String,
     #[serde(default)]
     pub agentic: Option<AgenticConfig>,
+    #[serde(default)]
+    pub maintenance: MaintenanceConfig,
+    #[serde(default)]
+    pub staleness: StalenessConfig,
+    #[serde(default)]
+    pub commands: ActionCommands,
+    #[serde(default)]
+    pub remediation: RemediationConfig,
+    #[serde(default)]
+    pub daemon: DaemonConfig,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    #[serde(default)]
+    pub detection: DetectionConfig,
+    #[serde(default)]
+    pub influx: Option<InfluxConfig>,
+    #[serde(default)]
+    pub ingest: IngestConfig,
+    #[serde(default)]
+    pub scrape_proxy: Option<ScrapeProxyConfig>,
+    #[serde(default)]
+    pub action_queue: Option<ActionQueueConfig>,
+    #[serde(default)]
+    pub client: ClientConfig,
+    #[serde(default)]
+    pub collector: CollectorConfig,
+    #[serde(default)]
+    pub risk_weights: RiskWeights,
+    #[serde(default)]
+    pub degradation: DegradationWeights,
+    #[serde(default)]
+    pub suppressions: Vec<SuppressionRule>,
+    #[serde(default)]
+    pub metric_alerts: Vec<MetricAlert>,
+    #[serde(default)]
+    pub validator_ids: ValidatorIdsConfig,
+    #[serde(default)]
+    pub profiling: ProfilingConfig,
+    #[serde(default)]
+    pub groups: GroupsConfig,
+    #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
+    pub auto_undrain: Option<AutoUndrainConfig>,
+}
+
+/// How validator ids are normalized before they are compared. Surrounding
+/// whitespace is always trimmed.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ValidatorIdsConfig {
+    /// Also lowercase ids, for fleets whose tooling disagrees on casing.
+    #[serde(default)]
+    pub lowercase: bool,
+}
+
+impl ValidatorIdsConfig {
+    pub fn normalize(&self, id: &str) -> String {
+        let id = id.trim();
+        if self.lowercase {
+            id.to_lowercase()
+        } else {
+            id.to_string()
+        }
+    }
 }
 
+/// Treat an issue as informational on matching validators: it is still
+/// detected and reported, but no playbook is dispatched for it.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-#[serde(tag = "provider", rename_all = "snake_case")]
-pub enum AgenticConfig {
-    OpenAi(OpenAiAgentConfig),
+pub struct SuppressionRule {
+    /// Shown as the suppression reason; defaults to `suppressions[<index>]`.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub issue: IssueKind,
+    /// Only validators carrying this tag.
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Only this validator.
+    #[serde(default)]
+    pub validator: Option<String>,
+    /// Only while the sample satisfies this predicate.
+    #[serde(default)]
+    pub when: Option<MetricPredicate>,
 }
 
+/// `metric` lies strictly between `above` and `below`; either bound may be
+/// left out.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct OpenAiAgentConfig {
-    #[serde(default = "default_openai_model")]
-    pub model: String,
+pub struct MetricPredicate {
+    pub metric: String,
     #[serde(default)]
-    pub api_base: Option<String>,
+    pub above: Option<f64>,
     #[serde(default)]
-    pub system_prompt: Option<String>,
+    pub below: Option<f64>,
+}
+
+impl SuppressionRule {
+    pub fn matches(
+        &self,
+        validator: &ValidatorConfig,
+        issue: IssueKind,
+        metrics: &ValidatorMetrics,
+    ) -> bool {
+        self.issue == issue
+            && self
+                .tag
+                .as_ref()
+                .is_none_or(|tag| validator.tags.contains(tag))
+            && self
+                .validator
+                .as_ref()
+                .is_none_or(|id| *id == validator.id.0)
+            && self.when.as_ref().is_none_or(|when| when.holds(metrics))
+    }
+}
+
+impl MetricPredicate {
+    pub fn holds(&self, metrics: &ValidatorMetrics) -> bool {
+        metrics.field(&self.metric).is_some_and(|value| {
+            self.above.is_none_or(|above| value > above)
+                && self.below.is_none_or(|below| value < below)
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CollectorConfig {
+    /// Maintain cluster-wide aggregates in `validator:cluster:summary`.
     #[serde(default)]
-    pub temperature: Option<f32>,
+    pub rollups: bool,
+    /// Expiry of each `validator:metrics:<id>` key, refreshed on every
+    /// sample, so a validator that stops publishing drops out. `0` keeps the
+    /// key forever.
+    #[serde(default = "default_metrics_ttl_secs")]
+    pub ttl_secs: u64,
+    /// Seconds of samples kept in the `validator:metrics:history:<id>` sorted
+    /// set, scored by `last_updated`. `0` turns history off.
+    #[serde(default = "default_history_retention_secs")]
+    pub history_retention_secs: u64,
+}
+
+impl Default for CollectorConfig {
+    fn default() -> Self {
+        Self {
+            rollups: false,
+            ttl_secs: default_metrics_ttl_secs(),
+            history_retention_secs: default_history_retention_secs(),
+        }
+    }
+}
+
+fn default_metrics_ttl_secs() -> u64 {
+    300
+}
+
+fn default_history_retention_secs() -> u64 {
+    3600
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProfilingConfig {
+    /// Export `agent_loop_phase_seconds`, how long each agent loop phase
+    /// (snapshot, detection, planning, dispatch) takes.
     #[serde(default)]
-    pub api_key_env: Option<String>,
+    pub loop_timings: bool,
 }
 
-fn default_openai_model() -> String {
-    "gpt-4o-mini".to_string()
+/// Access to the agent's HTTP API.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ApiConfig {
+    /// Environment variable holding the bearer token `/api/*` and `/debug/*`
+    /// require. Unset or empty, the API is open.
+    #[serde(default = "default_api_token_env")]
+    pub token_env: String,
 }
 
-pub fn load_config() -> Result<Config> {
-    let settings = RawConfig::builder()
-        .add_source(config::File::with_name("config").required(true))
-        .add_source(config::Environment::with_prefix("VALIDATOR_COPILOT").separator("__"))
-        .build()?;
-    let cfg: Config = settings.try_deserialize()?;
-    Ok(cfg)
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            token_env: default_api_token_env(),
+        }
+    }
 }
 
-/// Compute a rough risk score for a validator. Higher means riskier.
-pub fn risk_score(metrics: &ValidatorMetrics) -> f64 {
-    let mut score = 0.0;
-    score += (metrics.slot_lag.max(0) as f64 / 100.0).min(1.0) * 0.25;
-    score += ((1.0 - metrics.vote_success_rate).max(0.0)).min(1.0) * 0.2;
-    score += metrics.cpu_usage.clamp(0.0, 1.0) * 0.15;
-    score += (metrics.disk_usage_pct / 100.0).clamp(0.0, 1.0) * 0.1;
-    score += (metrics.rpc_error_rate).clamp(0.0, 1.0) * 0.1;
-    score += (metrics.rpc_qps / 2000.0).min(1.0) * 0.1;
-    score += (metrics.ram_usage_gb / 128.0).min(1.0) * 0.1;
-    score.min(1.0)
+fn default_api_token_env() -> String {
+    "AGENT_API_TOKEN".into()
 }
 
-pub fn now_ts() -> i64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs() as i64
+/// How the agent's `GET /api/groups` groups validators.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GroupsConfig {
+    /// A key of each validator's `labels`, or `tag` to group by tag. A
+    /// validator with several tags counts in each of their groups.
+    #[serde(default = "default_group_by")]
+    pub by: String,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl Default for GroupsConfig {
+    fn default() -> Self {
+        Self {
+            by: default_group_by(),
+        }
+    }
+}
 
-    #[test]
-    fn risk_score_increases_with_slot_lag() {
-        let base = ValidatorMetrics {
-            slot_lag: 0,
-            vote_success_rate: 0.99,
-            cpu_usage: 0.2,
-            ram_usage_gb: 16.0,
-            disk_usage_pct: 40.0,
-            rpc_qps: 100.0,
-            rpc_error_rate: 0.001,
-            last_updated: 0,
-        };
-        let low = risk_score(&base);
-        let mut degraded = base.clone();
-        degraded.slot_lag = 200;
-        let high = risk_score(&degraded);
-        assert!(high > low);
-        assert!(high <= 1.0);
+fn default_group_by() -> String {
+    "region".into()
+}
+
+/// HTTP(S) proxy the validator client scrapes its exporter through.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScrapeProxyConfig {
+    /// e.g. `http://proxy.corp:3128`; used for both `http` and `https` exporters.
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Environment variable holding the proxy password, sent with `username`
+    /// as `Proxy-Authorization: Basic ...`.
+    #[serde(default)]
+    pub password_env: Option<String>,
+}
+
+/// `[client]`: lets one validator client process serve several validators.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ClientConfig {
+    /// Ids from `[[validators]]` to serve, each with its own action stream
+    /// and publish loop. Empty serves the single validator named by the
+    /// client's `VALIDATOR_ID`.
+    #[serde(default)]
+    pub validators: Vec<String>,
+    /// Exporter scrapes allowed in flight at once across those validators.
+    #[serde(default = "default_scrape_concurrency")]
+    pub scrape_concurrency: usize,
+    #[serde(default)]
+    pub metrics: MetricMapping,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            validators: Vec::new(),
+            scrape_concurrency: default_scrape_concurrency(),
+            metrics: MetricMapping::default(),
+        }
     }
+}
 
-    #[test]
-    fn validator_metrics_serde_roundtrip() {
-        let metrics = ValidatorMetrics {
-            slot_lag: 10,
-            vote_success_rate: 0.95,
-            cpu_usage: 0.5,
-            ram_usage_gb: 32.0,
-            disk_usage_pct: 55.0,
-            rpc_qps: 500.0,
-            rpc_error_rate: 0.01,
-            last_updated: 123456,
-        };
-        let json = serde_json::to_string(&metrics).expect("serialize");
-        let back: ValidatorMetrics = serde_json::from_str(&json).expect("deserialize");
-        assert_eq!(metrics, back);
+fn default_scrape_concurrency() -> usize {
+    4
+}
+
+/// `[client.metrics]`: which exporter series the validator client reads each
+/// sample field from.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MetricMapping {
+    /// Series per field name (see [`METRIC_FIELDS`]): a metric name with an
+    /// optional label selector, e.g. `solana_rpc_latency_seconds{quantile="0.99"}`.
+    /// Unlisted fields read `validator_<field>`.
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+    /// Fail the scrape when a field's series is missing. Off, the field reads
+    /// its [`MetricMapping::neutral`] value instead.
+    #[serde(default = "default_strict_metrics")]
+    pub strict: bool,
+}
+
+impl Default for MetricMapping {
+    fn default() -> Self {
+        Self {
+            fields: BTreeMap::new(),
+            strict: default_strict_metrics(),
+        }
+    }
+}
+
+fn default_strict_metrics() -> bool {
+    true
+}
+
+impl MetricMapping {
+    /// The series selector `field` is read from.
+    pub fn selector(&self, field: &str) -> String {
+        self.fields
+            .get(field)
+            .cloned()
+            .unwrap_or_else(|| format!("validator_{field}"))
+    }
+
+    /// A value for `field` that raises no issue: a fully voting validator
+    /// with no lag and no load.
+    pub fn neutral(field: &str) -> f64 {
+        match field {
+            "vote_success_rate" => 1.0,
+            _ => 0.0,
+        }
+    }
+
+    fn check(&self) -> Result<()> {
+        for (field, selector) in &self.fields {
+            if !METRIC_FIELDS.contains(&field.as_str()) {
+                bail!("[client.metrics.fields] unknown field {field}");
+            }
+            if selector.trim().is_empty() {
+                bail!("[client.metrics.fields] {field} must name a metric");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ClientConfig {
+    /// The `[[validators]]` entries named in `validators`, in order.
+    pub fn resolve<'a>(
+        &self,
+        validators: &'a [ValidatorConfig],
+    ) -> Result<Vec<&'a ValidatorConfig>> {
+        if self.scrape_concurrency == 0 {
+            bail!("[client] scrape_concurrency must be positive");
+        }
+        self.metrics.check()?;
+        self.validators
+            .iter()
+            .map(|id| {
+                validators
+                    .iter()
+                    .find(|validator| validator.id.0 == *id)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("[client] validator {id} is not listed in [[validators]]")
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Optional InfluxDB sink the metrics collector writes to alongside Redis.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InfluxConfig {
+    /// Full write endpoint including `precision=s`, e.g.
+    /// `http://influx:8086/api/v2/write?org=ops&bucket=validators&precision=s`.
+    pub write_url: String,
+    /// Environment variable holding the API token, sent as `Authorization: Token ...`.
+    #[serde(default)]
+    pub token_env: Option<String>,
+    #[serde(default = "default_influx_batch_size")]
+    pub batch_size: usize,
+    #[serde(default = "default_influx_flush_interval_secs")]
+    pub flush_interval_secs: u64,
+}
+
+/// `[action_queue]`: the agent publishes each action envelope as JSON to a
+/// NATS subject instead of submitting it to the executor daemon.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ActionQueueConfig {
+    /// e.g. `nats://nats.corp:4222`, or `tls://` to require TLS.
+    pub url: String,
+    pub subject: String,
+    /// Environment variable holding the NATS auth token, if the server wants one.
+    #[serde(default)]
+    pub token_env: Option<String>,
+    /// CA bundle the NATS server's certificate is verified against, instead
+    /// of the system roots. Setting it requires TLS.
+    #[serde(default)]
+    pub ca_path: Option<String>,
+}
+
+/// `[auto_undrain]`: the agent re-enables a validator disabled on the daemon
+/// once its fresh metrics have scored below `max_risk` for `healthy_secs`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AutoUndrainConfig {
+    #[serde(default = "default_undrain_max_risk")]
+    pub max_risk: f64,
+    #[serde(default = "default_undrain_healthy_secs")]
+    pub healthy_secs: u64,
+}
+
+impl Default for AutoUndrainConfig {
+    fn default() -> Self {
+        Self {
+            max_risk: default_undrain_max_risk(),
+            healthy_secs: default_undrain_healthy_secs(),
+        }
+    }
+}
+
+fn default_undrain_max_risk() -> f64 {
+    0.25
+}
+
+fn default_undrain_healthy_secs() -> u64 {
+    600
+}
+
+fn default_influx_batch_size() -> usize {
+    100
+}
+
+fn default_influx_flush_interval_secs() -> u64 {
+    5
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DetectionConfig {
+    /// Vote success rate below which a sustained dip is a `VoteFailureSpike`.
+    #[serde(default = "default_vote_rate_threshold")]
+    pub vote_rate_threshold: f64,
+    /// A single sample below this fires immediately, without waiting for the window.
+    #[serde(default = "default_vote_rate_floor")]
+    pub vote_rate_floor: f64,
+    /// How long the rate must stay below the threshold. `0` fires on one sample.
+    #[serde(default = "default_vote_window_secs")]
+    pub vote_window_secs: i64,
+    /// Vote failures are ignored in samples taken within this long after a
+    /// `restart_validator` completed, while the validator catches up. `0`
+    /// disables the grace.
+    #[serde(default = "default_restart_vote_grace_secs")]
+    pub restart_vote_grace_secs: i64,
+    /// `RpcOverload` needs qps above this for every sample in the window...
+    #[serde(default = "default_rpc_qps_threshold")]
+    pub rpc_qps_threshold: f64,
+    /// ...and the window's mean error rate above this.
+    #[serde(default = "default_rpc_error_rate_threshold")]
+    pub rpc_error_rate_threshold: f64,
+    /// Length of the RPC window. `0` judges a single sample.
+    #[serde(default = "default_rpc_window_secs")]
+    pub rpc_window_secs: i64,
+    /// Per-field EMA smoothing factors in `(0, 1]`, applied before detection
+    /// and scoring. Fields not listed are used raw.
+    #[serde(default)]
+    pub smoothing: HashMap<String, f64>,
+    #[serde(default)]
+    pub thresholds: DetectionThresholds,
+    /// Scripted `<condition> => <issue_kind>` rules, evaluated alongside the
+    /// built-in ones. See [`DetectionRule`].
+    #[serde(default)]
+    pub rules: Vec<DetectionRule>,
+    /// Skip evaluating a validator whose last evaluation found no issue while
+    /// its sample values and vote / RPC windows are unchanged since.
+    #[serde(default)]
+    pub skip_unchanged: bool,
+}
+
+impl Default for DetectionConfig {
+    fn default() -> Self {
+        Self {
+            vote_rate_threshold: default_vote_rate_threshold(),
+            vote_rate_floor: default_vote_rate_floor(),
+            vote_window_secs: default_vote_window_secs(),
+            restart_vote_grace_secs: default_restart_vote_grace_secs(),
+            rpc_qps_threshold: default_rpc_qps_threshold(),
+            rpc_error_rate_threshold: default_rpc_error_rate_threshold(),
+            rpc_window_secs: default_rpc_window_secs(),
+            smoothing: HashMap::new(),
+            thresholds: DetectionThresholds::default(),
+            rules: Vec::new(),
+            skip_unchanged: false,
+        }
+    }
+}
+
+/// `[detection.thresholds]`: the single-sample built-in rules. A sample
+/// above a threshold raises its issue.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct DetectionThresholds {
+    /// Slots behind the cluster tip for `SlotLagHigh`.
+    #[serde(default = "default_slot_lag_threshold")]
+    pub slot_lag: i64,
+    /// Fraction in `(0, 1]` for `HardwareOverload`...
+    #[serde(default = "default_cpu_usage_threshold")]
+    pub cpu_usage: f64,
+    /// ...or resident memory in GB.
+    #[serde(default = "default_ram_usage_gb_threshold")]
+    pub ram_usage_gb: f64,
+    /// Percent in `(0, 100]` for `DiskAlmostFull`.
+    #[serde(default = "default_disk_usage_pct_threshold")]
+    pub disk_usage_pct: f64,
+}
+
+impl Default for DetectionThresholds {
+    fn default() -> Self {
+        Self {
+            slot_lag: default_slot_lag_threshold(),
+            cpu_usage: default_cpu_usage_threshold(),
+            ram_usage_gb: default_ram_usage_gb_threshold(),
+            disk_usage_pct: default_disk_usage_pct_threshold(),
+        }
+    }
+}
+
+fn default_slot_lag_threshold() -> i64 {
+    50
+}
+
+fn default_cpu_usage_threshold() -> f64 {
+    0.9
+}
+
+/// 90% of a 128 GB host.
+fn default_ram_usage_gb_threshold() -> f64 {
+    115.2
+}
+
+fn default_disk_usage_pct_threshold() -> f64 {
+    90.0
+}
+
+fn default_vote_rate_threshold() -> f64 {
+    0.8
+}
+
+fn default_vote_rate_floor() -> f64 {
+    0.5
+}
+
+fn default_vote_window_secs() -> i64 {
+    60
+}
+
+fn default_restart_vote_grace_secs() -> i64 {
+    120
+}
+
+fn default_rpc_qps_threshold() -> f64 {
+    1000.0
+}
+
+fn default_rpc_error_rate_threshold() -> f64 {
+    0.05
+}
+
+fn default_rpc_window_secs() -> i64 {
+    30
+}
+
+/// What the agent does with a `metrics_json` payload it cannot decode.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DecodeErrorPolicy {
+    /// Log and discard; the last good sample keeps serving.
+    #[default]
+    Drop,
+    /// Discard, and once failures persist mark the validator stale right away
+    /// instead of waiting for the last good sample to age out.
+    CountStale,
+    /// Once failures persist, report the validator as `bad_data` with the parse
+    /// error and stop remediating it until a good sample arrives.
+    BadData,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IngestConfig {
+    #[serde(default)]
+    pub on_decode_error: DecodeErrorPolicy,
+    /// Consecutive decode failures before `count_stale` / `bad_data` apply.
+    #[serde(default = "default_decode_failure_threshold")]
+    pub decode_failure_threshold: u32,
+    /// How long after start the agent may go without any sample before it
+    /// warns and `/readyz` reports not ready.
+    #[serde(default = "default_warmup_grace_secs")]
+    pub warmup_grace_secs: u64,
+    /// Agent loop tick interval until the first sample arrives; 0 keeps the
+    /// normal interval throughout.
+    #[serde(default)]
+    pub warmup_tick_secs: u64,
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self {
+            on_decode_error: DecodeErrorPolicy::default(),
+            decode_failure_threshold: default_decode_failure_threshold(),
+            warmup_grace_secs: default_warmup_grace_secs(),
+            warmup_tick_secs: 0,
+        }
+    }
+}
+
+fn default_warmup_grace_secs() -> u64 {
+    60
+}
+
+fn default_decode_failure_threshold() -> u32 {
+    3
+}
+
+/// Settings for the executor daemon's control plane.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DaemonConfig {
+    /// Samples retained per validator for `after_seq` and `backfill_since`
+    /// replays.
+    #[serde(default = "default_metrics_history_len")]
+    pub metrics_history_len: usize,
+    /// Reported action results kept for `ListActionHistory`, oldest dropped first.
+    #[serde(default = "default_action_history_len")]
+    pub action_history_len: usize,
+    /// How long a playbook run stays in `ListPlaybookExecutions` after its
+    /// last step update.
+    #[serde(default = "default_playbook_retention_secs")]
+    pub playbook_retention_secs: i64,
+    /// How long a submitted action's idempotency key suppresses resubmissions.
+    #[serde(default = "default_idempotency_window_secs")]
+    pub idempotency_window_secs: i64,
+    /// Queued actions undelivered for longer than this are expired, not sent.
+    /// `0` keeps them until delivery.
+    #[serde(default = "default_action_ttl_secs")]
+    pub action_ttl_secs: i64,
+    /// A delivered action with an idempotency key and no `ReportResult` after
+    /// this long, whose validator client has since disconnected, is
+    /// redelivered once, then dropped. `0` waits indefinitely. Must exceed
+    /// the longest command timeout plus the recovery check window.
+    #[serde(default)]
+    pub ack_timeout_secs: i64,
+    /// `PublishMetrics` samples from one validator closer together than this
+    /// are dropped, though the call succeeds. `0` accepts every sample.
+    #[serde(default = "default_min_publish_interval_ms")]
+    pub min_publish_interval_ms: u64,
+    #[serde(default)]
+    pub result_webhook: Option<ResultWebhookConfig>,
+    /// Serve gRPC over TLS, optionally requiring validator client certificates.
+    #[serde(default)]
+    pub tls: Option<DaemonTlsConfig>,
+    /// How the agent and metrics collector receive metrics from the daemon.
+    #[serde(default)]
+    pub metrics_transport: MetricsTransport,
+    /// Seconds between `GetAllMetrics` calls with `metrics_transport = "poll"`.
+    #[serde(default = "default_metrics_poll_interval_secs")]
+    pub metrics_poll_interval_secs: u64,
+    /// Let validator clients missing from `[[validators]]` register themselves.
+    #[serde(default)]
+    pub registration: Option<RegistrationConfig>,
+    /// Concurrent `StreamActions` clients accepted; `0` is unlimited.
+    #[serde(default)]
+    pub max_action_clients: usize,
+    /// Concurrent `SubscribeMetrics` streams accepted; `0` is unlimited.
+    #[serde(default)]
+    pub max_metrics_subscribers: usize,
+    /// Live metrics updates buffered for subscribers. One that falls further
+    /// behind skips the oldest and is counted as lagged.
+    #[serde(default = "default_metrics_broadcast_capacity")]
+    pub metrics_broadcast_capacity: usize,
+    #[serde(default)]
+    pub handler_timeouts: HandlerTimeoutsConfig,
+    /// What `SubmitAction` does when the envelope's `validator_id` differs
+    /// from the validator the action names.
+    #[serde(default)]
+    pub on_validator_mismatch: ValidatorMismatchPolicy,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidatorMismatchPolicy {
+    /// Fail the call with `INVALID_ARGUMENT`.
+    #[default]
+    Reject,
+    /// Queue the action for the validator it names, logging a warning.
+    UseAction,
+}
+
+/// gRPC methods served by the executor daemon, as named in
+/// `[daemon.handler_timeouts.methods]`.
+pub const DAEMON_METHODS: &[&str] = &[
+    "stream_actions",
+    "report_result",
+    "publish_metrics",
+    "subscribe_metrics",
+    "get_all_metrics",
+    "submit_action",
+    "disable_validator",
+    "enable_validator",
+    "scrape_now",
+    "register_validator",
+    "list_validator_states",
+    "list_action_history",
+    "list_pending_actions",
+    "list_playbook_executions",
+];
+
+/// `[daemon.handler_timeouts]`: how long a daemon handler may run before the
+/// call fails with `DEADLINE_EXCEEDED`. For the streaming methods this bounds
+/// opening the stream, not its lifetime. `0` disables a timeout.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HandlerTimeoutsConfig {
+    #[serde(default = "default_handler_timeout_ms")]
+    pub default_ms: u64,
+    /// Per-method overrides of `default_ms`, keyed by [`DAEMON_METHODS`] name.
+    #[serde(default)]
+    pub methods: BTreeMap<String, u64>,
+}
+
+impl Default for HandlerTimeoutsConfig {
+    fn default() -> Self {
+        Self {
+            default_ms: default_handler_timeout_ms(),
+            methods: BTreeMap::new(),
+        }
+    }
+}
+
+impl HandlerTimeoutsConfig {
+    pub fn for_method(&self, method: &str) -> Option<Duration> {
+        let ms = self.methods.get(method).copied().unwrap_or(self.default_ms);
+        (ms > 0).then(|| Duration::from_millis(ms))
+    }
+}
+
+fn default_handler_timeout_ms() -> u64 {
+    10_000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RegistrationConfig {
+    /// Environment variable holding the pre-shared bootstrap token.
+    pub bootstrap_token_env: String,
+    /// Keep registrations in Redis (`redis_url`) across daemon restarts.
+    #[serde(default = "default_persist_registrations")]
+    pub persist: bool,
+    /// Tags a registering client may claim; others are dropped. Empty
+    /// (default) ignores client-supplied tags, since tags select
+    /// `[[suppressions]]`.
+    #[serde(default)]
+    pub allowed_tags: Vec<String>,
+}
+
+fn default_persist_registrations() -> bool {
+    true
+}
+
+/// `stream` holds a `SubscribeMetrics` stream open; `poll` calls
+/// `GetAllMetrics` on an interval, for networks whose proxies kill long-lived
+/// streams.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricsTransport {
+    #[default]
+    Stream,
+    Poll,
+}
+
+/// PEM files for the daemon's TLS listener.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DaemonTlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+    /// CA that issued `cert_path`; the agent and metrics collector trust it
+    /// when connecting to the daemon.
+    #[serde(default)]
+    pub ca_path: Option<String>,
+    /// CA that issues validator client certificates. When set, every client
+    /// must present one whose DNS SAN equals the validator id it connects as,
+    /// or whose subject CN does if the certificate has no SAN.
+    #[serde(default)]
+    pub client_ca_path: Option<String>,
+    /// Keep checking `auth_token` as well. Turning this off makes the client
+    /// certificate the only credential, which requires `client_ca_path`.
+    #[serde(default = "default_token_auth")]
+    pub token_auth: bool,
+    /// A connection that has not completed its TLS handshake this long after
+    /// being accepted is dropped.
+    #[serde(default = "default_tls_handshake_timeout_secs")]
+    pub handshake_timeout_secs: u64,
+}
+
+fn default_token_auth() -> bool {
+    true
+}
+
+fn default_tls_handshake_timeout_secs() -> u64 {
+    10
+}
+
+/// External endpoint the daemon POSTs every reported action result to.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ResultWebhookConfig {
+    pub url: String,
+    /// Further attempts after a failed delivery before the result is dropped.
+    #[serde(default = "default_webhook_max_retries")]
+    pub max_retries: u32,
+    /// Results waiting for delivery; once full, new results are dropped.
+    #[serde(default = "default_webhook_queue_capacity")]
+    pub queue_capacity: usize,
+}
+
+fn default_webhook_max_retries() -> u32 {
+    3
+}
+
+fn default_webhook_queue_capacity() -> usize {
+    256
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            metrics_history_len: default_metrics_history_len(),
+            action_history_len: default_action_history_len(),
+            playbook_retention_secs: default_playbook_retention_secs(),
+            idempotency_window_secs: default_idempotency_window_secs(),
+            action_ttl_secs: default_action_ttl_secs(),
+            ack_timeout_secs: 0,
+            min_publish_interval_ms: default_min_publish_interval_ms(),
+            result_webhook: None,
+            tls: None,
+            metrics_transport: MetricsTransport::default(),
+            metrics_poll_interval_secs: default_metrics_poll_interval_secs(),
+            registration: None,
+            max_action_clients: 0,
+            max_metrics_subscribers: 0,
+            metrics_broadcast_capacity: default_metrics_broadcast_capacity(),
+            handler_timeouts: HandlerTimeoutsConfig::default(),
+            on_validator_mismatch: ValidatorMismatchPolicy::default(),
+        }
+    }
+}
+
+fn default_metrics_broadcast_capacity() -> usize {
+    256
+}
+
+fn default_metrics_history_len() -> usize {
+    120
+}
+
+fn default_action_history_len() -> usize {
+    1000
+}
+
+fn default_playbook_retention_secs() -> i64 {
+    600
+}
+
+fn default_metrics_poll_interval_secs() -> u64 {
+    5
+}
+
+fn default_idempotency_window_secs() -> i64 {
+    3600
+}
+
+fn default_action_ttl_secs() -> i64 {
+    900
+}
+
+fn default_min_publish_interval_ms() -> u64 {
+    1000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RemediationConfig {
+    /// Minimum seconds between two dispatches of the same issue's playbook on a validator.
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: i64,
+    /// Width of the time bucket in each action's idempotency key: dispatches
+    /// of the same playbook step within one bucket share a key.
+    #[serde(default = "default_idempotency_bucket_secs")]
+    pub idempotency_bucket_secs: i64,
+    /// Seconds an issue must persist, measured on sample timestamps, before it
+    /// is acted on. `0` acts on the first sample showing the issue.
+    #[serde(default)]
+    pub confirm_secs: i64,
+    /// Delivery priority overrides keyed by action kind; higher flushes first.
+    #[serde(default)]
+    pub priorities: HashMap<String, i32>,
+    /// Ceiling on a playbook's total run time on the validator client, counted
+    /// from its first step. Steps still running or queued past it are abandoned
+    /// and reported as timed out. `0` disables the limit.
+    #[serde(default)]
+    pub max_plan_secs: i64,
+    /// What to do when one dispatch contains mutually exclusive actions.
+    #[serde(default)]
+    pub conflict_policy: ConflictPolicy,
+    /// Issues below this severity only alert; their playbooks are not run.
+    /// Unset acts on every issue.
+    #[serde(default)]
+    pub min_action_severity: Option<Severity>,
+    /// Issue severity overrides keyed by issue name, e.g. `disk_almost_full = "critical"`.
+    #[serde(default)]
+    pub severities: HashMap<String, Severity>,
+    /// Fleet-wide cap on validators undergoing disruptive actions at once.
+    #[serde(default)]
+    pub disruption: DisruptionLimit,
+    /// Confirm a slot-lag restart worked before re-enabling RPC. Unset
+    /// re-enables RPC as soon as the restart succeeds.
+    #[serde(default)]
+    pub recovery_check: Option<RecoveryCheckConfig>,
+}
+
+/// `[remediation.recovery_check]`: before the slot-lag playbook's
+/// `enable_rpc`, the validator client re-scrapes until `slot_lag` is back
+/// within `[detection.thresholds] slot_lag`. If it never is, RPC stays
+/// disabled and an alert is sent instead.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RecoveryCheckConfig {
+    /// Scrapes before giving up.
+    #[serde(default = "default_recovery_attempts")]
+    pub attempts: u32,
+    /// Seconds between scrapes, giving the validator time to catch up.
+    #[serde(default = "default_recovery_interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl Default for RecoveryCheckConfig {
+    fn default() -> Self {
+        Self {
+            attempts: default_recovery_attempts(),
+            interval_secs: default_recovery_interval_secs(),
+        }
+    }
+}
+
+fn default_recovery_attempts() -> u32 {
+    6
+}
+
+fn default_recovery_interval_secs() -> u64 {
+    10
+}
+
+/// `[remediation.disruption]`: how many validators may run a disruptive
+/// playbook at the same time. Playbooks past the limit are deferred.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DisruptionLimit {
+    /// Most validators disrupted at once.
+    #[serde(default)]
+    pub max_validators: Option<usize>,
+    /// Most validators disrupted at once as a percentage of `[[validators]]`,
+    /// rounded down but at least one. The lower of the two limits applies;
+    /// with neither set there is no limit.
+    #[serde(default)]
+    pub max_percent: Option<u32>,
+    /// Action kinds that make a playbook disruptive.
+    #[serde(default = "default_disruptive_kinds")]
+    pub kinds: Vec<String>,
+    /// How long a dispatched disruptive playbook holds its slot, unless the
+    /// validator is evaluated with no issue first.
+    #[serde(default = "default_disruption_hold_secs")]
+    pub hold_secs: i64,
+}
+
+impl Default for DisruptionLimit {
+    fn default() -> Self {
+        Self {
+            max_validators: None,
+            max_percent: None,
+            kinds: default_disruptive_kinds(),
+            hold_secs: default_disruption_hold_secs(),
+        }
+    }
+}
+
+fn default_disruptive_kinds() -> Vec<String> {
+    vec!["restart_validator".into()]
+}
+
+fn default_disruption_hold_secs() -> i64 {
+    600
+}
+
+impl DisruptionLimit {
+    /// Validators that may be disrupted at once in a fleet of `fleet`.
+    pub fn limit(&self, fleet: usize) -> Option<usize> {
+        let by_percent = self
+            .max_percent
+            .map(|percent| (fleet * percent as usize / 100).max(1));
+        match (self.max_validators, by_percent) {
+            (Some(max), Some(by_percent)) => Some(max.min(by_percent)),
+            (max, by_percent) => max.or(by_percent),
+        }
+    }
+
+    pub fn is_disruptive(&self, action: &Action) -> bool {
+        self.kinds.iter().any(|kind| kind == action.kind())
+    }
+}
+
+/// Resolution for mutually exclusive steps (e.g. `disable_rpc` and
+/// `enable_rpc`) in one dispatch that are not sequenced by `depends_on`.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Drop the later step.
+    KeepFirst,
+    /// Drop both steps.
+    DropBoth,
+    /// Keep both, making the later step depend on the earlier one. The
+    /// default, since agentic plans list steps in the order they mean
+    /// them to run without a `depends_on`.
+    #[default]
+    Sequence,
+}
+
+impl RemediationConfig {
+    pub fn severity_for(&self, issue: IssueKind) -> Severity {
+        self.severities
+            .get(issue.as_str())
+            .copied()
+            .unwrap_or_else(|| issue.default_severity())
+    }
+
+    /// Whether `issue` is severe enough to run more than alerts.
+    pub fn acts_on(&self, issue: IssueKind) -> bool {
+        self.min_action_severity
+            .is_none_or(|min| self.severity_for(issue) >= min)
+    }
+
+    pub fn priority_for(&self, action: &Action) -> i32 {
+        self.priorities
+            .get(action.kind())
+            .copied()
+            .unwrap_or_else(|| action.default_priority())
+    }
+}
+
+impl Default for RemediationConfig {
+    fn default() -> Self {
+        Self {
+            cooldown_secs: default_cooldown_secs(),
+            idempotency_bucket_secs: default_idempotency_bucket_secs(),
+            confirm_secs: 0,
+            priorities: HashMap::new(),
+            max_plan_secs: 0,
+            conflict_policy: ConflictPolicy::default(),
+            min_action_severity: None,
+            severities: HashMap::new(),
+            disruption: DisruptionLimit::default(),
+            recovery_check: None,
+        }
+    }
+}
+
+fn default_cooldown_secs() -> i64 {
+    300
+}
+
+fn default_idempotency_bucket_secs() -> i64 {
+    300
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ActionCommands {
+    /// Operator-defined commands addressable by `Action::ExecCommand`. Only ids
+    /// registered here can be executed or proposed by the agentic planner.
+    #[serde(default)]
+    pub custom: HashMap<String, String>,
+    /// Guard commands keyed by action kind (e.g. `restart_validator`), run on
+    /// the validator before the action itself. A non-zero exit aborts it.
+    #[serde(default)]
+    pub preflight: HashMap<String, String>,
+    /// Named, parameterized maintenance operations run by
+    /// `Action::RunMaintenanceScript`.
+    #[serde(default)]
+    pub scripts: HashMap<String, MaintenanceScript>,
+    /// Shell command templates keyed by action kind, e.g. `restart_validator
+    /// = "systemctl restart {{validator}}"`, run instead of the built-in echo
+    /// stubs. A soft restart uses `restart_validator_soft` instead, or the
+    /// `restart_validator` template without one. See
+    /// [`action_placeholders`] for what each kind can use.
+    #[serde(default)]
+    pub actions: HashMap<String, String>,
+    /// Seconds an action's command (and its pre-flight guard) may run before
+    /// it is killed and the action fails.
+    #[serde(default = "default_command_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Overrides of `timeout_secs` keyed by action kind, e.g.
+    /// `restart_validator = 300`.
+    #[serde(default)]
+    pub timeouts: HashMap<String, u64>,
+    /// Log each action's rendered command and report success without running
+    /// it. The validator client also turns this on with `EXECUTOR_DRY_RUN`.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl Default for ActionCommands {
+    fn default() -> Self {
+        Self {
+            custom: HashMap::new(),
+            preflight: HashMap::new(),
+            scripts: HashMap::new(),
+            actions: HashMap::new(),
+            timeout_secs: default_command_timeout_secs(),
+            timeouts: HashMap::new(),
+            dry_run: false,
+        }
+    }
+}
+
+fn default_command_timeout_secs() -> u64 {
+    30
+}
+
+/// A command template such as `cleanup-logs --older-than {{older_than}}`.
+/// Every `{{placeholder}}` must be declared in `params`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MaintenanceScript {
+    pub command: String,
+    #[serde(default)]
+    pub params: BTreeMap<String, ScriptParam>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ScriptParam {
+    /// Used when the action leaves the parameter out; without one it is required.
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+impl MaintenanceScript {
+    /// Substitute `params` (falling back to declared defaults) into the
+    /// template. Values are shell-quoted.
+    pub fn render(&self, name: &str, params: &HashMap<String, String>) -> Result<String> {
+        if let Some(unknown) = params.keys().find(|key| !self.params.contains_key(*key)) {
+            bail!("script {name} has no parameter {unknown}");
+        }
+        let mut values = HashMap::with_capacity(self.params.len());
+        for (param, spec) in &self.params {
+            let Some(value) = params.get(param).or(spec.default.as_ref()) else {
+                bail!("script {name} requires parameter {param}");
+            };
+            values.insert(param.as_str(), value.as_str());
+        }
+        Ok(render_template(&self.command, |name| {
+            values.get(name).copied()
+        }))
+    }
+
+    fn placeholders(&self) -> impl Iterator<Item = &str> {
+        placeholders(&self.command)
+    }
+}
+
+fn placeholders(template: &str) -> impl Iterator<Item = &str> {
+    template
+        .split("{{")
+        .skip(1)
+        .filter_map(|rest| rest.split_once("}}").map(|(name, _)| name.trim()))
+}
+
+/// Replace each `{{ name }}` in `template` with its value from `lookup`,
+/// shell-quoted, in one left-to-right pass: a value that itself contains a
+/// placeholder is inserted as is, never expanded. Names are trimmed like
+/// [`placeholders`] does; unknown ones are left in place.
+fn render_template<'a>(template: &str, lookup: impl Fn(&str) -> Option<&'a str>) -> String {
+    let mut command = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + len + 2;
+        command.push_str(&rest[..start]);
+        match lookup(rest[start + 2..end - 2].trim()) {
+            Some(value) => command.push_str(&shell_quote(value)),
+            None => command.push_str(&rest[start..end]),
+        }
+        rest = &rest[end..];
+    }
+    command.push_str(rest);
+    command
+}
+
+/// `value` as one single-quoted `sh` word.
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// The placeholders a `[commands.actions]` template for `kind` may use, or
+/// `None` if actions of that kind are not templated. `exec_command` and
+/// library scripts already name their own commands.
+pub fn action_placeholders(kind: &str) -> Option<&'static [&'static str]> {
+    match kind {
+        "disable_rpc"
+        | "enable_rpc"
+        | "restart_validator"
+        | "restart_validator_soft"
+        | "throttle_rpc_client" => Some(&["validator"]),
+        "run_maintenance_script" => Some(&["validator", "script_name"]),
+        "send_alert" => Some(&["validator", "message"]),
+        _ => None,
+    }
+}
+
+impl ActionCommands {
+    /// The shell command for a maintenance script run. Library scripts are
+    /// rendered from their template; any other name runs as `sh <name>` and
+    /// takes no parameters.
+    pub fn script_command(
+        &self,
+        script_name: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<String> {
+        match self.scripts.get(script_name) {
+            Some(script) => script.render(script_name, params),
+            None if params.is_empty() => Ok(format!("sh {script_name}")),
+            None => {
+                bail!("script {script_name} is not in [commands.scripts] and takes no parameters")
+            }
+        }
+    }
+
+    /// The `[commands.actions]` command for `action`, with placeholders
+    /// substituted shell-quoted, if its kind has a template. A script in the
+    /// `[commands.scripts]` library keeps its own command.
+    pub fn action_command(&self, action: &Action) -> Option<String> {
+        // A soft restart without its own template runs the hard one rather
+        // than the stub, which would report a reload that never happened.
+        let template = self
+            .actions
+            .get(action.template_key())
+            .or_else(|| self.actions.get(action.kind()))?;
+        let values = match action {
+            Action::DisableRpc { validator }
+            | Action::EnableRpc { validator }
+            | Action::RestartValidator { validator, .. }
+            | Action::ThrottleRpcClient { validator } => vec![("validator", validator.0.as_str())],
+            Action::RunMaintenanceScript { script_name, .. }
+                if self.scripts.contains_key(script_name) =>
+            {
+                return None;
+            }
+            Action::RunMaintenanceScript {
+                validator,
+                script_name,
+                ..
+            } => vec![
+                ("validator", validator.0.as_str()),
+                ("script_name", script_name.as_str()),
+            ],
+            Action::SendAlert { validator, message } => vec![
+                ("validator", validator.0.as_str()),
+                ("message", message.as_str()),
+            ],
+            Action::ExecCommand { .. } => return None,
+        };
+        Some(render_template(template, |name| {
+            values
+                .iter()
+                .find(|(placeholder, _)| *placeholder == name)
+                .map(|(_, value)| *value)
+        }))
+    }
+
+    /// How long commands for an action of `kind` may run.
+    pub fn timeout_for(&self, kind: &str) -> Duration {
+        Duration::from_secs(
+            self.timeouts
+                .get(kind)
+                .copied()
+                .unwrap_or(self.timeout_secs),
+        )
+    }
+
+    /// Reject script and action templates using placeholders they cannot
+    /// fill, and zero timeouts.
+    pub fn validate(&self) -> Result<()> {
+        if self.timeout_secs == 0 {
+            bail!("[commands] timeout_secs must be positive");
+        }
+        if let Some((kind, _)) = self.timeouts.iter().find(|(_, secs)| **secs == 0) {
+            bail!("[commands.timeouts] {kind} must be positive");
+        }
+        for (name, script) in &self.scripts {
+            if let Some(undeclared) = script
+                .placeholders()
+                .find(|placeholder| !script.params.contains_key(*placeholder))
+            {
+                bail!("script {name} uses undeclared parameter {undeclared}");
+            }
+        }
+        for (kind, template) in &self.actions {
+            let Some(allowed) = action_placeholders(kind) else {
+                bail!("[commands.actions] {kind} is not an action kind that takes a template");
+            };
+            if let Some(unknown) =
+                placeholders(template).find(|placeholder| !allowed.contains(placeholder))
+            {
+                bail!("[commands.actions] {kind} uses unknown placeholder {unknown}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Registered custom command ids in a stable order.
+    pub fn command_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.custom.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StalenessConfig {
+    /// Metrics older than this many seconds are considered stale.
+    #[serde(default = "default_max_metric_age_secs")]
+    pub max_metric_age_secs: i64,
+    /// How long metrics must stay stale before a `went_stale` event is emitted.
+    #[serde(default = "default_stale_debounce_secs")]
+    pub debounce_secs: i64,
+    /// Per-field overrides of `max_metric_age_secs`, e.g. `disk_usage_pct = 600`.
+    #[serde(default)]
+    pub per_field: HashMap<String, i64>,
+    /// A client `last_updated` further than this from the daemon's receive
+    /// time is flagged as clock drift. `0` disables the check.
+    #[serde(default = "default_max_clock_drift_secs")]
+    pub max_clock_drift_secs: i64,
+    /// Judge freshness by the daemon's receive time rather than the client's
+    /// clock, so a skewed host clock cannot cause false staleness.
+    #[serde(default)]
+    pub use_receive_time: bool,
+}
+
+impl Default for StalenessConfig {
+    fn default() -> Self {
+        Self {
+            max_metric_age_secs: default_max_metric_age_secs(),
+            debounce_secs: default_stale_debounce_secs(),
+            per_field: HashMap::new(),
+            max_clock_drift_secs: default_max_clock_drift_secs(),
+            use_receive_time: false,
+        }
+    }
+}
+
+impl StalenessConfig {
+    /// Seconds the client clock lags the receiver (negative when it runs
+    /// ahead), if that exceeds `max_clock_drift_secs`.
+    pub fn clock_drift(&self, last_updated: i64, received_at: i64) -> Option<i64> {
+        let drift = received_at - last_updated;
+        (self.max_clock_drift_secs > 0 && drift.abs() > self.max_clock_drift_secs).then_some(drift)
+    }
+
+    pub fn max_age_for(&self, field: &str) -> i64 {
+        self.per_field
+            .get(field)
+            .copied()
+            .unwrap_or(self.max_metric_age_secs)
+    }
+
+    /// Seconds by which the most overdue field exceeds its allowed age.
+    /// Zero or negative means every field is fresh.
+    pub fn overdue_secs(&self, metrics: &ValidatorMetrics, now: i64) -> i64 {
+        METRIC_FIELDS
+            .iter()
+            .map(|field| now - metrics.field_updated_at(field) - self.max_age_for(field))
+            .max()
+            .unwrap_or(i64::MIN)
+    }
+
+    /// Fields older than their allowed age.
+    pub fn stale_fields(&self, metrics: &ValidatorMetrics, now: i64) -> Vec<&'static str> {
+        METRIC_FIELDS
+            .iter()
+            .copied()
+            .filter(|field| now - metrics.field_updated_at(field) > self.max_age_for(field))
+            .collect()
+    }
+}
+
+fn default_max_metric_age_secs() -> i64 {
+    60
+}
+
+fn default_stale_debounce_secs() -> i64 {
+    30
+}
+
+fn default_max_clock_drift_secs() -> i64 {
+    30
+}
+
+/// A notification on a raw metric, independent of issue detection and
+/// playbooks. Delivered to the sinks `[alerts.routes]` lists under `name`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MetricAlert {
+    pub name: String,
+    /// One of [`METRIC_FIELDS`].
+    pub field: String,
+    pub comparison: Comparison,
+    pub threshold: f64,
+    #[serde(default)]
+    pub severity: Severity,
+    /// Defaults to e.g. `disk_usage_pct 87 > 85`.
+    #[serde(default)]
+    pub message: Option<String>,
+    /// The issue this alert is an early warning of. Its notifications then
+    /// share a fingerprint with that issue's playbook alerts of the same
+    /// severity, so `[alerts] dedup_window_secs` collapses the two.
+    #[serde(default)]
+    pub issue: Option<IssueKind>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Comparison {
+    #[serde(rename = ">")]
+    Above,
+    #[serde(rename = ">=")]
+    AtLeast,
+    #[serde(rename = "<")]
+    Below,
+    #[serde(rename = "<=")]
+    AtMost,
+}
+
+impl Comparison {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Above => ">",
+            Self::AtLeast => ">=",
+            Self::Below => "<",
+            Self::AtMost => "<=",
+        }
+    }
+
+    pub fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Self::Above => value > threshold,
+            Self::AtLeast => value >= threshold,
+            Self::Below => value < threshold,
+            Self::AtMost => value <= threshold,
+        }
+    }
+}
+
+/// Of an alert or an issue; ordered from least to most severe.
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+impl MetricAlert {
+    /// The field's value when the sample breaches the threshold.
+    pub fn breached(&self, metrics: &ValidatorMetrics) -> Option<f64> {
+        metrics
+            .field(&self.field)
+            .filter(|value| self.comparison.holds(*value, self.threshold))
+    }
+
+    pub fn message_for(&self, value: f64) -> String {
+        self.message.clone().unwrap_or_else(|| {
+            format!(
+                "{} {value} {} {}",
+                self.field,
+                self.comparison.as_str(),
+                self.threshold
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AlertsConfig {
+    /// Alert message templates keyed by issue name (e.g. `slot_lag_high`).
+    /// Placeholders: `{{validator}}`, `{{host}}`, `{{issue}}`, `{{risk}}`, `{{message}}`.
+    #[serde(default)]
+    pub templates: HashMap<String, String>,
+    /// Named notification sinks alerts can be routed to.
+    #[serde(default)]
+    pub sinks: HashMap<String, SinkConfig>,
+    /// Sink names notified, all at once, for each issue name or
+    /// `[[metric_alerts]]` name.
+    #[serde(default)]
+    pub routes: HashMap<String, Vec<String>>,
+    /// A notification with the same fingerprint (validator, issue or alert
+    /// name, severity) as one delivered this many seconds ago is dropped,
+    /// whichever path raised it. `0` disables deduplication.
+    #[serde(default = "default_alert_dedup_window_secs")]
+    pub dedup_window_secs: u64,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            templates: HashMap::new(),
+            sinks: HashMap::new(),
+            routes: HashMap::new(),
+            dedup_window_secs: default_alert_dedup_window_secs(),
+        }
+    }
+}
+
+fn default_alert_dedup_window_secs() -> u64 {
+    300
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SinkConfig {
+    /// POSTs the alert as JSON to `url`.
+    Webhook { url: String },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MaintenanceConfig {
+    /// Scripts operators may trigger through the maintenance API.
+    #[serde(default)]
+    pub allowed_scripts: Vec<String>,
+}
+
+impl Config {
+    /// Whether `script` is allowlisted globally and for the given validator.
+    pub fn script_allowed(&self, validator: &ValidatorConfig, script: &str) -> bool {
+        if !self.maintenance.allowed_scripts.iter().any(|s| s == script) {
+            return false;
+        }
+        match &validator.allowed_scripts {
+            Some(scripts) => scripts.iter().any(|s| s == script),
+            None => true,
+        }
+    }
+
+    /// The first `[[suppressions]]` rule that makes `issue` informational for
+    /// `validator`, with its display name.
+    pub fn suppression_for(
+        &self,
+        validator: &ValidatorConfig,
+        issue: IssueKind,
+        metrics: &ValidatorMetrics,
+    ) -> Option<String> {
+        self.suppressions
+            .iter()
+            .enumerate()
+            .find(|(_, rule)| rule.matches(validator, issue, metrics))
+            .map(|(index, rule)| {
+                rule.name
+                    .clone()
+                    .unwrap_or_else(|| format!("suppressions[{index}]"))
+            })
+    }
+
+    /// Normalize configured validator ids, including those named by
+    /// `[[suppressions]]`, per `[validator_ids]`. Done at load time so every
+    /// later comparison sees the normalized form.
+    pub fn normalize_validator_ids(&mut self) {
+        let ids = self.validator_ids;
+        let configured = self
+            .validators
+            .iter_mut()
+            .map(|validator| &mut validator.id.0)
+            .chain(
+                self.suppressions
+                    .iter_mut()
+                    .filter_map(|rule| rule.validator.as_mut()),
+            )
+            .chain(self.client.validators.iter_mut());
+        for id in configured {
+            let normalized = ids.normalize(id);
+            if normalized != *id {
+                warn!(
+                    configured = id.as_str(),
+                    normalized, "normalized validator id"
+                );
+                *id = normalized;
+            }
+        }
+    }
+
+    /// Reject configs the services cannot run with.
+    pub fn validate(&self) -> Result<()> {
+        if self.validators.is_empty() && self.daemon.registration.is_none() {
+            bail!("config lists no validators and [daemon.registration] is unset");
+        }
+        self.validate_lenient()
+    }
+
+    /// Every check in [`Config::validate`] except requiring validators, for
+    /// tooling that reads global settings before any are provisioned.
+    pub fn validate_lenient(&self) -> Result<()> {
+        if self.redis_url.trim().is_empty() {
+            bail!("redis_url must not be empty");
+        }
+        let mut ids = HashSet::new();
+        for validator in &self.validators {
+            if validator.id.0.trim().is_empty() {
+                bail!("validator ids must not be empty");
+            }
+            if !ids.insert(&validator.id.0) {
+                bail!("validator {} is listed more than once", validator.id.0);
+            }
+        }
+        self.client.resolve(&self.validators)?;
+        if let Some(tls) = &self.daemon.tls {
+            if !tls.token_auth && tls.client_ca_path.is_none() {
+                bail!("[daemon.tls] token_auth = false requires client_ca_path");
+            }
+        }
+        if self.groups.by.trim().is_empty() {
+            bail!("[groups] by must name a label or be \"tag\"");
+        }
+        if self.daemon.action_history_len == 0 {
+            bail!("[daemon] action_history_len must be positive");
+        }
+        if self.daemon.playbook_retention_secs <= 0 {
+            bail!("[daemon] playbook_retention_secs must be positive");
+        }
+        if self.daemon.ack_timeout_secs < 0 {
+            bail!("[daemon] ack_timeout_secs must not be negative");
+        }
+        if self.daemon.metrics_broadcast_capacity == 0 {
+            bail!("[daemon] metrics_broadcast_capacity must be positive");
+        }
+        if self.daemon.metrics_transport == MetricsTransport::Poll
+            && self.daemon.metrics_poll_interval_secs == 0
+        {
+            bail!("[daemon] metrics_poll_interval_secs must be positive when polling");
+        }
+        let degradation = &self.degradation;
+        for (name, weight) in [
+            ("risk", degradation.risk),
+            ("info", degradation.info),
+            ("warning", degradation.warning),
+            ("critical", degradation.critical),
+        ] {
+            if !(0.0..=1.0).contains(&weight) {
+                bail!("[degradation] {name} must be between 0 and 1");
+            }
+        }
+        if let Some(undrain) = &self.auto_undrain {
+            if !(0.0..=1.0).contains(&undrain.max_risk) {
+                bail!("[auto_undrain] max_risk must be between 0 and 1");
+            }
+            if undrain.healthy_secs == 0 {
+                bail!("[auto_undrain] healthy_secs must be positive");
+            }
+        }
+        for method in self.daemon.handler_timeouts.methods.keys() {
+            if !DAEMON_METHODS.contains(&method.as_str()) {
+                bail!("[daemon.handler_timeouts] methods has unknown method {method}");
+            }
+        }
+        if let Some(queue) = &self.action_queue {
+            if !queue.url.starts_with("nats://") && !queue.url.starts_with("tls://") {
+                bail!("[action_queue] url must be a nats:// or tls:// URL");
+            }
+            if queue.subject.is_empty() || queue.subject.contains(char::is_whitespace) {
+                bail!("[action_queue] subject must be non-empty without whitespace");
+            }
+        }
+        let unknown_kind = |kinds: Option<&[String]>| {
+            kinds
+                .into_iter()
+                .flatten()
+                .find(|kind| !ACTION_KINDS.contains(&kind.as_str()))
+                .cloned()
+        };
+        if let Some(kind) = unknown_kind(
+            self.agentic
+                .as_ref()
+                .and_then(|agentic| agentic.settings().allowed_actions.as_deref()),
+        ) {
+            bail!("[agentic] allowed_actions has unknown action kind {kind}");
+        }
+        for validator in &self.validators {
+            if let Some(kind) = unknown_kind(validator.agentic_allowed_actions.as_deref()) {
+                bail!(
+                    "validator {} agentic_allowed_actions has unknown action kind {kind}",
+                    validator.id.0
+                );
+            }
+            if let Some(source) = validator.metrics_fallbacks.iter().find(|source| {
+                !["http://", "https://", "file://"]
+                    .iter()
+                    .any(|scheme| source.starts_with(scheme))
+            }) {
+                bail!(
+                    "validator {} metrics_fallbacks entry {source} must be an http(s):// URL or a file:// path",
+                    validator.id.0
+                );
+            }
+        }
+        if let Some(settings) = self.agentic.as_ref().map(AgenticConfig::settings) {
+            if settings.cache.ttl_secs < 0 {
+                bail!("[agentic.cache] ttl_secs must not be negative");
+            }
+            if settings.retry.max_attempts == 0 {
+                bail!("[agentic.retry] max_attempts must be at least 1");
+            }
+            if settings.request_timeout_secs == 0 {
+                bail!("[agentic] request_timeout_secs must be positive");
+            }
+        }
+        if let Some(AgenticConfig::OpenAiCompatible(local)) = &self.agentic {
+            if !local.api_base.starts_with("http://") && !local.api_base.starts_with("https://") {
+                bail!("[agentic] api_base must be an http:// or https:// URL");
+            }
+            if local.model.trim().is_empty() {
+                bail!("[agentic] model must not be empty");
+            }
+        }
+        let detection = &self.detection;
+        let thresholds = &detection.thresholds;
+        for (name, value, min_exclusive, max) in [
+            (
+                "vote_rate_threshold",
+                detection.vote_rate_threshold,
+                false,
+                1.0,
+            ),
+            ("vote_rate_floor", detection.vote_rate_floor, false, 1.0),
+            (
+                "rpc_error_rate_threshold",
+                detection.rpc_error_rate_threshold,
+                false,
+                1.0,
+            ),
+            ("thresholds.cpu_usage", thresholds.cpu_usage, true, 1.0),
+            (
+                "thresholds.disk_usage_pct",
+                thresholds.disk_usage_pct,
+                true,
+                100.0,
+            ),
+        ] {
+            let above_min = if min_exclusive {
+                value > 0.0
+            } else {
+                value >= 0.0
+            };
+            if !above_min || value > max {
+                let open = if min_exclusive { '(' } else { '[' };
+                bail!("[detection] {name} must be in {open}0, {max}], got {value}");
+            }
+        }
+        if thresholds.slot_lag < 0 {
+            bail!(
+                "[detection] thresholds.slot_lag must not be negative, got {}",
+                thresholds.slot_lag
+            );
+        }
+        if thresholds.ram_usage_gb.is_nan() || thresholds.ram_usage_gb <= 0.0 {
+            bail!(
+                "[detection] thresholds.ram_usage_gb must be positive, got {}",
+                thresholds.ram_usage_gb
+            );
+        }
+        if detection.rpc_qps_threshold.is_nan() || detection.rpc_qps_threshold < 0.0 {
+            bail!(
+                "[detection] rpc_qps_threshold must not be negative, got {}",
+                detection.rpc_qps_threshold
+            );
+        }
+        self.commands.validate()?;
+        if self
+            .remediation
+            .recovery_check
+            .as_ref()
+            .is_some_and(|check| check.attempts == 0)
+        {
+            bail!("[remediation.recovery_check] attempts must be positive");
+        }
+        if self.daemon.ack_timeout_secs > 0 {
+            let longest_command = self
+                .commands
+                .timeouts
+                .values()
+                .fold(self.commands.timeout_secs, |longest, secs| {
+                    longest.max(*secs)
+                });
+            let recovery_window = self
+                .remediation
+                .recovery_check
+                .as_ref()
+                .map_or(0, |check| u64::from(check.attempts) * check.interval_secs);
+            let needed = longest_command + recovery_window;
+            if self.daemon.ack_timeout_secs as u64 <= needed {
+                bail!(
+                    "[daemon] ack_timeout_secs must exceed the longest command timeout plus the recovery check window ({needed}s), got {}",
+                    self.daemon.ack_timeout_secs
+                );
+            }
+        }
+        if self.remediation.idempotency_bucket_secs <= 0 {
+            bail!(
+                "[remediation] idempotency_bucket_secs must be positive, got {}",
+                self.remediation.idempotency_bucket_secs
+            );
+        }
+        let disruption = &self.remediation.disruption;
+        if let Some(percent) = disruption.max_percent {
+            if percent == 0 || percent > 100 {
+                bail!("[remediation.disruption] max_percent must be in (0, 100], got {percent}");
+            }
+        }
+        if disruption.max_validators == Some(0) {
+            bail!("[remediation.disruption] max_validators must be positive");
+        }
+        if disruption.hold_secs <= 0 {
+            bail!(
+                "[remediation.disruption] hold_secs must be positive, got {}",
+                disruption.hold_secs
+            );
+        }
+        for alert in &self.metric_alerts {
+            if !METRIC_FIELDS.contains(&alert.field.as_str()) {
+                bail!(
+                    "metric alert {} watches unknown metric {}",
+                    alert.name,
+                    alert.field
+                );
+            }
+        }
+        for (index, rule) in self.suppressions.iter().enumerate() {
+            if let Some(when) = &rule.when {
+                if !METRIC_FIELDS.contains(&when.metric.as_str()) {
+                    bail!(
+                        "suppressions[{index}] matches on unknown metric {}",
+                        when.metric
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum AgenticConfig {
+    /// Written as `open_ai`, the spelling configs have always serialized
+    /// with; `openai` is accepted too.
+    #[serde(rename = "open_ai", alias = "openai")]
+    OpenAi(OpenAiAgentConfig),
+    Anthropic(AnthropicAgentConfig),
+    #[serde(rename = "openai_compatible", alias = "ollama")]
+    OpenAiCompatible(OpenAiCompatibleAgentConfig),
+}
+
+impl AgenticConfig {
+    pub fn settings(&self) -> &AgenticSettings {
+        match self {
+            AgenticConfig::OpenAi(cfg) => &cfg.settings,
+            AgenticConfig::Anthropic(cfg) => &cfg.settings,
+            AgenticConfig::OpenAiCompatible(cfg) => &cfg.settings,
+        }
+    }
+}
+
+/// Recent history added to each agentic prompt. Both default to 0 (off);
+/// every sample or result included costs prompt tokens.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PromptContextConfig {
+    /// Most recent raw metric samples received for the validator.
+    #[serde(default)]
+    pub metric_samples: usize,
+    /// Most recent action results the daemon recorded for the validator.
+    #[serde(default)]
+    pub recent_actions: usize,
+}
+
+/// `[agentic.cache]`: how long a plan is reused for the same validator,
+/// issue and coarsely bucketed sample instead of asking the model again.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DecisionCacheConfig {
+    /// `0` disables the cache.
+    #[serde(default = "default_decision_cache_ttl_secs")]
+    pub ttl_secs: i64,
+    /// Decisions kept at most; the oldest are evicted first.
+    #[serde(default = "default_decision_cache_max_entries")]
+    pub max_entries: usize,
+}
+
+fn default_decision_cache_ttl_secs() -> i64 {
+    120
+}
+
+fn default_decision_cache_max_entries() -> usize {
+    1024
+}
+
+impl Default for DecisionCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: default_decision_cache_ttl_secs(),
+            max_entries: default_decision_cache_max_entries(),
+        }
+    }
+}
+
+/// Token caps for agentic planning. Once a window's cap is reached the agent
+/// falls back to static playbooks until that window resets.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TokenBudget {
+    #[serde(default)]
+    pub hourly_tokens: Option<u64>,
+    #[serde(default)]
+    pub daily_tokens: Option<u64>,
+}
+
+/// Retries of a failed chat completion. Only rate limits, server errors and
+/// connection failures are retried; the delay doubles after each attempt.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CompletionRetry {
+    /// Attempts in total, including the first.
+    #[serde(default = "default_completion_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_completion_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+}
+
+fn default_completion_max_attempts() -> u32 {
+    3
+}
+
+fn default_completion_initial_backoff_ms() -> u64 {
+    500
+}
+
+impl Default for CompletionRetry {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_completion_max_attempts(),
+            initial_backoff_ms: default_completion_initial_backoff_ms(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AgenticMode {
+    /// Agentic plans are dispatched; static playbooks are the fallback.
+    #[default]
+    Active,
+    /// Static playbooks are dispatched; agentic plans are only recorded for comparison.
+    Shadow,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OpenAiAgentConfig {
+    #[serde(default = "default_openai_model")]
+    pub model: String,
+    #[serde(default)]
+    pub api_base: Option<String>,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// Recorded with every agentic plan in the decision log. Defaults to the
+    /// built-in prompt's version, or `custom` when `system_prompt` is set.
+    #[serde(default)]
+    pub prompt_version: Option<String>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    #[serde(flatten)]
+    pub settings: AgenticSettings,
+}
+
+/// How the agent uses a provider's plans, whichever provider it is. Read
+/// from the same `[agentic]` block as the provider's own fields.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AgenticSettings {
+    #[serde(default)]
+    pub mode: AgenticMode,
+    #[serde(default)]
+    pub budget: TokenBudget,
+    #[serde(default)]
+    pub context: PromptContextConfig,
+    #[serde(default)]
+    pub cache: DecisionCacheConfig,
+    /// Discard the whole plan when the model names an action kind that does
+    /// not exist, instead of dropping just that step.
+    #[serde(default)]
+    pub reject_unknown_actions: bool,
+    /// Action kinds agentic plans may use. Other steps are stripped, and a
+    /// plan left empty falls back to the static playbook. `None` allows all.
+    #[serde(default)]
+    pub allowed_actions: Option<Vec<String>>,
+    #[serde(default)]
+    pub retry: CompletionRetry,
+    /// Seconds one request to the model may take before it fails (and is
+    /// retried per `retry`).
+    #[serde(default = "default_agentic_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+}
+
+fn default_agentic_request_timeout_secs() -> u64 {
+    30
+}
+
+impl Default for AgenticSettings {
+    fn default() -> Self {
+        Self {
+            mode: AgenticMode::default(),
+            budget: TokenBudget::default(),
+            context: PromptContextConfig::default(),
+            cache: DecisionCacheConfig::default(),
+            reject_unknown_actions: false,
+            allowed_actions: None,
+            retry: CompletionRetry::default(),
+            request_timeout_secs: default_agentic_request_timeout_secs(),
+        }
+    }
+}
+
+fn default_openai_model() -> String {
+    "gpt-4o-mini".to_string()
+}
+
+/// `provider = "openai_compatible"`: a self-hosted server speaking the OpenAI
+/// chat completions API, e.g. llama.cpp, vLLM or Ollama, for deployments that
+/// may not send metrics to a third party.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OpenAiCompatibleAgentConfig {
+    /// The base the server serves `/chat/completions` under, e.g.
+    /// `http://127.0.0.1:8080/v1`.
+    pub api_base: String,
+    pub model: String,
+    /// Only for servers that check a key; none is sent when unset.
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub prompt_version: Option<String>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(flatten)]
+    pub settings: AgenticSettings,
+}
+
+/// `provider = "anthropic"`: plans come from Claude via the Messages API.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AnthropicAgentConfig {
+    #[serde(default = "default_anthropic_model")]
+    pub model: String,
+    #[serde(default)]
+    pub api_base: Option<String>,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// As for OpenAI: recorded with every agentic plan in the decision log.
+    #[serde(default)]
+    pub prompt_version: Option<String>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    /// Cap on the tokens Claude may generate per plan; the API requires one.
+    #[serde(default = "default_anthropic_max_tokens")]
+    pub max_tokens: u32,
+    /// Ask for the plan through a forced tool call. Turn off for gateways or
+    /// models without tool use; the plan is then parsed from the reply text.
+    #[serde(default = "default_tool_use")]
+    pub tool_use: bool,
+    #[serde(flatten)]
+    pub settings: AgenticSettings,
+}
+
+fn default_anthropic_model() -> String {
+    "claude-sonnet-4-5".to_string()
+}
+
+fn default_anthropic_max_tokens() -> u32 {
+    1024
+}
+
+fn default_tool_use() -> bool {
+    true
+}
+
+/// Load and validate the service config from `config.*` plus the
+/// environment.
+pub fn load_config() -> Result<Config> {
+    let cfg = read_config(config::File::with_name("config"))?;
+    cfg.validate()?;
+    Ok(cfg)
+}
+
+/// [`load_config`] for tooling: an empty validator list is accepted, every
+/// other check still applies.
+pub fn load_config_lenient() -> Result<Config> {
+    let cfg = read_config(config::File::with_name("config"))?;
+    cfg.validate_lenient()?;
+    Ok(cfg)
+}
+
+fn read_config<S>(file: S) -> Result<Config>
+where
+    S: config::Source + Send + Sync + 'static,
+{
+    let settings = RawConfig::builder()
+        .add_source(file)
+        .add_source(config::Environment::with_prefix("VALIDATOR_COPILOT").separator("__"))
+        .build()?;
+    let mut cfg: Config = settings.try_deserialize()?;
+    cfg.normalize_validator_ids();
+    Ok(cfg)
+}
+
+/// Load only the `[commands]` section. Used by processes such as the validator
+/// client that run without the full service config; a missing file yields defaults.
+pub fn load_action_commands() -> Result<ActionCommands> {
+    let settings = RawConfig::builder()
+        .add_source(config::File::with_name("config").required(false))
+        .add_source(config::Environment::with_prefix("VALIDATOR_COPILOT").separator("__"))
+        .build()?;
+    let commands = match settings.get::<ActionCommands>("commands") {
+        Ok(commands) => commands,
+        Err(config::ConfigError::NotFound(_)) => ActionCommands::default(),
+        Err(err) => return Err(err.into()),
+    };
+    commands.validate()?;
+    Ok(commands)
+}
+
+/// Load just the `[validator_ids]` section, so the validator client normalizes
+/// its own id the way the daemon does. A missing section yields the default.
+pub fn load_validator_ids() -> Result<ValidatorIdsConfig> {
+    let settings = RawConfig::builder()
+        .add_source(config::File::with_name("config").required(false))
+        .add_source(config::Environment::with_prefix("VALIDATOR_COPILOT").separator("__"))
+        .build()?;
+    match settings.get::<ValidatorIdsConfig>("validator_ids") {
+        Ok(ids) => Ok(ids),
+        Err(config::ConfigError::NotFound(_)) => Ok(ValidatorIdsConfig::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Load the `[client]` section with the `[[validators]]` it names, ids
+/// normalized per `ids`. Both sections are optional.
+pub fn load_client_config(
+    ids: &ValidatorIdsConfig,
+) -> Result<(ClientConfig, Vec<ValidatorConfig>)> {
+    let settings = RawConfig::builder()
+        .add_source(config::File::with_name("config").required(false))
+        .add_source(config::Environment::with_prefix("VALIDATOR_COPILOT").separator("__"))
+        .build()?;
+    let mut client = match settings.get::<ClientConfig>("client") {
+        Ok(client) => client,
+        Err(config::ConfigError::NotFound(_)) => ClientConfig::default(),
+        Err(err) => return Err(err.into()),
+    };
+    client.metrics.check()?;
+    if client.validators.is_empty() {
+        return Ok((client, Vec::new()));
+    }
+    let mut validators = settings.get::<Vec<ValidatorConfig>>("validators")?;
+    for validator in &mut validators {
+        validator.id.0 = ids.normalize(&validator.id.0);
+    }
+    for id in &mut client.validators {
+        *id = ids.normalize(id);
+    }
+    let served = client.resolve(&validators)?.into_iter().cloned().collect();
+    Ok((client, served))
+}
+
+/// Load just the `[scrape_proxy]` section; the validator client has no
+/// validator list of its own.
+pub fn load_scrape_proxy() -> Result<Option<ScrapeProxyConfig>> {
+    let settings = RawConfig::builder()
+        .add_source(config::File::with_name("config").required(false))
+        .add_source(config::Environment::with_prefix("VALIDATOR_COPILOT").separator("__"))
+        .build()?;
+    match settings.get::<ScrapeProxyConfig>("scrape_proxy") {
+        Ok(proxy) => Ok(Some(proxy)),
+        Err(config::ConfigError::NotFound(_)) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// How much each metric, normalized to `[0, 1]`, contributes to the risk score.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RiskWeights {
+    /// Slot lag, saturating at 100 slots.
+    pub slot_lag: f64,
+    /// `1 - vote_success_rate`.
+    pub vote_failure: f64,
+    pub cpu_usage: f64,
+    pub disk_usage: f64,
+    pub rpc_error_rate: f64,
+    /// RPC load, saturating at 2000 qps.
+    pub rpc_qps: f64,
+    /// RAM use, saturating at 128 GB.
+    pub ram_usage: f64,
+}
+
+impl Default for RiskWeights {
+    fn default() -> Self {
+        Self {
+            slot_lag: 0.25,
+            vote_failure: 0.2,
+            cpu_usage: 0.15,
+            disk_usage: 0.1,
+            rpc_error_rate: 0.1,
+            rpc_qps: 0.1,
+            ram_usage: 0.1,
+        }
+    }
+}
+
+/// `[degradation]`: how much the risk score and each detected issue add to
+/// [`composite_health`], by the issue's `[remediation.severities]` severity.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct DegradationWeights {
+    pub risk: f64,
+    pub info: f64,
+    pub warning: f64,
+    pub critical: f64,
+}
+
+impl Default for DegradationWeights {
+    fn default() -> Self {
+        Self {
+            risk: 0.5,
+            info: 0.05,
+            warning: 0.15,
+            critical: 0.35,
+        }
+    }
+}
+
+impl DegradationWeights {
+    fn for_severity(&self, severity: Severity) -> f64 {
+        match severity {
+            Severity::Info => self.info,
+            Severity::Warning => self.warning,
+            Severity::Critical => self.critical,
+        }
+    }
+}
+
+/// One metric's share of the risk score, with the displayed reading.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskFactor {
+    /// e.g. `slot lag 120` or `disk 92%`.
+    pub reading: String,
+    pub contribution: f64,
+}
+
+/// Per-metric contributions to [`risk_score_with`], in a fixed order.
+pub fn risk_breakdown(metrics: &ValidatorMetrics, weights: &RiskWeights) -> Vec<RiskFactor> {
+    let factor = |reading: String, normalized: f64, weight: f64| RiskFactor {
+        reading,
+        contribution: normalized * weight,
+    };
+    vec![
+        factor(
+            format!("slot lag {}", metrics.slot_lag),
+            (metrics.slot_lag.max(0) as f64 / 100.0).min(1.0),
+            weights.slot_lag,
+        ),
+        factor(
+            format!("vote success {:.0}%", metrics.vote_success_rate * 100.0),
+            (1.0 - metrics.vote_success_rate).clamp(0.0, 1.0),
+            weights.vote_failure,
+        ),
+        factor(
+            format!("cpu {:.0}%", metrics.cpu_usage * 100.0),
+            metrics.cpu_usage.clamp(0.0, 1.0),
+            weights.cpu_usage,
+        ),
+        factor(
+            format!("disk {:.0}%", metrics.disk_usage_pct),
+            (metrics.disk_usage_pct / 100.0).clamp(0.0, 1.0),
+            weights.disk_usage,
+        ),
+        factor(
+            format!("rpc errors {:.1}%", metrics.rpc_error_rate * 100.0),
+            metrics.rpc_error_rate.clamp(0.0, 1.0),
+            weights.rpc_error_rate,
+        ),
+        factor(
+            format!("rpc {:.0} qps", metrics.rpc_qps),
+            (metrics.rpc_qps / 2000.0).min(1.0),
+            weights.rpc_qps,
+        ),
+        factor(
+            format!("ram {:.0} GB", metrics.ram_usage_gb),
+            (metrics.ram_usage_gb / 128.0).min(1.0),
+            weights.ram_usage,
+        ),
+    ]
+}
+
+/// Compute a rough risk score for a validator. Higher means riskier.
+pub fn risk_score(metrics: &ValidatorMetrics) -> f64 {
+    risk_score_with(metrics, &RiskWeights::default())
+}
+
+pub fn risk_score_with(metrics: &ValidatorMetrics, weights: &RiskWeights) -> f64 {
+    risk_breakdown(metrics, weights)
+        .iter()
+        .map(|factor| factor.contribution)
+        .sum::<f64>()
+        .min(1.0)
+}
+
+/// Degradation in `[0, 1]` from a risk score and the severities of the issues
+/// detected alongside it, for ranking validators fleet-wide. Each term
+/// removes its weight's share of what health is left, so issues compound:
+/// three warnings outweigh one, and can outweigh a single critical issue.
+pub fn composite_health(
+    risk: f64,
+    issues: impl IntoIterator<Item = Severity>,
+    weights: &DegradationWeights,
+) -> f64 {
+    let healthy = issues
+        .into_iter()
+        .map(|severity| 1.0 - weights.for_severity(severity).clamp(0.0, 1.0))
+        .product::<f64>()
+        * (1.0 - (risk * weights.risk).clamp(0.0, 1.0));
+    1.0 - healthy
+}
+
+/// A risk score in coarse bands: `at_risk` from 0.5, `elevated` from 0.25.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskLevel {
+    Low,
+    Elevated,
+    AtRisk,
+}
+
+impl RiskLevel {
+    pub fn of(score: f64) -> Self {
+        if score >= 0.5 {
+            Self::AtRisk
+        } else if score >= 0.25 {
+            Self::Elevated
+        } else {
+            Self::Low
+        }
+    }
+
+    /// Leads the line written by [`explain_risk`].
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Low => "Low risk",
+            Self::Elevated => "Elevated risk",
+            Self::AtRisk => "At risk",
+        }
+    }
+}
+
+/// Factors listed by [`explain_risk`].
+const EXPLAINED_FACTORS: usize = 3;
+
+/// One-line explanation of a risk score, e.g. `At risk: slot lag 120, disk 92%`,
+/// naming the largest contributors first.
+pub fn explain_risk(metrics: &ValidatorMetrics, weights: &RiskWeights) -> String {
+    let level = RiskLevel::of(risk_score_with(metrics, weights)).label();
+    let mut factors = risk_breakdown(metrics, weights);
+    factors.retain(|factor| factor.contribution > 0.0);
+    if factors.is_empty() {
+        return format!("{level}: no contributing factors");
+    }
+    // Stable sort keeps the fixed breakdown order between equal contributions.
+    factors.sort_by(|a, b| b.contribution.total_cmp(&a.contribution));
+    let readings: Vec<_> = factors
+        .into_iter()
+        .take(EXPLAINED_FACTORS)
+        .map(|factor| factor.reading)
+        .collect();
+    format!("{level}: {}", readings.join(", "))
+}
+
+/// Deterministic key for one step of a remediation. Every agent instance that
+/// dispatches `playbook_id` for `issue` on `validator` within the same
+/// `bucket_secs` window derives the same key, so the daemon can drop a
+/// restarted agent's resubmission.
+pub fn idempotency_key(
+    validator: &ValidatorId,
+    issue: IssueKind,
+    playbook_id: &str,
+    step: usize,
+    now: i64,
+    bucket_secs: i64,
+) -> String {
+    let bucket = now.div_euclid(bucket_secs.max(1));
+    format!(
+        "{}:{}:{playbook_id}:{bucket}:{step}",
+        validator.0,
+        issue.as_str()
+    )
+}
+
+/// Compare a presented secret with the expected one in constant time, so a
+/// mismatch does not leak how long a prefix matched.
+pub fn tokens_match(presented: &str, expected: &str) -> bool {
+    use subtle::ConstantTimeEq;
+    presented.as_bytes().ct_eq(expected.as_bytes()).into()
+}
+
+/// Delays between retries: doubling from `base` up to `max`, each drawn from
+/// the upper half of its step so callers that failed together do not all
+/// retry at once.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    step: Duration,
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            step: base,
+        }
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        use rand::Rng;
+        let step = self.step;
+        self.step = step.saturating_mul(2).min(self.max);
+        step.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+
+    /// Start over from `base`, e.g. after a success.
+    pub fn reset(&mut self) {
+        self.step = self.base;
+    }
+}
+
+pub fn now_ts() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_to_the_cap_with_jitter_and_resets() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(60));
+        for step in [1, 2, 4, 8, 16, 32, 60, 60] {
+            let delay = backoff.next_delay();
+            let step = Duration::from_secs(step);
+            assert!(
+                delay >= step / 2 && delay <= step,
+                "{delay:?} outside {step:?}"
+            );
+        }
+        backoff.reset();
+        let delay = backoff.next_delay();
+        assert!(delay <= Duration::from_secs(1), "{delay:?} not reset");
+    }
+
+    #[test]
+    fn risk_score_increases_with_slot_lag() {
+        let base = ValidatorMetrics {
+            slot_lag: 0,
+            vote_success_rate: 0.99,
+            cpu_usage: 0.2,
+            ram_usage_gb: 16.0,
+            disk_usage_pct: 40.0,
+            rpc_qps: 100.0,
+            rpc_error_rate: 0.001,
+            last_updated: 0,
+            field_timestamps: BTreeMap::new(),
+        };
+        let low = risk_score(&base);
+        let mut degraded = base.clone();
+        degraded.slot_lag = 200;
+        let high = risk_score(&degraded);
+        assert!(high > low);
+        assert!(high <= 1.0);
+    }
+
+    #[test]
+    fn explain_risk_leads_with_the_dominant_factor() {
+        let metrics = ValidatorMetrics {
+            slot_lag: 120,
+            vote_success_rate: 0.97,
+            cpu_usage: 0.3,
+            ram_usage_gb: 16.0,
+            disk_usage_pct: 92.0,
+            rpc_qps: 100.0,
+            rpc_error_rate: 0.001,
+            last_updated: 0,
+            field_timestamps: BTreeMap::new(),
+        };
+        let weights = RiskWeights::default();
+        assert_eq!(
+            explain_risk(&metrics, &weights),
+            "Elevated risk: slot lag 120, disk 92%, cpu 30%"
+        );
+
+        let breakdown: f64 = risk_breakdown(&metrics, &weights)
+            .iter()
+            .map(|factor| factor.contribution)
+            .sum();
+        assert!((risk_score(&metrics) - breakdown).abs() < 1e-12);
+
+        let weights = RiskWeights {
+            slot_lag: 0.0,
+            ..RiskWeights::default()
+        };
+        assert!(explain_risk(&metrics, &weights).starts_with("Low risk: disk 92%"));
+    }
+
+    #[test]
+    fn compounding_issues_degrade_more_than_one_equivalent_issue() {
+        let weights = DegradationWeights::default();
+        let risk = 0.3;
+        let healthy = composite_health(risk, [], &weights);
+        let one = composite_health(risk, [Severity::Warning], &weights);
+        let three = composite_health(risk, [Severity::Warning; 3], &weights);
+        assert!(healthy < one && one < three, "{healthy} {one} {three}");
+        // Three mild issues at once rank worse than a single severe one.
+        assert!(three > composite_health(risk, [Severity::Critical], &weights));
+        let everything = composite_health(1.0, [Severity::Critical; 10], &weights);
+        assert!(everything <= 1.0 && everything > 0.9);
+        assert_eq!(composite_health(0.0, [], &weights), 0.0);
+    }
+
+    #[test]
+    fn lenient_validation_accepts_an_empty_validator_list() {
+        let read = |toml: &str| {
+            read_config(config::File::from_str(toml, config::FileFormat::Toml)).unwrap()
+        };
+        let empty = read(
+            r#"validators = []
+redis_url = "redis://127.0.0.1:6379"
+"#,
+        );
+        assert!(empty.validate_lenient().is_ok());
+        assert!(empty
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .contains("no validators"));
+        // A registration-only deployment starts with none.
+        let registration_only = read(
+            r#"validators = []
+redis_url = "redis://127.0.0.1:6379"
+[daemon.registration]
+bootstrap_token_env = "VALIDATOR_BOOTSTRAP_TOKEN"
+"#,
+        );
+        assert!(registration_only.validate().is_ok());
+
+        // Lenient still rejects everything else.
+        let duplicated = read(
+            r#"redis_url = "redis://127.0.0.1:6379"
+[[validators]]
+id = "v1"
+host = "v1.local"
+prometheus_url = "http://v1.local:9100/metrics"
+auth_token = "a"
+[[validators]]
+id = "v1"
+host = "v1b.local"
+prometheus_url = "http://v1b.local:9100/metrics"
+auth_token = "b"
+"#,
+        );
+        assert!(duplicated.validate_lenient().is_err());
+        assert!(duplicated.validate().is_err());
+    }
+
+    #[test]
+    fn handler_timeouts_default_per_method_and_reject_unknown_methods() {
+        let mut cfg = Config {
+            redis_url: "redis://127.0.0.1:6379".into(),
+            ..Config::default()
+        };
+        let timeouts = &mut cfg.daemon.handler_timeouts;
+        timeouts.methods.insert("publish_metrics".into(), 500);
+        timeouts.methods.insert("list_action_history".into(), 0);
+        assert_eq!(
+            timeouts.for_method("publish_metrics"),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(
+            timeouts.for_method("report_result"),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(timeouts.for_method("list_action_history"), None);
+        assert!(cfg.validate_lenient().is_ok());
+
+        cfg.daemon
+            .handler_timeouts
+            .methods
+            .insert("PublishMetrics".into(), 500);
+        assert_eq!(
+            cfg.validate_lenient().unwrap_err().to_string(),
+            "[daemon.handler_timeouts] methods has unknown method PublishMetrics"
+        );
+    }
+
+    #[test]
+    fn agentic_provider_is_selected_by_its_documented_tag() {
+        let read = |agentic: &str| {
+            read_config(config::File::from_str(
+                &format!(
+                    r#"validators = []
+redis_url = "redis://127.0.0.1:6379"
+[agentic]
+{agentic}
+"#
+                ),
+                config::FileFormat::Toml,
+            ))
+            .unwrap()
+        };
+        let agentic = |provider: &str| {
+            read(&format!(r#"provider = "{provider}""#))
+                .agentic
+                .unwrap()
+        };
+        let AgenticConfig::OpenAi(openai) = agentic("openai") else {
+            panic!("expected the openai provider");
+        };
+        assert_eq!(openai.settings, AgenticSettings::default());
+        assert_eq!(openai.settings.retry.max_attempts, 3);
+        assert_eq!(openai.settings.request_timeout_secs, 30);
+        let written = serde_json::to_value(AgenticConfig::OpenAi(openai.clone())).unwrap();
+        assert_eq!(written["provider"], "open_ai");
+        assert!(matches!(
+            agentic("open_ai"),
+            AgenticConfig::OpenAi(old) if old == openai
+        ));
+        let AgenticConfig::Anthropic(claude) = agentic("anthropic") else {
+            panic!("expected the anthropic provider");
+        };
+        assert_eq!(claude.model, "claude-sonnet-4-5");
+        assert_eq!(claude.max_tokens, 1024);
+        assert!(claude.tool_use);
+
+        let local = |api_base: &str| {
+            read(&format!(
+                r#"provider = "ollama"
+api_base = "{api_base}"
+model = "llama3.1:8b""#
+            ))
+        };
+        let config = local("http://127.0.0.1:11434/v1");
+        assert!(matches!(
+            config.agentic,
+            Some(AgenticConfig::OpenAiCompatible(_))
+        ));
+        assert!(config.validate_lenient().is_ok());
+        assert_eq!(
+            local("127.0.0.1:11434")
+                .validate_lenient()
+                .unwrap_err()
+                .to_string(),
+            "[agentic] api_base must be an http:// or https:// URL"
+        );
+
+        let allowlisted = read(
+            r#"provider = "openai"
+allowed_actions = ["send_alert", "reboot_host"]"#,
+        );
+        assert_eq!(
+            allowlisted.validate_lenient().unwrap_err().to_string(),
+            "[agentic] allowed_actions has unknown action kind reboot_host"
+        );
+
+        let no_attempts = read(
+            r#"provider = "openai"
+[agentic.retry]
+max_attempts = 0"#,
+        );
+        assert_eq!(
+            no_attempts.validate_lenient().unwrap_err().to_string(),
+            "[agentic.retry] max_attempts must be at least 1"
+        );
+    }
+
+    #[test]
+    fn ack_timeout_must_outlast_the_longest_command_and_recovery_wait() {
+        let read = |extra: &str| {
+            read_config(config::File::from_str(
+                &format!("validators = []\nredis_url = \"redis://127.0.0.1:6379\"\n{extra}"),
+                config::FileFormat::Toml,
+            ))
+            .unwrap()
+        };
+        assert!(read("").validate_lenient().is_ok());
+        assert!(read("[daemon]\nack_timeout_secs = 31\n")
+            .validate_lenient()
+            .is_ok());
+
+        // 300s for a restart, plus 6 scrapes 10s apart.
+        let slow = read(
+            "[daemon]\nack_timeout_secs = 360\n[commands.timeouts]\nrestart_validator = 300\n[remediation.recovery_check]\n",
+        );
+        assert_eq!(
+            slow.validate_lenient().unwrap_err().to_string(),
+            "[daemon] ack_timeout_secs must exceed the longest command timeout plus the recovery check window (360s), got 360"
+        );
+    }
+
+    #[test]
+    fn detection_thresholds_default_when_missing_and_are_range_checked() {
+        let read = |detection: &str| {
+            read_config(config::File::from_str(
+                &format!("validators = []\nredis_url = \"redis://127.0.0.1:6379\"\n{detection}"),
+                config::FileFormat::Toml,
+            ))
+            .unwrap()
+        };
+        let defaults = read("");
+        assert_eq!(
+            defaults.detection.thresholds,
+            DetectionThresholds::default()
+        );
+        assert_eq!(defaults.detection.thresholds.slot_lag, 50);
+        assert!(defaults.validate_lenient().is_ok());
+
+        let raised = read("[detection.thresholds]\nslot_lag = 150\n");
+        assert_eq!(raised.detection.thresholds.slot_lag, 150);
+        assert_eq!(raised.detection.thresholds.disk_usage_pct, 90.0);
+        assert!(raised.validate_lenient().is_ok());
+
+        let err = read("[detection]\nvote_rate_threshold = 80\n")
+            .validate_lenient()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[detection] vote_rate_threshold must be in [0, 1], got 80"
+        );
+        let err = read("[detection.thresholds]\ncpu_usage = 0\n")
+            .validate_lenient()
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("thresholds.cpu_usage must be in (0, 1]"));
+    }
+
+    #[test]
+    fn validator_metrics_serde_roundtrip() {
+        let metrics = ValidatorMetrics {
+            slot_lag: 10,
+            vote_success_rate: 0.95,
+            cpu_usage: 0.5,
+            ram_usage_gb: 32.0,
+            disk_usage_pct: 55.0,
+            rpc_qps: 500.0,
+            rpc_error_rate: 0.01,
+            last_updated: 123456,
+            field_timestamps: BTreeMap::from([("disk_usage_pct".to_string(), 123000)]),
+        };
+        let json = serde_json::to_string(&metrics).expect("serialize");
+        let back: ValidatorMetrics = serde_json::from_str(&json).expect("deserialize");
+        assert_eq!(metrics, back);
+    }
+
+    #[test]
+    fn legacy_field_names_deserialize_into_current_fields() {
+        let legacy = r#"{"slot_lag": 3, "vote_rate": 0.91, "cpu_usage": 0.5,
+            "ram_usage_gb": 32.0, "disk_usage_pct": 55.0, "rpc_qps": 500.0,
+            "rpc_error_rate": 0.01, "last_updated": 123456}"#;
+        let metrics: ValidatorMetrics = serde_json::from_str(legacy).expect("deserialize");
+        assert_eq!(metrics.vote_success_rate, 0.91);
+        let json = serde_json::to_value(&metrics).unwrap();
+        assert_eq!(json["vote_success_rate"], 0.91);
+        assert!(json.get("vote_rate").is_none());
+    }
+
+    #[test]
+    fn slow_disk_field_tolerated_while_slot_lag_gap_is_stale() {
+        let cfg = StalenessConfig {
+            per_field: HashMap::from([("disk_usage_pct".to_string(), 600)]),
+            ..StalenessConfig::default()
+        };
+        let mut metrics = ValidatorMetrics {
+            slot_lag: 0,
+            vote_success_rate: 0.99,
+            cpu_usage: 0.2,
+            ram_usage_gb: 16.0,
+            disk_usage_pct: 40.0,
+            rpc_qps: 100.0,
+            rpc_error_rate: 0.001,
+            last_updated: 1_000,
+            field_timestamps: BTreeMap::from([("disk_usage_pct".to_string(), 700)]),
+        };
+        assert!(cfg.stale_fields(&metrics, 1_030).is_empty());
+        assert!(cfg.overdue_secs(&metrics, 1_030) <= 0);
+
+        metrics.field_timestamps.insert("slot_lag".to_string(), 900);
+        assert_eq!(cfg.stale_fields(&metrics, 1_030), vec!["slot_lag"]);
+        assert_eq!(cfg.overdue_secs(&metrics, 1_030), 70);
+    }
+
+    #[test]
+    fn per_validator_allowlist_narrows_global_scripts() {
+        let validator = |id: &str, allowed: Option<Vec<String>>| ValidatorConfig {
+            id: ValidatorId(id.into()),
+            host: format!("{id}.local"),
+            prometheus_url: String::new(),
+            auth_token: String::new(),
+            allowed_scripts: allowed,
+            tags: Vec::new(),
+            labels: Default::default(),
+            agentic_allowed_actions: None,
+            metrics_fallbacks: Vec::new(),
+        };
+        let cfg = Config {
+            maintenance: MaintenanceConfig {
+                allowed_scripts: vec!["diag.sh".into(), "cleanup-logs.sh".into()],
+            },
+            ..Config::default()
+        };
+        let open = validator("v1", None);
+        let narrowed = validator("v2", Some(vec!["cleanup-logs.sh".into()]));
+        assert!(cfg.script_allowed(&open, "diag.sh"));
+        assert!(!cfg.script_allowed(&narrowed, "diag.sh"));
+        assert!(cfg.script_allowed(&narrowed, "cleanup-logs.sh"));
+        assert!(!cfg.script_allowed(&open, "rm-rf.sh"));
+    }
+
+    #[test]
+    fn alerts_outrank_restarts_unless_overridden() {
+        let validator = ValidatorId("v1".into());
+        let alert = Action::SendAlert {
+            validator: validator.clone(),
+            message: "slot lag".into(),
+        };
+        let restart = Action::RestartValidator {
+            validator,
+            restart_kind: RestartKind::Hard,
+        };
+        let mut cfg = RemediationConfig::default();
+        assert!(cfg.priority_for(&alert) > cfg.priority_for(&restart));
+
+        cfg.priorities.insert("restart_validator".into(), 500);
+        assert!(cfg.priority_for(&restart) > cfg.priority_for(&alert));
+    }
+
+    #[test]
+    fn action_kinds_lists_every_action_by_its_serialized_kind() {
+        let v = || ValidatorId("v1".into());
+        let actions = [
+            Action::DisableRpc { validator: v() },
+            Action::EnableRpc { validator: v() },
+            Action::RestartValidator {
+                validator: v(),
+                restart_kind: RestartKind::Hard,
+            },
+            Action::ThrottleRpcClient { validator: v() },
+            Action::RunMaintenanceScript {
+                validator: v(),
+                script_name: "s".into(),
+                params: HashMap::new(),
+            },
+            Action::SendAlert {
+                validator: v(),
+                message: "m".into(),
+            },
+            Action::ExecCommand {
+                validator: v(),
+                command_id: "c".into(),
+            },
+        ];
+        // Fails to compile when a variant is added, until it is added above.
+        for action in &actions {
+            match action {
+                Action::DisableRpc { .. }
+                | Action::EnableRpc { .. }
+                | Action::RestartValidator { .. }
+                | Action::ThrottleRpcClient { .. }
+                | Action::RunMaintenanceScript { .. }
+                | Action::SendAlert { .. }
+                | Action::ExecCommand { .. } => {}
+            }
+            let json = serde_json::to_value(action).unwrap();
+            assert_eq!(json["kind"], action.kind());
+        }
+        let kinds: Vec<&str> = actions.iter().map(Action::kind).collect();
+        assert_eq!(kinds, ACTION_KINDS);
+    }
+
+    #[test]
+    fn restart_kind_round_trips_and_defaults_to_hard() {
+        let soft = Action::RestartValidator {
+            validator: ValidatorId("v1".into()),
+            restart_kind: RestartKind::Soft,
+        };
+        let json = serde_json::to_string(&soft).unwrap();
+        assert_eq!(
+            json,
+            r#"{"kind":"restart_validator","validator":"v1","restart_kind":"soft"}"#
+        );
+        assert_eq!(serde_json::from_str::<Action>(&json).unwrap(), soft);
+
+        // Restarts from before restart_kind existed are hard, and hard ones
+        // serialize as they used to.
+        let legacy = r#"{"kind":"restart_validator","validator":"v1"}"#;
+        let hard: Action = serde_json::from_str(legacy).unwrap();
+        assert_eq!(
+            hard,
+            Action::RestartValidator {
+                validator: ValidatorId("v1".into()),
+                restart_kind: RestartKind::Hard,
+            }
+        );
+        assert_eq!(serde_json::to_string(&hard).unwrap(), legacy);
+        assert_eq!(hard.template_key(), "restart_validator");
+        assert_eq!(soft.template_key(), "restart_validator_soft");
+    }
+
+    #[test]
+    fn configured_ids_are_trimmed_and_optionally_lowercased() {
+        let read = |lowercase: bool| {
+            read_config(config::File::from_str(
+                &format!(
+                    r#"redis_url = "redis://127.0.0.1:6379"
+[validator_ids]
+lowercase = {lowercase}
+[[validators]]
+id = "Validator-1 "
+host = "v1.local"
+prometheus_url = "http://v1.local:9100/metrics"
+auth_token = "a"
+[[suppressions]]
+validator = " Validator-1"
+issue = "disk_almost_full"
+"#
+                ),
+                config::FileFormat::Toml,
+            ))
+            .unwrap()
+        };
+        let trimmed = read(false);
+        assert_eq!(trimmed.validators[0].id.0, "Validator-1");
+        assert_eq!(
+            trimmed.suppressions[0].validator.as_deref(),
+            Some("Validator-1")
+        );
+        let lowered = read(true);
+        assert_eq!(lowered.validators[0].id.0, "validator-1");
+        assert_eq!(
+            lowered.suppressions[0].validator.as_deref(),
+            Some("validator-1")
+        );
+    }
+
+    #[test]
+    fn client_validators_must_be_configured() {
+        let read = |client: &str| {
+            read_config(config::File::from_str(
+                &format!(
+                    r#"redis_url = "redis://127.0.0.1:6379"
+[[validators]]
+id = "v1"
+host = "v1.local"
+prometheus_url = "http://v1.local:9100/metrics"
+auth_token = "a"
+[[validators]]
+id = "v2"
+host = "v2.local"
+prometheus_url = "http://v2.local:9100/metrics"
+auth_token = "b"
+[client]
+{client}
+"#
+                ),
+                config::FileFormat::Toml,
+            ))
+            .unwrap()
+        };
+        let cfg = read(r#"validators = ["v2 ", "v1"]"#);
+        assert_eq!(cfg.client.scrape_concurrency, 4);
+        let served: Vec<&str> = cfg
+            .client
+            .resolve(&cfg.validators)
+            .unwrap()
+            .iter()
+            .map(|validator| validator.auth_token.as_str())
+            .collect();
+        assert_eq!(served, ["b", "a"]);
+
+        let err = read(r#"validators = ["v3"]"#)
+            .validate_lenient()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "[client] validator v3 is not listed in [[validators]]"
+        );
+        assert!(read("scrape_concurrency = 0").validate_lenient().is_err());
+    }
+
+    #[test]
+    fn parameterized_script_renders_and_rejects_unknown_params() {
+        let cfg = read_config(config::File::from_str(
+            r#"validators = []
+redis_url = "redis://127.0.0.1:6379"
+[commands.scripts.cleanup-logs]
+command = "cleanup-logs --older-than {{ older_than }} --dir {{dir}}"
+params.older_than = { default = "7d" }
+params.dir = {}
+"#,
+            config::FileFormat::Toml,
+        ))
+        .unwrap();
+        cfg.validate_lenient().unwrap();
+        let commands = &cfg.commands;
+        let params = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        assert_eq!(
+            commands
+                .script_command("cleanup-logs", &params(&[("dir", "/var/log/sol ana")]))
+                .unwrap(),
+            "cleanup-logs --older-than '7d' --dir '/var/log/sol ana'"
+        );
+        assert_eq!(
+            commands
+                .script_command(
+                    "cleanup-logs",
+                    &params(&[("dir", "it's"), ("older_than", "30d")])
+                )
+                .unwrap(),
+            r"cleanup-logs --older-than '30d' --dir 'it'\''s'"
+        );
+        let err = commands
+            .script_command("cleanup-logs", &params(&[("dir", "/x"), ("force", "1")]))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "script cleanup-logs has no parameter force"
+        );
+        let err = commands
+            .script_command("cleanup-logs", &HashMap::new())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "script cleanup-logs requires parameter dir"
+        );
+        assert_eq!(
+            commands.script_command("diag.sh", &HashMap::new()).unwrap(),
+            "sh diag.sh"
+        );
+        assert!(commands
+            .script_command("diag.sh", &params(&[("dir", "/x")]))
+            .is_err());
+        // A value naming another placeholder is quoted, not expanded.
+        assert_eq!(
+            commands
+                .script_command(
+                    "cleanup-logs",
+                    &params(&[("dir", "{{older_than}}"), ("older_than", "$(reboot)")])
+                )
+                .unwrap(),
+            "cleanup-logs --older-than '$(reboot)' --dir '{{older_than}}'"
+        );
+
+        let mut undeclared = cfg.clone();
+        undeclared
+            .commands
+            .scripts
+            .get_mut("cleanup-logs")
+            .unwrap()
+            .params
+            .remove("dir");
+        assert!(undeclared.validate_lenient().is_err());
+    }
+
+    #[test]
+    fn action_templates_may_only_use_their_kinds_placeholders() {
+        let mut commands = ActionCommands::default();
+        commands.actions.insert(
+            "run_maintenance_script".into(),
+            "/opt/scripts/{{script_name}} --validator {{validator}}".into(),
+        );
+        commands.validate().unwrap();
+        let run = |script_name: &str| Action::RunMaintenanceScript {
+            validator: ValidatorId("v1".into()),
+            script_name: script_name.into(),
+            params: HashMap::new(),
+        };
+        assert_eq!(
+            commands.action_command(&run("rotate-logs")).as_deref(),
+            Some("/opt/scripts/'rotate-logs' --validator 'v1'")
+        );
+        commands
+            .scripts
+            .insert("rotate-logs".into(), MaintenanceScript::default());
+        assert_eq!(commands.action_command(&run("rotate-logs")), None);
+
+        // An alert message naming a placeholder is quoted, not expanded.
+        commands.actions.insert(
+            "send_alert".into(),
+            "notify --to {{ validator }} {{message}}".into(),
+        );
+        let alert = Action::SendAlert {
+            validator: ValidatorId("v1".into()),
+            message: "{{validator}} $(reboot)".into(),
+        };
+        assert_eq!(
+            commands.action_command(&alert).as_deref(),
+            Some("notify --to 'v1' '{{validator}} $(reboot)'")
+        );
+
+        commands
+            .actions
+            .insert("restart_validator".into(), "echo {{message}}".into());
+        assert_eq!(
+            commands.validate().unwrap_err().to_string(),
+            "[commands.actions] restart_validator uses unknown placeholder message"
+        );
+        commands.actions.remove("restart_validator");
+        commands
+            .actions
+            .insert("exec_command".into(), "true".into());
+        assert!(commands.validate().is_err());
+    }
+
+    #[test]
+    fn suppression_predicate_bounds_the_metric() {
+        let cfg = read_config(config::File::from_str(
+            r#"redis_url = "redis://127.0.0.1:6379"
+[[validators]]
+id = "v1"
+host = "v1.local"
+prometheus_url = "http://v1.local:9100/metrics"
+auth_token = "a"
+tags = ["archival"]
+[[suppressions]]
+tag = "archival"
+issue = "disk_almost_full"
+when = { metric = "disk_usage_pct", below = 98.0 }
+"#,
+            config::FileFormat::Toml,
+        ))
+        .unwrap();
+        let archival = &cfg.validators[0];
+        let mut metrics = ValidatorMetrics {
+            slot_lag: 0,
+            vote_success_rate: 0.99,
+            cpu_usage: 0.2,
+            ram_usage_gb: 16.0,
+            disk_usage_pct: 95.0,
+            rpc_qps: 100.0,
+            rpc_error_rate: 0.001,
+            last_updated: 0,
+            field_timestamps: BTreeMap::new(),
+        };
+        assert_eq!(
+            cfg.suppression_for(archival, IssueKind::DiskAlmostFull, &metrics),
+            Some("suppressions[0]".into())
+        );
+        assert!(cfg
+            .suppression_for(archival, IssueKind::SlotLagHigh, &metrics)
+            .is_none());
+        metrics.disk_usage_pct = 99.0;
+        assert!(cfg
+            .suppression_for(archival, IssueKind::DiskAlmostFull, &metrics)
+            .is_none());
     }
 }
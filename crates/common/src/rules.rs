@@ -0,0 +1,438 @@
+//! `[detection] rules`: org-specific detections written as
+//! `<condition> => <issue_kind>`, e.g.
+//! `slot_lag > 30 && rpc_error_rate > 0.02 => slot_lag_high`. A rule may
+//! report an org-specific issue as `custom:<name>`.
+//!
+//! Conditions compare arithmetic over the [`METRIC_FIELDS`] of a sample with
+//! `>`, `>=`, `<`, `<=`, `==` and `!=`, and combine comparisons with `&&`,
+//! `||`, `!` and parentheses. Rules are compiled when the config is loaded,
+//! so a typo fails startup rather than silently never firing.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Comparison, IssueKind, ValidatorMetrics, METRIC_FIELDS};
+
+/// One compiled rule. Serializes back to its source text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DetectionRule {
+    source: String,
+    condition: Condition,
+    issue: IssueKind,
+}
+
+impl DetectionRule {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let (condition, issue) = source
+            .rsplit_once("=>")
+            .ok_or_else(|| format!("rule `{source}` has no `=> <issue_kind>`"))?;
+        let issue = issue.trim();
+        let issue: IssueKind = issue
+            .parse()
+            .map_err(|err| format!("rule `{source}` reports {err}"))?;
+        let condition = Parser::new(condition)
+            .and_then(Parser::finish)
+            .map_err(|err| format!("rule `{source}`: {err}"))?;
+        Ok(Self {
+            source: source.to_string(),
+            condition,
+            issue,
+        })
+    }
+
+    /// The rule's issue when `metrics` satisfies its condition.
+    pub fn evaluate(&self, metrics: &ValidatorMetrics) -> Option<IssueKind> {
+        self.condition.holds(metrics).then_some(self.issue)
+    }
+
+    pub fn issue(&self) -> IssueKind {
+        self.issue
+    }
+}
+
+impl TryFrom<String> for DetectionRule {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, String> {
+        Self::parse(&source)
+    }
+}
+
+impl From<DetectionRule> for String {
+    fn from(rule: DetectionRule) -> Self {
+        rule.source
+    }
+}
+
+impl fmt::Display for DetectionRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    Compare(Comparison, Value, Value),
+    Equal(bool, Value, Value),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    fn holds(&self, metrics: &ValidatorMetrics) -> bool {
+        match self {
+            Self::Compare(comparison, lhs, rhs) => {
+                comparison.holds(lhs.eval(metrics), rhs.eval(metrics))
+            }
+            Self::Equal(equal, lhs, rhs) => (lhs.eval(metrics) == rhs.eval(metrics)) == *equal,
+            Self::And(lhs, rhs) => lhs.holds(metrics) && rhs.holds(metrics),
+            Self::Or(lhs, rhs) => lhs.holds(metrics) || rhs.holds(metrics),
+            Self::Not(inner) => !inner.holds(metrics),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f64),
+    /// One of [`METRIC_FIELDS`], checked at parse time.
+    Field(&'static str),
+    Neg(Box<Value>),
+    Arith(char, Box<Value>, Box<Value>),
+}
+
+impl Value {
+    fn eval(&self, metrics: &ValidatorMetrics) -> f64 {
+        match self {
+            Self::Number(value) => *value,
+            Self::Field(field) => metrics.field(field).unwrap_or(f64::NAN),
+            Self::Neg(inner) => -inner.eval(metrics),
+            Self::Arith(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(metrics), rhs.eval(metrics));
+                match op {
+                    '+' => lhs + rhs,
+                    '-' => lhs - rhs,
+                    '*' => lhs * rhs,
+                    _ => lhs / rhs,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    /// Operators and parentheses.
+    Symbol(&'static str),
+}
+
+const SYMBOLS: &[&str] = &[
+    "&&", "||", ">=", "<=", "==", "!=", ">", "<", "!", "+", "-", "*", "/", "(", ")",
+];
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() || c == '.' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            let number = rest[..end]
+                .parse()
+                .map_err(|_| format!("invalid number `{}`", &rest[..end]))?;
+            tokens.push(Token::Number(number));
+            rest = &rest[end..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(**symbol))
+                .ok_or_else(|| format!("unexpected `{c}`"))?;
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Either side of a parenthesized group, until its context decides which is
+/// expected.
+enum Expr {
+    Condition(Condition),
+    Value(Value),
+}
+
+impl Expr {
+    fn condition(self) -> Result<Condition, String> {
+        match self {
+            Self::Condition(condition) => Ok(condition),
+            Self::Value(_) => Err("expected a comparison, found a number".into()),
+        }
+    }
+
+    fn value(self) -> Result<Value, String> {
+        match self {
+            Self::Value(value) => Ok(value),
+            Self::Condition(_) => Err("expected a number, found a comparison".into()),
+        }
+    }
+}
+
+/// Recursive descent, loosest binding first: `||`, `&&`, `!`, comparisons,
+/// `+ -`, `* /`, unary `-`.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(input: &str) -> Result<Self, String> {
+        Ok(Self {
+            tokens: tokenize(input)?,
+            pos: 0,
+        })
+    }
+
+    fn finish(mut self) -> Result<Condition, String> {
+        if self.tokens.is_empty() {
+            return Err("empty condition".into());
+        }
+        let condition = self.or()?.condition()?;
+        match self.tokens.get(self.pos) {
+            None => Ok(condition),
+            Some(token) => Err(format!("unexpected {token:?}")),
+        }
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        let matched = matches!(self.tokens.get(self.pos), Some(Token::Symbol(s)) if *s == symbol);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.and()?;
+        while self.eat("||") {
+            let rhs = self.and()?.condition()?;
+            lhs = Expr::Condition(Condition::Or(Box::new(lhs.condition()?), Box::new(rhs)));
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.not()?;
+        while self.eat("&&") {
+            let rhs = self.not()?.condition()?;
+            lhs = Expr::Condition(Condition::And(Box::new(lhs.condition()?), Box::new(rhs)));
+        }
+        Ok(lhs)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.eat("!") {
+            let inner = self.not()?.condition()?;
+            return Ok(Expr::Condition(Condition::Not(Box::new(inner))));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let lhs = self.sum()?;
+        let comparison = match self.tokens.get(self.pos) {
+            Some(Token::Symbol(">")) => Some(Comparison::Above),
+            Some(Token::Symbol(">=")) => Some(Comparison::AtLeast),
+            Some(Token::Symbol("<")) => Some(Comparison::Below),
+            Some(Token::Symbol("<=")) => Some(Comparison::AtMost),
+            _ => None,
+        };
+        let equal = match self.tokens.get(self.pos) {
+            Some(Token::Symbol("==")) => Some(true),
+            Some(Token::Symbol("!=")) => Some(false),
+            _ => None,
+        };
+        if comparison.is_none() && equal.is_none() {
+            return Ok(lhs);
+        }
+        self.pos += 1;
+        let lhs = lhs.value()?;
+        let rhs = self.sum()?.value()?;
+        Ok(Expr::Condition(match (comparison, equal) {
+            (Some(comparison), _) => Condition::Compare(comparison, lhs, rhs),
+            (_, Some(equal)) => Condition::Equal(equal, lhs, rhs),
+            _ => unreachable!(),
+        }))
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.product()?;
+        loop {
+            let op = if self.eat("+") {
+                '+'
+            } else if self.eat("-") {
+                '-'
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.product()?.value()?;
+            lhs = Expr::Value(Value::Arith(op, Box::new(lhs.value()?), Box::new(rhs)));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = if self.eat("*") {
+                '*'
+            } else if self.eat("/") {
+                '/'
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.unary()?.value()?;
+            lhs = Expr::Value(Value::Arith(op, Box::new(lhs.value()?), Box::new(rhs)));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            let inner = self.unary()?.value()?;
+            return Ok(Expr::Value(Value::Neg(Box::new(inner))));
+        }
+        if self.eat("(") {
+            let inner = self.or()?;
+            if !self.eat(")") {
+                return Err("unclosed `(`".into());
+            }
+            return Ok(inner);
+        }
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Number(value)) => Ok(Expr::Value(Value::Number(value))),
+            Some(Token::Ident(name)) => METRIC_FIELDS
+                .iter()
+                .find(|field| **field == name)
+                .map(|field| Expr::Value(Value::Field(field)))
+                .ok_or_else(|| format!("unknown metric `{name}`")),
+            Some(token) => Err(format!("unexpected {token:?}")),
+            None => Err("unexpected end of condition".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(slot_lag: i64, rpc_error_rate: f64) -> ValidatorMetrics {
+        ValidatorMetrics {
+            slot_lag,
+            vote_success_rate: 0.99,
+            cpu_usage: 0.2,
+            ram_usage_gb: 16.0,
+            disk_usage_pct: 40.0,
+            rpc_qps: 100.0,
+            rpc_error_rate,
+            last_updated: 0,
+            field_timestamps: Default::default(),
+        }
+    }
+
+    #[test]
+    fn simple_rule_fires_above_its_threshold() {
+        let rule = DetectionRule::parse("slot_lag > 30 => slot_lag_high").unwrap();
+        assert_eq!(
+            rule.evaluate(&sample(31, 0.0)),
+            Some(IssueKind::SlotLagHigh)
+        );
+        assert_eq!(rule.evaluate(&sample(30, 0.0)), None);
+    }
+
+    #[test]
+    fn rule_can_report_a_custom_issue() {
+        let rule = DetectionRule::parse("slot_lag > 30 => custom:fork_suspected").unwrap();
+        let Some(IssueKind::Custom(custom)) = rule.evaluate(&sample(31, 0.0)) else {
+            panic!("expected a custom issue");
+        };
+        assert_eq!(custom.name(), "fork_suspected");
+        assert_eq!(IssueKind::Custom(custom).as_str(), "custom:fork_suspected");
+        assert_eq!(
+            serde_json::to_value(IssueKind::Custom(custom)).unwrap(),
+            "custom:fork_suspected"
+        );
+        let err = DetectionRule::parse("slot_lag > 30 => custom:Fork").unwrap_err();
+        assert!(err.contains("not snake_case"), "{err}");
+    }
+
+    #[test]
+    fn precedence_and_grouping() {
+        let rule = DetectionRule::parse(
+            "!(slot_lag < 10) && rpc_error_rate * 100 >= 2 || disk_usage_pct > 95 => rpc_overload",
+        )
+        .unwrap();
+        assert_eq!(
+            rule.evaluate(&sample(10, 0.03)),
+            Some(IssueKind::RpcOverload)
+        );
+        assert_eq!(rule.evaluate(&sample(9, 0.03)), None);
+        assert_eq!(rule.evaluate(&sample(10, 0.01)), None);
+    }
+
+    #[test]
+    fn malformed_rules_are_rejected() {
+        for (source, error) in [
+            ("slot_lag > 30", "no `=> <issue_kind>`"),
+            (
+                "slot_lag > 30 => slot_lag_huge",
+                "unknown issue kind `slot_lag_huge`",
+            ),
+            (
+                "slot_lagg > 30 => slot_lag_high",
+                "unknown metric `slot_lagg`",
+            ),
+            ("slot_lag + 30 => slot_lag_high", "expected a comparison"),
+            ("(slot_lag > 30 => slot_lag_high", "unclosed `(`"),
+            (
+                "slot_lag > 30 30 => slot_lag_high",
+                "unexpected Number(30.0)",
+            ),
+            (" => slot_lag_high", "empty condition"),
+        ] {
+            let err = DetectionRule::parse(source).unwrap_err();
+            assert!(err.contains(error), "{source}: {err}");
+        }
+    }
+
+    #[test]
+    fn config_with_an_invalid_rule_fails_to_load() {
+        let read = |rule: &str| {
+            crate::read_config(config::File::from_str(
+                &format!(
+                    "validators = []\nredis_url = \"redis://127.0.0.1:6379\"\n[detection]\nrules = [\"{rule}\"]\n"
+                ),
+                config::FileFormat::Toml,
+            ))
+        };
+        let loaded = read("slot_lag > 30 => slot_lag_high").unwrap();
+        assert_eq!(loaded.detection.rules[0].issue(), IssueKind::SlotLagHigh);
+        let err = format!("{:#}", read("slot_lag >> 30 => slot_lag_high").unwrap_err());
+        assert!(
+            err.contains("rule `slot_lag >> 30 => slot_lag_high`"),
+            "{err}"
+        );
+    }
+}
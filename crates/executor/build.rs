@@ -2,6 +2,9 @@ fn main() {
     tonic_build::configure()
         .build_server(true)
         .build_client(true)
+        // The agent can publish envelopes to a message queue as JSON.
+        .type_attribute("executor.v1.ActionEnvelope", "#[derive(serde::Serialize)]")
+        .type_attribute("executor.v1.RecoveryCheck", "#[derive(serde::Serialize)]")
         .compile(&["proto/executor.proto"], &["proto"])
         .expect("failed to compile executor proto");
 }
@@ -1,22 +1,50 @@
-use anyhow::Result;
-use common::{Action, ValidatorConfig, ValidatorMetrics};
+// tonic::Status is large by design; every handler returns it.
+#![allow(clippy::result_large_err)]
+
+use anyhow::{Context, Result};
+use axum::{extract::State, routing::get, Router};
+use common::{
+    now_ts, tokens_match, Action, ActionCommands, Config, HandlerTimeoutsConfig, StalenessConfig,
+    ValidatorConfig, ValidatorId, ValidatorIdsConfig, ValidatorMetrics, ValidatorMismatchPolicy,
+};
+use executor::history::ActionHistory;
+use executor::latency::ActionLatency;
+use executor::playbooks::PlaybookExecutions;
 use executor::proto::executor_server::{Executor, ExecutorServer};
 use executor::proto::{
-    ActionEnvelope, ActionResult, ConnectRequest, MetricsUpdate, MetricsWatchRequest, ReportAck,
+    ActionEnvelope, ActionHistoryRequest, ActionHistoryResponse, ActionRecord, ActionResult,
+    ConnectRequest, ControlSignal, MetricsSnapshot, MetricsUpdate, MetricsWatchRequest,
+    PendingAction, PendingActionsRequest, PendingActionsResponse, PlaybookExecutionsRequest,
+    PlaybookExecutionsResponse, RegisterValidatorRequest, ReportAck, ScrapeRequest, StepState,
+    ValidatorState, ValidatorStatesRequest, ValidatorStatesResponse, ValidatorToggleRequest,
 };
+use executor::registry;
+use executor::result_webhook::{ResultEvent, ResultWebhook};
+use executor::tls::{self, PeerIdentity};
+use futures_util::future;
 use futures_util::stream::StreamExt;
+use redis::aio::ConnectionManager;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::env;
+use std::fmt::Write as _;
+use std::future::Future;
 use std::net::SocketAddr;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::{broadcast, mpsc, Mutex};
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
 use tokio_stream::wrappers::{BroadcastStream, ReceiverStream};
 use tokio_stream::Stream;
 use tonic::{Request, Response, Status};
-use tracing::{error, info};
+use tracing::{debug, error, info, warn};
 
 const DEFAULT_GRPC_ADDR: &str = "0.0.0.0:50051";
+const DEFAULT_METRICS_ADDR: &str = "0.0.0.0:9102";
+/// A scrape request is only useful shortly after it was made.
+const SCRAPE_NOW_TTL_SECS: i64 = 60;
+const ACK_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
 
 type ActionStream = Pin<Box<dyn Stream<Item = Result<ActionEnvelope, Status>> + Send + 'static>>;
 type MetricsStream = Pin<Box<dyn Stream<Item = Result<MetricsUpdate, Status>> + Send + 'static>>;
@@ -34,61 +62,416 @@ async fn main() -> Result<()> {
         .unwrap_or_else(|_| DEFAULT_GRPC_ADDR.to_string())
         .parse()
         .expect("invalid EXECUTOR_LISTEN_ADDR");
+    let metrics_addr: SocketAddr = env::var("EXECUTOR_METRICS_ADDR")
+        .unwrap_or_else(|_| DEFAULT_METRICS_ADDR.to_string())
+        .parse()
+        .expect("invalid EXECUTOR_METRICS_ADDR");
 
-    let state = SharedState::new(cfg.validators.clone());
+    let mut state = SharedState::new(&cfg);
+    if let Some(registration) = &cfg.daemon.registration {
+        let bootstrap_token = env::var(&registration.bootstrap_token_env).with_context(|| {
+            format!(
+                "[daemon.registration] bootstrap token variable {} is not set",
+                registration.bootstrap_token_env
+            )
+        })?;
+        let store = if registration.persist {
+            let client = redis::Client::open(cfg.redis_url.clone())?;
+            Some(ConnectionManager::new(client).await?)
+        } else {
+            None
+        };
+        state = state.with_registration(
+            bootstrap_token,
+            store.clone(),
+            registration.allowed_tags.clone(),
+        );
+        if let Some(mut conn) = store {
+            let restored = registry::load(&mut conn).await?;
+            info!(
+                restored = restored.len(),
+                "restored self-registered validators"
+            );
+            state.restore_registered(restored).await;
+        }
+    }
+    tokio::spawn(serve_metrics(metrics_addr, state.clone()));
+    if cfg.daemon.ack_timeout_secs > 0 {
+        tokio::spawn(redeliver_unacked(state.clone()));
+    }
     let svc = ControlService { state };
 
-    info!("executor control plane listening on {}", listen_addr);
-    tonic::transport::Server::builder()
-        .add_service(ExecutorServer::new(svc))
-        .serve(listen_addr)
-        .await?;
+    let server = tonic::transport::Server::builder().add_service(ExecutorServer::new(svc));
+    match &cfg.daemon.tls {
+        Some(tls_cfg) => {
+            let tls_config = tls::server_config(tls_cfg)?;
+            let listener = tokio::net::TcpListener::bind(listen_addr).await?;
+            info!("executor control plane listening on {} (TLS)", listen_addr);
+            server
+                .serve_with_incoming(tls::incoming(
+                    listener,
+                    tls_config,
+                    Duration::from_secs(tls_cfg.handshake_timeout_secs),
+                ))
+                .await?;
+        }
+        None => {
+            info!("executor control plane listening on {}", listen_addr);
+            server.serve(listen_addr).await?;
+        }
+    }
     Ok(())
 }
 
+/// Prometheus scrape endpoint for the daemon's internal queues.
+async fn serve_metrics(addr: SocketAddr, state: SharedState) {
+    let app = Router::new()
+        .route("/metrics", get(export_metrics))
+        .with_state(state);
+    let result = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => {
+            info!("executor metrics listening on {}", addr);
+            axum::serve(listener, app).await
+        }
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        error!(?err, "metrics listener failed");
+    }
+}
+
+async fn export_metrics(State(state): State<SharedState>) -> String {
+    state.render_metrics().await
+}
+
+/// Sweep for delivered actions past `[daemon] ack_timeout_secs`.
+async fn redeliver_unacked(state: SharedState) {
+    let mut ticker = tokio::time::interval(ACK_SWEEP_INTERVAL);
+    loop {
+        ticker.tick().await;
+        state.requeue_unacked(now_ts()).await;
+    }
+}
+
 #[derive(Clone)]
 struct SharedState {
     inner: Arc<Mutex<StateInner>>,
     metrics_tx: broadcast::Sender<MetricsUpdate>,
+    broadcast_drops: Arc<BroadcastDrops>,
+    commands: Arc<ActionCommands>,
+    result_webhook: Option<ResultWebhook>,
+    /// Reported results, for `ListActionHistory`. Locked separately from
+    /// `inner` and never across an await.
+    action_history: Arc<Mutex<ActionHistory>>,
+    /// A `[daemon.tls] client_ca_path` is set: validator RPCs need a client
+    /// certificate issued to the validator they act for.
+    client_certs: bool,
+    token_auth: bool,
+    registration: Option<Registration>,
+    /// Applied to ids sent by clients; configured ids are normalized at load.
+    ids: ValidatorIdsConfig,
+    handler_timeouts: Arc<HandlerTimeoutsConfig>,
+    on_validator_mismatch: ValidatorMismatchPolicy,
+}
+
+/// Metrics updates that did not reach a subscriber.
+#[derive(Default)]
+struct BroadcastDrops {
+    /// Sent while no `SubscribeMetrics` stream was open. Benign, since the
+    /// sample is still kept for snapshots and polls.
+    no_receivers: AtomicU64,
+    /// Skipped by subscribers that fell more than the channel capacity behind.
+    lagged: AtomicU64,
+}
+
+/// `RegisterValidator` is enabled: callers must present `bootstrap_token`.
+#[derive(Clone)]
+struct Registration {
+    bootstrap_token: String,
+    store: Option<ConnectionManager>,
+    allowed_tags: Vec<String>,
 }
 
 struct StateInner {
     validators: HashMap<String, ValidatorConfig>,
+    /// Validators in `validators` that registered themselves rather than
+    /// being configured.
+    registered: HashSet<String>,
     clients: HashMap<String, mpsc::Sender<ActionEnvelope>>,
-    pending_actions: HashMap<String, VecDeque<ActionEnvelope>>,
-    latest_metrics: HashMap<String, ValidatorMetrics>,
+    /// Undelivered actions per validator, ordered by descending priority,
+    /// each plan's steps kept together.
+    pending_actions: HashMap<String, VecDeque<QueuedAction>>,
+    latest_metrics: HashMap<String, RetainedSample>,
+    /// When each validator's last `PublishMetrics` was accepted.
+    last_published: HashMap<String, Instant>,
+    min_publish_interval: Duration,
+    /// Samples dropped for coming within `min_publish_interval`.
+    throttled_publishes: u64,
+    /// Recent samples per validator, oldest first, for subscriber backfill.
+    metrics_history: HashMap<String, VecDeque<RetainedSample>>,
+    history_len: usize,
+    /// `seq` of the next accepted sample. Starts at the daemon's start time
+    /// in microseconds so it keeps increasing across restarts.
+    next_seq: u64,
+    /// Validators paused by an operator: no action delivery, no metrics fan-out.
+    disabled: HashSet<String>,
+    /// The disabled validators whose drain the agent's `[auto_undrain]` may
+    /// lift.
+    auto_undrain: HashSet<String>,
+    /// `[auto_undrain]` is set: keep fanning out disabled validators'
+    /// metrics so the agent can tell when they are healthy again.
+    forward_disabled_metrics: bool,
+    /// Idempotency keys of accepted actions and when they were accepted.
+    idempotency_keys: HashMap<String, i64>,
+    idempotency_window_secs: i64,
+    /// TTL for queued actions that don't set their own `ttl_secs`.
+    action_ttl_secs: i64,
+    /// Delivered actions awaiting their `ReportResult`, by idempotency key.
+    /// Empty while `ack_timeout_secs` is `0`.
+    awaiting_ack: HashMap<String, DeliveredAction>,
+    ack_timeout_secs: i64,
+    /// Per validator, the idempotency key of its delivered restart awaiting
+    /// a result, and the action stream it went out on.
+    restarting: HashMap<String, (String, mpsc::Sender<ActionEnvelope>)>,
+    unacked_redelivered: u64,
+    unacked_dropped: u64,
+    staleness: StalenessConfig,
+    /// Latest flagged clock drift per validator; absent while within bounds.
+    clock_drift: HashMap<String, i64>,
+    latency: ActionLatency,
+    /// When each validator last reported a successful restart.
+    last_restart: HashMap<String, i64>,
+    playbooks: PlaybookExecutions,
+    /// `0` means unlimited.
+    max_action_clients: usize,
+    max_metrics_subscribers: usize,
+    /// Cloned into every open `SubscribeMetrics` stream, so its strong count
+    /// less one is the number of subscribers.
+    metrics_subscribers: Arc<()>,
+}
+
+/// An accepted sample as fanned out: `update` has its token cleared and
+/// `received_at` and `seq` stamped.
+struct RetainedSample {
+    last_updated: i64,
+    update: MetricsUpdate,
+}
+
+struct QueuedAction {
+    envelope: ActionEnvelope,
+    enqueued_at: i64,
+    ttl_secs: i64,
+    /// Already delivered once without a result reported.
+    redelivered: bool,
+}
+
+struct DeliveredAction {
+    queued: QueuedAction,
+    delivered_at: i64,
+    /// The action stream it went out on.
+    client: mpsc::Sender<ActionEnvelope>,
+}
+
+impl QueuedAction {
+    fn expired(&self, now: i64) -> bool {
+        self.ttl_secs > 0 && now - self.enqueued_at > self.ttl_secs
+    }
 }
 
 impl SharedState {
-    fn new(validators: Vec<ValidatorConfig>) -> Self {
-        let (metrics_tx, _) = broadcast::channel(256);
-        let validators_map = validators
-            .into_iter()
-            .map(|cfg| (cfg.id.0.clone(), cfg))
+    fn new(cfg: &Config) -> Self {
+        let (metrics_tx, _) = broadcast::channel(cfg.daemon.metrics_broadcast_capacity);
+        let validators_map = cfg
+            .validators
+            .iter()
+            .map(|validator| (validator.id.0.clone(), validator.clone()))
             .collect();
         let inner = StateInner {
             validators: validators_map,
+            registered: HashSet::new(),
             clients: HashMap::new(),
             pending_actions: HashMap::new(),
             latest_metrics: HashMap::new(),
+            last_published: HashMap::new(),
+            min_publish_interval: Duration::from_millis(cfg.daemon.min_publish_interval_ms),
+            throttled_publishes: 0,
+            metrics_history: HashMap::new(),
+            history_len: cfg.daemon.metrics_history_len,
+            next_seq: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(1, |elapsed| elapsed.as_micros() as u64),
+            disabled: HashSet::new(),
+            auto_undrain: HashSet::new(),
+            forward_disabled_metrics: cfg.auto_undrain.is_some(),
+            idempotency_keys: HashMap::new(),
+            idempotency_window_secs: cfg.daemon.idempotency_window_secs,
+            action_ttl_secs: cfg.daemon.action_ttl_secs,
+            awaiting_ack: HashMap::new(),
+            ack_timeout_secs: cfg.daemon.ack_timeout_secs,
+            restarting: HashMap::new(),
+            unacked_redelivered: 0,
+            unacked_dropped: 0,
+            staleness: cfg.staleness.clone(),
+            clock_drift: HashMap::new(),
+            latency: ActionLatency::default(),
+            last_restart: HashMap::new(),
+            playbooks: PlaybookExecutions::new(cfg.daemon.playbook_retention_secs),
+            max_action_clients: cfg.daemon.max_action_clients,
+            max_metrics_subscribers: cfg.daemon.max_metrics_subscribers,
+            metrics_subscribers: Arc::new(()),
         };
         Self {
             inner: Arc::new(Mutex::new(inner)),
             metrics_tx,
+            broadcast_drops: Arc::default(),
+            commands: Arc::new(cfg.commands.clone()),
+            result_webhook: cfg.daemon.result_webhook.clone().map(ResultWebhook::spawn),
+            action_history: Arc::new(Mutex::new(ActionHistory::new(
+                cfg.daemon.action_history_len,
+            ))),
+            client_certs: cfg
+                .daemon
+                .tls
+                .as_ref()
+                .is_some_and(|tls| tls.client_ca_path.is_some()),
+            token_auth: cfg.daemon.tls.as_ref().is_none_or(|tls| tls.token_auth),
+            registration: None,
+            ids: cfg.validator_ids,
+            handler_timeouts: Arc::new(cfg.daemon.handler_timeouts.clone()),
+            on_validator_mismatch: cfg.daemon.on_validator_mismatch,
+        }
+    }
+
+    /// Accept `RegisterValidator` calls carrying `bootstrap_token`, saving
+    /// each registration to `store` when given. Registrations keep only the
+    /// tags in `allowed_tags`.
+    fn with_registration(
+        self,
+        bootstrap_token: String,
+        store: Option<ConnectionManager>,
+        allowed_tags: Vec<String>,
+    ) -> Self {
+        Self {
+            registration: Some(Registration {
+                bootstrap_token,
+                store,
+                allowed_tags,
+            }),
+            ..self
+        }
+    }
+
+    /// Add persisted registrations. Validators in the config keep their
+    /// configured entry.
+    async fn restore_registered(&self, validators: Vec<ValidatorConfig>) {
+        let mut inner = self.inner.lock().await;
+        for mut validator in validators {
+            if inner.validators.contains_key(&validator.id.0) {
+                continue;
+            }
+            if let Some(registration) = &self.registration {
+                validator
+                    .tags
+                    .retain(|tag| registration.allowed_tags.contains(tag));
+            }
+            inner.registered.insert(validator.id.0.clone());
+            inner.validators.insert(validator.id.0.clone(), validator);
+        }
+    }
+
+    /// Add a validator at runtime. Registering again with the same auth token
+    /// is a no-op, so clients can register on every connect.
+    async fn register(&self, request: RegisterValidatorRequest) -> Result<(), Status> {
+        let Some(registration) = &self.registration else {
+            return Err(Status::failed_precondition(
+                "validator self-registration is disabled",
+            ));
+        };
+        if !tokens_match(&request.bootstrap_token, &registration.bootstrap_token) {
+            return Err(Status::unauthenticated("invalid bootstrap token"));
+        }
+        if request.validator_id.trim().is_empty() {
+            return Err(Status::invalid_argument("validator_id is required"));
+        }
+        if self.token_auth && request.auth_token.is_empty() {
+            return Err(Status::invalid_argument("auth_token is required"));
+        }
+        let validator = ValidatorConfig {
+            id: ValidatorId(self.ids.normalize(&request.validator_id)),
+            host: request.host,
+            prometheus_url: request.prometheus_url,
+            auth_token: request.auth_token,
+            allowed_scripts: None,
+            tags: request
+                .tags
+                .into_iter()
+                .filter(|tag| registration.allowed_tags.contains(tag))
+                .collect(),
+            labels: Default::default(),
+            agentic_allowed_actions: None,
+            metrics_fallbacks: Vec::new(),
+        };
+        {
+            let mut inner = self.inner.lock().await;
+            match inner.validators.get(&validator.id.0) {
+                Some(existing) if tokens_match(&validator.auth_token, &existing.auth_token) => {
+                    return Ok(())
+                }
+                Some(_) => {
+                    return Err(Status::already_exists(format!(
+                        "validator {} is already registered",
+                        validator.id.0
+                    )))
+                }
+                None => {
+                    inner.registered.insert(validator.id.0.clone());
+                    inner
+                        .validators
+                        .insert(validator.id.0.clone(), validator.clone());
+                }
+            }
+        }
+        info!(validator = validator.id.0, "validator self-registered");
+        if let Some(mut store) = registration.store.clone() {
+            if let Err(err) = registry::save(&mut store, &validator).await {
+                error!(
+                    validator = validator.id.0,
+                    ?err,
+                    "failed to persist validator registration"
+                );
+            }
         }
+        Ok(())
     }
 
     async fn authorize(&self, validator_id: &str, token: &str) -> Result<ValidatorConfig, Status> {
+        let normalized = self.ids.normalize(validator_id);
+        if normalized != validator_id {
+            warn!(
+                requested = validator_id,
+                normalized, "normalized validator id sent by client"
+            );
+        }
         let inner = self.inner.lock().await;
-        let Some(cfg) = inner.validators.get(validator_id) else {
+        let Some(cfg) = inner.validators.get(&normalized) else {
             return Err(Status::not_found("validator not registered"));
         };
-        if cfg.auth_token != token {
+        if self.token_auth && !tokens_match(token, &cfg.auth_token) {
             return Err(Status::unauthenticated("invalid auth token"));
         }
         Ok(cfg.clone())
     }
 
+    fn check_peer(&self, peer: Option<&PeerIdentity>, validator_id: &str) -> Result<(), Status> {
+        let validator_id = self.ids.normalize(validator_id);
+        if self.client_certs && !peer.is_some_and(|peer| peer.matches(&validator_id)) {
+            return Err(Status::unauthenticated(format!(
+                "client certificate is not issued to validator {validator_id}"
+            )));
+        }
+        Ok(())
+    }
+
     async fn attach_client(
         &self,
         validator_id: String,
@@ -98,88 +481,857 @@ impl SharedState {
         if !inner.validators.contains_key(&validator_id) {
             return Err(Status::not_found("validator not registered"));
         }
+        // A reconnecting validator replaces its old stream rather than taking
+        // another slot.
+        inner
+            .clients
+            .retain(|id, sender| *id != validator_id && !sender.is_closed());
+        let limit = inner.max_action_clients;
+        if limit > 0 && inner.clients.len() >= limit {
+            return Err(Status::resource_exhausted(format!(
+                "action stream limit reached ({limit} clients connected)"
+            )));
+        }
         inner.clients.insert(validator_id.clone(), sender);
         inner.flush(&validator_id);
         Ok(())
     }
 
-    async fn enqueue_action(&self, action: ActionEnvelope) -> Result<(), Status> {
+    async fn enqueue_action(&self, mut action: ActionEnvelope) -> Result<(), Status> {
         let validator_id = action.validator_id.clone();
         let mut inner = self.inner.lock().await;
         if !inner.validators.contains_key(&validator_id) {
             return Err(Status::not_found("validator not registered"));
         }
-        inner
+        // Held actions would all land at once on re-enable, long after they
+        // were decided.
+        if inner.disabled.contains(&validator_id) {
+            info!(
+                validator = validator_id,
+                action = action.action_json,
+                result = "rejected",
+                "validator is disabled, not queueing action"
+            );
+            return Err(Status::failed_precondition("validator is disabled"));
+        }
+        let kind = serde_json::from_str::<Action>(&action.action_json)
+            .map_or("unknown", |parsed| parsed.kind());
+        // Two issues remediated in overlapping ticks would otherwise bounce
+        // the validator twice. A lone restart is rejected before its key is
+        // taken, so it can be submitted again once this one is done.
+        let already_restarting =
+            kind == "restart_validator" && inner.restart_in_flight(&validator_id);
+        if already_restarting && action.plan_id.is_empty() {
+            info!(
+                validator = validator_id,
+                result = "already restarting",
+                "restart already in flight, dropping duplicate"
+            );
+            return Err(Status::already_exists(format!(
+                "already restarting: {validator_id} has a restart in flight"
+            )));
+        }
+        let now = now_ts();
+        if !action.idempotency_key.is_empty() && !inner.accept_key(&action.idempotency_key, now) {
+            info!(
+                validator = validator_id,
+                key = action.idempotency_key,
+                "duplicate action dropped"
+            );
+            return Ok(());
+        }
+        // A plan's restart is still delivered, refused, so the client skips
+        // its dependents and rolls back the steps it already ran.
+        if already_restarting {
+            info!(
+                validator = validator_id,
+                plan = action.plan_id,
+                step = action.step,
+                result = "already restarting",
+                "restart already in flight, refusing plan step"
+            );
+            action.refused = format!("already restarting: {validator_id} has a restart in flight");
+        }
+        if !action.idempotency_key.is_empty() {
+            inner
+                .latency
+                .submitted(&action.idempotency_key, kind, Instant::now());
+        }
+        if !action.plan_id.is_empty() {
+            inner.playbooks.queued(
+                &validator_id,
+                &action.plan_id,
+                &action.issue,
+                action.step,
+                kind,
+                now,
+            );
+        }
+        let ttl_secs = if action.ttl_secs > 0 {
+            action.ttl_secs
+        } else {
+            inner.action_ttl_secs
+        };
+        let queue = inner
             .pending_actions
             .entry(validator_id.clone())
-            .or_default()
-            .push_back(action);
+            .or_default();
+        // A plan's steps stay together and in step order, queued at the
+        // priority of its first step.
+        let same_plan = |queued: &QueuedAction| {
+            !action.plan_id.is_empty() && queued.envelope.plan_id == action.plan_id
+        };
+        let position = match queue.iter().position(same_plan) {
+            Some(first) => {
+                first
+                    + queue
+                        .iter()
+                        .skip(first)
+                        .take_while(|queued| {
+                            same_plan(queued) && queued.envelope.step <= action.step
+                        })
+                        .count()
+            }
+            None => {
+                // Ahead of the first entry of lower priority, counting the
+                // rest of a plan at its first step's.
+                let mut plan: Option<(&str, i32)> = None;
+                queue
+                    .iter()
+                    .position(|queued| {
+                        let priority = match plan {
+                            Some((id, priority))
+                                if !id.is_empty() && id == queued.envelope.plan_id =>
+                            {
+                                priority
+                            }
+                            _ => queued.envelope.priority,
+                        };
+                        plan = Some((&queued.envelope.plan_id, priority));
+                        priority < action.priority
+                    })
+                    .unwrap_or(queue.len())
+            }
+        };
+        queue.insert(
+            position,
+            QueuedAction {
+                envelope: action,
+                enqueued_at: now,
+                ttl_secs,
+                redelivered: false,
+            },
+        );
         inner.flush(&validator_id);
         Ok(())
     }
 
-    async fn record_metrics(&self, mut update: MetricsUpdate) -> Result<(), Status> {
+    async fn record_metrics(&self, update: MetricsUpdate) -> Result<(), Status> {
+        self.record_metrics_at(update, now_ts()).await
+    }
+
+    async fn record_metrics_at(
+        &self,
+        mut update: MetricsUpdate,
+        received_at: i64,
+    ) -> Result<(), Status> {
         let metrics: ValidatorMetrics = serde_json::from_str(&update.metrics_json)
             .map_err(|err| Status::invalid_argument(format!("invalid metrics payload: {err}")))?;
+        update.validator_id = self.ids.normalize(&update.validator_id);
+        let mut inner = self.inner.lock().await;
+        let Some(cfg) = inner.validators.get(&update.validator_id) else {
+            return Err(Status::not_found("validator not registered"));
+        };
+        if self.token_auth && !tokens_match(&update.auth_token, &cfg.auth_token) {
+            return Err(Status::unauthenticated("invalid auth token"));
+        }
+        if inner.disabled.contains(&update.validator_id) && !inner.forward_disabled_metrics {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let min_interval = inner.min_publish_interval;
+        // Dropped with an OK, so the client's retries and triggered
+        // publishes do not turn a burst into errors.
+        if let Some(last) = inner.last_published.get(&update.validator_id) {
+            if now.duration_since(*last) < min_interval {
+                debug!(
+                    validator = update.validator_id,
+                    min_interval_ms = min_interval.as_millis() as u64,
+                    "sample published too soon after the last one, dropping it"
+                );
+                inner.throttled_publishes += 1;
+                return Ok(());
+            }
+        }
+        inner
+            .last_published
+            .insert(update.validator_id.clone(), now);
+        match inner
+            .staleness
+            .clock_drift(metrics.last_updated, received_at)
         {
-            let mut inner = self.inner.lock().await;
-            let Some(cfg) = inner.validators.get(&update.validator_id) else {
-                return Err(Status::not_found("validator not registered"));
-            };
-            if cfg.auth_token != update.auth_token {
-                return Err(Status::unauthenticated("invalid auth token"));
+            Some(drift) => {
+                warn!(
+                    validator = update.validator_id,
+                    drift_secs = drift,
+                    "validator clock drift: last_updated is far from receive time"
+                );
+                inner.clock_drift.insert(update.validator_id.clone(), drift);
+            }
+            None => {
+                inner.clock_drift.remove(&update.validator_id);
             }
-            inner
-                .latest_metrics
-                .insert(update.validator_id.clone(), metrics);
         }
         update.auth_token.clear();
-        let _ = self.metrics_tx.send(update);
+        update.received_at = received_at;
+        update.seq = inner.next_seq;
+        inner.next_seq += 1;
+        let sample = || RetainedSample {
+            last_updated: metrics.last_updated,
+            update: update.clone(),
+        };
+        let history_len = inner.history_len;
+        if history_len > 0 {
+            let history = inner
+                .metrics_history
+                .entry(update.validator_id.clone())
+                .or_default();
+            if history.len() == history_len {
+                history.pop_front();
+            }
+            history.push_back(sample());
+        }
+        inner
+            .latest_metrics
+            .insert(update.validator_id.clone(), sample());
+        // Sent under the lock so subscribers see samples in `seq` order.
+        let validator_id = update.validator_id.clone();
+        if self.metrics_tx.send(update).is_err() {
+            self.broadcast_drops
+                .no_receivers
+                .fetch_add(1, Ordering::Relaxed);
+            debug!(
+                validator = validator_id,
+                "no metrics subscribers; update not broadcast"
+            );
+        }
         Ok(())
     }
 
-    async fn snapshot(&self, filter: &HashSet<String>) -> Vec<MetricsUpdate> {
+    /// The latest sample per validator, only those newer than `since` when it
+    /// is set.
+    async fn snapshot(&self, filter: &SubscriberFilter, since: i64) -> Vec<MetricsUpdate> {
         let inner = self.inner.lock().await;
-        let include_all = filter.is_empty();
         inner
             .latest_metrics
             .iter()
-            .filter_map(|(id, metrics)| {
-                if include_all || filter.contains(id) {
-                    Some(MetricsUpdate {
-                        validator_id: id.clone(),
-                        auth_token: String::new(),
-                        metrics_json: serde_json::to_string(metrics).unwrap_or_default(),
-                    })
-                } else {
+            .filter(|(id, sample)| {
+                filter.matches(id) && (since <= 0 || sample.last_updated > since)
+            })
+            .map(|(_, sample)| sample.update.clone())
+            .collect()
+    }
+
+    /// Retained samples newer than `since`, oldest first across validators.
+    async fn backfill(&self, filter: &SubscriberFilter, since: i64) -> Vec<MetricsUpdate> {
+        let inner = self.inner.lock().await;
+        let mut samples: Vec<&RetainedSample> = inner
+            .metrics_history
+            .iter()
+            .filter(|(id, _)| filter.matches(id))
+            .flat_map(|(_, history)| history.iter())
+            .filter(|sample| sample.last_updated > since)
+            .collect();
+        samples.sort_by_key(|sample| sample.last_updated);
+        samples
+            .into_iter()
+            .map(|sample| sample.update.clone())
+            .collect()
+    }
+
+    /// Samples with a `seq` greater than `after_seq`, oldest first: from the
+    /// retained history, or the latest per validator without one.
+    async fn replay_after(&self, filter: &SubscriberFilter, after_seq: u64) -> Vec<MetricsUpdate> {
+        let inner = self.inner.lock().await;
+        let mut samples: Vec<&MetricsUpdate> = if inner.history_len > 0 {
+            inner
+                .metrics_history
+                .iter()
+                .filter(|(id, _)| filter.matches(id))
+                .flat_map(|(_, history)| history.iter().map(|sample| &sample.update))
+                .filter(|update| update.seq > after_seq)
+                .collect()
+        } else {
+            inner
+                .latest_metrics
+                .iter()
+                .filter(|(id, _)| filter.matches(id))
+                .map(|(_, sample)| &sample.update)
+                .filter(|update| update.seq > after_seq)
+                .collect()
+        };
+        samples.sort_by_key(|update| update.seq);
+        samples.into_iter().cloned().collect()
+    }
+
+    /// Build a subscriber stream: optional snapshot or backfill followed by live updates.
+    /// The broadcast receiver is created before the snapshot is taken so no
+    /// update published in between is missed.
+    async fn subscribe(&self, request: MetricsWatchRequest) -> Result<MetricsStream, Status> {
+        let slot = {
+            let inner = self.inner.lock().await;
+            let limit = inner.max_metrics_subscribers;
+            if limit > 0 && Arc::strong_count(&inner.metrics_subscribers) > limit {
+                return Err(Status::resource_exhausted(format!(
+                    "metrics subscriber limit reached ({limit} subscribers connected)"
+                )));
+            }
+            inner.metrics_subscribers.clone()
+        };
+        let filter = SubscriberFilter::new(request.validator_ids);
+        let drops = self.broadcast_drops.clone();
+        let receiver = self.metrics_tx.subscribe();
+        let snapshot = if request.after_seq > 0 {
+            self.replay_after(&filter, request.after_seq).await
+        } else if request.backfill_since > 0 {
+            self.backfill(&filter, request.backfill_since).await
+        } else if request.include_snapshot {
+            self.snapshot(&filter, 0).await
+        } else {
+            Vec::new()
+        };
+
+        let live = BroadcastStream::new(receiver).filter_map(move |event| {
+            // Held until the stream is dropped.
+            let _slot = &slot;
+            future::ready(match event {
+                Ok(mut update) if filter.matches(&update.validator_id) => {
+                    update.auth_token.clear();
+                    Some(Ok(update))
+                }
+                Ok(_) => None,
+                Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                    drops.lagged.fetch_add(skipped, Ordering::Relaxed);
+                    warn!(skipped, "metrics subscriber lagged; oldest updates skipped");
                     None
                 }
             })
+        });
+        Ok(Box::pin(
+            tokio_stream::iter(snapshot.into_iter().map(Ok)).chain(live),
+        ))
+    }
+
+    /// One `GetAllMetrics` poll: the samples after `after_seq` when set, else
+    /// the retained samples newer than `backfill_since`, or the latest sample
+    /// per validator when neither is. Without retained history, only latest
+    /// samples past the watermark are returned.
+    async fn poll(&self, request: MetricsWatchRequest) -> Vec<MetricsUpdate> {
+        let filter = SubscriberFilter::new(request.validator_ids);
+        if request.after_seq > 0 {
+            return self.replay_after(&filter, request.after_seq).await;
+        }
+        let since = request.backfill_since;
+        let has_history = self.inner.lock().await.history_len > 0;
+        if since > 0 && has_history {
+            self.backfill(&filter, since).await
+        } else {
+            self.snapshot(&filter, since).await
+        }
+    }
+
+    /// Enable or disable `validator_id`. `auto_undrain` applies to a disable:
+    /// whether the agent may lift it.
+    async fn set_enabled(
+        &self,
+        validator_id: &str,
+        enabled: bool,
+        auto_undrain: bool,
+    ) -> Result<(), Status> {
+        let mut inner = self.inner.lock().await;
+        if !inner.validators.contains_key(validator_id) {
+            return Err(Status::not_found("validator not registered"));
+        }
+        if enabled {
+            inner.disabled.remove(validator_id);
+            inner.auto_undrain.remove(validator_id);
+            inner.flush(validator_id);
+        } else {
+            inner.disabled.insert(validator_id.to_string());
+            if auto_undrain {
+                inner.auto_undrain.insert(validator_id.to_string());
+            } else {
+                inner.auto_undrain.remove(validator_id);
+            }
+            let now = now_ts();
+            let dropped = inner
+                .pending_actions
+                .remove(validator_id)
+                .unwrap_or_default();
+            for queued in &dropped {
+                inner.playbooks.finished(
+                    &queued.envelope.plan_id,
+                    queued.envelope.step,
+                    StepState::Failed,
+                    "dropped: validator disabled before delivery",
+                    now,
+                );
+            }
+            if !dropped.is_empty() {
+                info!(
+                    validator = validator_id,
+                    dropped = dropped.len(),
+                    "dropped undelivered actions of a disabled validator"
+                );
+            }
+        }
+        info!(
+            validator = validator_id,
+            enabled,
+            auto_undrain = !enabled && auto_undrain,
+            "validator automation toggled"
+        );
+        Ok(())
+    }
+
+    async fn validator_states(&self) -> Vec<ValidatorState> {
+        let inner = self.inner.lock().await;
+        let mut states: Vec<ValidatorState> = inner
+            .validators
+            .iter()
+            .map(|(id, cfg)| ValidatorState {
+                validator_id: id.clone(),
+                enabled: !inner.disabled.contains(id),
+                connected: inner.clients.contains_key(id),
+                last_restart_at: inner.last_restart.get(id).copied().unwrap_or_default(),
+                registered: inner.registered.contains(id),
+                host: cfg.host.clone(),
+                prometheus_url: cfg.prometheus_url.clone(),
+                tags: cfg.tags.clone(),
+                auto_undrain: inner.auto_undrain.contains(id),
+            })
+            .collect();
+        states.sort_by(|a, b| a.validator_id.cmp(&b.validator_id));
+        states
+    }
+
+    /// Note a successful restart reported at `at`, for `ListValidatorStates`.
+    /// A restart of `validator_id` reported its result: another may follow.
+    async fn restart_reported(&self, validator_id: &str, action_id: &str) {
+        let mut inner = self.inner.lock().await;
+        if inner
+            .restarting
+            .get(validator_id)
+            .is_some_and(|(key, _)| key == action_id)
+        {
+            inner.restarting.remove(validator_id);
+        }
+    }
+
+    async fn restart_completed(&self, validator_id: &str, at: i64) {
+        self.inner
+            .lock()
+            .await
+            .last_restart
+            .insert(validator_id.to_string(), at);
+    }
+
+    /// Undelivered actions for `validator_id`, or every validator when it is
+    /// empty, by validator then delivery order.
+    async fn pending(&self, validator_id: &str, now: i64) -> Vec<PendingAction> {
+        let inner = self.inner.lock().await;
+        let mut ids: Vec<&String> = inner
+            .pending_actions
+            .keys()
+            .filter(|id| validator_id.is_empty() || *id == validator_id)
+            .collect();
+        ids.sort();
+        ids.into_iter()
+            .flat_map(|id| &inner.pending_actions[id])
+            .map(|queued| PendingAction {
+                validator_id: queued.envelope.validator_id.clone(),
+                kind: serde_json::from_str::<Action>(&queued.envelope.action_json)
+                    .map_or("unknown", |parsed| parsed.kind())
+                    .to_string(),
+                priority: queued.envelope.priority,
+                action_id: queued.envelope.idempotency_key.clone(),
+                age_secs: now - queued.enqueued_at,
+            })
             .collect()
     }
 
+    /// Record a reported step of a playbook run: its start, its outcome, or
+    /// the outcome of its compensation.
+    async fn step_reported(&self, result: &ActionResult, now: i64) {
+        let mut inner = self.inner.lock().await;
+        let playbooks = &mut inner.playbooks;
+        if result.started {
+            playbooks.started(&result.plan_id, result.step, now);
+            return;
+        }
+        if result.rollback {
+            playbooks.rolled_back(&result.plan_id, result.step, result.success, now);
+            return;
+        }
+        let state = if result.skipped {
+            StepState::Skipped
+        } else if result.success {
+            StepState::Succeeded
+        } else {
+            StepState::Failed
+        };
+        playbooks.finished(&result.plan_id, result.step, state, &result.message, now);
+    }
+
+    /// Close out the submit-to-result latency of `action_id`, if it was
+    /// submitted through this daemon.
+    async fn action_completed(&self, action_id: &str, at: Instant) -> Option<Duration> {
+        let mut inner = self.inner.lock().await;
+        inner.awaiting_ack.remove(action_id);
+        inner.latency.completed(action_id, at)
+    }
+
+    /// Requeue, at the front of their queue, delivered actions with no result
+    /// after `ack_timeout_secs` whose action stream has since gone. While it
+    /// is still open the client may be running the action, so it is left
+    /// alone. An action already redelivered is dropped instead, so a
+    /// validator that never reports does not loop forever.
+    async fn requeue_unacked(&self, now: i64) {
+        let mut inner = self.inner.lock().await;
+        let timeout = inner.ack_timeout_secs;
+        if timeout <= 0 {
+            return;
+        }
+        let inner = &mut *inner;
+        let clients = &inner.clients;
+        let mut overdue: Vec<(String, String, i32, String)> = inner
+            .awaiting_ack
+            .iter()
+            .filter(|(_, delivered)| {
+                let connected = clients
+                    .get(&delivered.queued.envelope.validator_id)
+                    .is_some_and(|client| {
+                        client.same_channel(&delivered.client) && !client.is_closed()
+                    });
+                now - delivered.delivered_at > timeout && !connected
+            })
+            .map(|(key, delivered)| {
+                let envelope = &delivered.queued.envelope;
+                (
+                    envelope.validator_id.clone(),
+                    envelope.plan_id.clone(),
+                    envelope.step,
+                    key.clone(),
+                )
+            })
+            .collect();
+        // Pushed to the front last step first, so a plan keeps its order.
+        overdue.sort_unstable_by(|a, b| b.cmp(a));
+        let mut validators = HashSet::new();
+        for (_, _, _, key) in overdue {
+            let Some(DeliveredAction { mut queued, .. }) = inner.awaiting_ack.remove(&key) else {
+                continue;
+            };
+            let validator_id = queued.envelope.validator_id.clone();
+            // A step whose prerequisite failed would be skipped by the
+            // client, which on the next stream no longer knows it did.
+            let envelope = &mut queued.envelope;
+            let state = |dep: &i32| inner.playbooks.state(&envelope.plan_id, *dep);
+            if let Some(dep) = envelope
+                .depends_on
+                .iter()
+                .find(|dep| matches!(state(dep), Some(StepState::Failed | StepState::Skipped)))
+            {
+                let message = format!("skipped: prerequisite step {dep} did not succeed");
+                info!(
+                    validator = validator_id,
+                    key, message, "not redelivering action"
+                );
+                inner.playbooks.finished(
+                    &envelope.plan_id,
+                    envelope.step,
+                    StepState::Skipped,
+                    &message,
+                    now,
+                );
+                continue;
+            }
+            if queued.redelivered {
+                warn!(
+                    validator = validator_id,
+                    key,
+                    action = envelope.action_json,
+                    result = "unacked",
+                    "no result reported after redelivery, dropping action"
+                );
+                inner.unacked_dropped += 1;
+                inner.playbooks.finished(
+                    &envelope.plan_id,
+                    envelope.step,
+                    StepState::Failed,
+                    "unacked: no result reported after redelivery",
+                    now,
+                );
+                continue;
+            }
+            // Nor does it know of the prerequisites that succeeded, so it
+            // would park the step for them forever.
+            let succeeded: Vec<i32> = envelope
+                .depends_on
+                .iter()
+                .copied()
+                .filter(|dep| state(dep) == Some(StepState::Succeeded))
+                .collect();
+            envelope.depends_on.retain(|dep| !succeeded.contains(dep));
+            info!(
+                validator = validator_id,
+                key, "no result reported within ack timeout, redelivering action"
+            );
+            queued.redelivered = true;
+            inner.unacked_redelivered += 1;
+            inner
+                .pending_actions
+                .entry(validator_id.clone())
+                .or_default()
+                .push_front(queued);
+            validators.insert(validator_id);
+        }
+        for validator_id in validators {
+            inner.flush(&validator_id);
+        }
+    }
+
+    /// Queue depths and subscriber state in the Prometheus text format.
+    async fn render_metrics(&self) -> String {
+        let inner = self.inner.lock().await;
+        let mut ids: Vec<&String> = inner.validators.keys().collect();
+        ids.sort();
+
+        let mut out = String::new();
+        out.push_str("# HELP executor_pending_actions Actions queued but not yet delivered to the validator.\n");
+        out.push_str("# TYPE executor_pending_actions gauge\n");
+        for id in ids {
+            let depth = inner.pending_actions.get(id).map_or(0, VecDeque::len);
+            let _ = writeln!(
+                out,
+                "executor_pending_actions{{validator=\"{id}\"}} {depth}"
+            );
+        }
+        let gauges = [
+            (
+                "executor_connected_clients",
+                "Validator clients with an open action stream.",
+                inner.clients.len(),
+            ),
+            (
+                "executor_metrics_subscribers",
+                "Open SubscribeMetrics streams.",
+                self.metrics_tx.receiver_count(),
+            ),
+            (
+                "executor_metrics_broadcast_backlog",
+                "Metrics updates the slowest subscriber has not yet received.",
+                self.metrics_tx.len(),
+            ),
+        ];
+        for (name, help, value) in gauges {
+            let _ = writeln!(
+                out,
+                "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}"
+            );
+        }
+        out.push_str("# HELP executor_clock_drift_secs Receive time minus client last_updated, for validators over the drift limit.\n");
+        out.push_str("# TYPE executor_clock_drift_secs gauge\n");
+        let mut drifting: Vec<_> = inner.clock_drift.iter().collect();
+        drifting.sort();
+        for (id, drift) in drifting {
+            let _ = writeln!(
+                out,
+                "executor_clock_drift_secs{{validator=\"{id}\"}} {drift}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "# HELP executor_actions_awaiting_ack Delivered actions with no result reported yet.\n# TYPE executor_actions_awaiting_ack gauge\nexecutor_actions_awaiting_ack {}",
+            inner.awaiting_ack.len()
+        );
+        for (name, help, value) in [
+            (
+                "executor_unacked_actions_redelivered_total",
+                "Actions redelivered after no result was reported within the ack timeout.",
+                inner.unacked_redelivered,
+            ),
+            (
+                "executor_unacked_actions_dropped_total",
+                "Actions dropped after no result was reported for their redelivery.",
+                inner.unacked_dropped,
+            ),
+            (
+                "executor_metrics_publishes_throttled_total",
+                "Samples dropped for arriving within min_publish_interval_ms of the last.",
+                inner.throttled_publishes,
+            ),
+        ] {
+            let _ = writeln!(
+                out,
+                "# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}"
+            );
+        }
+        let counters = [
+            (
+                "executor_metrics_broadcast_no_receivers_total",
+                "Metrics updates broadcast while no subscriber was connected.",
+                &self.broadcast_drops.no_receivers,
+            ),
+            (
+                "executor_metrics_broadcast_lagged_total",
+                "Metrics updates skipped by subscribers that fell too far behind.",
+                &self.broadcast_drops.lagged,
+            ),
+        ];
+        for (name, help, value) in counters {
+            let _ = writeln!(
+                out,
+                "# HELP {name} {help}\n# TYPE {name} counter\n{name} {}",
+                value.load(Ordering::Relaxed)
+            );
+        }
+        inner.latency.render(&mut out);
+        out
+    }
+
+    #[cfg(test)]
     fn metrics_sender(&self) -> broadcast::Sender<MetricsUpdate> {
         self.metrics_tx.clone()
     }
 }
 
+/// Which validators a metrics subscriber wants. Evaluated synchronously for
+/// every broadcast event, so it must stay cheap.
+#[derive(Debug)]
+enum SubscriberFilter {
+    All,
+    Only(HashSet<String>),
+}
+
+impl SubscriberFilter {
+    fn new(validator_ids: Vec<String>) -> Self {
+        if validator_ids.is_empty() {
+            Self::All
+        } else {
+            Self::Only(validator_ids.into_iter().collect())
+        }
+    }
+
+    fn matches(&self, validator_id: &str) -> bool {
+        match self {
+            Self::All => true,
+            Self::Only(ids) => ids.contains(validator_id),
+        }
+    }
+}
+
 impl StateInner {
+    /// Whether a restart of `validator_id` is queued, or delivered on its
+    /// current action stream and still awaiting its result.
+    fn restart_in_flight(&self, validator_id: &str) -> bool {
+        let is_restart = |envelope: &ActionEnvelope| {
+            envelope.validator_id == validator_id
+                && envelope.refused.is_empty()
+                && matches!(
+                    serde_json::from_str(&envelope.action_json),
+                    Ok(Action::RestartValidator { .. })
+                )
+        };
+        self.pending_actions
+            .get(validator_id)
+            .is_some_and(|queue| queue.iter().any(|queued| is_restart(&queued.envelope)))
+            || self
+                .awaiting_ack
+                .values()
+                .any(|delivered| is_restart(&delivered.queued.envelope))
+            || self
+                .restarting
+                .get(validator_id)
+                .zip(self.clients.get(validator_id))
+                .is_some_and(|((_, delivered_on), client)| {
+                    client.same_channel(delivered_on) && !client.is_closed()
+                })
+    }
+
+    /// Record `key` unless it was already accepted within the window.
+    fn accept_key(&mut self, key: &str, now: i64) -> bool {
+        let window = self.idempotency_window_secs;
+        self.idempotency_keys
+            .retain(|_, accepted_at| now - *accepted_at < window);
+        if self.idempotency_keys.contains_key(key) {
+            return false;
+        }
+        self.idempotency_keys.insert(key.to_string(), now);
+        true
+    }
+
     fn flush(&mut self, validator_id: &str) {
+        if self.disabled.contains(validator_id) {
+            return;
+        }
         let Some(sender) = self.clients.get_mut(validator_id) else {
             return;
         };
         let Some(queue) = self.pending_actions.get_mut(validator_id) else {
             return;
         };
-        while let Some(action) = queue.pop_front() {
-            match sender.try_send(action.clone()) {
-                Ok(_) => continue,
-                Err(mpsc::error::TrySendError::Full(item)) => {
-                    queue.push_front(item);
+        let now = now_ts();
+        while let Some(queued) = queue.pop_front() {
+            if queued.expired(now) {
+                info!(
+                    validator = validator_id,
+                    action = queued.envelope.action_json,
+                    age_secs = now - queued.enqueued_at,
+                    result = "expired",
+                    "queued action outlived its ttl, dropping instead of delivering"
+                );
+                self.playbooks.finished(
+                    &queued.envelope.plan_id,
+                    queued.envelope.step,
+                    StepState::Failed,
+                    "expired: outlived its ttl before delivery",
+                    now,
+                );
+                continue;
+            }
+            match sender.try_send(queued.envelope.clone()) {
+                Ok(_) => {
+                    self.playbooks
+                        .delivered(&queued.envelope.plan_id, queued.envelope.step, now);
+                    if queued.envelope.refused.is_empty()
+                        && matches!(
+                            serde_json::from_str(&queued.envelope.action_json),
+                            Ok(Action::RestartValidator { .. })
+                        )
+                    {
+                        self.restarting.insert(
+                            validator_id.to_string(),
+                            (queued.envelope.idempotency_key.clone(), sender.clone()),
+                        );
+                    }
+                    if self.ack_timeout_secs > 0 && !queued.envelope.idempotency_key.is_empty() {
+                        self.awaiting_ack.insert(
+                            queued.envelope.idempotency_key.clone(),
+                            DeliveredAction {
+                                queued,
+                                delivered_at: now,
+                                client: sender.clone(),
+                            },
+                        );
+                    }
+                }
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    queue.push_front(queued);
                     break;
                 }
-                Err(mpsc::error::TrySendError::Closed(item)) => {
-                    queue.push_front(item);
+                Err(mpsc::error::TrySendError::Closed(_)) => {
+                    queue.push_front(queued);
                     self.clients.remove(validator_id);
                     break;
                 }
@@ -192,137 +1344,1705 @@ struct ControlService {
     state: SharedState,
 }
 
-#[tonic::async_trait]
-impl Executor for ControlService {
-    type StreamActionsStream = ActionStream;
+impl ControlService {
+    /// Run `method`'s handler under its `[daemon.handler_timeouts]` limit, so
+    /// a handler stuck behind a lock fails the call instead of piling up.
+    async fn timed<T>(
+        &self,
+        method: &'static str,
+        handler: impl Future<Output = Result<T, Status>>,
+    ) -> Result<T, Status> {
+        let Some(limit) = self.state.handler_timeouts.for_method(method) else {
+            return handler.await;
+        };
+        match tokio::time::timeout(limit, handler).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!(
+                    method,
+                    timeout_ms = limit.as_millis() as u64,
+                    "handler ran past its timeout"
+                );
+                Err(Status::deadline_exceeded(format!(
+                    "{method} did not finish within {}ms",
+                    limit.as_millis()
+                )))
+            }
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Executor for ControlService {
+    type StreamActionsStream = ActionStream;
     type SubscribeMetricsStream = MetricsStream;
 
     async fn stream_actions(
         &self,
         request: Request<ConnectRequest>,
     ) -> Result<Response<Self::StreamActionsStream>, Status> {
-        let ConnectRequest {
-            validator_id,
-            auth_token,
-        } = request.into_inner();
+        self.timed("stream_actions", async {
+            let peer = request.extensions().get::<PeerIdentity>().cloned();
+            let ConnectRequest {
+                validator_id,
+                auth_token,
+            } = request.into_inner();
 
-        let cfg = self.state.authorize(&validator_id, &auth_token).await?;
-        info!(validator = cfg.id.0, "validator client connected");
+            self.state.check_peer(peer.as_ref(), &validator_id)?;
+            let cfg = self.state.authorize(&validator_id, &auth_token).await?;
+            info!(validator = cfg.id.0, "validator client connected");
 
-        let (tx, rx) = mpsc::channel(32);
-        self.state
-            .attach_client(cfg.id.0.clone(), tx)
-            .await
-            .map_err(|err| {
-                error!(?err, "failed to attach client");
-                err
-            })?;
+            let (tx, rx) = mpsc::channel(32);
+            self.state
+                .attach_client(cfg.id.0.clone(), tx)
+                .await
+                .map_err(|err| {
+                    error!(?err, "failed to attach client");
+                    err
+                })?;
 
-        let stream = ReceiverStream::new(rx).map(|msg| Ok(msg));
-        Ok(Response::new(Box::pin(stream) as ActionStream))
+            let stream = ReceiverStream::new(rx).map(Ok);
+            Ok(Response::new(Box::pin(stream) as ActionStream))
+        })
+        .await
     }
 
     async fn report_result(
         &self,
         request: Request<ActionResult>,
     ) -> Result<Response<ReportAck>, Status> {
-        let ActionResult {
-            validator_id,
-            action_json,
-            success,
-            message,
-        } = request.into_inner();
-
-        let action: Action = serde_json::from_str(&action_json)
-            .map_err(|err| Status::invalid_argument(format!("invalid action payload: {err}")))?;
-
-        if success {
-            info!(validator = validator_id, action = ?action, "action completed successfully");
-        } else {
-            error!(
-                validator = validator_id,
-                action = ?action,
-                %message,
-                "action failed"
-            );
-        }
-        Ok(Response::new(ReportAck {}))
+        self.timed("report_result", async {
+            let peer = request.extensions().get::<PeerIdentity>().cloned();
+            let result = request.into_inner();
+            let validator_id = self.state.ids.normalize(&result.validator_id);
+
+            self.state.check_peer(peer.as_ref(), &validator_id)?;
+            if !result.plan_id.is_empty() {
+                self.state.step_reported(&result, now_ts()).await;
+            }
+            // Progress only: the outcome is acked, recorded and published
+            // when it is reported.
+            if result.started {
+                return Ok(Response::new(ReportAck {}));
+            }
+            let ActionResult {
+                action_json,
+                success,
+                message,
+                action_id,
+                issue,
+                dry_run,
+                ..
+            } = result;
+            let action: Action = serde_json::from_str(&action_json).map_err(|err| {
+                Status::invalid_argument(format!("invalid action payload: {err}"))
+            })?;
+            if matches!(action, Action::RestartValidator { .. }) {
+                self.state.restart_reported(&validator_id, &action_id).await;
+            }
+            if !action_id.is_empty() {
+                if let Some(latency) = self
+                    .state
+                    .action_completed(&action_id, Instant::now())
+                    .await
+                {
+                    info!(
+                        validator = validator_id,
+                        action_id,
+                        latency_secs = latency.as_secs_f64(),
+                        "action result received"
+                    );
+                }
+            }
+
+            if dry_run {
+                info!(validator = validator_id, action = ?action, "action simulated (dry run)");
+            } else if success {
+                if matches!(action, Action::RestartValidator { .. }) {
+                    self.state.restart_completed(&validator_id, now_ts()).await;
+                }
+                info!(validator = validator_id, action = ?action, "action completed successfully");
+            } else {
+                error!(
+                    validator = validator_id,
+                    action = ?action,
+                    %message,
+                    "action failed"
+                );
+            }
+            self.state.action_history.lock().await.record(ActionRecord {
+                validator_id: validator_id.clone(),
+                action_json,
+                success,
+                message: message.clone(),
+                timestamp: now_ts(),
+                dry_run,
+            });
+            if let Some(webhook) = &self.state.result_webhook {
+                webhook.notify(ResultEvent {
+                    validator: validator_id,
+                    action: serde_json::to_value(&action).unwrap_or_default(),
+                    success,
+                    message,
+                    action_id: Some(action_id).filter(|id| !id.is_empty()),
+                    issue: Some(issue).filter(|issue| !issue.is_empty()),
+                    dry_run,
+                });
+            }
+            Ok(Response::new(ReportAck {}))
+        })
+        .await
     }
 
     async fn publish_metrics(
         &self,
         request: Request<MetricsUpdate>,
     ) -> Result<Response<ReportAck>, Status> {
-        let update = request.into_inner();
-        self.state.record_metrics(update).await?;
-        Ok(Response::new(ReportAck {}))
+        self.timed("publish_metrics", async {
+            let peer = request.extensions().get::<PeerIdentity>().cloned();
+            let update = request.into_inner();
+            self.state.check_peer(peer.as_ref(), &update.validator_id)?;
+            self.state.record_metrics(update).await?;
+            Ok(Response::new(ReportAck {}))
+        })
+        .await
     }
 
     async fn subscribe_metrics(
         &self,
         request: Request<MetricsWatchRequest>,
     ) -> Result<Response<Self::SubscribeMetricsStream>, Status> {
-        let req = request.into_inner();
-        let filter: HashSet<String> = req.validator_ids.into_iter().collect();
-        let include_snapshot = req.include_snapshot;
-        let include_all = filter.is_empty();
-        let filter = Arc::new(filter);
-
-        let snapshot_stream = if include_snapshot {
-            let snapshot = self.state.snapshot(&filter).await;
-            tokio_stream::iter(snapshot.into_iter().map(Ok)).boxed()
-        } else {
-            tokio_stream::empty().boxed()
-        };
-
-        let metrics_tx = self.state.metrics_sender();
-        let broadcast_stream = BroadcastStream::new(metrics_tx.subscribe())
-            .filter_map(move |event| {
-                let filter = filter.clone();
-                async move {
-                    match event {
-                        Ok(mut update) => {
-                            if include_all || filter.contains(&update.validator_id) {
-                                update.auth_token.clear();
-                                Some(Ok(update))
-                            } else {
-                                None
-                            }
-                        }
-                        Err(_) => None,
-                    }
-                }
-            })
-            .boxed();
+        self.timed("subscribe_metrics", async {
+            let stream = self.state.subscribe(request.into_inner()).await?;
+            Ok(Response::new(stream))
+        })
+        .await
+    }
 
-        let combined = snapshot_stream.chain(broadcast_stream);
-        Ok(Response::new(Box::pin(combined) as MetricsStream))
+    async fn get_all_metrics(
+        &self,
+        request: Request<MetricsWatchRequest>,
+    ) -> Result<Response<MetricsSnapshot>, Status> {
+        self.timed("get_all_metrics", async {
+            let updates = self.state.poll(request.into_inner()).await;
+            Ok(Response::new(MetricsSnapshot { updates }))
+        })
+        .await
     }
 
     async fn submit_action(
         &self,
         request: Request<ActionEnvelope>,
     ) -> Result<Response<ReportAck>, Status> {
-        let envelope = request.into_inner();
-        let action: Action = serde_json::from_str(&envelope.action_json)
-            .map_err(|err| Status::invalid_argument(format!("invalid action payload: {err}")))?;
-        if validator_id(&action) != envelope.validator_id {
-            return Err(Status::invalid_argument(
-                "validator id mismatch between envelope and action",
-            ));
-        }
-        self.state.enqueue_action(envelope).await?;
-        Ok(Response::new(ReportAck {}))
+        self.timed("submit_action", async {
+            let mut envelope = request.into_inner();
+            let action: Action = serde_json::from_str(&envelope.action_json).map_err(|err| {
+                Status::invalid_argument(format!("invalid action payload: {err}"))
+            })?;
+            let action_validator = action.validator().0.clone();
+            if action_validator != envelope.validator_id {
+                match self.state.on_validator_mismatch {
+                    ValidatorMismatchPolicy::Reject => {
+                        return Err(Status::invalid_argument(format!(
+                            "validator id mismatch: envelope is for {} but the action targets {action_validator}",
+                            envelope.validator_id
+                        )));
+                    }
+                    ValidatorMismatchPolicy::UseAction => {
+                        warn!(
+                            envelope_validator = envelope.validator_id,
+                            validator = action_validator,
+                            "envelope validator id differs from the action's, queueing for the action's validator"
+                        );
+                        envelope.validator_id = action_validator;
+                    }
+                }
+            }
+            if let Action::ExecCommand { command_id, .. } = &action {
+                if !self.state.commands.custom.contains_key(command_id) {
+                    return Err(Status::invalid_argument(format!(
+                        "command {command_id} is not registered"
+                    )));
+                }
+            }
+            if let Action::RunMaintenanceScript {
+                script_name,
+                params,
+                ..
+            } = &action
+            {
+                self.state
+                    .commands
+                    .script_command(script_name, params)
+                    .map_err(|err| Status::invalid_argument(err.to_string()))?;
+            }
+            self.state.enqueue_action(envelope).await?;
+            Ok(Response::new(ReportAck {}))
+        })
+        .await
+    }
+
+    async fn disable_validator(
+        &self,
+        request: Request<ValidatorToggleRequest>,
+    ) -> Result<Response<ReportAck>, Status> {
+        self.timed("disable_validator", async {
+            let request = request.into_inner();
+            self.state
+                .set_enabled(&request.validator_id, false, request.auto_undrain)
+                .await?;
+            Ok(Response::new(ReportAck {}))
+        })
+        .await
+    }
+
+    async fn enable_validator(
+        &self,
+        request: Request<ValidatorToggleRequest>,
+    ) -> Result<Response<ReportAck>, Status> {
+        self.timed("enable_validator", async {
+            let validator_id = request.into_inner().validator_id;
+            self.state.set_enabled(&validator_id, true, false).await?;
+            Ok(Response::new(ReportAck {}))
+        })
+        .await
+    }
+
+    async fn scrape_now(
+        &self,
+        request: Request<ScrapeRequest>,
+    ) -> Result<Response<ReportAck>, Status> {
+        self.timed("scrape_now", async {
+            let validator_id = request.into_inner().validator_id;
+            // Lowest priority so the scrape observes any remediation queued before it.
+            let signal = ActionEnvelope {
+                validator_id,
+                priority: i32::MIN,
+                ttl_secs: SCRAPE_NOW_TTL_SECS,
+                control: ControlSignal::ScrapeNow as i32,
+                ..ActionEnvelope::default()
+            };
+            self.state.enqueue_action(signal).await?;
+            Ok(Response::new(ReportAck {}))
+        })
+        .await
+    }
+
+    async fn register_validator(
+        &self,
+        request: Request<RegisterValidatorRequest>,
+    ) -> Result<Response<ReportAck>, Status> {
+        self.timed("register_validator", async {
+            let peer = request.extensions().get::<PeerIdentity>().cloned();
+            let request = request.into_inner();
+            self.state
+                .check_peer(peer.as_ref(), &request.validator_id)?;
+            self.state.register(request).await?;
+            Ok(Response::new(ReportAck {}))
+        })
+        .await
+    }
+
+    async fn list_validator_states(
+        &self,
+        _request: Request<ValidatorStatesRequest>,
+    ) -> Result<Response<ValidatorStatesResponse>, Status> {
+        self.timed("list_validator_states", async {
+            let validators = self.state.validator_states().await;
+            Ok(Response::new(ValidatorStatesResponse { validators }))
+        })
+        .await
+    }
+
+    async fn list_action_history(
+        &self,
+        request: Request<ActionHistoryRequest>,
+    ) -> Result<Response<ActionHistoryResponse>, Status> {
+        self.timed("list_action_history", async {
+            let mut request = request.into_inner();
+            request.validator_id = self.state.ids.normalize(&request.validator_id);
+            let records = self.state.action_history.lock().await.query(&request);
+            Ok(Response::new(ActionHistoryResponse { records }))
+        })
+        .await
+    }
+
+    async fn list_pending_actions(
+        &self,
+        request: Request<PendingActionsRequest>,
+    ) -> Result<Response<PendingActionsResponse>, Status> {
+        self.timed("list_pending_actions", async {
+            let validator_id = self.state.ids.normalize(&request.into_inner().validator_id);
+            let actions = self.state.pending(&validator_id, now_ts()).await;
+            Ok(Response::new(PendingActionsResponse { actions }))
+        })
+        .await
+    }
+
+    async fn list_playbook_executions(
+        &self,
+        request: Request<PlaybookExecutionsRequest>,
+    ) -> Result<Response<PlaybookExecutionsResponse>, Status> {
+        self.timed("list_playbook_executions", async {
+            let validator_id = self.state.ids.normalize(&request.into_inner().validator_id);
+            let executions = self
+                .state
+                .inner
+                .lock()
+                .await
+                .playbooks
+                .list(&validator_id, now_ts());
+            Ok(Response::new(PlaybookExecutionsResponse { executions }))
+        })
+        .await
     }
 }
 
-fn validator_id(action: &Action) -> String {
-    match action {
-        Action::DisableRpc { validator }
-        | Action::EnableRpc { validator }
-        | Action::RestartValidator { validator }
-        | Action::ThrottleRpcClient { validator }
-        | Action::RunMaintenanceScript { validator, .. }
-        | Action::SendAlert { validator, .. } => validator.0.clone(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{IssueKind, RestartKind, ValidatorId};
+    use executor::proto::executor_client::ExecutorClient;
+    use executor::tls::DaemonConnector;
+
+    fn validator(id: &str) -> ValidatorConfig {
+        ValidatorConfig {
+            id: ValidatorId(id.into()),
+            host: format!("{id}.local"),
+            prometheus_url: format!("http://{id}.local:9100/metrics"),
+            auth_token: format!("{id}-secret"),
+            allowed_scripts: None,
+            tags: Vec::new(),
+            labels: Default::default(),
+            agentic_allowed_actions: None,
+            metrics_fallbacks: Vec::new(),
+        }
+    }
+
+    /// Tests publish samples back to back, so the publish interval is off.
+    fn state() -> SharedState {
+        SharedState::new(&Config {
+            validators: vec![validator("v1"), validator("v2")],
+            daemon: common::DaemonConfig {
+                min_publish_interval_ms: 0,
+                ..common::DaemonConfig::default()
+            },
+            ..Config::default()
+        })
+    }
+
+    fn envelope(id: &str) -> ActionEnvelope {
+        let action = Action::SendAlert {
+            validator: ValidatorId(id.into()),
+            message: "hello".into(),
+        };
+        ActionEnvelope {
+            validator_id: id.into(),
+            action_json: serde_json::to_string(&action).unwrap(),
+            ..ActionEnvelope::default()
+        }
+    }
+
+    fn metrics_update(id: &str) -> MetricsUpdate {
+        metrics_update_at(id, 0)
+    }
+
+    fn metrics_update_at(id: &str, last_updated: i64) -> MetricsUpdate {
+        let metrics = ValidatorMetrics {
+            slot_lag: 0,
+            vote_success_rate: 0.99,
+            cpu_usage: 0.2,
+            ram_usage_gb: 16.0,
+            disk_usage_pct: 30.0,
+            rpc_qps: 100.0,
+            rpc_error_rate: 0.001,
+            last_updated,
+            field_timestamps: Default::default(),
+        };
+        MetricsUpdate {
+            validator_id: id.into(),
+            auth_token: format!("{id}-secret"),
+            metrics_json: serde_json::to_string(&metrics).unwrap(),
+            ..MetricsUpdate::default()
+        }
+    }
+
+    fn tls_fixture(name: &str) -> String {
+        format!("{}/testdata/tls/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    /// Serve `state` over mTLS on an ephemeral port and return a client for it
+    /// that presents `client_cert` (a fixture name) if given.
+    async fn mtls_client(
+        state: SharedState,
+        tls_cfg: &common::DaemonTlsConfig,
+        client_cert: Option<&str>,
+    ) -> ExecutorClient<tonic::transport::Channel> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming = tls::incoming(
+            listener,
+            tls::server_config(tls_cfg).unwrap(),
+            Duration::from_secs(tls_cfg.handshake_timeout_secs),
+        );
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(ExecutorServer::new(ControlService { state }))
+                .serve_with_incoming(incoming),
+        );
+
+        let (cert, key) = client_cert
+            .map(|name| {
+                (
+                    tls_fixture(&format!("{name}.pem")),
+                    tls_fixture(&format!("{name}.key")),
+                )
+            })
+            .unzip();
+        let connector = DaemonConnector::new(
+            &tls_fixture("ca.pem"),
+            cert.as_deref().zip(key.as_deref()),
+            Some("localhost"),
+        )
+        .unwrap();
+        let endpoint = tonic::transport::Endpoint::from_shared(format!("http://{addr}")).unwrap();
+        ExecutorClient::new(tls::connect(&endpoint, Some(&connector)).await.unwrap())
+    }
+
+    fn connect_request(id: &str) -> Request<ConnectRequest> {
+        Request::new(ConnectRequest {
+            validator_id: id.into(),
+            auth_token: format!("{id}-secret"),
+        })
+    }
+
+    #[tokio::test]
+    async fn client_cert_for_another_validator_is_rejected() {
+        let tls_cfg = common::DaemonTlsConfig {
+            cert_path: tls_fixture("server.pem"),
+            key_path: tls_fixture("server.key"),
+            ca_path: None,
+            client_ca_path: Some(tls_fixture("ca.pem")),
+            token_auth: true,
+            handshake_timeout_secs: 10,
+        };
+        let state = SharedState::new(&Config {
+            validators: vec![validator("v1"), validator("v2")],
+            daemon: common::DaemonConfig {
+                tls: Some(tls_cfg.clone()),
+                ..Default::default()
+            },
+            ..Config::default()
+        });
+
+        // The fixture certificate's only SAN is `DNS:v1`.
+        let mut v1 = mtls_client(state.clone(), &tls_cfg, Some("v1")).await;
+        let err = v1.stream_actions(connect_request("v2")).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+        assert!(err.message().contains("not issued to validator v2"));
+        let err = v1
+            .publish_metrics(Request::new(metrics_update("v2")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+        assert!(v1.stream_actions(connect_request("v1")).await.is_ok());
+
+        let mut anonymous = mtls_client(state, &tls_cfg, None).await;
+        let err = anonymous
+            .stream_actions(connect_request("v1"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+    }
+
+    #[tokio::test]
+    async fn client_cert_without_a_san_is_matched_on_its_common_name() {
+        let tls_cfg = common::DaemonTlsConfig {
+            cert_path: tls_fixture("server.pem"),
+            key_path: tls_fixture("server.key"),
+            ca_path: None,
+            client_ca_path: Some(tls_fixture("ca.pem")),
+            token_auth: true,
+            handshake_timeout_secs: 10,
+        };
+        let state = SharedState::new(&Config {
+            validators: vec![validator("v1"), validator("v3")],
+            daemon: common::DaemonConfig {
+                tls: Some(tls_cfg.clone()),
+                ..Default::default()
+            },
+            ..Config::default()
+        });
+
+        // Issued by the test CA with `CN=v3` and no SAN extension.
+        let mut v3 = mtls_client(state, &tls_cfg, Some("v3-cn-only")).await;
+        assert!(v3.stream_actions(connect_request("v3")).await.is_ok());
+        assert!(v3
+            .publish_metrics(Request::new(metrics_update("v3")))
+            .await
+            .is_ok());
+        let err = v3.stream_actions(connect_request("v1")).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+        assert!(err.message().contains("not issued to validator v1"));
+    }
+
+    #[tokio::test]
+    async fn disabled_validator_gets_no_actions_or_broadcasts_until_enabled() {
+        let state = state();
+        let (tx, rx) = mpsc::channel(8);
+        state.attach_client("v1".into(), tx).await.unwrap();
+        let mut metrics_rx = state.metrics_sender().subscribe();
+
+        // Queued while the client is away, then disabled: dropped, not held.
+        drop(rx);
+        state.enqueue_action(envelope("v1")).await.unwrap();
+        state.set_enabled("v1", false, false).await.unwrap();
+        let rejected = state.enqueue_action(envelope("v1")).await.unwrap_err();
+        assert_eq!(rejected.code(), tonic::Code::FailedPrecondition);
+        state.record_metrics(metrics_update("v1")).await.unwrap();
+        assert!(metrics_rx.try_recv().is_err());
+        let states = state.validator_states().await;
+        assert!(!states[0].enabled);
+        assert!(!states[0].auto_undrain);
+        assert!(states[1].enabled);
+
+        let (tx, mut rx) = mpsc::channel(8);
+        state.attach_client("v1".into(), tx).await.unwrap();
+        state.set_enabled("v1", true, false).await.unwrap();
+        assert!(rx.try_recv().is_err(), "nothing piled up while disabled");
+        state.enqueue_action(envelope("v1")).await.unwrap();
+        assert_eq!(rx.try_recv().unwrap().validator_id, "v1");
+        state.record_metrics(metrics_update("v1")).await.unwrap();
+        assert_eq!(metrics_rx.try_recv().unwrap().validator_id, "v1");
+    }
+
+    #[tokio::test]
+    async fn disabled_validator_metrics_still_fan_out_for_auto_undrain() {
+        let state = SharedState::new(&Config {
+            validators: vec![validator("v1")],
+            daemon: common::DaemonConfig {
+                min_publish_interval_ms: 0,
+                ..common::DaemonConfig::default()
+            },
+            auto_undrain: Some(common::AutoUndrainConfig::default()),
+            ..Config::default()
+        });
+        let (tx, mut rx) = mpsc::channel(8);
+        state.attach_client("v1".into(), tx).await.unwrap();
+        let mut metrics_rx = state.metrics_sender().subscribe();
+
+        state.set_enabled("v1", false, true).await.unwrap();
+        assert!(state.enqueue_action(envelope("v1")).await.is_err());
+        state.record_metrics(metrics_update("v1")).await.unwrap();
+        assert!(rx.try_recv().is_err());
+        assert_eq!(metrics_rx.try_recv().unwrap().validator_id, "v1");
+        assert!(state.validator_states().await[0].auto_undrain);
+
+        // Re-enabled, then drained by hand: the drain holds.
+        state.set_enabled("v1", true, false).await.unwrap();
+        assert!(!state.validator_states().await[0].auto_undrain);
+        state.set_enabled("v1", false, false).await.unwrap();
+        assert!(!state.validator_states().await[0].auto_undrain);
+    }
+
+    #[tokio::test]
+    async fn restarted_agent_resubmission_is_deduped() {
+        let state = state();
+        let validator = ValidatorId("v1".into());
+        // Two agent instances, the second started 40s after the first,
+        // dispatch the same playbook step within one default bucket.
+        let bucket_secs = common::RemediationConfig::default().idempotency_bucket_secs;
+        for now in [600, 640] {
+            let key = common::idempotency_key(
+                &validator,
+                IssueKind::SlotLagHigh,
+                "pb-1",
+                0,
+                now,
+                bucket_secs,
+            );
+            let mut action = envelope("v1");
+            action.idempotency_key = key;
+            state.enqueue_action(action).await.unwrap();
+        }
+        state.enqueue_action(envelope("v1")).await.unwrap();
+
+        let inner = state.inner.lock().await;
+        assert_eq!(inner.pending_actions["v1"].len(), 2);
+        assert_eq!(inner.idempotency_keys.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn action_older_than_ttl_expires_on_reconnect() {
+        let state = state();
+        let mut stale = envelope("v1");
+        stale.ttl_secs = 60;
+        stale.idempotency_key = "stale".into();
+        let fresh = envelope("v1");
+        state.enqueue_action(stale).await.unwrap();
+        state.enqueue_action(fresh.clone()).await.unwrap();
+        // The first action was queued two minutes before the validator returns.
+        state
+            .inner
+            .lock()
+            .await
+            .pending_actions
+            .get_mut("v1")
+            .unwrap()[0]
+            .enqueued_at -= 120;
+
+        let (tx, mut rx) = mpsc::channel(8);
+        state.attach_client("v1".into(), tx).await.unwrap();
+        assert_eq!(rx.try_recv().unwrap(), fresh);
+        assert!(rx.try_recv().is_err());
+        assert!(state.inner.lock().await.pending_actions["v1"].is_empty());
+    }
+
+    #[tokio::test]
+    async fn delivered_but_unacked_action_is_redelivered_once_after_the_timeout() {
+        let state = SharedState::new(&Config {
+            validators: vec![validator("v1")],
+            daemon: common::DaemonConfig {
+                ack_timeout_secs: 30,
+                ..common::DaemonConfig::default()
+            },
+            ..Config::default()
+        });
+        let (tx, mut rx) = mpsc::channel(8);
+        state.attach_client("v1".into(), tx).await.unwrap();
+        let mut unacked = envelope("v1");
+        unacked.idempotency_key = "unacked".into();
+        let mut acked = envelope("v1");
+        acked.idempotency_key = "acked".into();
+        state.enqueue_action(unacked.clone()).await.unwrap();
+        state.enqueue_action(acked.clone()).await.unwrap();
+        assert_eq!(rx.try_recv().unwrap(), unacked);
+        assert_eq!(rx.try_recv().unwrap(), acked);
+        state.action_completed("acked", Instant::now()).await;
+
+        let delivered_at = state.inner.lock().await.awaiting_ack["unacked"].delivered_at;
+        // The client is still connected, maybe still running it.
+        state.requeue_unacked(delivered_at + 300).await;
+        assert!(rx.try_recv().is_err());
+
+        // Gone, but not yet for the timeout.
+        drop(rx);
+        state.requeue_unacked(delivered_at + 30).await;
+        let (tx, mut rx) = mpsc::channel(8);
+        state.attach_client("v1".into(), tx).await.unwrap();
+        assert!(rx.try_recv().is_err());
+        drop(rx);
+        state.requeue_unacked(delivered_at + 31).await;
+        let (tx, mut rx) = mpsc::channel(8);
+        state.attach_client("v1".into(), tx).await.unwrap();
+        assert_eq!(rx.try_recv().unwrap(), unacked);
+        assert!(rx.try_recv().is_err());
+
+        // No result for the redelivery either: dropped, not sent again.
+        let (tx, mut next) = mpsc::channel(8);
+        state.attach_client("v1".into(), tx).await.unwrap();
+        state.requeue_unacked(now_ts() + 62).await;
+        assert!(rx.try_recv().is_err());
+        assert!(next.try_recv().is_err());
+        assert!(state.inner.lock().await.awaiting_ack.is_empty());
+        let rendered = state.render_metrics().await;
+        assert!(rendered.contains("executor_unacked_actions_redelivered_total 1\n"));
+        assert!(rendered.contains("executor_unacked_actions_dropped_total 1\n"));
+    }
+
+    #[tokio::test]
+    async fn restart_while_one_is_pending_or_delivered_is_dropped() {
+        // `ack_timeout_secs` left at 0: delivered restarts are tracked anyway.
+        let state = SharedState::new(&Config {
+            validators: vec![validator("v1"), validator("v2")],
+            ..Config::default()
+        });
+        let restart = |id: &str, key: &str| ActionEnvelope {
+            action_json: serde_json::to_string(&Action::RestartValidator {
+                validator: ValidatorId(id.into()),
+                restart_kind: common::RestartKind::Hard,
+            })
+            .unwrap(),
+            idempotency_key: key.into(),
+            ..envelope(id)
+        };
+        let already_restarting = |result: Result<(), Status>| {
+            matches!(result, Err(status) if status.code() == tonic::Code::AlreadyExists
+                && status.message().starts_with("already restarting"))
+        };
+
+        // Queued while no client is attached.
+        state.enqueue_action(restart("v1", "detect")).await.unwrap();
+        assert!(already_restarting(
+            state.enqueue_action(restart("v1", "escalate")).await
+        ));
+        state.enqueue_action(envelope("v1")).await.unwrap();
+        state.enqueue_action(restart("v2", "other")).await.unwrap();
+
+        // Delivered, still without a result.
+        let (tx, mut rx) = mpsc::channel(8);
+        state.attach_client("v1".into(), tx).await.unwrap();
+        assert_eq!(rx.try_recv().unwrap().idempotency_key, "detect");
+        rx.try_recv().unwrap();
+        assert!(already_restarting(
+            state.enqueue_action(restart("v1", "escalate")).await
+        ));
+
+        // A second playbook overlapping the delivered restart: its restart
+        // goes out refused, the steps around it as they are.
+        let plan_step = |step: i32, action: Action, depends_on: Vec<i32>| ActionEnvelope {
+            action_json: serde_json::to_string(&action).unwrap(),
+            idempotency_key: format!("escalate-{step}"),
+            plan_id: "escalate".into(),
+            step,
+            depends_on,
+            ..envelope("v1")
+        };
+        let v1 = ValidatorId("v1".into());
+        for step in [
+            plan_step(
+                0,
+                Action::DisableRpc {
+                    validator: v1.clone(),
+                },
+                vec![],
+            ),
+            plan_step(
+                1,
+                Action::RestartValidator {
+                    validator: v1.clone(),
+                    restart_kind: common::RestartKind::Hard,
+                },
+                vec![0],
+            ),
+            plan_step(
+                2,
+                Action::EnableRpc {
+                    validator: v1.clone(),
+                },
+                vec![1],
+            ),
+        ] {
+            state.enqueue_action(step).await.unwrap();
+        }
+        let refused: Vec<String> = (0..3).map(|_| rx.try_recv().unwrap().refused).collect();
+        assert_eq!(
+            refused,
+            ["", "already restarting: v1 has a restart in flight", ""]
+        );
+
+        // Its skipped report leaves the first restart in flight.
+        state.restart_reported("v1", "escalate-1").await;
+        assert!(already_restarting(
+            state.enqueue_action(restart("v1", "escalate")).await
+        ));
+
+        // Reported: the next restart goes through.
+        state.restart_reported("v1", "detect").await;
+        state.enqueue_action(restart("v1", "later")).await.unwrap();
+        let later = rx.try_recv().unwrap();
+        assert_eq!(
+            (later.idempotency_key, later.refused),
+            ("later".into(), String::new())
+        );
+
+        // So does one after the stream it went out on has gone.
+        drop(rx);
+        let (tx, mut rx) = mpsc::channel(8);
+        state.attach_client("v1".into(), tx).await.unwrap();
+        state
+            .enqueue_action(restart("v1", "reconnected"))
+            .await
+            .unwrap();
+        assert_eq!(rx.try_recv().unwrap().idempotency_key, "reconnected");
+    }
+
+    #[tokio::test]
+    async fn redelivered_step_does_not_wait_for_prerequisites_reported_before() {
+        let state = SharedState::new(&Config {
+            validators: vec![validator("v1")],
+            daemon: common::DaemonConfig {
+                ack_timeout_secs: 30,
+                ..common::DaemonConfig::default()
+            },
+            ..Config::default()
+        });
+        let (tx, mut rx) = mpsc::channel(8);
+        state.attach_client("v1".into(), tx).await.unwrap();
+        let step = |step: i32, depends_on: Vec<i32>| ActionEnvelope {
+            idempotency_key: format!("plan-{step}"),
+            plan_id: "plan".into(),
+            step,
+            depends_on,
+            ..envelope("v1")
+        };
+        for envelope in [step(0, vec![]), step(1, vec![]), step(2, vec![0, 1])] {
+            state.enqueue_action(envelope).await.unwrap();
+        }
+        for _ in 0..3 {
+            rx.try_recv().unwrap();
+        }
+        // Step 0 reports; then the stream drops with 1 and 2 unreported.
+        state.action_completed("plan-0", Instant::now()).await;
+        state
+            .step_reported(
+                &ActionResult {
+                    plan_id: "plan".into(),
+                    step: 0,
+                    success: true,
+                    ..ActionResult::default()
+                },
+                now_ts(),
+            )
+            .await;
+        drop(rx);
+        state.requeue_unacked(now_ts() + 31).await;
+
+        let (tx, mut rx) = mpsc::channel(8);
+        state.attach_client("v1".into(), tx).await.unwrap();
+        let first = rx.try_recv().unwrap();
+        assert_eq!(first.step, 1);
+        let second = rx.try_recv().unwrap();
+        assert_eq!((second.step, second.depends_on), (2, vec![1]));
+
+        // A prerequisite that failed skips its dependent instead.
+        state
+            .step_reported(
+                &ActionResult {
+                    plan_id: "plan".into(),
+                    step: 1,
+                    success: false,
+                    ..ActionResult::default()
+                },
+                now_ts(),
+            )
+            .await;
+        state.action_completed("plan-1", Instant::now()).await;
+        drop(rx);
+        state.requeue_unacked(now_ts() + 62).await;
+        let inner = state.inner.lock().await;
+        assert!(inner.awaiting_ack.is_empty());
+        assert_eq!(inner.playbooks.state("plan", 2), Some(StepState::Skipped));
+    }
+
+    #[tokio::test]
+    async fn pending_actions_are_listed_with_kind_age_and_priority() {
+        let state = state();
+        let service = ControlService {
+            state: state.clone(),
+        };
+        let mut restart = envelope("v1");
+        restart.action_json = serde_json::to_string(&Action::RestartValidator {
+            validator: ValidatorId("v1".into()),
+            restart_kind: RestartKind::Hard,
+        })
+        .unwrap();
+        restart.idempotency_key = "v1:slot_lag_high:slot-lag-recovery:0:0".into();
+        let mut alert = envelope("v1");
+        alert.priority = 100;
+        state.enqueue_action(restart).await.unwrap();
+        state.enqueue_action(alert).await.unwrap();
+        state.enqueue_action(envelope("v2")).await.unwrap();
+        state
+            .inner
+            .lock()
+            .await
+            .pending_actions
+            .get_mut("v1")
+            .unwrap()[1]
+            .enqueued_at -= 90;
+
+        let actions = service
+            .list_pending_actions(Request::new(PendingActionsRequest {
+                validator_id: " v1".into(),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .actions;
+        let listed: Vec<_> = actions
+            .iter()
+            .map(|action| {
+                (
+                    action.kind.as_str(),
+                    action.priority,
+                    action.action_id.as_str(),
+                    action.age_secs >= 90,
+                )
+            })
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("send_alert", 100, "", false),
+                (
+                    "restart_validator",
+                    0,
+                    "v1:slot_lag_high:slot-lag-recovery:0:0",
+                    true
+                ),
+            ]
+        );
+
+        let all = state.pending("", now_ts()).await;
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[2].validator_id, "v2");
+    }
+
+    #[tokio::test]
+    async fn result_after_delay_lands_in_latency_bucket() {
+        let state = state();
+        let action = Action::RestartValidator {
+            validator: ValidatorId("v1".into()),
+            restart_kind: RestartKind::Hard,
+        };
+        let mut restart = envelope("v1");
+        restart.action_json = serde_json::to_string(&action).unwrap();
+        restart.idempotency_key = "v1:slot_lag_high:slot-lag-recovery:3:0".into();
+        state.enqueue_action(restart).await.unwrap();
+
+        let reported_at = Instant::now() + Duration::from_secs(7);
+        let latency = state
+            .action_completed("v1:slot_lag_high:slot-lag-recovery:3:0", reported_at)
+            .await
+            .unwrap();
+        assert!(latency >= Duration::from_secs(7));
+        // A repeated or unknown id records nothing.
+        assert!(state
+            .action_completed("v1:slot_lag_high:slot-lag-recovery:3:0", reported_at)
+            .await
+            .is_none());
+
+        let rendered = state.render_metrics().await;
+        for line in [
+            "copilot_action_latency_seconds_bucket{kind=\"restart_validator\",le=\"5\"} 0",
+            "copilot_action_latency_seconds_bucket{kind=\"restart_validator\",le=\"10\"} 1",
+            "copilot_action_latency_seconds_bucket{kind=\"restart_validator\",le=\"+Inf\"} 1",
+            "copilot_action_latency_seconds_count{kind=\"restart_validator\"} 1",
+        ] {
+            assert!(rendered.contains(line), "missing {line} in:\n{rendered}");
+        }
+    }
+
+    #[tokio::test]
+    async fn successful_restart_result_is_reported_in_validator_states() {
+        let state = state();
+        let service = ControlService {
+            state: state.clone(),
+        };
+        let restart = Action::RestartValidator {
+            validator: ValidatorId("v1".into()),
+            restart_kind: RestartKind::Hard,
+        };
+        let result = |success| {
+            Request::new(ActionResult {
+                validator_id: "v1".into(),
+                action_json: serde_json::to_string(&restart).unwrap(),
+                success,
+                ..ActionResult::default()
+            })
+        };
+        service.report_result(result(false)).await.unwrap();
+        assert_eq!(state.validator_states().await[0].last_restart_at, 0);
+        let mut simulated = result(true);
+        simulated.get_mut().dry_run = true;
+        service.report_result(simulated).await.unwrap();
+        assert_eq!(state.validator_states().await[0].last_restart_at, 0);
+
+        let before = now_ts();
+        service.report_result(result(true)).await.unwrap();
+        let states = state.validator_states().await;
+        assert!(states[0].last_restart_at >= before);
+        assert_eq!(states[1].last_restart_at, 0);
+    }
+
+    #[tokio::test]
+    async fn three_step_playbook_reports_progressive_step_states() {
+        let state = state();
+        let service = ControlService {
+            state: state.clone(),
+        };
+        let plan_id = "v1:slot-lag-recovery:1700000000";
+        let steps = [
+            Action::DisableRpc {
+                validator: ValidatorId("v1".into()),
+            },
+            Action::RestartValidator {
+                validator: ValidatorId("v1".into()),
+                restart_kind: RestartKind::Hard,
+            },
+            Action::EnableRpc {
+                validator: ValidatorId("v1".into()),
+            },
+        ];
+        let envelopes: Vec<ActionEnvelope> = steps
+            .iter()
+            .enumerate()
+            .map(|(step, action)| ActionEnvelope {
+                validator_id: "v1".into(),
+                action_json: serde_json::to_string(action).unwrap(),
+                issue: "slot_lag_high".into(),
+                plan_id: plan_id.into(),
+                step: step as i32,
+                ..ActionEnvelope::default()
+            })
+            .collect();
+        let progress = || async {
+            let executions = service
+                .list_playbook_executions(Request::new(PlaybookExecutionsRequest {
+                    validator_id: "v1".into(),
+                }))
+                .await
+                .unwrap()
+                .into_inner()
+                .executions;
+            assert_eq!(executions.len(), 1);
+            assert_eq!(executions[0].plan_id, plan_id);
+            executions[0]
+                .steps
+                .iter()
+                .map(|step| (step.kind.clone(), step.state()))
+                .collect::<Vec<_>>()
+        };
+        let states = |states: [StepState; 3]| {
+            ["disable_rpc", "restart_validator", "enable_rpc"]
+                .map(String::from)
+                .into_iter()
+                .zip(states)
+                .collect::<Vec<_>>()
+        };
+        use StepState::{Delivered, Pending, Running, Succeeded};
+
+        for envelope in &envelopes {
+            state.enqueue_action(envelope.clone()).await.unwrap();
+        }
+        assert_eq!(progress().await, states([Pending; 3]));
+        let (tx, _rx) = mpsc::channel(8);
+        state.attach_client("v1".into(), tx).await.unwrap();
+        assert_eq!(progress().await, states([Delivered; 3]));
+
+        let mut expected = [Delivered; 3];
+        for envelope in &envelopes {
+            let report = |started: bool| ActionResult {
+                validator_id: "v1".into(),
+                action_json: envelope.action_json.clone(),
+                success: !started,
+                message: if started { String::new() } else { "ok".into() },
+                plan_id: plan_id.into(),
+                step: envelope.step,
+                started,
+                ..ActionResult::default()
+            };
+            service
+                .report_result(Request::new(report(true)))
+                .await
+                .unwrap();
+            expected[envelope.step as usize] = Running;
+            assert_eq!(progress().await, states(expected));
+            service
+                .report_result(Request::new(report(false)))
+                .await
+                .unwrap();
+            expected[envelope.step as usize] = Succeeded;
+            assert_eq!(progress().await, states(expected));
+        }
+        // Only outcomes are recorded as results.
+        let history = state
+            .action_history
+            .lock()
+            .await
+            .query(&ActionHistoryRequest {
+                validator_id: "v1".into(),
+                ..ActionHistoryRequest::default()
+            });
+        assert!(history.iter().all(|record| record.success));
+        assert_eq!(history.len(), 3);
+        let other = service
+            .list_playbook_executions(Request::new(PlaybookExecutionsRequest {
+                validator_id: "v2".into(),
+            }))
+            .await
+            .unwrap();
+        assert!(other.into_inner().executions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn action_history_is_queryable_while_metrics_fan_out() {
+        let state = state();
+        let service = ControlService {
+            state: state.clone(),
+        };
+        let mut updates = state
+            .subscribe(MetricsWatchRequest {
+                validator_ids: vec![],
+                include_snapshot: true,
+                backfill_since: 0,
+                after_seq: 0,
+            })
+            .await
+            .unwrap();
+        let result = |round: i64| {
+            let id = if round % 2 == 0 { "v1" } else { "v2" };
+            let alert = Action::SendAlert {
+                validator: ValidatorId(id.into()),
+                message: format!("round {round}"),
+            };
+            Request::new(ActionResult {
+                validator_id: id.into(),
+                action_json: serde_json::to_string(&alert).unwrap(),
+                success: round % 5 != 0,
+                message: format!("round {round}"),
+                ..ActionResult::default()
+            })
+        };
+
+        let publish = async {
+            for round in 0..50 {
+                let at = 10_000 + round;
+                state
+                    .record_metrics_at(metrics_update_at("v1", at), at)
+                    .await
+                    .unwrap();
+            }
+        };
+        let report = async {
+            for round in 0..50 {
+                service.report_result(result(round)).await.unwrap();
+                tokio::task::yield_now().await;
+            }
+        };
+        let read = async {
+            for _ in 0..50 {
+                updates.next().await.unwrap().unwrap();
+                service
+                    .list_action_history(Request::new(ActionHistoryRequest::default()))
+                    .await
+                    .unwrap();
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(publish, report, read)
+        })
+        .await
+        .expect("history queries do not block metrics fan-out");
+
+        let v2 = service
+            .list_action_history(Request::new(ActionHistoryRequest {
+                validator_id: "v2".into(),
+                limit: 2,
+                ..ActionHistoryRequest::default()
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .records;
+        let messages: Vec<_> = v2.iter().map(|record| record.message.as_str()).collect();
+        assert_eq!(messages, ["round 49", "round 47"]);
+        assert!(v2[0].success);
+        assert!(v2[0].timestamp > 0);
+    }
+
+    #[tokio::test]
+    async fn publish_stuck_behind_the_state_lock_times_out() {
+        let mut cfg = Config {
+            validators: vec![validator("v1")],
+            ..Config::default()
+        };
+        cfg.daemon
+            .handler_timeouts
+            .methods
+            .insert("publish_metrics".into(), 50);
+        let state = SharedState::new(&cfg);
+        let service = ControlService {
+            state: state.clone(),
+        };
+
+        let held = state.inner.lock().await;
+        let status = tokio::time::timeout(
+            Duration::from_secs(5),
+            service.publish_metrics(Request::new(metrics_update("v1"))),
+        )
+        .await
+        .expect("handler returned instead of hanging")
+        .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::DeadlineExceeded);
+        drop(held);
+
+        service
+            .publish_metrics(Request::new(metrics_update("v1")))
+            .await
+            .unwrap();
+        assert!(state.inner.lock().await.latest_metrics.contains_key("v1"));
+    }
+
+    #[tokio::test]
+    async fn mismatched_envelope_is_rejected_or_corrected_per_policy() {
+        let mismatched = || ActionEnvelope {
+            validator_id: "v2".into(),
+            ..envelope("v1")
+        };
+
+        let strict = ControlService { state: state() };
+        let status = strict
+            .submit_action(Request::new(mismatched()))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(
+            status.message(),
+            "validator id mismatch: envelope is for v2 but the action targets v1"
+        );
+
+        let mut cfg = Config {
+            validators: vec![validator("v1"), validator("v2")],
+            ..Config::default()
+        };
+        cfg.daemon.on_validator_mismatch = ValidatorMismatchPolicy::UseAction;
+        let lenient = ControlService {
+            state: SharedState::new(&cfg),
+        };
+        lenient
+            .submit_action(Request::new(mismatched()))
+            .await
+            .unwrap();
+        let inner = lenient.state.inner.lock().await;
+        assert_eq!(inner.pending_actions["v1"].len(), 1);
+        assert_eq!(inner.pending_actions["v1"][0].envelope.validator_id, "v1");
+        assert!(!inner.pending_actions.contains_key("v2"));
+    }
+
+    #[tokio::test]
+    async fn enqueued_actions_raise_queue_depth_gauge() {
+        let state = state();
+        assert!(state
+            .render_metrics()
+            .await
+            .contains("executor_pending_actions{validator=\"v1\"} 0\n"));
+
+        state.enqueue_action(envelope("v1")).await.unwrap();
+        state.enqueue_action(envelope("v1")).await.unwrap();
+        let rendered = state.render_metrics().await;
+        assert!(rendered.contains("executor_pending_actions{validator=\"v1\"} 2\n"));
+        assert!(rendered.contains("executor_pending_actions{validator=\"v2\"} 0\n"));
+        assert!(rendered.contains("executor_connected_clients 0\n"));
+    }
+
+    #[tokio::test]
+    async fn publishing_faster_than_the_minimum_interval_is_dropped_with_an_ok() {
+        let state = SharedState::new(&Config {
+            validators: vec![validator("v1"), validator("v2")],
+            ..Config::default()
+        });
+        let mut metrics_rx = state.metrics_sender().subscribe();
+        state.record_metrics(metrics_update("v1")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        state.record_metrics(metrics_update("v1")).await.unwrap();
+        assert_eq!(metrics_rx.try_recv().unwrap().validator_id, "v1");
+        assert!(metrics_rx.try_recv().is_err(), "the second was dropped");
+        // The limit is per validator.
+        state.record_metrics(metrics_update("v2")).await.unwrap();
+        assert_eq!(metrics_rx.try_recv().unwrap().validator_id, "v2");
+        assert!(state
+            .render_metrics()
+            .await
+            .contains("executor_metrics_publishes_throttled_total 1\n"));
+    }
+
+    #[tokio::test]
+    async fn update_without_subscribers_counts_as_not_broadcast() {
+        let state = state();
+        state
+            .record_metrics_at(metrics_update_at("v1", 10_000), 10_000)
+            .await
+            .expect("ingest succeeds without subscribers");
+        assert!(state
+            .render_metrics()
+            .await
+            .contains("executor_metrics_broadcast_no_receivers_total 1\n"));
+        assert!(state.inner.lock().await.latest_metrics.contains_key("v1"));
+
+        let _rx = state.metrics_tx.subscribe();
+        state
+            .record_metrics_at(metrics_update_at("v1", 10_005), 10_005)
+            .await
+            .unwrap();
+        assert!(state
+            .render_metrics()
+            .await
+            .contains("executor_metrics_broadcast_no_receivers_total 1\n"));
+    }
+
+    #[tokio::test]
+    async fn large_client_clock_skew_is_flagged_and_stamped() {
+        let state = state();
+        let mut rx = state.metrics_tx.subscribe();
+        state
+            .record_metrics_at(metrics_update_at("v1", 9_400), 10_000)
+            .await
+            .unwrap();
+        assert_eq!(rx.recv().await.unwrap().received_at, 10_000);
+        assert!(state
+            .render_metrics()
+            .await
+            .contains("executor_clock_drift_secs{validator=\"v1\"} 600\n"));
+
+        state
+            .record_metrics_at(metrics_update_at("v1", 10_005), 10_010)
+            .await
+            .unwrap();
+        assert!(!state
+            .render_metrics()
+            .await
+            .contains("executor_clock_drift_secs{validator="));
+    }
+
+    #[tokio::test]
+    async fn higher_priority_action_flushes_first() {
+        let state = state();
+        let disable = Action::DisableRpc {
+            validator: ValidatorId("v1".into()),
+        };
+        let mut low = envelope("v1");
+        low.action_json = serde_json::to_string(&disable).unwrap();
+        let mut second_low = low.clone();
+        second_low.idempotency_key = "second".into();
+        let mut high = envelope("v1");
+        high.priority = 100;
+        for action in [low.clone(), second_low.clone(), high.clone()] {
+            state.enqueue_action(action).await.unwrap();
+        }
+
+        let (tx, mut rx) = mpsc::channel(8);
+        state.attach_client("v1".into(), tx).await.unwrap();
+        assert_eq!(rx.try_recv().unwrap(), high);
+        assert_eq!(rx.try_recv().unwrap(), low);
+        assert_eq!(rx.try_recv().unwrap(), second_low);
+    }
+
+    #[tokio::test]
+    async fn plan_steps_stay_in_step_order_whatever_their_priority() {
+        let state = state();
+        let step = |step: i32, priority: i32| ActionEnvelope {
+            plan_id: "plan-1".into(),
+            step,
+            priority,
+            idempotency_key: format!("plan-1:{step}"),
+            ..envelope("v1")
+        };
+        let (disable, restart, enable) = (step(0, 50), step(1, 0), step(2, 50));
+        let mut alert = envelope("v1");
+        alert.priority = 100;
+        let mut script = envelope("v1");
+        script.priority = 20;
+        for action in [
+            disable.clone(),
+            restart.clone(),
+            enable.clone(),
+            alert.clone(),
+            script.clone(),
+        ] {
+            state.enqueue_action(action).await.unwrap();
+        }
+
+        let (tx, mut rx) = mpsc::channel(8);
+        state.attach_client("v1".into(), tx).await.unwrap();
+        for expected in [alert, disable, restart, enable, script] {
+            assert_eq!(rx.try_recv().unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn targeted_subscriber_only_sees_its_validator() {
+        let state = state();
+        let mut others = Vec::new();
+        for _ in 0..64 {
+            others.push(
+                state
+                    .subscribe(MetricsWatchRequest {
+                        validator_ids: vec![],
+                        include_snapshot: false,
+                        backfill_since: 0,
+                        after_seq: 0,
+                    })
+                    .await
+                    .unwrap(),
+            );
+        }
+        state.record_metrics(metrics_update("v1")).await.unwrap();
+        let mut targeted = state
+            .subscribe(MetricsWatchRequest {
+                validator_ids: vec!["v2".into()],
+                include_snapshot: true,
+                backfill_since: 0,
+                after_seq: 0,
+            })
+            .await
+            .unwrap();
+
+        state.record_metrics(metrics_update("v1")).await.unwrap();
+        state.record_metrics(metrics_update("v2")).await.unwrap();
+        let first = targeted.next().await.unwrap().unwrap();
+        assert_eq!(first.validator_id, "v2");
+        assert!(first.auth_token.is_empty());
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(20), targeted.next())
+                .await
+                .is_err(),
+            "no further updates expected for the targeted subscriber"
+        );
+
+        let all = others[0].next().await.unwrap().unwrap();
+        assert_eq!(all.validator_id, "v1");
+    }
+
+    #[tokio::test]
+    async fn configured_id_with_trailing_whitespace_matches_trimmed_client_id() {
+        let mut config = Config {
+            validators: vec![validator("v1")],
+            ..Config::default()
+        };
+        config.validators[0].id = ValidatorId("v1 ".into());
+        config.normalize_validator_ids();
+        let state = SharedState::new(&config);
+
+        let cfg = state.authorize("v1", "v1-secret").await.unwrap();
+        assert_eq!(cfg.id.0, "v1");
+        let (tx, _rx) = mpsc::channel(1);
+        state.attach_client(cfg.id.0, tx).await.unwrap();
+        let mut update = metrics_update("v1");
+        update.validator_id = " v1\t".into();
+        state.record_metrics(update).await.unwrap();
+        assert!(state.inner.lock().await.latest_metrics.contains_key("v1"));
+        assert!(state.validator_states().await[0].connected);
+    }
+
+    #[tokio::test]
+    async fn lowercased_ids_match_clients_with_other_casing() {
+        let mut config = Config {
+            validators: vec![validator("Validator-1")],
+            ..Config::default()
+        };
+        config.validator_ids.lowercase = true;
+        config.normalize_validator_ids();
+        let state = SharedState::new(&config);
+        assert!(state
+            .authorize("VALIDATOR-1", "Validator-1-secret")
+            .await
+            .is_ok());
+        assert!(state
+            .authorize("validator-1 ", "Validator-1-secret")
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn subscribers_past_the_limit_are_rejected_until_one_closes() {
+        let mut config = Config {
+            validators: vec![validator("v1"), validator("v2")],
+            ..Config::default()
+        };
+        config.daemon.max_metrics_subscribers = 2;
+        config.daemon.max_action_clients = 1;
+        let state = SharedState::new(&config);
+        let request = MetricsWatchRequest {
+            validator_ids: vec![],
+            include_snapshot: false,
+            backfill_since: 0,
+            after_seq: 0,
+        };
+
+        let first = state.subscribe(request.clone()).await.unwrap();
+        let _second = state.subscribe(request.clone()).await.unwrap();
+        let Err(err) = state.subscribe(request.clone()).await else {
+            panic!("third subscriber should be rejected");
+        };
+        assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+        assert!(err.message().contains("limit reached"), "{}", err.message());
+        drop(first);
+        assert!(state.subscribe(request).await.is_ok());
+
+        let (tx, _rx) = mpsc::channel(1);
+        state.attach_client("v1".into(), tx).await.unwrap();
+        let (tx, _rx2) = mpsc::channel(1);
+        // Reconnecting keeps the same slot; a second validator does not fit.
+        state.attach_client("v1".into(), tx.clone()).await.unwrap();
+        let err = state.attach_client("v2".into(), tx).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+    }
+
+    #[tokio::test]
+    async fn backfill_replays_retained_samples_before_live_updates() {
+        let state = state();
+        for (id, ts) in [("v1", 10), ("v2", 15), ("v1", 20), ("v1", 30)] {
+            state
+                .record_metrics(metrics_update_at(id, ts))
+                .await
+                .unwrap();
+        }
+        let mut stream = state
+            .subscribe(MetricsWatchRequest {
+                validator_ids: vec!["v1".into()],
+                include_snapshot: true,
+                backfill_since: 15,
+                after_seq: 0,
+            })
+            .await
+            .unwrap();
+        state
+            .record_metrics(metrics_update_at("v1", 40))
+            .await
+            .unwrap();
+
+        let mut seen = Vec::new();
+        for _ in 0..3 {
+            let update = stream.next().await.unwrap().unwrap();
+            let metrics: ValidatorMetrics = serde_json::from_str(&update.metrics_json).unwrap();
+            seen.push(metrics.last_updated);
+        }
+        assert_eq!(seen, vec![20, 30, 40]);
+    }
+
+    #[tokio::test]
+    async fn self_registered_validator_publishes_metrics_and_receives_actions() {
+        let state = state().with_registration("bootstrap".into(), None, vec!["archival".into()]);
+        let service = ControlService {
+            state: state.clone(),
+        };
+        assert_eq!(
+            state
+                .enqueue_action(envelope("v9"))
+                .await
+                .unwrap_err()
+                .code(),
+            tonic::Code::NotFound
+        );
+        let register = |bootstrap_token: &str| {
+            Request::new(RegisterValidatorRequest {
+                validator_id: "v9".into(),
+                bootstrap_token: bootstrap_token.into(),
+                auth_token: "v9-secret".into(),
+                host: "v9.local".into(),
+                prometheus_url: "http://v9.local:9100/metrics".into(),
+                tags: vec!["archival".into(), "no-remediate".into()],
+            })
+        };
+        let err = service
+            .register_validator(register("wrong"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+
+        service
+            .register_validator(register("bootstrap"))
+            .await
+            .unwrap();
+        // Registering again on reconnect is accepted.
+        service
+            .register_validator(register("bootstrap"))
+            .await
+            .unwrap();
+
+        state.record_metrics(metrics_update("v9")).await.unwrap();
+        assert!(state
+            .snapshot(&SubscriberFilter::new(vec![]), 0)
+            .await
+            .iter()
+            .any(|update| update.validator_id == "v9"));
+        let cfg = state.authorize("v9", "v9-secret").await.unwrap();
+        // Only allowlisted tags are kept.
+        assert_eq!(cfg.tags, vec!["archival".to_string()]);
+        let states = state.validator_states().await;
+        let registered = |id: &str| {
+            states
+                .iter()
+                .find(|state| state.validator_id == id)
+                .unwrap()
+                .registered
+        };
+        assert!(registered("v9"));
+        assert!(!registered("v1"));
+        let (tx, mut rx) = mpsc::channel(8);
+        state.attach_client("v9".into(), tx).await.unwrap();
+        state.enqueue_action(envelope("v9")).await.unwrap();
+        assert_eq!(rx.try_recv().unwrap().validator_id, "v9");
+    }
+
+    #[tokio::test]
+    async fn polling_after_a_seq_returns_samples_from_lagging_clocks() {
+        let state = state();
+        let service = ControlService {
+            state: state.clone(),
+        };
+        let poll = |after_seq| {
+            service.get_all_metrics(Request::new(MetricsWatchRequest {
+                after_seq,
+                ..MetricsWatchRequest::default()
+            }))
+        };
+        state
+            .record_metrics_at(metrics_update_at("v1", 1_000), 1_000)
+            .await
+            .unwrap();
+        let first = poll(0).await.unwrap().into_inner().updates;
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].received_at, 1_000);
+
+        // v2's clock is behind v1's: a last_updated watermark would skip it.
+        state
+            .record_metrics_at(metrics_update_at("v2", 900), 1_001)
+            .await
+            .unwrap();
+        let next = poll(first[0].seq).await.unwrap().into_inner().updates;
+        assert_eq!(next.len(), 1);
+        assert_eq!(next[0].validator_id, "v2");
+        assert_eq!(next[0].received_at, 1_001);
+        assert!(next[0].seq > first[0].seq);
+        assert!(poll(next[0].seq)
+            .await
+            .unwrap()
+            .into_inner()
+            .updates
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn polling_returns_what_a_subscription_replays() {
+        let state = state();
+        for (id, ts) in [("v1", 10), ("v2", 15), ("v1", 20), ("v2", 25), ("v1", 30)] {
+            state
+                .record_metrics(metrics_update_at(id, ts))
+                .await
+                .unwrap();
+        }
+        let service = ControlService {
+            state: state.clone(),
+        };
+
+        for (include_snapshot, backfill_since, expected) in [(true, 0, 2), (false, 15, 3)] {
+            let request = MetricsWatchRequest {
+                validator_ids: vec![],
+                include_snapshot,
+                backfill_since,
+                after_seq: 0,
+            };
+            let mut streamed: Vec<MetricsUpdate> = state
+                .subscribe(request.clone())
+                .await
+                .unwrap()
+                .take(expected)
+                .map(Result::unwrap)
+                .collect()
+                .await;
+            let mut polled = service
+                .get_all_metrics(Request::new(request))
+                .await
+                .unwrap()
+                .into_inner()
+                .updates;
+            // Snapshot order follows the daemon's map; backfill is time-ordered.
+            streamed.sort_by(|a, b| a.validator_id.cmp(&b.validator_id));
+            polled.sort_by(|a, b| a.validator_id.cmp(&b.validator_id));
+            assert_eq!(polled, streamed);
+        }
     }
 }
@@ -1,19 +1,44 @@
-use anyhow::{anyhow, Context, Result};
-use common::{Action, ValidatorMetrics};
-use executor::execute_action;
+use anyhow::{anyhow, bail, Context, Result};
+use common::{
+    Action, ActionCommands, Backoff, MetricMapping, ScrapeProxyConfig, ValidatorIdsConfig,
+    ValidatorMetrics,
+};
+use executor::execute_action_with;
 use executor::proto::executor_client::ExecutorClient;
-use executor::proto::{ActionResult, ConnectRequest, MetricsUpdate};
+use executor::proto::{
+    ActionEnvelope, ActionResult, ConnectRequest, ControlSignal, MetricsUpdate, RecoveryCheck,
+    RegisterValidatorRequest,
+};
+use executor::tls::{self, DaemonConnector};
+use futures_util::stream::{FuturesUnordered, StreamExt};
 use reqwest::Client as HttpClient;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::env;
+use std::fmt::Write;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::time::{interval, sleep};
+use tokio::sync::{Notify, Semaphore};
+use tokio::time::{interval, sleep, timeout_at, Instant};
 use tonic::transport::{Channel, Endpoint};
 use tonic::Status;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
 const DEFAULT_SERVER_ADDR: &str = "http://127.0.0.1:50051";
 const DEFAULT_PROM_URL: &str = "http://127.0.0.1:9100/metrics";
+const SCRAPE_INTERVAL: Duration = Duration::from_secs(5);
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+/// A connection that stayed up this long resets the reconnect backoff.
+const HEALTHY_CONNECTION: Duration = Duration::from_secs(30);
+const DEFAULT_MAX_PERMANENT_FAILURES: u32 = 5;
+const DEFAULT_PUBLISH_RETRIES: u32 = 2;
+const DEFAULT_PUBLISH_BACKOFF: Duration = Duration::from_millis(250);
+/// How long a plan's start time is remembered after its deadline passed, so
+/// late steps are still abandoned rather than starting a fresh clock.
+const PLAN_DEADLINE_RETENTION: Duration = Duration::from_secs(3600);
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -25,121 +50,1256 @@ async fn main() -> Result<()> {
 
     let server_addr =
         env::var("EXECUTOR_SERVER_ADDR").unwrap_or_else(|_| DEFAULT_SERVER_ADDR.into());
-    let validator_id =
+    let ids = common::load_validator_ids()?;
+    let (client_cfg, served) = common::load_client_config(&ids)?;
+    let daemon_tls = daemon_connector_from_env()?;
+    let targets = if served.is_empty() {
+        vec![target_from_env(&ids)?]
+    } else {
+        // Registration sends one id's details, and the daemon would take
+        // every served validator for that one.
+        if env::var_os("VALIDATOR_BOOTSTRAP_TOKEN").is_some() {
+            bail!("VALIDATOR_BOOTSTRAP_TOKEN cannot be used with [client] validators; self-registration serves a single VALIDATOR_ID");
+        }
+        info!(
+            validators = ?client_cfg.validators,
+            "serving the validators listed under [client]"
+        );
+        served
+            .into_iter()
+            .map(|validator| Target {
+                validator_id: validator.id.0,
+                auth_token: validator.auth_token,
+                prometheus_url: validator.prometheus_url,
+                metrics_fallbacks: validator.metrics_fallbacks,
+                registration: None,
+            })
+            .collect()
+    };
+    let mut commands = common::load_action_commands()?;
+    if dry_run_from_env()? {
+        commands.dry_run = true;
+    }
+    if commands.dry_run {
+        warn!("dry run: actions are logged and reported as simulated, not run");
+    }
+    let scraper = Scraper {
+        http: build_http_client(common::load_scrape_proxy()?.as_ref())?,
+        ids,
+        retry: publish_retry_from_env()?,
+        stats: Arc::default(),
+        permits: Arc::new(Semaphore::new(client_cfg.scrape_concurrency)),
+        metrics: Arc::new(client_cfg.metrics.clone()),
+    };
+    if let Ok(addr) = env::var("VALIDATOR_CLIENT_METRICS_ADDR") {
+        let addr: SocketAddr = addr
+            .parse()
+            .context("VALIDATOR_CLIENT_METRICS_ADDR must be a socket address")?;
+        tokio::spawn(serve_metrics(addr, scraper.stats.clone()));
+    }
+    let max_permanent_failures = match env::var("VALIDATOR_MAX_PERMANENT_FAILURES") {
+        Ok(raw) => raw
+            .parse()
+            .context("VALIDATOR_MAX_PERMANENT_FAILURES must be a non-negative integer")?,
+        Err(_) => DEFAULT_MAX_PERMANENT_FAILURES,
+    };
+
+    let (err, validator_id) = serve_validators(
+        &server_addr,
+        daemon_tls.as_ref(),
+        &targets,
+        &commands,
+        &scraper,
+        max_permanent_failures,
+    )
+    .await;
+    Err(err.context(format!(
+        "validator {validator_id}: giving up after {max_permanent_failures} consecutive permanent failures, and no other validator is left to serve"
+    )))
+}
+
+/// One validator the client acts for.
+struct Target {
+    validator_id: String,
+    auth_token: String,
+    prometheus_url: String,
+    /// Tried in order when a scrape of `prometheus_url` fails.
+    metrics_fallbacks: Vec<String>,
+    /// Sent before every connection attempt when self-registration is on.
+    registration: Option<RegisterValidatorRequest>,
+}
+
+/// The single validator configured by `VALIDATOR_ID` and friends.
+fn target_from_env(ids: &ValidatorIdsConfig) -> Result<Target> {
+    let raw_validator_id =
         env::var("VALIDATOR_ID").context("VALIDATOR_ID environment variable is required")?;
-    let auth_token = env::var("VALIDATOR_AUTH_TOKEN")
-        .context("VALIDATOR_AUTH_TOKEN environment variable is required")?;
+    let validator_id = ids.normalize(&raw_validator_id);
+    if validator_id != raw_validator_id {
+        warn!(
+            configured = raw_validator_id,
+            normalized = validator_id,
+            "normalized VALIDATOR_ID"
+        );
+    }
+    // A client certificate can stand in for the token when the daemon has
+    // `[daemon.tls] token_auth = false`.
+    let auth_token = match env::var("VALIDATOR_AUTH_TOKEN") {
+        Ok(token) => token,
+        Err(_) if env::var_os("VALIDATOR_TLS_CERT_PATH").is_some() => String::new(),
+        Err(_) => bail!("VALIDATOR_AUTH_TOKEN environment variable is required"),
+    };
     let prometheus_url =
         env::var("VALIDATOR_METRICS_URL").unwrap_or_else(|_| DEFAULT_PROM_URL.to_string());
+    let metrics_fallbacks = env::var("VALIDATOR_METRICS_FALLBACKS")
+        .map(|sources| {
+            sources
+                .split(',')
+                .map(str::trim)
+                .filter(|source| !source.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    let registration = registration_from_env(&validator_id, &auth_token, &prometheus_url);
+    Ok(Target {
+        validator_id,
+        auth_token,
+        prometheus_url,
+        metrics_fallbacks,
+        registration,
+    })
+}
 
+/// Serve every target concurrently, each with its own action stream and
+/// publish loop. A target that gives up per [`run_with_retries`] is logged
+/// and dropped while the rest keep running. Returns once every target gave
+/// up, with the last one's error and validator.
+async fn serve_validators(
+    server_addr: &str,
+    daemon_tls: Option<&DaemonConnector>,
+    targets: &[Target],
+    commands: &ActionCommands,
+    scraper: &Scraper,
+    max_permanent_failures: u32,
+) -> (anyhow::Error, String) {
+    let serving = targets.iter().map(|target| {
+        Box::pin(async move {
+            let backoff = Backoff::new(RECONNECT_BASE_DELAY, RECONNECT_MAX_DELAY);
+            run_with_retries(max_permanent_failures, backoff, || async {
+                if let Some(registration) = &target.registration {
+                    register(server_addr, daemon_tls, registration).await?;
+                }
+                run_client(server_addr, daemon_tls, target, commands, scraper).await
+            })
+            .await
+        })
+    });
+    let mut serving: FuturesUnordered<_> = serving
+        .enumerate()
+        .map(|(index, serve)| async move { (serve.await, index) })
+        .collect();
+    let mut last = None;
+    while let Some((err, index)) = serving.next().await {
+        let validator_id = &targets[index].validator_id;
+        if !serving.is_empty() {
+            error!(
+                validator = validator_id,
+                ?err,
+                remaining = serving.len(),
+                "giving up on validator after consecutive permanent failures"
+            );
+        }
+        last = Some((err, validator_id.clone()));
+    }
+    last.unwrap_or_else(|| (anyhow!("no validators to serve"), String::new()))
+}
+
+/// Keep re-running `attempt` until it fails permanently `max_permanent_failures`
+/// times in a row, then return that error. Transient failures are retried
+/// indefinitely and break the streak, as does an attempt that ends cleanly.
+/// Each retry waits per `backoff`, which starts over after an attempt that
+/// stayed up for [`HEALTHY_CONNECTION`].
+async fn run_with_retries<F, Fut>(
+    max_permanent_failures: u32,
+    mut backoff: Backoff,
+    mut attempt: F,
+) -> anyhow::Error
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut permanent_failures = 0;
     loop {
-        if let Err(err) =
-            run_client(&server_addr, &validator_id, &auth_token, &prometheus_url).await
-        {
-            error!(?err, "validator client loop failed, retrying in 3s");
-            sleep(Duration::from_secs(3)).await;
+        let started = Instant::now();
+        let Err(err) = attempt().await else {
+            permanent_failures = 0;
+            continue;
+        };
+        if started.elapsed() >= HEALTHY_CONNECTION {
+            backoff.reset();
         }
+        let retry_in = backoff.next_delay();
+        if is_permanent(&err) {
+            permanent_failures += 1;
+            if permanent_failures >= max_permanent_failures.max(1) {
+                return err;
+            }
+            error!(
+                ?err,
+                permanent_failures,
+                ?retry_in,
+                "validator rejected by control plane, retrying"
+            );
+        } else {
+            permanent_failures = 0;
+            error!(?err, ?retry_in, "validator client loop failed, retrying");
+        }
+        sleep(retry_in).await;
     }
 }
 
-async fn run_client(
-    server_addr: &str,
+/// Errors retrying cannot fix: a bad token or a validator the daemon does not know.
+fn is_permanent(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<Status>().is_some_and(|status| {
+        matches!(
+            status.code(),
+            tonic::Code::Unauthenticated | tonic::Code::NotFound
+        )
+    })
+}
+
+/// `EXECUTOR_DRY_RUN`, unset meaning no.
+fn dry_run_from_env() -> Result<bool> {
+    match env::var("EXECUTOR_DRY_RUN").as_deref() {
+        Err(_) | Ok("" | "0" | "false") => Ok(false),
+        Ok("1" | "true") => Ok(true),
+        Ok(raw) => bail!("EXECUTOR_DRY_RUN must be true or false, got {raw}"),
+    }
+}
+
+/// `VALIDATOR_PUBLISH_RETRIES` and `VALIDATOR_PUBLISH_BACKOFF_MS`.
+fn publish_retry_from_env() -> Result<PublishRetry> {
+    let retries = match env::var("VALIDATOR_PUBLISH_RETRIES") {
+        Ok(raw) => raw
+            .parse()
+            .context("VALIDATOR_PUBLISH_RETRIES must be a non-negative integer")?,
+        Err(_) => DEFAULT_PUBLISH_RETRIES,
+    };
+    let backoff = match env::var("VALIDATOR_PUBLISH_BACKOFF_MS") {
+        Ok(raw) => Duration::from_millis(
+            raw.parse()
+                .context("VALIDATOR_PUBLISH_BACKOFF_MS must be a non-negative integer")?,
+        ),
+        Err(_) => DEFAULT_PUBLISH_BACKOFF,
+    };
+    Ok(PublishRetry {
+        retries,
+        backoff,
+        budget: SCRAPE_INTERVAL,
+    })
+}
+
+/// Prometheus scrape endpoint for the client's own publish counters.
+async fn serve_metrics(addr: SocketAddr, stats: Arc<PublishStats>) {
+    let app = axum::Router::new().route(
+        "/metrics",
+        axum::routing::get(move || {
+            let stats = stats.clone();
+            async move { stats.render() }
+        }),
+    );
+    let result = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => {
+            info!("validator client metrics listening on {}", addr);
+            axum::serve(listener, app).await
+        }
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        error!(?err, "metrics listener failed");
+    }
+}
+
+/// TLS settings for the daemon connection: `VALIDATOR_TLS_CA_PATH` enables
+/// TLS, `VALIDATOR_TLS_CERT_PATH` + `VALIDATOR_TLS_KEY_PATH` add a client
+/// certificate, and `VALIDATOR_TLS_SERVER_NAME` overrides the name checked
+/// against the daemon certificate.
+fn daemon_connector_from_env() -> Result<Option<DaemonConnector>> {
+    let Ok(ca_path) = env::var("VALIDATOR_TLS_CA_PATH") else {
+        return Ok(None);
+    };
+    let cert_path = env::var("VALIDATOR_TLS_CERT_PATH").ok();
+    let key_path = env::var("VALIDATOR_TLS_KEY_PATH").ok();
+    let client_cert = match (&cert_path, &key_path) {
+        (Some(cert), Some(key)) => Some((cert.as_str(), key.as_str())),
+        (None, None) => None,
+        _ => bail!("VALIDATOR_TLS_CERT_PATH and VALIDATOR_TLS_KEY_PATH must be set together"),
+    };
+    let server_name = env::var("VALIDATOR_TLS_SERVER_NAME").ok();
+    DaemonConnector::new(&ca_path, client_cert, server_name.as_deref()).map(Some)
+}
+
+/// With `VALIDATOR_BOOTSTRAP_TOKEN` set, the client registers itself with the
+/// daemon before each connect. `VALIDATOR_HOST` defaults to the metrics URL's
+/// host; `VALIDATOR_TAGS` is a comma-separated list.
+fn registration_from_env(
     validator_id: &str,
     auth_token: &str,
     prometheus_url: &str,
+) -> Option<RegisterValidatorRequest> {
+    let bootstrap_token = env::var("VALIDATOR_BOOTSTRAP_TOKEN").ok()?;
+    let host = env::var("VALIDATOR_HOST").unwrap_or_else(|_| {
+        reqwest::Url::parse(prometheus_url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default()
+    });
+    let tags = env::var("VALIDATOR_TAGS")
+        .map(|tags| {
+            tags.split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    Some(RegisterValidatorRequest {
+        validator_id: validator_id.to_string(),
+        bootstrap_token,
+        auth_token: auth_token.to_string(),
+        host,
+        prometheus_url: prometheus_url.to_string(),
+        tags,
+    })
+}
+
+async fn register(
+    server_addr: &str,
+    daemon_tls: Option<&DaemonConnector>,
+    request: &RegisterValidatorRequest,
 ) -> Result<()> {
-    let channel = Endpoint::from_shared(server_addr.to_string())?
-        .connect()
+    let endpoint = Endpoint::from_shared(server_addr.to_string())?;
+    let channel = tls::connect(&endpoint, daemon_tls)
+        .await
+        .with_context(|| format!("failed to connect to executor server at {server_addr}"))?;
+    ExecutorClient::new(channel)
+        .register_validator(tonic::Request::new(request.clone()))
+        .await
+        .map_err(map_status)?;
+    info!(
+        validator = request.validator_id,
+        "registered with control plane"
+    );
+    Ok(())
+}
+
+async fn run_client(
+    server_addr: &str,
+    daemon_tls: Option<&DaemonConnector>,
+    target: &Target,
+    commands: &ActionCommands,
+    scraper: &Scraper,
+) -> Result<()> {
+    let validator_id = target.validator_id.as_str();
+    let endpoint = Endpoint::from_shared(server_addr.to_string())?;
+    let channel = tls::connect(&endpoint, daemon_tls)
         .await
         .with_context(|| format!("failed to connect to executor server at {server_addr}"))?;
     let mut action_client = ExecutorClient::new(channel.clone());
     let mut report_client = ExecutorClient::new(channel.clone());
-    let mut metrics_client = ExecutorClient::new(channel);
+    let metrics_client = ExecutorClient::new(channel);
 
     let request = tonic::Request::new(ConnectRequest {
         validator_id: validator_id.to_string(),
-        auth_token: auth_token.to_string(),
+        auth_token: target.auth_token.clone(),
     });
 
-    let mut stream = action_client.stream_actions(request).await?.into_inner();
+    let mut stream = action_client
+        .stream_actions(request)
+        .await
+        .map_err(map_status)?
+        .into_inner();
     info!(validator = validator_id, "connected to control plane");
 
+    let scrape_trigger = Arc::new(Notify::new());
     let metrics_task = tokio::spawn(publish_metrics_loop(
         metrics_client,
         validator_id.to_string(),
-        auth_token.to_string(),
-        prometheus_url.to_string(),
+        target.auth_token.clone(),
+        Arc::new(ChainedMetricsSource::new(
+            std::iter::once(&target.prometheus_url)
+                .chain(&target.metrics_fallbacks)
+                .cloned()
+                .map(ConfiguredSource)
+                .collect(),
+        )),
+        scraper.clone(),
+        scrape_trigger.clone(),
     ));
 
-    while let Some(msg) = stream.message().await? {
-        let action: Action = serde_json::from_str(&msg.action_json)
-            .map_err(|err| anyhow!("invalid action payload: {err}"))?;
-        info!(validator = validator_id, "executing action from server");
+    let probe = ExporterProbe {
+        scraper,
+        validator_id,
+        prometheus_url: &target.prometheus_url,
+    };
+    let mut deadlines = PlanDeadlines::default();
+    let mut dependencies = StepDependencies::default();
+    while let Some(msg) = stream.message().await.map_err(map_status)? {
+        if msg.control() == ControlSignal::ScrapeNow {
+            info!(validator = validator_id, "out-of-band scrape requested");
+            scrape_trigger.notify_one();
+            continue;
+        }
+        let results = run_envelope(
+            msg,
+            commands,
+            &probe,
+            &mut report_client,
+            &mut deadlines,
+            &mut dependencies,
+        )
+        .await?;
+        for StepReport {
+            msg,
+            success,
+            message,
+            skipped,
+            rollback,
+        } in results
+        {
+            report_client
+                .report_result(tonic::Request::new(ActionResult {
+                    validator_id: validator_id.to_string(),
+                    action_json: msg.action_json,
+                    success,
+                    message,
+                    action_id: msg.idempotency_key,
+                    issue: msg.issue,
+                    dry_run: commands.dry_run,
+                    plan_id: msg.plan_id,
+                    step: msg.step,
+                    skipped,
+                    rollback,
+                    started: false,
+                }))
+                .await
+                .map_err(map_status)?;
+        }
+    }
 
-        let execution_result = execute_action(action.clone()).await;
-        let (success, message) = match execution_result {
-            Ok(_) => (true, String::from("ok")),
-            Err(err) => (false, err.to_string()),
-        };
+    metrics_task.abort();
+    Err(anyhow!("action stream closed by server"))
+}
 
-        report_client
-            .report_result(tonic::Request::new(ActionResult {
-                validator_id: validator_id.to_string(),
-                action_json: msg.action_json.clone(),
+/// Run `msg` if its prerequisites allow, along with any parked steps its
+/// outcome unblocks, returning each finished step with the result to report.
+/// Each step's start is reported to `reporter` as it begins. A failed or
+/// refused step rolls its plan back: the compensations of the steps that
+/// succeeded run newest first, and the plan's remaining steps are skipped.
+async fn run_envelope(
+    msg: ActionEnvelope,
+    commands: &ActionCommands,
+    probe: &impl SlotLagProbe,
+    reporter: &mut impl StepReporter,
+    deadlines: &mut PlanDeadlines,
+    dependencies: &mut StepDependencies,
+) -> Result<Vec<StepReport>> {
+    let mut results = Vec::new();
+    let mut ready = VecDeque::from([msg]);
+    while let Some(msg) = ready.pop_front() {
+        let mut rollback = Vec::new();
+        let mut skipped = true;
+        let (success, message) = match dependencies.readiness(&msg) {
+            Readiness::Wait => {
+                info!(
+                    plan = msg.plan_id,
+                    step = msg.step,
+                    "waiting for prerequisite steps"
+                );
+                dependencies.park(msg, Instant::now());
+                continue;
+            }
+            Readiness::Skip(failed) => (
+                false,
+                format!("skipped: prerequisite step {failed} did not succeed"),
+            ),
+            Readiness::RolledBack(failed) => (
+                false,
+                format!("skipped: plan rolled back after step {failed} failed"),
+            ),
+            // Another restart of the validator is in flight.
+            Readiness::Run if !msg.refused.is_empty() => {
+                rollback = dependencies.abort(&msg, Instant::now());
+                (false, msg.refused.clone())
+            }
+            Readiness::Run => {
+                skipped = false;
+                let action: Action = serde_json::from_str(&msg.action_json)
+                    .map_err(|err| anyhow!("invalid action payload: {err}"))?;
+                // A held recovery step leaves RPC disabled on purpose, and a
+                // failed alert leaves nothing to undo.
+                let aborts =
+                    msg.recovery_check.is_none() && !matches!(action, Action::SendAlert { .. });
+                info!(validator = msg.validator_id, "executing action from server");
+                reporter.started(&msg).await?;
+                let (success, message) =
+                    execute_step(&msg, action, commands, probe, deadlines).await;
+                if success {
+                    dependencies.succeeded(&msg, Instant::now());
+                } else if aborts {
+                    rollback = dependencies.abort(&msg, Instant::now());
+                }
+                (success, message)
+            }
+        };
+        ready.extend(dependencies.complete(&msg, success, Instant::now()));
+        if !rollback.is_empty() {
+            warn!(
+                validator = msg.validator_id,
+                plan = msg.plan_id,
+                step = msg.step,
+                compensations = rollback.len(),
+                "plan step failed, rolling back completed steps"
+            );
+        }
+        results.push(StepReport {
+            msg,
+            success,
+            message,
+            skipped,
+            rollback: false,
+        });
+        for compensation in rollback {
+            let (success, message) = run_compensation(&compensation, commands).await;
+            results.push(StepReport {
+                msg: compensation,
                 success,
                 message,
-            }))
+                skipped: false,
+                rollback: true,
+            });
+        }
+    }
+    Ok(results)
+}
+
+/// Where the client reports that a plan step started, ahead of its result;
+/// the executor daemon outside of tests.
+trait StepReporter {
+    async fn started(&mut self, msg: &ActionEnvelope) -> Result<()>;
+}
+
+impl StepReporter for ExecutorClient<Channel> {
+    async fn started(&mut self, msg: &ActionEnvelope) -> Result<()> {
+        // Only plan steps have progress to show.
+        if msg.plan_id.is_empty() {
+            return Ok(());
+        }
+        self.report_result(tonic::Request::new(ActionResult {
+            validator_id: msg.validator_id.clone(),
+            action_json: msg.action_json.clone(),
+            action_id: msg.idempotency_key.clone(),
+            issue: msg.issue.clone(),
+            plan_id: msg.plan_id.clone(),
+            step: msg.step,
+            started: true,
+            ..ActionResult::default()
+        }))
+        .await
+        .map_err(map_status)?;
+        Ok(())
+    }
+}
+
+/// A finished step and the result to report for it.
+struct StepReport {
+    msg: ActionEnvelope,
+    success: bool,
+    message: String,
+    /// Not run: a prerequisite failed or the plan was rolled back.
+    skipped: bool,
+    /// The result of the compensation undoing `msg.step`.
+    rollback: bool,
+}
+
+/// Run the compensation of a rolled-back step, returning the result to report.
+async fn run_compensation(msg: &ActionEnvelope, commands: &ActionCommands) -> (bool, String) {
+    let result = match serde_json::from_str::<Action>(&msg.action_json) {
+        Ok(action) => execute_action_with(action, commands).await,
+        Err(err) => Err(anyhow!("invalid compensation payload: {err}")),
+    };
+    match result {
+        Ok(()) => (true, format!("rollback of step {}: ok", msg.step)),
+        Err(err) => {
+            error!(
+                validator = msg.validator_id,
+                plan = msg.plan_id,
+                step = msg.step,
+                ?err,
+                "compensation failed"
+            );
+            (false, format!("rollback of step {}: {err}", msg.step))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Readiness {
+    Run,
+    Wait,
+    /// A prerequisite (this step index) failed or was itself skipped.
+    Skip(i32),
+    /// The plan was rolled back after this step failed.
+    RolledBack(i32),
+}
+
+/// Step outcomes and not-yet-runnable steps of one plan.
+struct PlanSteps {
+    outcomes: HashMap<i32, bool>,
+    parked: Vec<ActionEnvelope>,
+    /// Compensations of the steps that succeeded, in the order they ran.
+    compensations: Vec<ActionEnvelope>,
+    /// The step whose failure rolled the plan back.
+    rolled_back: Option<i32>,
+    last_seen: Instant,
+}
+
+/// Orders a plan's steps by their `depends_on` edges. Steps arrive in
+/// delivery-priority order, so a dependent may be parked until its
+/// prerequisites have run.
+#[derive(Default)]
+struct StepDependencies {
+    plans: HashMap<String, PlanSteps>,
+}
+
+impl StepDependencies {
+    fn readiness(&self, msg: &ActionEnvelope) -> Readiness {
+        if msg.plan_id.is_empty() {
+            return Readiness::Run;
+        }
+        let plan = self.plans.get(&msg.plan_id);
+        let mut waiting = false;
+        for dep in &msg.depends_on {
+            match plan.and_then(|plan| plan.outcomes.get(dep)) {
+                Some(false) => return Readiness::Skip(*dep),
+                Some(true) => {}
+                None => waiting = true,
+            }
+        }
+        if waiting {
+            Readiness::Wait
+        } else if let Some(failed) = plan.and_then(|plan| plan.rolled_back) {
+            Readiness::RolledBack(failed)
+        } else {
+            Readiness::Run
+        }
+    }
+
+    fn plan(&mut self, plan_id: &str, now: Instant) -> &mut PlanSteps {
+        self.plans
+            .retain(|_, plan| now.duration_since(plan.last_seen) < PLAN_DEADLINE_RETENTION);
+        let plan = self
+            .plans
+            .entry(plan_id.to_string())
+            .or_insert_with(|| PlanSteps {
+                outcomes: HashMap::new(),
+                parked: Vec::new(),
+                compensations: Vec::new(),
+                rolled_back: None,
+                last_seen: now,
+            });
+        plan.last_seen = now;
+        plan
+    }
+
+    fn park(&mut self, msg: ActionEnvelope, now: Instant) {
+        let plan_id = msg.plan_id.clone();
+        self.plan(&plan_id, now).parked.push(msg);
+    }
+
+    /// Remember the compensation of a step that succeeded, if it has one.
+    fn succeeded(&mut self, msg: &ActionEnvelope, now: Instant) {
+        if msg.plan_id.is_empty() || msg.compensation_json.is_empty() {
+            return;
+        }
+        let compensation = ActionEnvelope {
+            validator_id: msg.validator_id.clone(),
+            action_json: msg.compensation_json.clone(),
+            idempotency_key: if msg.idempotency_key.is_empty() {
+                String::new()
+            } else {
+                format!("{}:rollback", msg.idempotency_key)
+            },
+            issue: msg.issue.clone(),
+            plan_id: msg.plan_id.clone(),
+            step: msg.step,
+            ..ActionEnvelope::default()
+        };
+        self.plan(&msg.plan_id, now)
+            .compensations
+            .push(compensation);
+    }
+
+    /// Mark the plan of a failed step rolled back, returning the compensations
+    /// to run, newest first. A plan is only rolled back once.
+    fn abort(&mut self, msg: &ActionEnvelope, now: Instant) -> Vec<ActionEnvelope> {
+        if msg.plan_id.is_empty() {
+            return Vec::new();
+        }
+        let plan = self.plan(&msg.plan_id, now);
+        if plan.rolled_back.is_some() {
+            return Vec::new();
+        }
+        plan.rolled_back = Some(msg.step);
+        let mut compensations = std::mem::take(&mut plan.compensations);
+        compensations.reverse();
+        compensations
+    }
+
+    /// Record a step's outcome and return the parked steps it made decidable.
+    fn complete(
+        &mut self,
+        msg: &ActionEnvelope,
+        success: bool,
+        now: Instant,
+    ) -> Vec<ActionEnvelope> {
+        if msg.plan_id.is_empty() {
+            return Vec::new();
+        }
+        let plan = self.plan(&msg.plan_id, now);
+        plan.outcomes.insert(msg.step, success);
+        let parked = std::mem::take(&mut plan.parked);
+        let (waiting, ready): (Vec<_>, Vec<_>) = parked
+            .into_iter()
+            .partition(|parked| self.readiness(parked) == Readiness::Wait);
+        if let Some(plan) = self.plans.get_mut(&msg.plan_id) {
+            plan.parked = waiting;
+        }
+        ready
+    }
+}
+
+/// Start times of the playbook runs seen on this connection.
+#[derive(Default)]
+struct PlanDeadlines {
+    started: HashMap<String, Instant>,
+}
+
+impl PlanDeadlines {
+    /// Time left for the step's plan, `Some(None)` when it is unbounded and
+    /// `None` once the deadline has passed.
+    fn remaining(&mut self, msg: &ActionEnvelope, now: Instant) -> Option<Option<Duration>> {
+        if msg.plan_id.is_empty() || msg.plan_deadline_secs <= 0 {
+            return Some(None);
+        }
+        let limit = Duration::from_secs(msg.plan_deadline_secs as u64);
+        self.started
+            .retain(|_, started| now.duration_since(*started) < limit + PLAN_DEADLINE_RETENTION);
+        let started = *self.started.entry(msg.plan_id.clone()).or_insert(now);
+        let deadline = started + limit;
+        (now < deadline).then(|| Some(deadline - now))
+    }
+}
+
+/// Reads the validator's current slot lag for a step's [`RecoveryCheck`];
+/// its `prometheus_url` outside of tests, never a fallback source, whose
+/// numbers may predate the step.
+trait SlotLagProbe {
+    async fn slot_lag(&self) -> Result<i64>;
+}
+
+struct ExporterProbe<'a> {
+    scraper: &'a Scraper,
+    validator_id: &'a str,
+    prometheus_url: &'a str,
+}
+
+impl SlotLagProbe for ExporterProbe<'_> {
+    async fn slot_lag(&self) -> Result<i64> {
+        let _permit = self.scraper.permits.acquire().await;
+        scrape_validator_metrics(self.scraper, self.validator_id, self.prometheus_url)
             .await
-            .map_err(map_status)?;
+            .map(|metrics| metrics.slot_lag)
     }
+}
 
-    metrics_task.abort();
-    Err(anyhow!("action stream closed by server"))
+/// Scrape until `check` passes. Returns why the step is held if it never does.
+async fn await_recovery(probe: &impl SlotLagProbe, check: &RecoveryCheck) -> Result<(), String> {
+    let mut last = String::new();
+    for attempt in 1..=check.attempts {
+        match probe.slot_lag().await {
+            Ok(lag) if lag <= check.max_slot_lag => return Ok(()),
+            Ok(lag) => last = format!("slot_lag {lag} still above {}", check.max_slot_lag),
+            Err(err) => last = format!("slot_lag could not be scraped ({err:#})"),
+        }
+        info!(
+            attempt,
+            attempts = check.attempts,
+            status = last,
+            "waiting for recovery"
+        );
+        if attempt < check.attempts {
+            sleep(Duration::from_secs(check.interval_secs)).await;
+        }
+    }
+    Err(format!(
+        "held: {last} after {} checks, RPC left disabled",
+        check.attempts
+    ))
+}
+
+/// Wait for `msg`'s recovery check, if any, to pass, giving up at the plan's
+/// `deadline`. A held step sends an alert in its place and returns why.
+async fn check_recovery(
+    msg: &ActionEnvelope,
+    commands: &ActionCommands,
+    probe: &impl SlotLagProbe,
+    deadline: Option<Instant>,
+) -> Result<(), String> {
+    let Some(check) = &msg.recovery_check else {
+        return Ok(());
+    };
+    let recovery = await_recovery(probe, check);
+    let held = match deadline {
+        None => recovery.await,
+        Some(deadline) => timeout_at(deadline, recovery).await.unwrap_or_else(|_| {
+            Err(format!(
+                "held: plan {} reached its {}s deadline before recovery, RPC left disabled",
+                msg.plan_id, msg.plan_deadline_secs
+            ))
+        }),
+    };
+    let Err(held) = held else {
+        return Ok(());
+    };
+    warn!(
+        validator = msg.validator_id,
+        plan = msg.plan_id,
+        reason = held,
+        "step held"
+    );
+    let alert = Action::SendAlert {
+        validator: common::ValidatorId(msg.validator_id.clone()),
+        message: format!("{} not recovered after restart: {held}", msg.validator_id),
+    };
+    if let Err(err) = execute_action_with(alert, commands).await {
+        error!(?err, "failed to send recovery alert");
+    }
+    Err(held)
+}
+
+/// Run one step within its plan's deadline, returning the `(success, message)` to report.
+async fn execute_step(
+    msg: &ActionEnvelope,
+    action: Action,
+    commands: &ActionCommands,
+    probe: &impl SlotLagProbe,
+    deadlines: &mut PlanDeadlines,
+) -> (bool, String) {
+    let timed_out = || {
+        format!(
+            "timeout: plan {} exceeded its {}s deadline, step abandoned",
+            msg.plan_id, msg.plan_deadline_secs
+        )
+    };
+    let now = Instant::now();
+    let Some(remaining) = deadlines.remaining(msg, now) else {
+        warn!(
+            plan = msg.plan_id,
+            "plan deadline passed, skipping queued step"
+        );
+        return (false, timed_out());
+    };
+    let deadline = remaining.map(|remaining| now + remaining);
+    if let Err(held) = check_recovery(msg, commands, probe, deadline).await {
+        return (false, held);
+    }
+    let execution_result = match deadline {
+        None => execute_action_with(action, commands).await,
+        Some(deadline) => match timeout_at(deadline, execute_action_with(action, commands)).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!(
+                    plan = msg.plan_id,
+                    "plan deadline passed, aborted running step"
+                );
+                return (false, timed_out());
+            }
+        },
+    };
+    match execution_result {
+        Ok(_) => (true, String::from("ok")),
+        Err(err) => (false, err.to_string()),
+    }
 }
 
 async fn publish_metrics_loop(
-    mut client: ExecutorClient<Channel>,
+    client: ExecutorClient<Channel>,
     validator_id: String,
     auth_token: String,
-    prometheus_url: String,
+    sources: Arc<ChainedMetricsSource<ConfiguredSource>>,
+    scraper: Scraper,
+    scrape_trigger: Arc<Notify>,
 ) {
-    let http = HttpClient::builder()
-        .timeout(Duration::from_secs(5))
-        .build()
-        .expect("failed to build http client");
-    let mut ticker = interval(Duration::from_secs(5));
+    run_scrape_schedule(SCRAPE_INTERVAL, scrape_trigger, || {
+        let mut client = client.clone();
+        let scraper = scraper.clone();
+        let validator_id = validator_id.clone();
+        let auth_token = auth_token.clone();
+        let sources = sources.clone();
+        async move {
+            scrape_and_publish(&mut client, &scraper, &validator_id, &auth_token, &sources).await
+        }
+    })
+    .await
+}
+
+/// Run `scrape` on every `period` tick, and additionally whenever `trigger`
+/// is notified between ticks.
+async fn run_scrape_schedule<F, Fut>(period: Duration, trigger: Arc<Notify>, mut scrape: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut ticker = interval(period);
     loop {
-        ticker.tick().await;
-        match scrape_validator_metrics(&http, &validator_id, &prometheus_url).await {
-            Ok(metrics) => {
-                let payload = MetricsUpdate {
-                    validator_id: validator_id.clone(),
-                    auth_token: auth_token.clone(),
-                    metrics_json: match serde_json::to_string(&metrics) {
-                        Ok(json) => json,
-                        Err(err) => {
-                            error!(?err, "failed to serialize metrics");
-                            continue;
-                        }
-                    },
-                };
-                if let Err(err) = client.publish_metrics(tonic::Request::new(payload)).await {
-                    error!(?err, "failed to publish metrics update");
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = trigger.notified() => {}
+        }
+        scrape().await;
+    }
+}
+
+async fn scrape_and_publish(
+    client: &mut impl MetricsPublisher,
+    scraper: &Scraper,
+    validator_id: &str,
+    auth_token: &str,
+    sources: &ChainedMetricsSource<ConfiguredSource>,
+) {
+    let scraped = {
+        let _permit = scraper.permits.acquire().await;
+        sources.fetch(scraper, validator_id).await
+    };
+    let (metrics, source) = match scraped {
+        Ok(scraped) => scraped,
+        Err(err) => {
+            warn!(?err, "failed to scrape local metrics");
+            return;
+        }
+    };
+    let metrics_json = match serde_json::to_string(&metrics) {
+        Ok(json) => json,
+        Err(err) => {
+            error!(?err, "failed to serialize metrics");
+            return;
+        }
+    };
+    let payload = MetricsUpdate {
+        validator_id: validator_id.to_string(),
+        auth_token: auth_token.to_string(),
+        metrics_json,
+        source: source.to_string(),
+        ..MetricsUpdate::default()
+    };
+    publish_with_retries(client, payload, &scraper.retry, &scraper.stats).await;
+}
+
+/// What the client scrapes its exporter with, and how it publishes the result.
+#[derive(Clone)]
+struct Scraper {
+    http: HttpClient,
+    /// Exporter `id` labels are normalized like the client's own id.
+    ids: ValidatorIdsConfig,
+    retry: PublishRetry,
+    stats: Arc<PublishStats>,
+    /// Bounds concurrent scrapes when serving several validators.
+    permits: Arc<Semaphore>,
+    metrics: Arc<MetricMapping>,
+}
+
+/// Somewhere a validator's metrics can be read from.
+trait MetricsSource {
+    /// Names the source in logs and the `source` label: its URL or path.
+    fn name(&self) -> &str;
+
+    async fn fetch(&self, scraper: &Scraper, validator_id: &str) -> Result<ValidatorMetrics>;
+}
+
+/// An exporter URL, or `file:///path` to a Prometheus text file, e.g. a
+/// cache another job keeps or a node_exporter textfile collector's output.
+/// A file's sample is as old as the file, so a cache that stopped being
+/// refreshed goes stale downstream.
+struct ConfiguredSource(String);
+
+impl MetricsSource for ConfiguredSource {
+    fn name(&self) -> &str {
+        &self.0
+    }
+
+    async fn fetch(&self, scraper: &Scraper, validator_id: &str) -> Result<ValidatorMetrics> {
+        match self.0.strip_prefix("file://") {
+            Some(path) => {
+                let body = tokio::fs::read_to_string(path)
+                    .await
+                    .with_context(|| format!("failed reading {path}"))?;
+                let modified = tokio::fs::metadata(path)
+                    .await
+                    .and_then(|metadata| metadata.modified())
+                    .with_context(|| format!("failed reading the modification time of {path}"))?;
+                let mut metrics =
+                    parse_prometheus_samples(&body, validator_id, &scraper.ids, &scraper.metrics)?;
+                metrics.last_updated = modified
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs() as i64;
+                Ok(metrics)
+            }
+            None => scrape_validator_metrics(scraper, validator_id, &self.0).await,
+        }
+    }
+}
+
+/// Tries its sources in order, the first being the primary, and returns
+/// the first success along with the source that served it.
+struct ChainedMetricsSource<S> {
+    sources: Vec<S>,
+}
+
+impl<S: MetricsSource> ChainedMetricsSource<S> {
+    fn new(sources: Vec<S>) -> Self {
+        Self { sources }
+    }
+
+    /// Counts the serving source in `validator_client_metrics_scrapes_total`
+    /// and warns when it was not the primary.
+    async fn fetch(
+        &self,
+        scraper: &Scraper,
+        validator_id: &str,
+    ) -> Result<(ValidatorMetrics, &str)> {
+        let mut failures = Vec::new();
+        for source in &self.sources {
+            match source.fetch(scraper, validator_id).await {
+                Ok(metrics) => {
+                    scraper.stats.served(validator_id, source.name());
+                    if failures.is_empty() {
+                        debug!(
+                            validator = validator_id,
+                            source = source.name(),
+                            "scraped metrics"
+                        );
+                    } else {
+                        warn!(
+                            validator = validator_id,
+                            source = source.name(),
+                            ?failures,
+                            "served metrics from a fallback source"
+                        );
+                    }
+                    return Ok((metrics, source.name()));
                 }
+                Err(err) => failures.push(format!("{}: {err:#}", source.name())),
             }
-            Err(err) => {
-                warn!(?err, "failed to scrape local metrics");
+        }
+        bail!("every metrics source failed: {}", failures.join("; "))
+    }
+}
+
+/// Where metrics updates are published; the executor daemon outside of tests.
+trait MetricsPublisher {
+    async fn publish(&mut self, update: MetricsUpdate) -> Result<(), Status>;
+}
+
+impl MetricsPublisher for ExecutorClient<Channel> {
+    async fn publish(&mut self, update: MetricsUpdate) -> Result<(), Status> {
+        self.publish_metrics(tonic::Request::new(update))
+            .await
+            .map(|_| ())
+    }
+}
+
+/// How hard one tick tries to publish before dropping its update.
+#[derive(Clone, Copy, Debug)]
+struct PublishRetry {
+    /// Attempts after the first; the jittered backoff doubles after each.
+    retries: u32,
+    backoff: Duration,
+    /// No retry is started that would end past this much time after the
+    /// first attempt, so a slow daemon does not push back the next tick.
+    budget: Duration,
+}
+
+/// Counters behind the client's `/metrics` endpoint.
+#[derive(Debug, Default)]
+struct PublishStats {
+    published: AtomicU64,
+    retries: AtomicU64,
+    dropped_auth: AtomicU64,
+    dropped_network: AtomicU64,
+    dropped_rejected: AtomicU64,
+    /// Successful scrapes by validator and the source that served them.
+    scrapes: std::sync::Mutex<BTreeMap<(String, String), u64>>,
+}
+
+impl PublishStats {
+    fn served(&self, validator_id: &str, source: &str) {
+        *self
+            .scrapes
+            .lock()
+            .unwrap()
+            .entry((validator_id.to_string(), source.to_string()))
+            .or_default() += 1;
+    }
+
+    fn dropped(&self, reason: DropReason) -> &AtomicU64 {
+        match reason {
+            DropReason::Auth => &self.dropped_auth,
+            DropReason::Network => &self.dropped_network,
+            DropReason::Rejected => &self.dropped_rejected,
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP validator_client_metrics_published_total Metrics updates the daemon accepted.\n");
+        out.push_str("# TYPE validator_client_metrics_published_total counter\n");
+        let _ = writeln!(
+            out,
+            "validator_client_metrics_published_total {}",
+            self.published.load(Ordering::Relaxed)
+        );
+        out.push_str("# HELP validator_client_metrics_publish_retries_total Publish attempts retried after a transient failure.\n");
+        out.push_str("# TYPE validator_client_metrics_publish_retries_total counter\n");
+        let _ = writeln!(
+            out,
+            "validator_client_metrics_publish_retries_total {}",
+            self.retries.load(Ordering::Relaxed)
+        );
+        out.push_str("# HELP validator_client_metrics_dropped_total Metrics updates given up on, by why the last attempt failed.\n");
+        out.push_str("# TYPE validator_client_metrics_dropped_total counter\n");
+        for reason in [DropReason::Auth, DropReason::Network, DropReason::Rejected] {
+            let _ = writeln!(
+                out,
+                "validator_client_metrics_dropped_total{{reason=\"{}\"}} {}",
+                reason.label(),
+                self.dropped(reason).load(Ordering::Relaxed)
+            );
+        }
+        out.push_str("# HELP validator_client_metrics_scrapes_total Successful metrics scrapes, by validator and the source that served them.\n");
+        out.push_str("# TYPE validator_client_metrics_scrapes_total counter\n");
+        for ((validator, source), count) in self.scrapes.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "validator_client_metrics_scrapes_total{{validator=\"{validator}\",source=\"{source}\"}} {count}"
+            );
+        }
+        out
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DropReason {
+    /// Bad token or unregistered validator; retrying cannot help.
+    Auth,
+    /// The daemon could not be reached or was briefly unable to answer.
+    Network,
+    /// The daemon refused the update itself, e.g. an invalid payload.
+    Rejected,
+}
+
+impl DropReason {
+    fn of(status: &Status) -> Self {
+        use tonic::Code;
+        match status.code() {
+            Code::Unauthenticated | Code::PermissionDenied | Code::NotFound => Self::Auth,
+            Code::Unavailable
+            | Code::DeadlineExceeded
+            | Code::Unknown
+            | Code::Cancelled
+            | Code::Aborted => Self::Network,
+            _ => Self::Rejected,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Auth => "auth",
+            Self::Network => "network",
+            Self::Rejected => "rejected",
+        }
+    }
+}
+
+/// Publish `update`, retrying network failures with backoff per `retry`.
+/// An update that still fails is dropped and counted in `stats` by reason.
+async fn publish_with_retries<P: MetricsPublisher>(
+    publisher: &mut P,
+    update: MetricsUpdate,
+    retry: &PublishRetry,
+    stats: &PublishStats,
+) -> Option<DropReason> {
+    let started = Instant::now();
+    let mut backoff = Backoff::new(retry.backoff, retry.budget);
+    let mut attempt = 0;
+    loop {
+        let err = match publisher.publish(update.clone()).await {
+            Ok(()) => {
+                stats.published.fetch_add(1, Ordering::Relaxed);
+                return None;
             }
+            Err(err) => err,
+        };
+        let reason = DropReason::of(&err);
+        let retry_in = backoff.next_delay();
+        if reason == DropReason::Network
+            && attempt < retry.retries
+            && started.elapsed() + retry_in <= retry.budget
+        {
+            attempt += 1;
+            stats.retries.fetch_add(1, Ordering::Relaxed);
+            warn!(?err, attempt, ?retry_in, "metrics publish failed, retrying");
+            sleep(retry_in).await;
+            continue;
+        }
+        stats.dropped(reason).fetch_add(1, Ordering::Relaxed);
+        error!(
+            ?err,
+            reason = reason.label(),
+            attempts = attempt + 1,
+            "dropping metrics update"
+        );
+        return Some(reason);
+    }
+}
+
+/// The scrape client, routed through `[scrape_proxy]` when one is configured.
+fn build_http_client(proxy: Option<&ScrapeProxyConfig>) -> Result<HttpClient> {
+    let mut builder = HttpClient::builder().timeout(Duration::from_secs(5));
+    if let Some(cfg) = proxy {
+        let mut proxy = reqwest::Proxy::all(&cfg.url)
+            .with_context(|| format!("invalid scrape proxy url {}", cfg.url))?;
+        if let Some(username) = &cfg.username {
+            let password = match &cfg.password_env {
+                Some(var) => env::var(var)
+                    .with_context(|| format!("scrape proxy password env {var} is not set"))?,
+                None => String::new(),
+            };
+            proxy = proxy.basic_auth(username, &password);
         }
+        builder = builder.proxy(proxy);
     }
+    builder.build().context("failed to build http client")
 }
 
 async fn scrape_validator_metrics(
-    http: &HttpClient,
+    scraper: &Scraper,
     validator_id: &str,
     url: &str,
 ) -> Result<ValidatorMetrics> {
-    let response = http
+    let response = scraper
+        .http
         .get(url)
         .send()
         .await
@@ -150,90 +1310,1260 @@ async fn scrape_validator_metrics(
         .text()
         .await
         .context("failed reading response body")?;
-    parse_prometheus_samples(&body, validator_id)
+    parse_prometheus_samples(&body, validator_id, &scraper.ids, &scraper.metrics)
+}
+
+/// A parsed exposition sample; `timestamp_ms` is set when the exporter
+/// appended an explicit timestamp to the line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sample {
+    value: f64,
+    timestamp_ms: Option<i64>,
 }
 
-fn parse_prometheus_samples(body: &str, validator_id: &str) -> Result<ValidatorMetrics> {
-    let samples = parse_samples_map(body, validator_id);
-    let value_for = |name: &str| -> Result<f64> {
-        samples
-            .get(name)
-            .copied()
-            .ok_or_else(|| anyhow!("missing {name} metric for validator {validator_id}"))
+/// One exposition line: a series' name, its labels and its sample.
+#[derive(Debug, Clone, PartialEq)]
+struct Series {
+    name: String,
+    labels: Vec<(String, String)>,
+    sample: Sample,
+}
+
+fn parse_prometheus_samples(
+    body: &str,
+    validator_id: &str,
+    ids: &ValidatorIdsConfig,
+    mapping: &MetricMapping,
+) -> Result<ValidatorMetrics> {
+    let series = parse_samples_map(body, validator_id, ids);
+    let mut field_timestamps = BTreeMap::new();
+    let mut value_for = |field: &str| -> Result<f64> {
+        let selector = mapping.selector(field);
+        let Some(sample) = select(&series, &selector) else {
+            if mapping.strict {
+                bail!("missing {selector} metric for validator {validator_id}");
+            }
+            debug!(
+                validator = validator_id,
+                field, selector, "metric missing, using its neutral value"
+            );
+            return Ok(MetricMapping::neutral(field));
+        };
+        if let Some(ms) = sample.timestamp_ms {
+            field_timestamps.insert(field.to_string(), ms / 1000);
+        }
+        Ok(sample.value)
     };
 
     Ok(ValidatorMetrics {
-        slot_lag: value_for("validator_slot_lag")? as i64,
-        vote_success_rate: value_for("validator_vote_success_rate")?,
-        cpu_usage: value_for("validator_cpu_usage")?,
-        ram_usage_gb: value_for("validator_ram_usage_gb")?,
-        disk_usage_pct: value_for("validator_disk_usage_pct")?,
-        rpc_qps: value_for("validator_rpc_qps")?,
-        rpc_error_rate: value_for("validator_rpc_error_rate")?,
+        slot_lag: value_for("slot_lag")? as i64,
+        vote_success_rate: value_for("vote_success_rate")?,
+        cpu_usage: value_for("cpu_usage")?,
+        ram_usage_gb: value_for("ram_usage_gb")?,
+        disk_usage_pct: value_for("disk_usage_pct")?,
+        rpc_qps: value_for("rpc_qps")?,
+        rpc_error_rate: value_for("rpc_error_rate")?,
         last_updated: common::now_ts(),
+        field_timestamps,
     })
 }
 
-fn parse_samples_map(body: &str, validator_id: &str) -> HashMap<String, f64> {
-    let mut samples = HashMap::new();
+/// Every series on `validator_id`, in exposition order. Histogram
+/// (`_bucket`, `_sum`, `_count`) and summary (`quantile`) series are kept
+/// apart by their labels.
+fn parse_samples_map(body: &str, validator_id: &str, ids: &ValidatorIdsConfig) -> Vec<Series> {
+    let mut series = Vec::new();
     for line in body.lines() {
         let line = line.trim();
         if line.is_empty() || line.starts_with('#') {
             continue;
         }
-        let mut parts = line.split_whitespace();
-        let metric_label = match parts.next() {
-            Some(val) => val,
-            None => continue,
+        // Label values may hold spaces, so the name and labels are split
+        // off before the rest of the line.
+        let name_end = line
+            .find(|c: char| c == '{' || c.is_whitespace())
+            .unwrap_or(line.len());
+        let (metric_name, rest) = line.split_at(name_end);
+        let (labels, rest) = match rest.strip_prefix('{') {
+            Some(rest) => match parse_label_set(rest) {
+                Some(parsed) => parsed,
+                None => continue,
+            },
+            None => (Vec::new(), rest),
         };
+        let mut parts = rest.split_whitespace();
         let value = match parts.next() {
             Some(val) => val,
             None => continue,
         };
-        let (metric_name, labels) = if let Some(pos) = metric_label.find('{') {
-            let name = &metric_label[..pos];
-            let rest = &metric_label[pos + 1..];
-            match rest.find('}') {
-                Some(end) => (name, Some(&rest[..end])),
-                None => continue,
+
+        if !labels_match_validator(&labels, validator_id, ids) {
+            continue;
+        }
+
+        if let Ok(parsed) = value.parse::<f64>() {
+            let timestamp_ms = parts.next().and_then(|ts| ts.parse::<i64>().ok());
+            series.push(Series {
+                name: metric_name.to_string(),
+                labels,
+                sample: Sample {
+                    value: parsed,
+                    timestamp_ms,
+                },
+            });
+        }
+    }
+    series
+}
+
+/// Parse the labels after a `{`, e.g. `id="v1",path="/a,b"}`, up to the
+/// closing brace, returning them unescaped with what follows the brace.
+/// Values are quoted and may hold commas, braces and `\"`, `\\` or `\n`
+/// escapes.
+fn parse_label_set(input: &str) -> Option<(Vec<(String, String)>, &str)> {
+    let mut labels = Vec::new();
+    let mut rest = input.trim_start();
+    loop {
+        if let Some(after) = rest.strip_prefix('}') {
+            return Some((labels, after));
+        }
+        let (key, after) = rest.split_once('=')?;
+        let quoted = after.trim_start().strip_prefix('"')?;
+        let mut chars = quoted.char_indices();
+        let mut value = String::new();
+        let end = loop {
+            match chars.next()? {
+                (i, '"') => break i + 1,
+                (_, '\\') => match chars.next()?.1 {
+                    'n' => value.push('\n'),
+                    escaped => value.push(escaped),
+                },
+                (_, c) => value.push(c),
             }
-        } else {
-            (metric_label, None)
         };
+        labels.push((key.trim().to_string(), value));
+        rest = quoted[end..].trim_start();
+        rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+    }
+}
 
-        if let Some(labels) = labels {
-            if !labels_match_validator(labels, validator_id) {
-                continue;
+/// Whether the series labels include every `wanted` label. `quantile` and
+/// `le` hold numbers, so `0.99` and `0.990` are the same.
+fn carries_labels(labels: &[(String, String)], wanted: &[(String, String)]) -> bool {
+    let same_number =
+        |a: &str, b: &str| matches!((a.parse::<f64>(), b.parse::<f64>()), (Ok(a), Ok(b)) if a == b);
+    wanted.iter().all(|(key, value)| {
+        labels.iter().any(|(have_key, have)| {
+            have_key == key
+                && (have == value
+                    || (matches!(key.as_str(), "quantile" | "le") && same_number(have, value)))
+        })
+    })
+}
+
+/// The first series matching `selector`, a metric name with optional labels
+/// the series must carry, e.g. `validator_rpc_latency_seconds{quantile="0.99"}`.
+/// A `quantile` of a histogram is estimated from its `_bucket` series.
+fn select(series: &[Series], selector: &str) -> Option<Sample> {
+    let (name, wanted) = match selector.split_once('{') {
+        Some((name, labels)) => {
+            let (labels, rest) = parse_label_set(labels)?;
+            if !rest.trim().is_empty() {
+                return None;
             }
+            (name.trim(), labels)
         }
+        None => (selector.trim(), Vec::new()),
+    };
+    let carries =
+        |series: &Series, labels: &[(String, String)]| carries_labels(&series.labels, labels);
+    if let Some(found) = series
+        .iter()
+        .find(|series| series.name == name && carries(series, &wanted))
+    {
+        return Some(found.sample);
+    }
 
-        if samples.contains_key(metric_name) {
-            continue;
-        }
+    let (quantile, wanted): (Vec<_>, Vec<_>) =
+        wanted.into_iter().partition(|(key, _)| key == "quantile");
+    let quantile: f64 = quantile.first()?.1.parse().ok()?;
+    let bucket_name = format!("{name}_bucket");
+    let without_le = |series: &Series| -> Vec<(String, String)> {
+        series
+            .labels
+            .iter()
+            .filter(|(key, _)| key != "le")
+            .cloned()
+            .collect()
+    };
+    let mut buckets = series
+        .iter()
+        .filter(|series| series.name == bucket_name && carries(series, &wanted));
+    // With several label sets left, use the first series' buckets.
+    let first = buckets.next()?;
+    let group = without_le(first);
+    let mut bounds: Vec<(f64, Sample)> = std::iter::once(first)
+        .chain(buckets)
+        .filter(|series| without_le(series) == group)
+        .filter_map(|series| {
+            let (_, le) = series.labels.iter().find(|(key, _)| key == "le")?;
+            Some((le.parse().ok()?, series.sample))
+        })
+        .collect();
+    bounds.sort_by(|a, b| a.0.total_cmp(&b.0));
+    histogram_quantile(quantile, &bounds)
+}
 
-        if let Ok(parsed) = value.parse::<f64>() {
-            samples.insert(metric_name.to_string(), parsed);
+/// Estimate `quantile` from cumulative `(le, sample)` buckets sorted by
+/// bound, interpolating linearly within a bucket like PromQL's
+/// `histogram_quantile`. An empty histogram reads as `0`.
+fn histogram_quantile(quantile: f64, buckets: &[(f64, Sample)]) -> Option<Sample> {
+    let &(last_le, last) = buckets.last()?;
+    if last_le != f64::INFINITY || !(0.0..=1.0).contains(&quantile) {
+        return None;
+    }
+    let timestamp_ms = last.timestamp_ms;
+    if last.value <= 0.0 {
+        return Some(Sample {
+            value: 0.0,
+            timestamp_ms,
+        });
+    }
+    let rank = quantile * last.value;
+    let (mut lower_le, mut lower_count) = (0.0, 0.0);
+    for &(le, bucket) in buckets {
+        if bucket.value >= rank {
+            let value = if le == f64::INFINITY {
+                lower_le
+            } else if bucket.value == lower_count {
+                le
+            } else {
+                lower_le + (le - lower_le) * (rank - lower_count) / (bucket.value - lower_count)
+            };
+            return Some(Sample {
+                value,
+                timestamp_ms,
+            });
         }
+        (lower_le, lower_count) = (le, bucket.value);
     }
-    samples
+    None
 }
 
-fn labels_match_validator(labels: &str, validator_id: &str) -> bool {
+fn labels_match_validator(
+    labels: &[(String, String)],
+    validator_id: &str,
+    ids: &ValidatorIdsConfig,
+) -> bool {
     if validator_id.is_empty() {
         return true;
     }
-    for pair in labels.split(',') {
-        let mut kv = pair.splitn(2, '=');
-        let key = kv.next().unwrap_or("").trim();
-        let raw_value = kv.next().unwrap_or("").trim();
-        if key == "id" {
-            let normalized = raw_value.trim_matches('"');
-            return normalized == validator_id;
-        }
+    match labels.iter().find(|(key, _)| key == "id") {
+        Some((_, value)) => ids.normalize(value) == validator_id,
+        None => true,
     }
-    true
 }
 
 fn map_status(err: Status) -> anyhow::Error {
-    anyhow!("gRPC error: {err}")
+    anyhow::Error::new(err).context("gRPC error")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use executor::proto::{
+        ActionHistoryRequest, ActionHistoryResponse, MetricsSnapshot, MetricsWatchRequest,
+        PendingActionsRequest, PendingActionsResponse, PlaybookExecutionsRequest,
+        PlaybookExecutionsResponse, ReportAck, ScrapeRequest, ValidatorStatesRequest,
+        ValidatorStatesResponse, ValidatorToggleRequest,
+    };
+    use tokio::time::timeout;
+
+    const BODY: &str = "\
+# TYPE validator_slot_lag gauge
+validator_slot_lag{id=\"v1\"} 12
+validator_vote_success_rate{id=\"v1\"} 0.97
+validator_cpu_usage{id=\"v1\"} 0.4
+validator_ram_usage_gb{id=\"v1\"} 32
+validator_disk_usage_pct{id=\"v1\"} 71 1700000000000
+validator_rpc_qps{id=\"v1\"} 250
+validator_rpc_error_rate{id=\"v1\"} 0.01
+";
+
+    #[test]
+    fn exporter_ids_are_normalized_before_matching() {
+        let trim = ValidatorIdsConfig::default();
+        let lowercase = ValidatorIdsConfig { lowercase: true };
+        let id = |value: &str| vec![("id".to_string(), value.to_string())];
+        assert!(labels_match_validator(&id("v1 "), "v1", &trim));
+        assert!(!labels_match_validator(&id("V1"), "v1", &trim));
+        assert!(labels_match_validator(&id("V1"), "v1", &lowercase));
+        assert!(!labels_match_validator(&id("v2"), "v1", &lowercase));
+    }
+
+    #[test]
+    fn records_explicit_sample_timestamps_per_field() {
+        let metrics = parse_prometheus_samples(
+            BODY,
+            "v1",
+            &ValidatorIdsConfig::default(),
+            &MetricMapping::default(),
+        )
+        .expect("parsed");
+        assert_eq!(metrics.slot_lag, 12);
+        assert_eq!(metrics.disk_usage_pct, 71.0);
+        assert_eq!(
+            metrics.field_timestamps,
+            BTreeMap::from([("disk_usage_pct".to_string(), 1_700_000_000)])
+        );
+        assert_eq!(metrics.field_updated_at("slot_lag"), metrics.last_updated);
+    }
+
+    #[test]
+    fn configured_names_read_summary_quantiles_and_histogram_estimates() {
+        let body = format!(
+            "{BODY}\
+# TYPE validator_rpc_latency_seconds summary
+validator_rpc_latency_seconds{{id=\"v1\",quantile=\"0.5\"}} 0.02
+validator_rpc_latency_seconds{{id=\"v1\",quantile=\"0.99\"}} 0.4
+validator_rpc_latency_seconds_sum{{id=\"v1\"}} 31.5
+validator_rpc_latency_seconds_count{{id=\"v1\"}} 1000
+# TYPE vote_latency_seconds histogram
+vote_latency_seconds_bucket{{id=\"v1\",le=\"0.1\"}} 50
+vote_latency_seconds_bucket{{id=\"v1\",le=\"0.5\"}} 90
+vote_latency_seconds_bucket{{id=\"v1\",le=\"1\"}} 100
+vote_latency_seconds_bucket{{id=\"v1\",le=\"+Inf\"}} 100
+vote_latency_seconds_sum{{id=\"v1\"}} 17
+vote_latency_seconds_count{{id=\"v1\"}} 100
+"
+        );
+        let fields = BTreeMap::from([
+            (
+                "rpc_error_rate".to_string(),
+                r#"validator_rpc_latency_seconds{quantile="0.99"}"#.to_string(),
+            ),
+            (
+                "cpu_usage".to_string(),
+                r#"vote_latency_seconds{quantile="0.7"}"#.to_string(),
+            ),
+            (
+                "rpc_qps".to_string(),
+                "validator_rpc_latency_seconds_count".to_string(),
+            ),
+        ]);
+        let mapping = MetricMapping {
+            fields,
+            ..MetricMapping::default()
+        };
+        let metrics =
+            parse_prometheus_samples(&body, "v1", &ValidatorIdsConfig::default(), &mapping)
+                .expect("parsed");
+        assert_eq!(metrics.rpc_error_rate, 0.4);
+        assert_eq!(metrics.rpc_qps, 1000.0);
+        // Rank 70 of 100 falls halfway through the (0.1, 0.5] bucket.
+        assert!((metrics.cpu_usage - 0.3).abs() < 1e-9);
+        assert_eq!(metrics.slot_lag, 12);
+
+        let missing = MetricMapping {
+            fields: BTreeMap::from([(
+                "rpc_qps".to_string(),
+                r#"validator_rpc_latency_seconds{quantile="0.9"}"#.to_string(),
+            )]),
+            ..MetricMapping::default()
+        };
+        let err = parse_prometheus_samples(&body, "v1", &ValidatorIdsConfig::default(), &missing)
+            .unwrap_err();
+        assert!(err.to_string().contains("quantile=\"0.9\""), "{err}");
+    }
+
+    #[test]
+    fn quoted_label_values_and_numeric_quantiles_are_matched() {
+        let body = format!(
+            "{BODY}\
+validator_rpc_latency_seconds{{id=\"v1\",method=\"getBlock, full\",quantile=\"0.990\"}} 0.7
+validator_rpc_latency_seconds{{id=\"v1\",method=\"a}}\\\"b\",quantile=\"0.5\"}} 0.2
+"
+        );
+        let mapping = MetricMapping {
+            fields: BTreeMap::from([
+                (
+                    "rpc_error_rate".to_string(),
+                    r#"validator_rpc_latency_seconds{method="getBlock, full",quantile="0.99"}"#
+                        .to_string(),
+                ),
+                (
+                    "cpu_usage".to_string(),
+                    r#"validator_rpc_latency_seconds{method="a}\"b"}"#.to_string(),
+                ),
+            ]),
+            ..MetricMapping::default()
+        };
+        let metrics =
+            parse_prometheus_samples(&body, "v1", &ValidatorIdsConfig::default(), &mapping)
+                .expect("parsed");
+        assert_eq!(metrics.rpc_error_rate, 0.7);
+        assert_eq!(metrics.cpu_usage, 0.2);
+
+        assert_eq!(
+            parse_label_set(r#"id="v1", path="/a,b" } 3"#),
+            Some((
+                vec![
+                    ("id".to_string(), "v1".to_string()),
+                    ("path".to_string(), "/a,b".to_string()),
+                ],
+                " 3"
+            ))
+        );
+        assert_eq!(parse_label_set(r#"id="v1"#), None);
+    }
+
+    #[test]
+    fn renamed_exporter_metrics_are_mapped_and_missing_ones_neutral_unless_strict() {
+        let body = "\
+solana_validator_slot_distance{id=\"v1\"} 40
+solana_vote_success_ratio{id=\"v1\"} 0.9
+";
+        let mut mapping = MetricMapping {
+            fields: BTreeMap::from([
+                (
+                    "slot_lag".to_string(),
+                    "solana_validator_slot_distance".to_string(),
+                ),
+                (
+                    "vote_success_rate".to_string(),
+                    "solana_vote_success_ratio".to_string(),
+                ),
+            ]),
+            strict: true,
+        };
+        let ids = ValidatorIdsConfig::default();
+        let err = parse_prometheus_samples(body, "v1", &ids, &mapping).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "missing validator_cpu_usage metric for validator v1"
+        );
+
+        mapping.strict = false;
+        let metrics = parse_prometheus_samples(body, "v1", &ids, &mapping).expect("parsed");
+        assert_eq!(metrics.slot_lag, 40);
+        assert_eq!(metrics.vote_success_rate, 0.9);
+        assert_eq!(metrics.cpu_usage, 0.0);
+        assert_eq!(metrics.rpc_error_rate, 0.0);
+
+        // A missing vote rate reads as fully voting, not as a vote failure.
+        let metrics = parse_prometheus_samples("", "v1", &ids, &mapping).expect("parsed");
+        assert_eq!(metrics.vote_success_rate, 1.0);
+    }
+
+    #[tokio::test]
+    async fn trigger_scrapes_between_ticks() {
+        let trigger = Arc::new(Notify::new());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let schedule = tokio::spawn(run_scrape_schedule(
+            Duration::from_secs(60),
+            trigger.clone(),
+            move || {
+                let _ = tx.send(());
+                async {}
+            },
+        ));
+
+        // The first tick fires immediately; the next is a minute away.
+        let wait = Duration::from_secs(1);
+        tokio::time::timeout(wait, rx.recv())
+            .await
+            .expect("first tick");
+        trigger.notify_one();
+        tokio::time::timeout(wait, rx.recv())
+            .await
+            .expect("triggered scrape");
+        schedule.abort();
+    }
+
+    #[tokio::test]
+    async fn unauthenticated_exits_after_cap() {
+        let mut attempts = 0;
+        let err = run_with_retries(3, Backoff::new(Duration::ZERO, Duration::ZERO), || {
+            attempts += 1;
+            async { Err(map_status(Status::unauthenticated("invalid auth token"))) }
+        })
+        .await;
+        assert_eq!(attempts, 3);
+        assert!(is_permanent(&err));
+    }
+
+    #[tokio::test]
+    async fn clean_attempt_resets_the_rejection_streak() {
+        let mut attempts = 0;
+        // Two rejections at a time, then a session that ends cleanly, twice
+        // over, before three rejections in a row.
+        let err = run_with_retries(3, Backoff::new(Duration::ZERO, Duration::ZERO), || {
+            attempts += 1;
+            let clean = attempts % 3 == 0 && attempts <= 6;
+            async move {
+                if clean {
+                    Ok(())
+                } else {
+                    Err(map_status(Status::unauthenticated("invalid auth token")))
+                }
+            }
+        })
+        .await;
+        assert_eq!(attempts, 9);
+        assert!(is_permanent(&err));
+    }
+
+    #[tokio::test]
+    async fn connection_refused_keeps_retrying() {
+        let mut attempts = 0;
+        // Refused connections for ten attempts, then a permanent rejection so the loop ends.
+        let err = run_with_retries(3, Backoff::new(Duration::ZERO, Duration::ZERO), || {
+            attempts += 1;
+            let refused = attempts <= 10;
+            async move {
+                if refused {
+                    Endpoint::from_static("http://127.0.0.1:1")
+                        .connect()
+                        .await
+                        .context("failed to connect to executor server")?;
+                }
+                Err(map_status(Status::not_found("validator not registered")))
+            }
+        })
+        .await;
+        assert_eq!(attempts, 13);
+        assert!(is_permanent(&err));
+    }
+
+    /// Fails every publish with `status`, counting attempts.
+    struct FailingPublisher {
+        status: Status,
+        attempts: u32,
+    }
+
+    impl MetricsPublisher for FailingPublisher {
+        async fn publish(&mut self, _update: MetricsUpdate) -> Result<(), Status> {
+            self.attempts += 1;
+            Err(self.status.clone())
+        }
+    }
+
+    /// Accepts every publish, keeping the updates.
+    #[derive(Default)]
+    struct RecordingPublisher {
+        updates: Vec<MetricsUpdate>,
+    }
+
+    impl MetricsPublisher for RecordingPublisher {
+        async fn publish(&mut self, update: MetricsUpdate) -> Result<(), Status> {
+            self.updates.push(update);
+            Ok(())
+        }
+    }
+
+    fn retry(retries: u32) -> PublishRetry {
+        PublishRetry {
+            retries,
+            backoff: Duration::ZERO,
+            budget: Duration::from_secs(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn persistent_network_failure_drops_after_retries() {
+        let stats = PublishStats::default();
+        let mut publisher = FailingPublisher {
+            status: Status::unavailable("connection refused"),
+            attempts: 0,
+        };
+        for _ in 0..2 {
+            let dropped =
+                publish_with_retries(&mut publisher, MetricsUpdate::default(), &retry(3), &stats)
+                    .await;
+            assert_eq!(dropped, Some(DropReason::Network));
+        }
+        assert_eq!(publisher.attempts, 8);
+        assert_eq!(stats.retries.load(Ordering::Relaxed), 6);
+        assert_eq!(stats.dropped_network.load(Ordering::Relaxed), 2);
+        let rendered = stats.render();
+        assert!(rendered.contains("validator_client_metrics_dropped_total{reason=\"network\"} 2\n"));
+        assert!(rendered.contains("validator_client_metrics_dropped_total{reason=\"auth\"} 0\n"));
+    }
+
+    #[tokio::test]
+    async fn auth_failure_is_dropped_without_retrying() {
+        let stats = PublishStats::default();
+        let mut publisher = FailingPublisher {
+            status: Status::unauthenticated("invalid auth token"),
+            attempts: 0,
+        };
+        let dropped =
+            publish_with_retries(&mut publisher, MetricsUpdate::default(), &retry(3), &stats).await;
+        assert_eq!(dropped, Some(DropReason::Auth));
+        assert_eq!(publisher.attempts, 1);
+        assert_eq!(stats.retries.load(Ordering::Relaxed), 0);
+        assert_eq!(stats.dropped_auth.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn resource_exhausted_is_not_retried() {
+        let stats = PublishStats::default();
+        let mut publisher = FailingPublisher {
+            status: Status::resource_exhausted("too many requests"),
+            attempts: 0,
+        };
+        let dropped =
+            publish_with_retries(&mut publisher, MetricsUpdate::default(), &retry(3), &stats).await;
+        assert_eq!(dropped, Some(DropReason::Rejected));
+        assert_eq!(publisher.attempts, 1);
+        assert_eq!(stats.retries.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn scrapes_through_configured_proxy_with_auth() {
+        use axum::{http::HeaderMap, http::StatusCode, routing::get, Router};
+
+        // Stands in for the proxy: answers the absolute-form request itself.
+        async fn proxy(headers: HeaderMap) -> (StatusCode, &'static str) {
+            // base64("ops:hunter2")
+            match headers.get("proxy-authorization") {
+                Some(auth) if auth == "Basic b3BzOmh1bnRlcjI=" => (StatusCode::OK, BODY),
+                _ => (StatusCode::PROXY_AUTHENTICATION_REQUIRED, ""),
+            }
+        }
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route("/metrics", get(proxy));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        env::set_var("SCRAPE_PROXY_TEST_PASSWORD", "hunter2");
+        let scraper = Scraper {
+            http: build_http_client(Some(&ScrapeProxyConfig {
+                url: format!("http://{addr}"),
+                username: Some("ops".into()),
+                password_env: Some("SCRAPE_PROXY_TEST_PASSWORD".into()),
+            }))
+            .expect("client"),
+            ids: ValidatorIdsConfig::default(),
+            retry: PublishRetry {
+                retries: 0,
+                backoff: Duration::ZERO,
+                budget: Duration::ZERO,
+            },
+            stats: Arc::default(),
+            permits: Arc::new(Semaphore::new(1)),
+            metrics: Arc::default(),
+        };
+
+        // Unresolvable unless the request actually goes via the proxy.
+        let metrics =
+            scrape_validator_metrics(&scraper, "v1", "http://exporter.invalid:9100/metrics")
+                .await
+                .expect("scraped via proxy");
+        assert_eq!(metrics.slot_lag, 12);
+    }
+
+    #[tokio::test]
+    async fn failing_primary_falls_through_to_a_cached_file() {
+        // Bound then dropped, so nothing is listening on it.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let primary = format!("http://{}/metrics", listener.local_addr().unwrap());
+        drop(listener);
+        let cache = env::temp_dir().join(format!("validator-metrics-{}.prom", std::process::id()));
+        std::fs::write(&cache, BODY).unwrap();
+        let cached = format!("file://{}", cache.display());
+        let scraper = Scraper {
+            http: build_http_client(None).expect("client"),
+            ids: ValidatorIdsConfig::default(),
+            retry: PublishRetry {
+                retries: 0,
+                backoff: Duration::ZERO,
+                budget: Duration::ZERO,
+            },
+            stats: Arc::default(),
+            permits: Arc::new(Semaphore::new(1)),
+            metrics: Arc::default(),
+        };
+
+        let chain = ChainedMetricsSource::new(vec![
+            ConfiguredSource(primary.clone()),
+            ConfiguredSource(cached.clone()),
+        ]);
+        // Last refreshed an hour ago.
+        let modified = common::now_ts() - 3_600;
+        std::fs::File::options()
+            .write(true)
+            .open(&cache)
+            .unwrap()
+            .set_modified(std::time::UNIX_EPOCH + Duration::from_secs(modified as u64))
+            .unwrap();
+
+        let (metrics, served_by) = chain.fetch(&scraper, "v1").await.expect("fell through");
+        assert_eq!(served_by, cached);
+        assert_eq!(metrics.slot_lag, 12);
+        assert_eq!(metrics.last_updated, modified);
+        assert!(scraper.stats.render().contains(&format!(
+            "validator_client_metrics_scrapes_total{{validator=\"v1\",source=\"{cached}\"}} 1\n"
+        )));
+
+        let mut publisher = RecordingPublisher::default();
+        scrape_and_publish(&mut publisher, &scraper, "v1", "token", &chain).await;
+        let [update] = &publisher.updates[..] else {
+            panic!("expected one update, got {:?}", publisher.updates);
+        };
+        assert_eq!(update.source, cached);
+        let published: ValidatorMetrics = serde_json::from_str(&update.metrics_json).unwrap();
+        assert_eq!(published.last_updated, modified);
+
+        std::fs::remove_file(&cache).unwrap();
+        let err = chain.fetch(&scraper, "v1").await.unwrap_err().to_string();
+        assert!(err.starts_with("every metrics source failed"), "{err}");
+        assert!(err.contains(&primary) && err.contains(&cached), "{err}");
+    }
+
+    /// Daemon stand-in recording which validators opened an action stream
+    /// and which published metrics.
+    #[derive(Clone, Default)]
+    struct RecordingDaemon {
+        streams: Arc<std::sync::Mutex<Vec<String>>>,
+        publishes: Arc<std::sync::Mutex<Vec<String>>>,
+        /// Streams refused because the validator is `retired`.
+        rejected: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    type Streamed<T> =
+        std::pin::Pin<Box<dyn tokio_stream::Stream<Item = Result<T, Status>> + Send + 'static>>;
+
+    #[tonic::async_trait]
+    impl executor::proto::executor_server::Executor for RecordingDaemon {
+        type StreamActionsStream = Streamed<ActionEnvelope>;
+        type SubscribeMetricsStream = Streamed<MetricsUpdate>;
+
+        async fn stream_actions(
+            &self,
+            request: tonic::Request<ConnectRequest>,
+        ) -> Result<tonic::Response<Self::StreamActionsStream>, Status> {
+            let validator_id = request.into_inner().validator_id;
+            if validator_id == "retired" {
+                self.rejected.lock().unwrap().push(validator_id);
+                return Err(Status::not_found("validator not registered"));
+            }
+            self.streams.lock().unwrap().push(validator_id);
+            Ok(tonic::Response::new(Box::pin(tokio_stream::pending())))
+        }
+
+        async fn report_result(
+            &self,
+            _request: tonic::Request<ActionResult>,
+        ) -> Result<tonic::Response<ReportAck>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+
+        async fn publish_metrics(
+            &self,
+            request: tonic::Request<MetricsUpdate>,
+        ) -> Result<tonic::Response<ReportAck>, Status> {
+            let validator_id = request.into_inner().validator_id;
+            self.publishes.lock().unwrap().push(validator_id);
+            Ok(tonic::Response::new(ReportAck {}))
+        }
+
+        async fn subscribe_metrics(
+            &self,
+            _request: tonic::Request<MetricsWatchRequest>,
+        ) -> Result<tonic::Response<Self::SubscribeMetricsStream>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+
+        async fn get_all_metrics(
+            &self,
+            _request: tonic::Request<MetricsWatchRequest>,
+        ) -> Result<tonic::Response<MetricsSnapshot>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+
+        async fn submit_action(
+            &self,
+            _request: tonic::Request<ActionEnvelope>,
+        ) -> Result<tonic::Response<ReportAck>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+
+        async fn disable_validator(
+            &self,
+            _request: tonic::Request<ValidatorToggleRequest>,
+        ) -> Result<tonic::Response<ReportAck>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+
+        async fn enable_validator(
+            &self,
+            _request: tonic::Request<ValidatorToggleRequest>,
+        ) -> Result<tonic::Response<ReportAck>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+
+        async fn list_validator_states(
+            &self,
+            _request: tonic::Request<ValidatorStatesRequest>,
+        ) -> Result<tonic::Response<ValidatorStatesResponse>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+
+        async fn scrape_now(
+            &self,
+            _request: tonic::Request<ScrapeRequest>,
+        ) -> Result<tonic::Response<ReportAck>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+
+        async fn list_action_history(
+            &self,
+            _request: tonic::Request<ActionHistoryRequest>,
+        ) -> Result<tonic::Response<ActionHistoryResponse>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+
+        async fn list_pending_actions(
+            &self,
+            _request: tonic::Request<PendingActionsRequest>,
+        ) -> Result<tonic::Response<PendingActionsResponse>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+
+        async fn list_playbook_executions(
+            &self,
+            _request: tonic::Request<PlaybookExecutionsRequest>,
+        ) -> Result<tonic::Response<PlaybookExecutionsResponse>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+
+        async fn register_validator(
+            &self,
+            _request: tonic::Request<RegisterValidatorRequest>,
+        ) -> Result<tonic::Response<ReportAck>, Status> {
+            Err(Status::unimplemented("not used"))
+        }
+    }
+
+    #[tokio::test]
+    async fn multi_validator_client_serves_each_and_outlives_one_giving_up() {
+        use axum::{routing::get, Router};
+        use executor::proto::executor_server::ExecutorServer;
+
+        let exporter = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let exporter_addr = exporter.local_addr().unwrap();
+        let app = Router::new().route("/v1", get(|| async { BODY })).route(
+            "/v2",
+            get(|| async { BODY.replace(r#"id="v1""#, r#"id="v2""#) }),
+        );
+        tokio::spawn(async move { axum::serve(exporter, app).await });
+
+        let daemon = RecordingDaemon::default();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let daemon_addr = listener.local_addr().unwrap();
+        let server = tonic::transport::Server::builder()
+            .add_service(ExecutorServer::new(daemon.clone()))
+            .serve_with_incoming(futures_util::stream::unfold(listener, |listener| async {
+                let conn = listener.accept().await.map(|(tcp, _)| tcp);
+                Some((conn, listener))
+            }));
+        tokio::spawn(server);
+
+        // The daemon rejects `retired`, so it gives up on the first attempt.
+        let targets: Vec<Target> = ["v1", "v2", "retired"]
+            .into_iter()
+            .map(|id| Target {
+                validator_id: id.into(),
+                auth_token: format!("{id}-token"),
+                prometheus_url: format!("http://{exporter_addr}/{id}"),
+                metrics_fallbacks: Vec::new(),
+                registration: None,
+            })
+            .collect();
+        let scraper = Scraper {
+            http: build_http_client(None).expect("client"),
+            ids: ValidatorIdsConfig::default(),
+            retry: PublishRetry {
+                retries: 0,
+                backoff: Duration::ZERO,
+                budget: Duration::ZERO,
+            },
+            stats: Arc::default(),
+            permits: Arc::new(Semaphore::new(1)),
+            metrics: Arc::default(),
+        };
+        let server_addr = format!("http://{daemon_addr}");
+        let commands = ActionCommands::default();
+        let serving = serve_validators(&server_addr, None, &targets, &commands, &scraper, 1);
+        tokio::pin!(serving);
+
+        let sorted = |seen: &std::sync::Mutex<Vec<String>>| {
+            let mut seen = seen.lock().unwrap().clone();
+            seen.sort();
+            seen.dedup();
+            seen
+        };
+        let both = vec!["v1".to_string(), "v2".to_string()];
+        timeout(Duration::from_secs(10), async {
+            loop {
+                tokio::select! {
+                    (err, id) = &mut serving => panic!("{id} gave up: {err:#}"),
+                    _ = sleep(Duration::from_millis(20)) => {}
+                }
+                if sorted(&daemon.streams) == both
+                    && sorted(&daemon.publishes) == both
+                    && !daemon.rejected.lock().unwrap().is_empty()
+                {
+                    break;
+                }
+            }
+        })
+        .await
+        .expect("both validators streamed and published");
+        // The others keep serving after `retired` gave up.
+        tokio::select! {
+            (err, id) = &mut serving => panic!("{id} gave up: {err:#}"),
+            _ = sleep(Duration::from_millis(200)) => {}
+        }
+        assert_eq!(daemon.streams.lock().unwrap().len(), 2);
+        assert_eq!(*daemon.rejected.lock().unwrap(), ["retired"]);
+    }
+
+    /// Reports the queued slot lags in order, then fails to scrape.
+    #[derive(Default)]
+    struct ScriptedLag(std::sync::Mutex<VecDeque<i64>>);
+
+    impl SlotLagProbe for ScriptedLag {
+        async fn slot_lag(&self) -> Result<i64> {
+            self.0
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or_else(|| anyhow!("exporter unreachable"))
+        }
+    }
+
+    /// Runs the checked `enable_rpc` step of a slot-lag plan after the probe
+    /// reports `lags`, `interval_secs` apart and within a `deadline_secs`
+    /// plan deadline (0 for none), returning its result and whether RPC was
+    /// re-enabled, plus any alert sent.
+    async fn enable_after_restart(
+        lags: Vec<i64>,
+        interval_secs: u64,
+        deadline_secs: i64,
+    ) -> ((bool, String), bool, String) {
+        let dir = std::env::temp_dir().join(format!(
+            "recovery-{}-{}-{deadline_secs}",
+            std::process::id(),
+            lags.len()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (enabled, alerts) = (dir.join("enabled"), dir.join("alerts"));
+        let _ = std::fs::remove_file(&enabled);
+        let _ = std::fs::remove_file(&alerts);
+        let mut commands = ActionCommands::default();
+        commands
+            .actions
+            .insert("enable_rpc".into(), format!("touch {}", enabled.display()));
+        commands.actions.insert(
+            "send_alert".into(),
+            format!("printf '%s' {{{{message}}}} > {}", alerts.display()),
+        );
+        let action = Action::EnableRpc {
+            validator: common::ValidatorId("v1".into()),
+        };
+        let msg = ActionEnvelope {
+            validator_id: "v1".into(),
+            action_json: serde_json::to_string(&action).unwrap(),
+            recovery_check: Some(RecoveryCheck {
+                max_slot_lag: 50,
+                attempts: 3,
+                interval_secs,
+            }),
+            plan_id: "v1:slot-lag-recovery:1700000000".into(),
+            plan_deadline_secs: deadline_secs,
+            ..ActionEnvelope::default()
+        };
+        let probe = ScriptedLag(std::sync::Mutex::new(lags.into()));
+        let result = execute_step(
+            &msg,
+            action,
+            &commands,
+            &probe,
+            &mut PlanDeadlines::default(),
+        )
+        .await;
+        let alert = std::fs::read_to_string(&alerts).unwrap_or_default();
+        (result, enabled.exists(), alert)
+    }
+
+    #[tokio::test]
+    async fn recovered_validator_gets_rpc_back() {
+        let (result, enabled, alert) = enable_after_restart(vec![120, 30], 0, 0).await;
+        assert_eq!(result, (true, "ok".to_string()));
+        assert!(enabled);
+        assert!(alert.is_empty(), "{alert}");
+    }
+
+    #[tokio::test]
+    async fn unrecovered_validator_keeps_rpc_disabled_and_alerts() {
+        let (result, enabled, alert) = enable_after_restart(vec![120, 110, 90], 0, 0).await;
+        let held = "held: slot_lag 90 still above 50 after 3 checks, RPC left disabled";
+        assert_eq!(result, (false, held.to_string()));
+        assert!(!enabled);
+        assert_eq!(alert, format!("v1 not recovered after restart: {held}"));
+    }
+
+    #[tokio::test]
+    async fn recovery_wait_cut_short_by_the_plan_deadline_still_alerts() {
+        let started = std::time::Instant::now();
+        let (result, enabled, alert) = enable_after_restart(vec![120, 110, 90], 5, 1).await;
+        assert!(started.elapsed() < Duration::from_secs(3));
+        let held = "held: plan v1:slot-lag-recovery:1700000000 reached its 1s deadline before recovery, RPC left disabled";
+        assert_eq!(result, (false, held.to_string()));
+        assert!(!enabled);
+        assert_eq!(alert, format!("v1 not recovered after restart: {held}"));
+    }
+
+    #[tokio::test]
+    async fn plan_past_its_deadline_is_aborted_partway() {
+        let commands = ActionCommands {
+            custom: HashMap::from([
+                ("quick".to_string(), "true".to_string()),
+                ("slow".to_string(), "sleep 5".to_string()),
+            ]),
+            ..ActionCommands::default()
+        };
+        let step = |command_id: &str| {
+            let action = Action::ExecCommand {
+                validator: common::ValidatorId("v1".into()),
+                command_id: command_id.into(),
+            };
+            let msg = ActionEnvelope {
+                validator_id: "v1".into(),
+                action_json: serde_json::to_string(&action).unwrap(),
+                plan_id: "v1:diag:1700000000".into(),
+                plan_deadline_secs: 1,
+                ..ActionEnvelope::default()
+            };
+            (msg, action)
+        };
+
+        let mut deadlines = PlanDeadlines::default();
+        let mut results = Vec::new();
+        for command_id in ["quick", "slow", "quick"] {
+            let (msg, action) = step(command_id);
+            let probe = ScriptedLag::default();
+            results.push(execute_step(&msg, action, &commands, &probe, &mut deadlines).await);
+        }
+
+        assert_eq!(results[0], (true, "ok".to_string()));
+        for (success, message) in &results[1..] {
+            assert!(!success);
+            assert!(
+                message.starts_with("timeout: plan v1:diag:1700000000"),
+                "{message}"
+            );
+        }
+    }
+
+    fn plan_step(step: i32, depends_on: Vec<i32>, command: &str) -> ActionEnvelope {
+        let action = Action::ExecCommand {
+            validator: common::ValidatorId("v1".into()),
+            command_id: command.into(),
+        };
+        ActionEnvelope {
+            validator_id: "v1".into(),
+            action_json: serde_json::to_string(&action).unwrap(),
+            plan_id: "v1:slot-lag-recovery:1700000000".into(),
+            step,
+            depends_on,
+            ..ActionEnvelope::default()
+        }
+    }
+
+    /// The steps reported as started, in order.
+    #[derive(Default)]
+    struct StartedSteps(Vec<i32>);
+
+    impl StepReporter for StartedSteps {
+        async fn started(&mut self, msg: &ActionEnvelope) -> Result<()> {
+            self.0.push(msg.step);
+            Ok(())
+        }
+    }
+
+    async fn run_plan(steps: Vec<ActionEnvelope>) -> Vec<(i32, bool, String)> {
+        run_plan_reporting(steps, &mut StartedSteps::default()).await
+    }
+
+    async fn run_plan_reporting(
+        steps: Vec<ActionEnvelope>,
+        started: &mut StartedSteps,
+    ) -> Vec<(i32, bool, String)> {
+        let commands = ActionCommands {
+            custom: HashMap::from([
+                ("pass".to_string(), "true".to_string()),
+                ("fail".to_string(), "false".to_string()),
+                ("undo".to_string(), "true".to_string()),
+            ]),
+            ..ActionCommands::default()
+        };
+        let mut deadlines = PlanDeadlines::default();
+        let mut dependencies = StepDependencies::default();
+        let mut results = Vec::new();
+        for msg in steps {
+            let probe = ScriptedLag::default();
+            let finished = run_envelope(
+                msg,
+                &commands,
+                &probe,
+                started,
+                &mut deadlines,
+                &mut dependencies,
+            )
+            .await
+            .unwrap();
+            results.extend(
+                finished
+                    .into_iter()
+                    .map(|report| (report.msg.step, report.success, report.message)),
+            );
+        }
+        results
+    }
+
+    #[tokio::test]
+    async fn dependent_step_is_skipped_when_prerequisite_fails() {
+        // The dependent arrives first, as a higher-priority step would.
+        let results = run_plan(vec![
+            plan_step(2, vec![1], "pass"),
+            plan_step(1, vec![], "fail"),
+        ])
+        .await;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, 1);
+        assert!(!results[0].1);
+        assert_eq!(
+            results[1],
+            (
+                2,
+                false,
+                "skipped: prerequisite step 1 did not succeed".to_string()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn only_steps_that_run_are_reported_started() {
+        let mut started = StartedSteps::default();
+        // Step 3 is parked behind step 1, then skipped when it fails.
+        run_plan_reporting(
+            vec![
+                plan_step(3, vec![1], "pass"),
+                plan_step(2, vec![], "pass"),
+                plan_step(1, vec![], "fail"),
+            ],
+            &mut started,
+        )
+        .await;
+        assert_eq!(started.0, [2, 1]);
+    }
+
+    #[tokio::test]
+    async fn dependent_step_runs_after_prerequisite_succeeds() {
+        let results = run_plan(vec![
+            plan_step(2, vec![1], "pass"),
+            plan_step(1, vec![], "pass"),
+        ])
+        .await;
+        assert_eq!(
+            results,
+            vec![(1, true, "ok".to_string()), (2, true, "ok".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_step_rolls_back_completed_steps_newest_first() {
+        let undoable = |step: i32| ActionEnvelope {
+            compensation_json: serde_json::to_string(&Action::ExecCommand {
+                validator: common::ValidatorId("v1".into()),
+                command_id: "undo".into(),
+            })
+            .unwrap(),
+            ..plan_step(step, vec![], "pass")
+        };
+        let results = run_plan(vec![
+            undoable(0),
+            undoable(1),
+            plan_step(3, vec![2], "pass"),
+            plan_step(2, vec![], "fail"),
+            plan_step(4, vec![], "pass"),
+        ])
+        .await;
+        let ok = |step: i32, message: &str| (step, true, message.to_string());
+        assert_eq!(results[..2], [ok(0, "ok"), ok(1, "ok")]);
+        assert_eq!(results[2].0, 2);
+        assert!(!results[2].1);
+        assert_eq!(
+            results[3..],
+            [
+                ok(1, "rollback of step 1: ok"),
+                ok(0, "rollback of step 0: ok"),
+                (
+                    3,
+                    false,
+                    "skipped: prerequisite step 2 did not succeed".to_string()
+                ),
+                (
+                    4,
+                    false,
+                    "skipped: plan rolled back after step 2 failed".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn refused_step_is_not_run_and_rolls_its_plan_back() {
+        let mut started = StartedSteps::default();
+        let results = run_plan_reporting(
+            vec![
+                ActionEnvelope {
+                    compensation_json: serde_json::to_string(&Action::ExecCommand {
+                        validator: common::ValidatorId("v1".into()),
+                        command_id: "undo".into(),
+                    })
+                    .unwrap(),
+                    ..plan_step(0, vec![], "pass")
+                },
+                ActionEnvelope {
+                    refused: "already restarting: v1 has a restart in flight".into(),
+                    ..plan_step(1, vec![0], "pass")
+                },
+                plan_step(2, vec![1], "pass"),
+            ],
+            &mut started,
+        )
+        .await;
+        assert_eq!(started.0, [0]);
+        assert_eq!(
+            results,
+            [
+                (0, true, "ok".to_string()),
+                (
+                    1,
+                    false,
+                    "already restarting: v1 has a restart in flight".to_string()
+                ),
+                (0, true, "rollback of step 0: ok".to_string()),
+                (
+                    2,
+                    false,
+                    "skipped: prerequisite step 1 did not succeed".to_string()
+                ),
+            ]
+        );
+    }
 }
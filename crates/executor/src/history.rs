@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+
+use crate::proto::{ActionHistoryRequest, ActionRecord};
+
+/// The last `capacity` reported action results, oldest first.
+///
+/// Kept apart from the daemon's main state so history queries never wait on
+/// (or hold) the lock that action delivery and metrics fan-out take.
+pub struct ActionHistory {
+    records: VecDeque<ActionRecord>,
+    capacity: usize,
+}
+
+impl ActionHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            records: VecDeque::with_capacity(capacity.min(1024)),
+            capacity,
+        }
+    }
+
+    pub fn record(&mut self, record: ActionRecord) {
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    /// Records matching `request`, newest first.
+    pub fn query(&self, request: &ActionHistoryRequest) -> Vec<ActionRecord> {
+        let limit = match request.limit {
+            0 => usize::MAX,
+            limit => limit as usize,
+        };
+        self.records
+            .iter()
+            .rev()
+            .filter(|record| {
+                (request.validator_id.is_empty() || record.validator_id == request.validator_id)
+                    && (request.since == 0 || record.timestamp >= request.since)
+                    && (request.until == 0 || record.timestamp <= request.until)
+            })
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(validator_id: &str, timestamp: i64) -> ActionRecord {
+        ActionRecord {
+            validator_id: validator_id.into(),
+            timestamp,
+            success: true,
+            ..ActionRecord::default()
+        }
+    }
+
+    #[test]
+    fn oldest_records_are_dropped_and_queries_filter_newest_first() {
+        let mut history = ActionHistory::new(3);
+        for (id, at) in [("v1", 10), ("v2", 20), ("v1", 30), ("v1", 40)] {
+            history.record(record(id, at));
+        }
+        let timestamps = |request: ActionHistoryRequest| -> Vec<i64> {
+            history
+                .query(&request)
+                .iter()
+                .map(|record| record.timestamp)
+                .collect()
+        };
+
+        assert_eq!(timestamps(ActionHistoryRequest::default()), [40, 30, 20]);
+        let v1 = ActionHistoryRequest {
+            validator_id: "v1".into(),
+            ..ActionHistoryRequest::default()
+        };
+        assert_eq!(timestamps(v1.clone()), [40, 30]);
+        assert_eq!(
+            timestamps(ActionHistoryRequest {
+                until: 35,
+                ..v1.clone()
+            }),
+            [30]
+        );
+        assert_eq!(
+            timestamps(ActionHistoryRequest {
+                since: 20,
+                limit: 1,
+                ..ActionHistoryRequest::default()
+            }),
+            [40]
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// Upper bounds, in seconds, of the `copilot_action_latency_seconds` buckets.
+pub const LATENCY_BUCKETS: [f64; 10] = [0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0];
+
+/// Submissions whose result never arrives are forgotten after this long.
+const IN_FLIGHT_RETENTION: Duration = Duration::from_secs(24 * 3600);
+
+#[derive(Default)]
+struct Histogram {
+    /// Per-bucket (non-cumulative) counts; the last slot is `+Inf`.
+    counts: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+/// Time from an action's submission to its reported result, by action kind.
+///
+/// Only actions with an `action_id` (the envelope's idempotency key) can be
+/// matched to their result, so operator-initiated actions are not measured.
+#[derive(Default)]
+pub struct ActionLatency {
+    in_flight: HashMap<String, (String, Instant)>,
+    histograms: BTreeMap<String, Histogram>,
+}
+
+impl ActionLatency {
+    pub fn submitted(&mut self, action_id: &str, kind: &str, at: Instant) {
+        self.in_flight
+            .retain(|_, (_, since)| at.saturating_duration_since(*since) < IN_FLIGHT_RETENTION);
+        self.in_flight
+            .insert(action_id.to_string(), (kind.to_string(), at));
+    }
+
+    /// Record the latency of `action_id` if its submission was seen.
+    pub fn completed(&mut self, action_id: &str, at: Instant) -> Option<Duration> {
+        let (kind, since) = self.in_flight.remove(action_id)?;
+        let latency = at.saturating_duration_since(since);
+        let secs = latency.as_secs_f64();
+        let histogram = self.histograms.entry(kind).or_default();
+        let bucket = LATENCY_BUCKETS.partition_point(|le| *le < secs);
+        histogram.counts[bucket] += 1;
+        histogram.sum += secs;
+        histogram.count += 1;
+        Some(latency)
+    }
+
+    pub fn render(&self, out: &mut String) {
+        out.push_str("# HELP copilot_action_latency_seconds Time from action submission to its reported result.\n");
+        out.push_str("# TYPE copilot_action_latency_seconds histogram\n");
+        for (kind, histogram) in &self.histograms {
+            let mut cumulative = 0;
+            for (le, count) in LATENCY_BUCKETS.iter().zip(&histogram.counts) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "copilot_action_latency_seconds_bucket{{kind=\"{kind}\",le=\"{le}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "copilot_action_latency_seconds_bucket{{kind=\"{kind}\",le=\"+Inf\"}} {}",
+                histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "copilot_action_latency_seconds_sum{{kind=\"{kind}\"}} {}",
+                histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "copilot_action_latency_seconds_count{{kind=\"{kind}\"}} {}",
+                histogram.count
+            );
+        }
+    }
+}
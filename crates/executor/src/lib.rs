@@ -1,5 +1,5 @@
 use anyhow::{bail, Result};
-use common::Action;
+use common::{shell_quote, Action, ActionCommands, RestartKind};
 use std::process::Stdio;
 use std::time::Duration;
 use tokio::process::Command;
@@ -9,48 +9,141 @@ pub mod proto {
     tonic::include_proto!("executor.v1");
 }
 
+pub mod history;
+pub mod latency;
+pub mod playbooks;
+pub mod registry;
+pub mod result_webhook;
+pub mod tls;
+
 /// Executes an action locally on the validator host.
 pub async fn execute_action(action: Action) -> Result<()> {
+    execute_action_with(action, &ActionCommands::default()).await
+}
+
+/// Executes an action, resolving `ExecCommand` ids against `commands` and
+/// running its `[commands.actions]` template when one is configured.
+pub async fn execute_action_with(action: Action, commands: &ActionCommands) -> Result<()> {
+    let runner = Runner {
+        kind: action.kind(),
+        limit: commands.timeout_for(action.kind()),
+        dry_run: commands.dry_run,
+    };
+    if let Some(guard) = commands.preflight.get(action.kind()) {
+        info!(kind = action.kind(), "running pre-flight check");
+        if let Err(err) = runner.run(guard).await {
+            bail!("pre-flight failed for {}: {err}", action.kind());
+        }
+    }
+    let template = commands.action_command(&action);
+    let stub = |stub: &str| template.clone().unwrap_or_else(|| stub.to_string());
     match action {
         Action::DisableRpc { validator } => {
             info!(validator = validator.0, "disabling RPC traffic");
-            run_command("echo disabling rpc").await?;
+            runner.run(&stub("echo disabling rpc")).await?;
         }
         Action::EnableRpc { validator } => {
             info!(validator = validator.0, "enabling RPC traffic");
-            run_command("echo enabling rpc").await?;
+            runner.run(&stub("echo enabling rpc")).await?;
         }
-        Action::RestartValidator { validator } => {
+        Action::RestartValidator {
+            validator,
+            restart_kind: RestartKind::Hard,
+        } => {
             info!(validator = validator.0, "restarting validator process");
-            run_command("echo restarting validator").await?;
+            runner.run(&stub("echo restarting validator")).await?;
+        }
+        Action::RestartValidator {
+            validator,
+            restart_kind: RestartKind::Soft,
+        } => {
+            info!(validator = validator.0, "reloading validator configuration");
+            runner.run(&stub("echo reloading validator")).await?;
         }
         Action::ThrottleRpcClient { validator } => {
             info!(validator = validator.0, "throttling rpc client");
-            run_command("echo throttling rpc client").await?;
+            runner.run(&stub("echo throttling rpc client")).await?;
         }
         Action::RunMaintenanceScript {
             validator,
             script_name,
+            params,
         } => {
+            let command = match template {
+                Some(command) if params.is_empty() => command,
+                _ => commands.script_command(&script_name, &params)?,
+            };
             info!(validator = validator.0, script = %script_name, "running maintenance script");
-            run_command(&format!("sh {}", script_name)).await?;
+            runner.run(&command).await?;
         }
         Action::SendAlert { validator, message } => {
             info!(validator = validator.0, %message, "sending alert");
-            run_command(&format!("echo alert: {message}")).await?;
+            let command = match template {
+                Some(command) => command,
+                None => format!("echo alert: {}", shell_quote(&message)),
+            };
+            runner.run(&command).await?;
+        }
+        Action::ExecCommand {
+            validator,
+            command_id,
+        } => {
+            let Some(command) = commands.custom.get(&command_id) else {
+                bail!("command {command_id} is not registered");
+            };
+            info!(validator = validator.0, command_id = %command_id, "running custom command");
+            runner.run(command).await?;
         }
     }
     Ok(())
 }
 
-async fn run_command(command: &str) -> Result<()> {
-    let status = Command::new("sh")
+/// How the commands for one action are run.
+struct Runner {
+    kind: &'static str,
+    limit: Duration,
+    dry_run: bool,
+}
+
+impl Runner {
+    async fn run(&self, command: &str) -> Result<()> {
+        if self.dry_run {
+            info!(kind = self.kind, command, "dry run, not running command");
+            return Ok(());
+        }
+        run_command(command, self.limit).await
+    }
+}
+
+/// Run `command` under `sh -c`, killing it if it outlives `limit`.
+async fn run_command(command: &str, limit: Duration) -> Result<()> {
+    // In its own process group, so a timeout kills whatever the command
+    // started along with `sh`.
+    let mut child = Command::new("sh")
         .arg("-c")
         .arg(command)
         .stdout(Stdio::null())
         .stderr(Stdio::null())
-        .status()
-        .await?;
+        .process_group(0)
+        .kill_on_drop(true)
+        .spawn()?;
+    let status = match tokio::time::timeout(limit, child.wait()).await {
+        Ok(status) => status?,
+        Err(_) => {
+            if let Some(pid) = child.id() {
+                // SAFETY: `kill` takes no pointers; a negative pid names
+                // the process group `sh` leads.
+                unsafe {
+                    libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+                }
+            }
+            child.kill().await?;
+            bail!(
+                "command `{command}` timed out after {}s and was killed",
+                limit.as_secs_f64()
+            );
+        }
+    };
     tokio::time::sleep(Duration::from_millis(50)).await;
     if status.success() {
         Ok(())
@@ -72,4 +165,229 @@ mod tests {
             .await
             .expect("disable rpc should succeed with stub command");
     }
+
+    #[tokio::test]
+    async fn untemplated_alert_message_is_not_run_by_the_shell() {
+        let marker = std::env::temp_dir().join(format!("alert-message-{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        let touch = format!("touch {}", marker.display());
+        let action = Action::SendAlert {
+            validator: common::ValidatorId("test".into()),
+            message: format!("lagging; {touch} $({touch}) `{touch}`"),
+        };
+        execute_action(action)
+            .await
+            .expect("alert should be echoed");
+        assert!(!marker.exists());
+    }
+
+    #[tokio::test]
+    async fn exec_command_runs_registered_template() {
+        let marker = std::env::temp_dir().join(format!("exec-command-{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        let mut commands = ActionCommands::default();
+        commands
+            .custom
+            .insert("touch-marker".into(), format!("touch {}", marker.display()));
+        let action = Action::ExecCommand {
+            validator: common::ValidatorId("test".into()),
+            command_id: "touch-marker".into(),
+        };
+        execute_action_with(action, &commands)
+            .await
+            .expect("registered command should run");
+        assert!(marker.exists());
+        let _ = std::fs::remove_file(&marker);
+    }
+
+    #[tokio::test]
+    async fn library_script_runs_with_rendered_params() {
+        let marker = std::env::temp_dir().join(format!("script-param-{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        let mut commands = ActionCommands::default();
+        commands.scripts.insert(
+            "touch".into(),
+            common::MaintenanceScript {
+                command: "touch {{path}}".into(),
+                params: [("path".to_string(), common::ScriptParam::default())].into(),
+            },
+        );
+        let action = |params: &[(&str, String)]| Action::RunMaintenanceScript {
+            validator: common::ValidatorId("test".into()),
+            script_name: "touch".into(),
+            params: params
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        };
+        let path = marker.display().to_string();
+        let err = execute_action_with(action(&[("depth", "1".into())]), &commands)
+            .await
+            .expect_err("undeclared parameter must fail");
+        assert!(err.to_string().contains("no parameter depth"));
+        assert!(!marker.exists());
+        execute_action_with(action(&[("path", path)]), &commands)
+            .await
+            .expect("rendered script should run");
+        assert!(marker.exists());
+        let _ = std::fs::remove_file(&marker);
+    }
+
+    #[tokio::test]
+    async fn action_templates_replace_stubs_with_quoted_values() {
+        let dir = std::env::temp_dir().join(format!("action-template-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut commands = ActionCommands::default();
+        commands.actions.insert(
+            "restart_validator".into(),
+            format!("touch {}/restarted-{{{{validator}}}}", dir.display()),
+        );
+        commands.actions.insert(
+            "send_alert".into(),
+            format!("printf %s {{{{message}}}} > {}/alert", dir.display()),
+        );
+        let validator = common::ValidatorId("v1".into());
+
+        execute_action_with(
+            Action::RestartValidator {
+                validator: validator.clone(),
+                restart_kind: RestartKind::Hard,
+            },
+            &commands,
+        )
+        .await
+        .expect("templated restart should run");
+        assert!(dir.join("restarted-v1").exists());
+
+        // Without its own template, a soft restart runs the hard one.
+        std::fs::remove_file(dir.join("restarted-v1")).unwrap();
+        execute_action_with(
+            Action::RestartValidator {
+                validator: validator.clone(),
+                restart_kind: RestartKind::Soft,
+            },
+            &commands,
+        )
+        .await
+        .expect("soft restart should fall back to the restart template");
+        assert!(dir.join("restarted-v1").exists());
+
+        commands.actions.insert(
+            "restart_validator_soft".into(),
+            format!("touch {}/reloaded-{{{{validator}}}}", dir.display()),
+        );
+        std::fs::remove_file(dir.join("restarted-v1")).unwrap();
+        execute_action_with(
+            Action::RestartValidator {
+                validator: validator.clone(),
+                restart_kind: RestartKind::Soft,
+            },
+            &commands,
+        )
+        .await
+        .expect("templated soft restart should run");
+        assert!(dir.join("reloaded-v1").exists());
+        assert!(!dir.join("restarted-v1").exists());
+
+        let message = "disk full'; touch pwned; echo '$(id)";
+        execute_action_with(
+            Action::SendAlert {
+                validator,
+                message: message.into(),
+            },
+            &commands,
+        )
+        .await
+        .expect("templated alert should run");
+        assert_eq!(std::fs::read_to_string(dir.join("alert")).unwrap(), message);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn dry_run_logs_commands_without_running_them() {
+        let marker = std::env::temp_dir().join(format!("dry-run-{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        let mut commands = ActionCommands {
+            dry_run: true,
+            ..ActionCommands::default()
+        };
+        commands.actions.insert(
+            "restart_validator".into(),
+            format!("touch {}", marker.display()),
+        );
+        commands
+            .preflight
+            .insert("restart_validator".into(), "false".into());
+        let action = Action::RestartValidator {
+            validator: common::ValidatorId("test".into()),
+            restart_kind: RestartKind::Hard,
+        };
+        execute_action_with(action, &commands)
+            .await
+            .expect("dry run reports success");
+        assert!(!marker.exists());
+    }
+
+    #[tokio::test]
+    async fn command_past_its_timeout_is_killed() {
+        let pid_file = std::env::temp_dir().join(format!("timeout-pid-{}", std::process::id()));
+        let _ = std::fs::remove_file(&pid_file);
+        // The pid is of a process `sh` started, not `sh` itself.
+        let command = format!("sleep 5 & echo $! > {}; wait", pid_file.display());
+
+        let started = std::time::Instant::now();
+        let err = run_command(&command, Duration::from_secs(1))
+            .await
+            .expect_err("hung command must time out");
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert!(err.to_string().contains("timed out after 1s"), "{err}");
+
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // Killed but not yet reaped counts as gone.
+        let state = std::fs::read_to_string(format!("/proc/{}/stat", pid.trim()))
+            .ok()
+            .and_then(|stat| stat.rsplit_once(") ")?.1.chars().next());
+        assert!(
+            state.is_none_or(|state| state == 'Z'),
+            "process {} still running",
+            pid.trim()
+        );
+        let _ = std::fs::remove_file(&pid_file);
+    }
+
+    #[tokio::test]
+    async fn exec_command_rejects_unregistered_id() {
+        let action = Action::ExecCommand {
+            validator: common::ValidatorId("test".into()),
+            command_id: "missing".into(),
+        };
+        let err = execute_action_with(action, &ActionCommands::default())
+            .await
+            .expect_err("unregistered command must fail");
+        assert!(err.to_string().contains("not registered"));
+    }
+
+    #[tokio::test]
+    async fn failing_preflight_prevents_the_action() {
+        let marker = std::env::temp_dir().join(format!("preflight-{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        let mut commands = ActionCommands::default();
+        commands
+            .custom
+            .insert("touch-marker".into(), format!("touch {}", marker.display()));
+        commands
+            .preflight
+            .insert("exec_command".into(), "false".into());
+        let action = Action::ExecCommand {
+            validator: common::ValidatorId("test".into()),
+            command_id: "touch-marker".into(),
+        };
+        let err = execute_action_with(action, &commands)
+            .await
+            .expect_err("failing guard must abort");
+        assert!(err.to_string().starts_with("pre-flight failed"), "{err}");
+        assert!(!marker.exists());
+    }
 }
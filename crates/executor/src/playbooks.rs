@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use crate::proto::{PlaybookExecution, PlaybookStep, StepState};
+
+/// Step-by-step progress of the playbook runs the daemon has queued steps
+/// for, by plan id.
+///
+/// A run is dropped `retention_secs` after its last step update, so a
+/// finished run stays listed for that long and a stuck one eventually goes.
+pub struct PlaybookExecutions {
+    executions: HashMap<String, PlaybookExecution>,
+    retention_secs: i64,
+}
+
+impl PlaybookExecutions {
+    pub fn new(retention_secs: i64) -> Self {
+        Self {
+            executions: HashMap::new(),
+            retention_secs,
+        }
+    }
+
+    /// A `kind` step of `plan_id` was queued for delivery.
+    pub fn queued(
+        &mut self,
+        validator_id: &str,
+        plan_id: &str,
+        issue: &str,
+        step: i32,
+        kind: &str,
+        now: i64,
+    ) {
+        let retention_secs = self.retention_secs;
+        self.executions
+            .retain(|_, execution| now - execution.updated_at < retention_secs);
+        let execution = self
+            .executions
+            .entry(plan_id.to_string())
+            .or_insert_with(|| PlaybookExecution {
+                plan_id: plan_id.to_string(),
+                validator_id: validator_id.to_string(),
+                issue: issue.to_string(),
+                started_at: now,
+                ..PlaybookExecution::default()
+            });
+        execution.updated_at = now;
+        let queued = PlaybookStep {
+            step,
+            kind: kind.to_string(),
+            ..PlaybookStep::default()
+        };
+        match execution
+            .steps
+            .binary_search_by_key(&step, |known| known.step)
+        {
+            // A resubmitted step starts over.
+            Ok(index) => execution.steps[index] = queued,
+            Err(index) => execution.steps.insert(index, queued),
+        }
+    }
+
+    fn step_mut(&mut self, plan_id: &str, step: i32, now: i64) -> Option<&mut PlaybookStep> {
+        let execution = self.executions.get_mut(plan_id)?;
+        execution.updated_at = now;
+        execution.steps.iter_mut().find(|known| known.step == step)
+    }
+
+    /// The step was handed to the validator client.
+    pub fn delivered(&mut self, plan_id: &str, step: i32, now: i64) {
+        if let Some(step) = self.step_mut(plan_id, step, now) {
+            step.set_state(StepState::Delivered);
+        }
+    }
+
+    /// The validator client started running the step. A start reported
+    /// after its outcome is ignored.
+    pub fn started(&mut self, plan_id: &str, step: i32, now: i64) {
+        if let Some(step) = self.step_mut(plan_id, step, now) {
+            if matches!(step.state(), StepState::Pending | StepState::Delivered) {
+                step.set_state(StepState::Running);
+            }
+        }
+    }
+
+    /// Where the step is, if the run is known.
+    pub fn state(&self, plan_id: &str, step: i32) -> Option<StepState> {
+        let execution = self.executions.get(plan_id)?;
+        let step = execution.steps.iter().find(|known| known.step == step)?;
+        Some(step.state())
+    }
+
+    /// The step ended in `state` with the reported `message`.
+    pub fn finished(
+        &mut self,
+        plan_id: &str,
+        step: i32,
+        state: StepState,
+        message: &str,
+        now: i64,
+    ) {
+        if let Some(step) = self.step_mut(plan_id, step, now) {
+            step.set_state(state);
+            step.message = message.to_string();
+        }
+    }
+
+    /// The compensation undoing the step ran.
+    pub fn rolled_back(&mut self, plan_id: &str, step: i32, success: bool, now: i64) {
+        if let Some(step) = self.step_mut(plan_id, step, now) {
+            step.rolled_back = Some(success);
+        }
+    }
+
+    /// Runs for `validator_id`, or every validator when it is empty, newest
+    /// first.
+    pub fn list(&self, validator_id: &str, now: i64) -> Vec<PlaybookExecution> {
+        let mut executions: Vec<PlaybookExecution> = self
+            .executions
+            .values()
+            .filter(|execution| {
+                (validator_id.is_empty() || execution.validator_id == validator_id)
+                    && now - execution.updated_at < self.retention_secs
+            })
+            .cloned()
+            .collect();
+        executions.sort_by(|a, b| {
+            b.started_at
+                .cmp(&a.started_at)
+                .then_with(|| a.plan_id.cmp(&b.plan_id))
+        });
+        executions
+    }
+}
@@ -0,0 +1,31 @@
+//! Redis persistence for validators that registered themselves with
+//! `RegisterValidator`, so they survive a daemon restart.
+
+use anyhow::{Context, Result};
+use common::ValidatorConfig;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+
+/// Hash of validator id to its `ValidatorConfig` as JSON.
+pub const REGISTERED_VALIDATORS_KEY: &str = "validator:registered";
+
+pub async fn load(conn: &mut ConnectionManager) -> Result<Vec<ValidatorConfig>> {
+    let entries: Vec<(String, String)> = conn.hgetall(REGISTERED_VALIDATORS_KEY).await?;
+    entries
+        .into_iter()
+        .map(|(id, json)| {
+            serde_json::from_str(&json)
+                .with_context(|| format!("invalid registration stored for validator {id}"))
+        })
+        .collect()
+}
+
+pub async fn save(conn: &mut ConnectionManager, validator: &ValidatorConfig) -> Result<()> {
+    conn.hset::<_, _, _, ()>(
+        REGISTERED_VALIDATORS_KEY,
+        &validator.id.0,
+        serde_json::to_string(validator)?,
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use common::ResultWebhookConfig;
+use reqwest::Client as HttpClient;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+use tracing::{error, warn};
+
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// JSON body POSTed for every action result a validator reports.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResultEvent {
+    pub validator: String,
+    pub action: serde_json::Value,
+    pub success: bool,
+    pub message: String,
+    pub action_id: Option<String>,
+    pub issue: Option<String>,
+    /// The client only logged the command; nothing ran.
+    pub dry_run: bool,
+}
+
+/// Handle to a background task delivering [`ResultEvent`]s to a webhook.
+///
+/// Results go through a bounded queue so a slow or failing endpoint never
+/// holds up `report_result`; when the queue is full new results are dropped.
+#[derive(Clone)]
+pub struct ResultWebhook {
+    tx: mpsc::Sender<ResultEvent>,
+}
+
+impl ResultWebhook {
+    pub fn spawn(cfg: ResultWebhookConfig) -> Self {
+        Self::spawn_with_delay(cfg, RETRY_BASE_DELAY)
+    }
+
+    fn spawn_with_delay(cfg: ResultWebhookConfig, retry_delay: Duration) -> Self {
+        let (tx, rx) = mpsc::channel(cfg.queue_capacity.max(1));
+        tokio::spawn(run_delivery(cfg, rx, retry_delay));
+        Self { tx }
+    }
+
+    pub fn notify(&self, event: ResultEvent) {
+        if let Err(err) = self.tx.try_send(event) {
+            warn!(%err, "result webhook queue unavailable, dropping result");
+        }
+    }
+}
+
+async fn run_delivery(
+    cfg: ResultWebhookConfig,
+    mut rx: mpsc::Receiver<ResultEvent>,
+    retry_delay: Duration,
+) {
+    let http = HttpClient::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("failed to build http client");
+    while let Some(event) = rx.recv().await {
+        let mut attempt = 0;
+        loop {
+            match deliver(&http, &cfg.url, &event).await {
+                Ok(()) => break,
+                Err(err) if attempt < cfg.max_retries => {
+                    attempt += 1;
+                    warn!(?err, attempt, "result webhook delivery failed, retrying");
+                    sleep(retry_delay * attempt).await;
+                }
+                Err(err) => {
+                    error!(
+                        ?err,
+                        validator = event.validator,
+                        "result webhook delivery failed, giving up"
+                    );
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn deliver(http: &HttpClient, url: &str, event: &ResultEvent) -> Result<()> {
+    http.post(url)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(event)?)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Clone)]
+    struct Mock {
+        calls: Arc<AtomicUsize>,
+        received: mpsc::UnboundedSender<serde_json::Value>,
+    }
+
+    /// Fails the first delivery so the retry path is exercised.
+    async fn receive(State(mock): State<Mock>, Json(body): Json<serde_json::Value>) -> StatusCode {
+        if mock.calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            return StatusCode::SERVICE_UNAVAILABLE;
+        }
+        let _ = mock.received.send(body);
+        StatusCode::NO_CONTENT
+    }
+
+    #[tokio::test]
+    async fn reported_result_is_posted_as_json() {
+        let (received, mut bodies) = mpsc::unbounded_channel();
+        let mock = Mock {
+            calls: Arc::default(),
+            received,
+        };
+        let app = Router::new()
+            .route("/hook", post(receive))
+            .with_state(mock.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let webhook = ResultWebhook::spawn_with_delay(
+            ResultWebhookConfig {
+                url: format!("http://{addr}/hook"),
+                max_retries: 2,
+                queue_capacity: 4,
+            },
+            Duration::from_millis(10),
+        );
+        webhook.notify(ResultEvent {
+            validator: "v1".into(),
+            action: serde_json::json!({"kind": "restart_validator", "validator": "v1"}),
+            success: false,
+            message: "exit status 1".into(),
+            action_id: Some("v1:slot_lag_high:slot-lag-recovery:3:0".into()),
+            issue: Some("slot_lag_high".into()),
+            dry_run: false,
+        });
+
+        let body = tokio::time::timeout(Duration::from_secs(5), bodies.recv())
+            .await
+            .expect("delivered")
+            .unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "validator": "v1",
+                "action": {"kind": "restart_validator", "validator": "v1"},
+                "success": false,
+                "message": "exit status 1",
+                "action_id": "v1:slot_lag_high:slot-lag-recovery:3:0",
+                "issue": "slot_lag_high",
+                "dry_run": false,
+            })
+        );
+        assert_eq!(mock.calls.load(Ordering::SeqCst), 2);
+    }
+}
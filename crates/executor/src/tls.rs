@@ -0,0 +1,381 @@
+//! TLS for the daemon's gRPC listener and the clients that connect to it.
+//!
+//! tonic's own `tls` feature is not used: the listener is built on
+//! `tokio-rustls`, and each connection records the client certificate so
+//! handlers can check which validator it was issued to.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use common::DaemonTlsConfig;
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{ClientConfig, RootCertStore, ServerConfig};
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::server::Connected;
+use tonic::transport::{Channel, Endpoint, Uri};
+use tracing::warn;
+
+const H2: &[u8] = b"h2";
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect())
+        .with_context(|| format!("failed to read certificates from {path}"))
+}
+
+fn load_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    PrivateKeyDer::from_pem_file(path).with_context(|| format!("failed to read private key {path}"))
+}
+
+fn load_roots(path: &str) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots
+            .add(cert)
+            .with_context(|| format!("invalid CA certificate in {path}"))?;
+    }
+    Ok(roots)
+}
+
+/// Listener config for `[daemon.tls]`.
+///
+/// Client certificates are optional at the handshake so the agent and metrics
+/// collector can still connect; the validator RPCs enforce them through
+/// [`PeerIdentity`] instead.
+pub fn server_config(cfg: &DaemonTlsConfig) -> Result<Arc<ServerConfig>> {
+    let builder =
+        ServerConfig::builder_with_provider(provider()).with_safe_default_protocol_versions()?;
+    let builder = match &cfg.client_ca_path {
+        Some(ca) => builder.with_client_cert_verifier(
+            WebPkiClientVerifier::builder_with_provider(Arc::new(load_roots(ca)?), provider())
+                .allow_unauthenticated()
+                .build()?,
+        ),
+        None if !cfg.token_auth => {
+            bail!("[daemon.tls] token_auth = false requires client_ca_path")
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut config =
+        builder.with_single_cert(load_certs(&cfg.cert_path)?, load_key(&cfg.key_path)?)?;
+    config.alpn_protocols = vec![H2.to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// The client certificate presented on a connection, if any.
+#[derive(Clone, Debug, Default)]
+pub struct PeerIdentity {
+    cert: Option<CertificateDer<'static>>,
+}
+
+impl PeerIdentity {
+    /// Whether the certificate was issued to `validator_id`: it names it in a
+    /// DNS (or IP) subject alternative name or, for a certificate without
+    /// any, in its subject common name.
+    pub fn matches(&self, validator_id: &str) -> bool {
+        let Some(cert) = &self.cert else {
+            return false;
+        };
+        let san_matches = ServerName::try_from(validator_id).is_ok_and(|name| {
+            webpki::EndEntityCert::try_from(cert)
+                .is_ok_and(|cert| cert.verify_is_valid_for_subject_name(&name).is_ok())
+        });
+        san_matches || common_name_without_san(cert).is_some_and(|cn| cn == validator_id)
+    }
+}
+
+/// The subject common name of a certificate with no subject alternative
+/// name extension. Such a certificate has nothing else naming its holder.
+fn common_name_without_san(cert: &[u8]) -> Option<String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(cert).ok()?;
+    if cert
+        .subject_alternative_name()
+        .map_or(true, |san| san.is_some())
+    {
+        return None;
+    }
+    let cn = cert.subject().iter_common_name().next()?;
+    cn.as_str().ok().map(str::to_string)
+}
+
+/// An accepted TLS connection, exposing its [`PeerIdentity`] to handlers via
+/// `Request::extensions`.
+pub struct TlsConnection(tokio_rustls::server::TlsStream<TcpStream>);
+
+impl Connected for TlsConnection {
+    type ConnectInfo = PeerIdentity;
+
+    fn connect_info(&self) -> PeerIdentity {
+        PeerIdentity {
+            cert: self
+                .0
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|chain| chain.first())
+                .map(|cert| cert.clone().into_owned()),
+        }
+    }
+}
+
+impl AsyncRead for TlsConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TlsConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+/// Accept TCP connections and complete their handshakes off the accept loop.
+/// Failed handshakes, and ones not done within `handshake_timeout`, are
+/// logged and dropped rather than ending the stream.
+pub fn incoming(
+    listener: TcpListener,
+    config: Arc<ServerConfig>,
+    handshake_timeout: Duration,
+) -> ReceiverStream<io::Result<TlsConnection>> {
+    let acceptor = TlsAcceptor::from(config);
+    let (tx, rx) = mpsc::channel(32);
+    tokio::spawn(async move {
+        while !tx.is_closed() {
+            let (tcp, addr) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(err) => {
+                    warn!(%err, "failed to accept connection");
+                    continue;
+                }
+            };
+            let acceptor = acceptor.clone();
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                match tokio::time::timeout(handshake_timeout, acceptor.accept(tcp)).await {
+                    Ok(Ok(stream)) => {
+                        let _ = tx.send(Ok(TlsConnection(stream))).await;
+                    }
+                    Ok(Err(err)) => warn!(%addr, %err, "TLS handshake failed"),
+                    Err(_) => warn!(%addr, ?handshake_timeout, "TLS handshake timed out"),
+                }
+            });
+        }
+    });
+    ReceiverStream::new(rx)
+}
+
+/// Dials the daemon over TLS. Plugs into `Endpoint::connect_with_connector`.
+#[derive(Clone)]
+pub struct DaemonConnector {
+    connector: TlsConnector,
+    server_name: Option<ServerName<'static>>,
+}
+
+impl DaemonConnector {
+    /// Trust `ca_path` for the daemon certificate and, when both paths are
+    /// given, present a client certificate. The server name defaults to the
+    /// endpoint's host.
+    pub fn new(
+        ca_path: &str,
+        client_cert: Option<(&str, &str)>,
+        server_name: Option<&str>,
+    ) -> Result<Self> {
+        let builder = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(load_roots(ca_path)?);
+        let mut config = match client_cert {
+            Some((cert, key)) => {
+                builder.with_client_auth_cert(load_certs(cert)?, load_key(key)?)?
+            }
+            None => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = vec![H2.to_vec()];
+        let server_name = server_name
+            .map(|name| ServerName::try_from(name.to_string()))
+            .transpose()
+            .context("invalid TLS server name")?;
+        Ok(Self {
+            connector: TlsConnector::from(Arc::new(config)),
+            server_name,
+        })
+    }
+
+    /// The connector the agent and metrics collector use when `[daemon.tls]`
+    /// is configured.
+    pub fn from_daemon_config(cfg: &DaemonTlsConfig) -> Result<Self> {
+        let Some(ca) = &cfg.ca_path else {
+            bail!("[daemon.tls] ca_path is required to connect to a TLS daemon");
+        };
+        Self::new(ca, None, None)
+    }
+}
+
+impl tower_service::Service<Uri> for DaemonConnector {
+    type Response = tokio_rustls::client::TlsStream<TcpStream>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let connector = self.connector.clone();
+        let server_name = self.server_name.clone();
+        Box::pin(async move {
+            let host = uri
+                .host()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "missing host"))?;
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            let server_name = match server_name {
+                Some(name) => name,
+                None => ServerName::try_from(host.to_string())
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?,
+            };
+            let tcp = TcpStream::connect((host, uri.port_u16().unwrap_or(443))).await?;
+            tcp.set_nodelay(true)?;
+            connector.connect(server_name, tcp).await
+        })
+    }
+}
+
+/// Connect `endpoint`, through `tls` when the daemon serves TLS.
+pub async fn connect(
+    endpoint: &Endpoint,
+    tls: Option<&DaemonConnector>,
+) -> Result<Channel, tonic::transport::Error> {
+    match tls {
+        Some(tls) => endpoint.connect_with_connector(tls.clone()).await,
+        None => endpoint.connect().await,
+    }
+}
+
+/// Like [`connect`], but dials on first use.
+pub fn connect_lazy(endpoint: &Endpoint, tls: Option<&DaemonConnector>) -> Channel {
+    match tls {
+        Some(tls) => endpoint.connect_with_connector_lazy(tls.clone()),
+        None => endpoint.connect_lazy(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TESTDATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/tls");
+
+    fn fixture(name: &str) -> String {
+        format!("{TESTDATA}/{name}")
+    }
+
+    #[test]
+    fn peer_identity_matches_only_the_san_it_was_issued_for() {
+        let cert = load_certs(&fixture("v1.pem")).unwrap().remove(0);
+        let peer = PeerIdentity { cert: Some(cert) };
+        assert!(peer.matches("v1"));
+        assert!(!peer.matches("v2"));
+        assert!(!PeerIdentity::default().matches("v1"));
+    }
+
+    #[test]
+    fn common_name_identifies_only_certificates_without_a_san() {
+        let cn_only = load_certs(&fixture("v3-cn-only.pem")).unwrap().remove(0);
+        assert_eq!(common_name_without_san(&cn_only).as_deref(), Some("v3"));
+        let peer = PeerIdentity {
+            cert: Some(cn_only),
+        };
+        assert!(peer.matches("v3"));
+        assert!(!peer.matches("v1"));
+
+        // v1.pem has CN=v1 too, but its SAN takes precedence.
+        let with_san = load_certs(&fixture("v1.pem")).unwrap().remove(0);
+        assert_eq!(common_name_without_san(&with_san), None);
+    }
+
+    #[test]
+    fn disabling_token_auth_requires_a_client_ca() {
+        let cfg = DaemonTlsConfig {
+            cert_path: fixture("server.pem"),
+            key_path: fixture("server.key"),
+            ca_path: None,
+            client_ca_path: None,
+            token_auth: false,
+            handshake_timeout_secs: 10,
+        };
+        assert!(server_config(&cfg).is_err());
+        let cfg = DaemonTlsConfig {
+            client_ca_path: Some(fixture("ca.pem")),
+            ..cfg
+        };
+        assert!(server_config(&cfg).is_ok());
+    }
+
+    #[tokio::test]
+    async fn stalled_handshake_is_dropped_after_the_timeout() {
+        use tokio::io::AsyncReadExt;
+        use tokio_stream::StreamExt;
+
+        let cfg = DaemonTlsConfig {
+            cert_path: fixture("server.pem"),
+            key_path: fixture("server.key"),
+            ca_path: None,
+            client_ca_path: None,
+            token_auth: true,
+            handshake_timeout_secs: 0,
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut connections = incoming(
+            listener,
+            server_config(&cfg).unwrap(),
+            Duration::from_millis(100),
+        );
+
+        // Connects but never sends a ClientHello.
+        let mut stalled = TcpStream::connect(addr).await.unwrap();
+        let mut buf = [0u8; 1];
+        let read = tokio::time::timeout(Duration::from_secs(5), stalled.read(&mut buf))
+            .await
+            .expect("server closed the stalled connection");
+        assert_eq!(read.unwrap_or(0), 0);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), connections.next())
+                .await
+                .is_err(),
+            "nothing was yielded for it"
+        );
+    }
+}
@@ -0,0 +1,37 @@
+#!/bin/sh
+# Regenerates every certificate and key in this directory. The CA key is
+# thrown away, so to add a certificate, add it below and rerun the script.
+set -eu
+cd "$(dirname "$0")"
+
+days=36500
+ca_key=$(mktemp)
+trap 'rm -f "$ca_key" ./*.csr ./*.ext' EXIT
+
+openssl ecparam -name prime256v1 -genkey -noout | openssl pkcs8 -topk8 -nocrypt -out "$ca_key"
+openssl req -x509 -new -key "$ca_key" -subj "/CN=validator-copilot test CA" -days "$days" \
+    -addext "basicConstraints=critical,CA:TRUE" \
+    -addext "keyUsage=critical,keyCertSign,cRLSign" \
+    -out ca.pem
+
+# leaf NAME SUBJECT EXTENDED_KEY_USAGE [SUBJECT_ALT_NAME]
+leaf() {
+    openssl ecparam -name prime256v1 -genkey -noout | openssl pkcs8 -topk8 -nocrypt -out "$1.key"
+    openssl req -new -key "$1.key" -subj "$2" -out "$1.csr"
+    {
+        echo "basicConstraints=CA:FALSE"
+        echo "keyUsage=critical,digitalSignature"
+        echo "extendedKeyUsage=$3"
+        echo "subjectKeyIdentifier=hash"
+        echo "authorityKeyIdentifier=keyid"
+        if [ -n "${4:-}" ]; then echo "subjectAltName=$4"; fi
+    } > "$1.ext"
+    openssl x509 -req -in "$1.csr" -CA ca.pem -CAkey "$ca_key" -CAcreateserial -days "$days" \
+        -sha256 -extfile "$1.ext" -out "$1.pem"
+    rm -f ca.srl
+}
+
+leaf server "/CN=localhost" serverAuth "DNS:localhost"
+leaf v1 "/CN=v1" clientAuth "DNS:v1"
+# Names its validator only in the subject CN, as older client certificates do.
+leaf v3-cn-only "/O=Validator Copilot/CN=v3" clientAuth
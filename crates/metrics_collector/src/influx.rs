@@ -0,0 +1,123 @@
+use std::env;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use common::{InfluxConfig, ValidatorMetrics};
+use tokio::sync::mpsc;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{error, info};
+
+const MEASUREMENT: &str = "validator_metrics";
+const QUEUE_CAPACITY: usize = 1024;
+
+/// One `validator_metrics` point in InfluxDB line protocol, second precision.
+pub fn line_protocol(id: &str, metrics: &ValidatorMetrics) -> String {
+    format!(
+        "{MEASUREMENT},id={} slot_lag={}i,vote_success_rate={},cpu_usage={},ram_usage_gb={},\
+         disk_usage_pct={},rpc_qps={},rpc_error_rate={} {}",
+        escape_tag(id),
+        metrics.slot_lag,
+        metrics.vote_success_rate,
+        metrics.cpu_usage,
+        metrics.ram_usage_gb,
+        metrics.disk_usage_pct,
+        metrics.rpc_qps,
+        metrics.rpc_error_rate,
+        metrics.last_updated,
+    )
+}
+
+fn escape_tag(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if matches!(ch, ',' | '=' | ' ') {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out
+}
+
+/// Start the batching writer and return the queue feeding it. Points are
+/// written once `batch_size` are buffered or every `flush_interval_secs`.
+pub fn spawn_sink(cfg: InfluxConfig) -> Result<mpsc::Sender<String>> {
+    let token = match &cfg.token_env {
+        Some(var) => Some(env::var(var).with_context(|| format!("{var} is not set"))?),
+        None => None,
+    };
+    let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+    info!(url = cfg.write_url, "writing metrics to influxdb");
+    tokio::spawn(run_sink(cfg, token, rx));
+    Ok(tx)
+}
+
+async fn run_sink(cfg: InfluxConfig, token: Option<String>, mut rx: mpsc::Receiver<String>) {
+    let client = reqwest::Client::new();
+    let batch_size = cfg.batch_size.max(1);
+    let mut batch: Vec<String> = Vec::with_capacity(batch_size);
+    let mut ticker = interval(Duration::from_secs(cfg.flush_interval_secs.max(1)));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            line = rx.recv() => {
+                let Some(line) = line else {
+                    break;
+                };
+                batch.push(line);
+                if batch.len() < batch_size {
+                    continue;
+                }
+            }
+            _ = ticker.tick() => {
+                if batch.is_empty() {
+                    continue;
+                }
+            }
+        }
+        let points = batch.len();
+        if let Err(err) = write_batch(&client, &cfg.write_url, token.as_deref(), &batch).await {
+            error!(?err, points, "failed to write metrics batch to influxdb");
+        }
+        batch.clear();
+    }
+}
+
+async fn write_batch(
+    client: &reqwest::Client,
+    url: &str,
+    token: Option<&str>,
+    lines: &[String],
+) -> Result<()> {
+    let mut request = client.post(url).body(lines.join("\n"));
+    if let Some(token) = token {
+        request = request.header("Authorization", format!("Token {token}"));
+    }
+    request.send().await?.error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_sample_as_line_protocol() {
+        let metrics = ValidatorMetrics {
+            slot_lag: 12,
+            vote_success_rate: 0.99,
+            cpu_usage: 0.45,
+            ram_usage_gb: 64.5,
+            disk_usage_pct: 71.0,
+            rpc_qps: 850.0,
+            rpc_error_rate: 0.002,
+            last_updated: 1_700_000_000,
+            field_timestamps: Default::default(),
+        };
+        assert_eq!(
+            line_protocol("validator 1,a=b", &metrics),
+            "validator_metrics,id=validator\\ 1\\,a\\=b slot_lag=12i,vote_success_rate=0.99,\
+             cpu_usage=0.45,ram_usage_gb=64.5,disk_usage_pct=71,rpc_qps=850,\
+             rpc_error_rate=0.002 1700000000"
+        );
+    }
+}
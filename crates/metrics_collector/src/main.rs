@@ -1,9 +1,20 @@
+mod influx;
+mod purge;
+mod rollup;
+
 use anyhow::Result;
-use common::ValidatorMetrics;
+use common::{now_ts, CollectorConfig, MetricsTransport, ValidatorMetrics};
 use executor::proto::executor_client::ExecutorClient;
-use executor::proto::MetricsWatchRequest;
-use redis::AsyncCommands;
+use executor::proto::{MetricsUpdate, MetricsWatchRequest};
+use executor::tls::{self, DaemonConnector};
+use purge::{purge_orphaned_metrics, HISTORY_KEY_PREFIX, METRICS_KEY_PREFIX};
+use redis::aio::ConnectionManager;
+use rollup::Rollup;
+use std::collections::HashSet;
 use std::env;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{interval, sleep};
 use tracing::{error, info};
 
 const DEFAULT_SERVER_ADDR: &str = "http://127.0.0.1:50051";
@@ -19,46 +30,251 @@ async fn main() -> Result<()> {
     let cfg = common::load_config()?;
     let client = redis::Client::open(cfg.redis_url.clone())?;
     let mut conn = redis::aio::ConnectionManager::new(client).await?;
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--purge") {
+        let dry_run = args.iter().any(|arg| arg == "--dry-run");
+        // Self-registered validators count as configured.
+        let mut configured: HashSet<String> =
+            cfg.validators.iter().map(|v| v.id.0.clone()).collect();
+        configured.extend(
+            executor::registry::load(&mut conn)
+                .await?
+                .into_iter()
+                .map(|v| v.id.0),
+        );
+        let report = purge_orphaned_metrics(&mut conn, &configured, dry_run).await?;
+        info!(
+            orphaned = report.orphaned.len(),
+            deleted = report.deleted,
+            dry_run,
+            "metrics purge complete"
+        );
+        return Ok(());
+    }
+
     let server_addr =
         env::var("EXECUTOR_SERVER_ADDR").unwrap_or_else(|_| DEFAULT_SERVER_ADDR.to_string());
-    let mut grpc = ExecutorClient::connect(server_addr.clone())
-        .await
-        .map_err(|err| anyhow::anyhow!("failed to connect to executor daemon: {err}"))?;
-
+    let endpoint = tonic::transport::Endpoint::from_shared(server_addr)?;
+    let daemon_tls = cfg
+        .daemon
+        .tls
+        .as_ref()
+        .map(DaemonConnector::from_daemon_config)
+        .transpose()?;
     info!(
         "metrics collector writing Redis metrics for {} validators",
         cfg.validators.len()
     );
 
-    let request = tonic::Request::new(MetricsWatchRequest {
-        validator_ids: vec![],
-        include_snapshot: true,
-    });
-    let mut stream = grpc.subscribe_metrics(request).await?.into_inner();
+    let influx = cfg.influx.clone().map(influx::spawn_sink).transpose()?;
+    let mut rollup = cfg
+        .collector
+        .rollups
+        .then(|| Rollup::new(cfg.staleness.clone(), cfg.risk_weights.clone()));
 
-    while let Some(update) = stream.message().await? {
-        match serde_json::from_str::<ValidatorMetrics>(&update.metrics_json) {
-            Ok(metrics) => {
-                let key = format!("validator:metrics:{}", update.validator_id);
-                let payload = serde_json::to_string(&metrics)?;
-                if let Err(err) = conn.set::<_, _, ()>(&key, payload).await {
-                    error!(
-                        validator = update.validator_id,
-                        ?err,
-                        "failed to persist metrics"
-                    );
-                } else {
-                    info!(validator = update.validator_id, "metrics synced to redis");
-                }
-            }
-            Err(err) => {
-                error!(
-                    validator = update.validator_id,
-                    ?err,
-                    "failed to decode metrics payload"
-                );
+    let poll_interval = (cfg.daemon.metrics_transport == MetricsTransport::Poll)
+        .then(|| Duration::from_secs(cfg.daemon.metrics_poll_interval_secs));
+    let mut last_seen = 0;
+    loop {
+        if let Err(err) = mirror_metrics(
+            &endpoint,
+            daemon_tls.as_ref(),
+            poll_interval,
+            &mut RedisSink {
+                conn: &mut conn,
+                collector: &cfg.collector,
+            },
+            influx.as_ref(),
+            rollup.as_mut(),
+            &mut last_seen,
+        )
+        .await
+        {
+            error!(?err, "metrics stream failed, reconnecting in 3s");
+        }
+        sleep(Duration::from_secs(3)).await;
+    }
+}
+
+/// Mirror the daemon's metrics stream into Redis, and InfluxDB when configured.
+/// After a reconnect the daemon replays samples accepted after the `seq` in
+/// `last_seen` so gaps are filled. With a `poll_interval` the daemon is polled
+/// for those samples instead of streamed from.
+async fn mirror_metrics(
+    endpoint: &tonic::transport::Endpoint,
+    daemon_tls: Option<&DaemonConnector>,
+    poll_interval: Option<Duration>,
+    redis: &mut RedisSink<'_>,
+    influx: Option<&mpsc::Sender<String>>,
+    mut rollup: Option<&mut Rollup>,
+    last_seen: &mut u64,
+) -> Result<()> {
+    let channel = tls::connect(endpoint, daemon_tls)
+        .await
+        .map_err(|err| anyhow::anyhow!("failed to connect to executor daemon: {err}"))?;
+    let mut grpc = ExecutorClient::new(channel);
+
+    let watch = |last_seen: u64| {
+        tonic::Request::new(MetricsWatchRequest {
+            validator_ids: vec![],
+            include_snapshot: true,
+            backfill_since: 0,
+            after_seq: last_seen,
+        })
+    };
+
+    if let Some(every) = poll_interval {
+        let mut ticker = interval(every);
+        loop {
+            ticker.tick().await;
+            let snapshot = grpc.get_all_metrics(watch(*last_seen)).await?.into_inner();
+            for update in snapshot.updates {
+                mirror_update(update, redis, influx, rollup.as_deref_mut(), last_seen).await?;
             }
         }
     }
+
+    let mut stream = grpc
+        .subscribe_metrics(watch(*last_seen))
+        .await?
+        .into_inner();
+    while let Some(update) = stream.message().await? {
+        mirror_update(update, redis, influx, rollup.as_deref_mut(), last_seen).await?;
+    }
+    Err(anyhow::anyhow!("metrics stream closed by server"))
+}
+
+/// The Redis connection and how samples are kept there.
+struct RedisSink<'a> {
+    conn: &'a mut ConnectionManager,
+    collector: &'a CollectorConfig,
+}
+
+async fn mirror_update(
+    update: MetricsUpdate,
+    redis: &mut RedisSink<'_>,
+    influx: Option<&mpsc::Sender<String>>,
+    rollup: Option<&mut Rollup>,
+    last_seen: &mut u64,
+) -> Result<()> {
+    *last_seen = (*last_seen).max(update.seq);
+    let metrics = match serde_json::from_str::<ValidatorMetrics>(&update.metrics_json) {
+        Ok(metrics) => metrics,
+        Err(err) => {
+            error!(
+                validator = update.validator_id,
+                ?err,
+                "failed to decode metrics payload"
+            );
+            return Ok(());
+        }
+    };
+    if let Some(influx) = influx {
+        let line = influx::line_protocol(&update.validator_id, &metrics);
+        if influx.try_send(line).is_err() {
+            error!(
+                validator = update.validator_id,
+                "influxdb queue full, dropping point"
+            );
+        }
+    }
+    let payload = serde_json::to_string(&metrics)?;
+    let writes = metrics_writes(
+        &update.validator_id,
+        &payload,
+        metrics.last_updated,
+        redis.collector,
+    );
+    if let Err(err) = writes.query_async::<_, ()>(redis.conn).await {
+        error!(
+            validator = update.validator_id,
+            ?err,
+            "failed to persist metrics"
+        );
+    } else {
+        info!(validator = update.validator_id, "metrics synced to redis");
+    }
+    if let Some(rollup) = rollup {
+        if let Err(err) = rollup
+            .ingest(redis.conn, &update.validator_id, metrics, now_ts())
+            .await
+        {
+            error!(?err, "failed to update cluster summary");
+        }
+    }
     Ok(())
 }
+
+/// The latest-sample key, expiring after `ttl_secs`, and the sample added to
+/// the validator's history with samples older than the retention window
+/// trimmed, in one transaction.
+fn metrics_writes(
+    validator_id: &str,
+    payload: &str,
+    last_updated: i64,
+    collector: &CollectorConfig,
+) -> redis::Pipeline {
+    let key = format!("{METRICS_KEY_PREFIX}{validator_id}");
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    pipe.set(&key, payload);
+    if collector.ttl_secs > 0 {
+        pipe.arg("EX").arg(collector.ttl_secs);
+    }
+    pipe.ignore();
+    let retention = collector.history_retention_secs as i64;
+    if retention > 0 {
+        let history = format!("{HISTORY_KEY_PREFIX}{validator_id}");
+        pipe.zadd(&history, payload, last_updated)
+            .ignore()
+            .zrembyscore(&history, "-inf", format!("({}", last_updated - retention))
+            .ignore()
+            .expire(&history, retention)
+            .ignore();
+    }
+    pipe
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(pipe: &redis::Pipeline) -> Vec<String> {
+        pipe.cmd_iter()
+            .map(|cmd| {
+                cmd.args_iter()
+                    .map(|arg| match arg {
+                        redis::Arg::Simple(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+                        redis::Arg::Cursor => "<cursor>".to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn samples_expire_and_are_kept_in_a_trimmed_history() {
+        let collector = CollectorConfig::default();
+        assert_eq!(
+            commands(&metrics_writes("v1", "{}", 1_700_003_600, &collector)),
+            [
+                "SET validator:metrics:v1 {} EX 300",
+                "ZADD validator:metrics:history:v1 1700003600 {}",
+                "ZREMRANGEBYSCORE validator:metrics:history:v1 -inf (1700000000",
+                "EXPIRE validator:metrics:history:v1 3600",
+            ]
+        );
+
+        let unbounded = CollectorConfig {
+            ttl_secs: 0,
+            history_retention_secs: 0,
+            ..CollectorConfig::default()
+        };
+        assert_eq!(
+            commands(&metrics_writes("v1", "{}", 1_700_003_600, &unbounded)),
+            ["SET validator:metrics:v1 {}"]
+        );
+    }
+}
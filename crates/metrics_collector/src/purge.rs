@@ -0,0 +1,153 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use tracing::info;
+
+pub const METRICS_KEY_PREFIX: &str = "validator:metrics:";
+/// Also matched by [`METRICS_KEY_PREFIX`] scans.
+pub const HISTORY_KEY_PREFIX: &str = "validator:metrics:history:";
+
+/// The subset of Redis operations the purge pass needs.
+pub trait MetricsStore {
+    async fn metric_keys(&mut self) -> Result<Vec<String>>;
+    async fn delete_key(&mut self, key: &str) -> Result<()>;
+}
+
+impl MetricsStore for ConnectionManager {
+    async fn metric_keys(&mut self) -> Result<Vec<String>> {
+        let mut iter = self
+            .scan_match::<_, String>(format!("{METRICS_KEY_PREFIX}*"))
+            .await?;
+        let mut keys = Vec::new();
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        Ok(keys)
+    }
+
+    async fn delete_key(&mut self, key: &str) -> Result<()> {
+        self.del::<_, ()>(key).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct PurgeReport {
+    pub orphaned: Vec<String>,
+    pub deleted: usize,
+}
+
+/// Remove metrics and history keys whose validator id is no longer configured.
+/// With `dry_run` the orphaned keys are only reported.
+pub async fn purge_orphaned_metrics<S: MetricsStore>(
+    store: &mut S,
+    configured: &HashSet<String>,
+    dry_run: bool,
+) -> Result<PurgeReport> {
+    let mut report = PurgeReport::default();
+    for key in store.metric_keys().await? {
+        let Some(id) = key
+            .strip_prefix(HISTORY_KEY_PREFIX)
+            .or_else(|| key.strip_prefix(METRICS_KEY_PREFIX))
+        else {
+            continue;
+        };
+        if configured.contains(id) {
+            continue;
+        }
+        if dry_run {
+            info!(%key, "dry run: would purge orphaned metrics key");
+        } else {
+            store.delete_key(&key).await?;
+            info!(%key, "purged orphaned metrics key");
+            report.deleted += 1;
+        }
+        report.orphaned.push(key);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(Default)]
+    struct FakeStore {
+        keys: BTreeMap<String, String>,
+    }
+
+    impl MetricsStore for FakeStore {
+        async fn metric_keys(&mut self) -> Result<Vec<String>> {
+            Ok(self
+                .keys
+                .keys()
+                .filter(|key| key.starts_with(METRICS_KEY_PREFIX))
+                .cloned()
+                .collect())
+        }
+
+        async fn delete_key(&mut self, key: &str) -> Result<()> {
+            self.keys.remove(key);
+            Ok(())
+        }
+    }
+
+    fn store() -> FakeStore {
+        let mut store = FakeStore::default();
+        for id in ["validator-1", "validator-2", "retired"] {
+            store
+                .keys
+                .insert(format!("{METRICS_KEY_PREFIX}{id}"), "{}".into());
+        }
+        store
+            .keys
+            .insert(format!("{HISTORY_KEY_PREFIX}validator-1"), "{}".into());
+        store
+            .keys
+            .insert(format!("{HISTORY_KEY_PREFIX}retired"), "{}".into());
+        store
+    }
+
+    fn configured() -> HashSet<String> {
+        ["validator-1", "validator-2"]
+            .into_iter()
+            .map(String::from)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn purges_orphaned_key_and_keeps_configured() {
+        let mut store = store();
+        let report = purge_orphaned_metrics(&mut store, &configured(), false)
+            .await
+            .expect("purge");
+        assert_eq!(
+            report.orphaned,
+            vec![
+                "validator:metrics:history:retired".to_string(),
+                "validator:metrics:retired".to_string()
+            ]
+        );
+        assert_eq!(report.deleted, 2);
+        assert!(store.keys.contains_key("validator:metrics:validator-1"));
+        assert!(store
+            .keys
+            .contains_key("validator:metrics:history:validator-1"));
+        assert!(store.keys.contains_key("validator:metrics:validator-2"));
+        assert!(!store.keys.contains_key("validator:metrics:retired"));
+    }
+
+    #[tokio::test]
+    async fn dry_run_reports_without_deleting() {
+        let mut store = store();
+        let report = purge_orphaned_metrics(&mut store, &configured(), true)
+            .await
+            .expect("purge");
+        assert_eq!(report.orphaned.len(), 2);
+        assert_eq!(report.deleted, 0);
+        assert_eq!(store.keys.len(), 5);
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use common::{risk_score_with, RiskWeights, StalenessConfig, ValidatorMetrics};
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::Serialize;
+
+pub const CLUSTER_SUMMARY_KEY: &str = "validator:cluster:summary";
+
+/// Where the rollup pass writes its summary.
+pub trait SummaryStore {
+    async fn put_summary(&mut self, json: String) -> Result<()>;
+}
+
+impl SummaryStore for ConnectionManager {
+    async fn put_summary(&mut self, json: String) -> Result<()> {
+        self.set::<_, _, ()>(CLUSTER_SUMMARY_KEY, json).await?;
+        Ok(())
+    }
+}
+
+/// Cluster-wide aggregates over the latest sample of every validator seen.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ClusterSummary {
+    pub validators: usize,
+    pub total_rpc_qps: f64,
+    pub avg_risk_score: f64,
+    /// `ok` or `stale` (by the `[staleness]` limits), per validator.
+    pub by_status: BTreeMap<&'static str, usize>,
+    pub updated_at: i64,
+}
+
+pub fn summarize(
+    latest: &HashMap<String, ValidatorMetrics>,
+    staleness: &StalenessConfig,
+    weights: &RiskWeights,
+    now: i64,
+) -> ClusterSummary {
+    let mut by_status = BTreeMap::new();
+    let mut total_rpc_qps = 0.0;
+    let mut total_risk = 0.0;
+    for metrics in latest.values() {
+        let status = if staleness.overdue_secs(metrics, now) > 0 {
+            "stale"
+        } else {
+            "ok"
+        };
+        *by_status.entry(status).or_default() += 1;
+        total_rpc_qps += metrics.rpc_qps;
+        total_risk += risk_score_with(metrics, weights);
+    }
+    ClusterSummary {
+        validators: latest.len(),
+        total_rpc_qps,
+        avg_risk_score: if latest.is_empty() {
+            0.0
+        } else {
+            total_risk / latest.len() as f64
+        },
+        by_status,
+        updated_at: now,
+    }
+}
+
+/// Keeps the latest sample per validator and rewrites the cluster summary on
+/// every ingest, so dashboards read a single key.
+pub struct Rollup {
+    latest: HashMap<String, ValidatorMetrics>,
+    staleness: StalenessConfig,
+    weights: RiskWeights,
+}
+
+impl Rollup {
+    pub fn new(staleness: StalenessConfig, weights: RiskWeights) -> Self {
+        Self {
+            latest: HashMap::new(),
+            staleness,
+            weights,
+        }
+    }
+
+    pub async fn ingest<S: SummaryStore>(
+        &mut self,
+        store: &mut S,
+        validator: &str,
+        metrics: ValidatorMetrics,
+        now: i64,
+    ) -> Result<ClusterSummary> {
+        self.latest.insert(validator.to_string(), metrics);
+        let summary = summarize(&self.latest, &self.staleness, &self.weights, now);
+        store.put_summary(serde_json::to_string(&summary)?).await?;
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeStore {
+        summary: Option<String>,
+    }
+
+    impl SummaryStore for FakeStore {
+        async fn put_summary(&mut self, json: String) -> Result<()> {
+            self.summary = Some(json);
+            Ok(())
+        }
+    }
+
+    fn metrics(rpc_qps: f64, last_updated: i64) -> ValidatorMetrics {
+        ValidatorMetrics {
+            slot_lag: 0,
+            vote_success_rate: 1.0,
+            cpu_usage: 0.0,
+            ram_usage_gb: 0.0,
+            disk_usage_pct: 0.0,
+            rpc_qps,
+            rpc_error_rate: 0.0,
+            last_updated,
+            field_timestamps: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn summary_key_reflects_latest_sample_of_each_validator() {
+        let mut store = FakeStore::default();
+        let mut rollup = Rollup::new(StalenessConfig::default(), RiskWeights::default());
+        let now = 1_000;
+        for (id, qps, last_updated) in [
+            ("v1", 200.0, 990),
+            ("v2", 600.0, 995),
+            ("v3", 1000.0, 100),
+            // A newer sample replaces v1's earlier one.
+            ("v1", 400.0, 1_000),
+        ] {
+            rollup
+                .ingest(&mut store, id, metrics(qps, last_updated), now)
+                .await
+                .unwrap();
+        }
+
+        let stored: serde_json::Value =
+            serde_json::from_str(store.summary.as_deref().unwrap()).unwrap();
+        // risk_score is only the rpc_qps term here: qps / 2000 * 0.1.
+        let avg_risk = (0.02 + 0.03 + 0.05) / 3.0;
+        assert_eq!(stored["validators"], 3);
+        assert_eq!(stored["total_rpc_qps"], 2000.0);
+        assert!((stored["avg_risk_score"].as_f64().unwrap() - avg_risk).abs() < 1e-9);
+        assert_eq!(
+            stored["by_status"],
+            serde_json::json!({"ok": 2, "stale": 1})
+        );
+        assert_eq!(stored["updated_at"], now);
+    }
+}